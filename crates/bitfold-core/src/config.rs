@@ -1,9 +1,11 @@
-use std::{default::Default, time::Duration};
+use std::{default::Default, error::Error, fmt, time::Duration};
 
 use super::constants::{DEFAULT_MTU, FRAGMENT_SIZE_DEFAULT, MAX_FRAGMENTS_DEFAULT};
 
 /// Compression algorithm to use for packet data.
 #[derive(Clone, Debug, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum CompressionAlgorithm {
     /// No compression
     None,
@@ -11,17 +13,138 @@ pub enum CompressionAlgorithm {
     Zlib,
     /// LZ4 compression (fast, lower ratio)
     Lz4,
+    /// Zstd compression at the given level (1-22; better ratio than Zlib at comparable speed).
+    /// Requires the `zstd` feature of `bitfold-protocol`; without it, sending compressed
+    /// datagrams fails.
+    Zstd {
+        /// Compression level; higher trades speed for ratio
+        level: i32,
+    },
 }
 
-#[derive(Clone, Debug)]
+/// Checksum appended to datagrams to detect corruption.
+#[derive(Clone, Debug, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ChecksumAlgorithm {
+    /// No checksum
+    None,
+    /// CRC32 (4 bytes)
+    #[default]
+    Crc32,
+    /// xxHash32 (4 bytes; faster than CRC32 in software)
+    XxHash32,
+    /// xxHash64 (8 bytes; fewer collisions)
+    XxHash64,
+}
+
+impl ChecksumAlgorithm {
+    /// Returns the bytes the checksum adds to each datagram.
+    pub fn len_bytes(self) -> usize {
+        match self {
+            ChecksumAlgorithm::None => 0,
+            ChecksumAlgorithm::Crc32 | ChecksumAlgorithm::XxHash32 => 4,
+            ChecksumAlgorithm::XxHash64 => 8,
+        }
+    }
+}
+
+/// Congestion controller a peer uses to gate reliable sends on bytes in flight.
+#[derive(Clone, Debug, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CongestionControllerKind {
+    /// No byte-based congestion window; sends are gated by the throttle and flow control only
+    #[default]
+    None,
+    /// NewReno-style slow start and additive increase, halving the window on loss
+    NewReno,
+    /// A window that never changes, for tests
+    FixedWindow {
+        /// Window size in bytes
+        bytes: usize,
+    },
+}
+
+/// Which received commands are answered with an acknowledgment.
+///
+/// Pure ACKs and keepalives (ping/pong) are never acknowledged under any policy, so ACKs
+/// cannot trigger ACKs in a loop.
+#[derive(Clone, Debug, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum AckPolicy {
+    /// Only reliable data is acknowledged
+    #[default]
+    ReliableOnly,
+    /// Every ack-eliciting command is acknowledged, including control commands
+    AllEliciting,
+}
+
+/// Content of PMTU probe payloads.
+///
+/// Some middleboxes inspect payloads; switching modes helps diagnose and work around
+/// probes being dropped by deep packet inspection.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum PmtuProbePayload {
+    /// Random bytes, which cannot be shrunk by compression
+    #[default]
+    Random,
+    /// All zero bytes
+    Zeroed,
+    /// Caller-supplied pattern, repeated to fill the payload (zeroed if empty)
+    Pattern(Vec<u8>),
+    /// The target size and payload length followed by a pattern derived from the token; a
+    /// receiver in this mode only answers probes that arrived whole
+    Verifiable,
+}
+
+/// How the PMTU search picks probe sizes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum PmtuSearchMode {
+    /// Bisect the remaining range on every probe
+    #[default]
+    Binary,
+    /// Grow the probe size above the known-good size by a step that doubles with each reply,
+    /// then bisect between the last success and the first failure
+    Exponential,
+    /// Probe exactly the sizes in `pmtu_candidate_sizes`, largest first, and converge on the
+    /// largest one that gets a reply (or `pmtu_min` if none does)
+    Schedule,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 /// Configuration options to tune protocol and runtime behavior.
+///
+/// Prefer building one with [`Config::builder`], which checks that the options are
+/// consistent. The fields stay public for backward compatibility; a config assembled by
+/// setting them directly can be checked with [`Config::validate`].
+///
+/// With the `serde` feature it can be deserialized, e.g. from a settings file; fields the
+/// input leaves out take their [`Config::default`] values. Deserialized configs are not
+/// validated.
 pub struct Config {
     /// Make the underlying UDP socket block when true, otherwise non-blocking.
     pub blocking_mode: bool,
-    /// Max idle time before considering a connection disconnected.
+    /// Max idle time before considering a connection disconnected. Advertised in the handshake;
+    /// both sides enforce the smaller of the two values.
     pub idle_connection_timeout: Duration,
+    /// Max time to wait for the close handshake after a shutdown before dropping the peer.
+    pub disconnect_timeout: Duration,
+    /// Max time to wait for the pong answering `Peer::ping` before it fails (default: 1s).
+    pub ping_timeout: Duration,
     /// Interval for heartbeat packets if no data is sent. None disables heartbeats.
     pub heartbeat_interval: Option<Duration>,
+    /// Send heartbeats once nothing was sent for `heartbeat_interval`, even while traffic keeps
+    /// arriving (default: false, only when both directions are idle). Keeps the NAT mapping of
+    /// a peer that mostly receives open.
+    pub heartbeat_on_send_idle: bool,
     /// Max total packet size in bytes (including fragmentation).
     pub max_packet_size: usize,
     /// Max number of fragments per packet (u8).
@@ -32,14 +155,40 @@ pub struct Config {
     pub fragment_reassembly_buffer_size: u16,
     /// Max receive buffer size in bytes.
     pub receive_buffer_max_size: usize,
+    /// Most payload bytes held per connection in incomplete fragment groups awaiting
+    /// reassembly; beyond it the least recently updated groups are evicted (0 = unlimited,
+    /// default: 1 MB).
+    pub max_reassembly_bytes: usize,
     /// Smoothing factor (0..1) for RTT measurements.
     pub rtt_smoothing_factor: f32,
     /// Max acceptable RTT in milliseconds before considering a problem.
     pub rtt_max_value: u16,
+    /// Lowest retransmission timeout in milliseconds before backoff; RFC 6298 recommends 1000
+    /// for TCP (default: 0).
+    pub rto_min_ms: u32,
+    /// Highest retransmission timeout in milliseconds, however often it backs off
+    /// (default: 60000).
+    pub rto_max_ms: u32,
+    /// Factor the retransmission timeout grows by per consecutive timeout, at least 1
+    /// (default: 2.0).
+    pub rto_backoff_factor: f32,
+    /// Highest loss rate (0.0 to 1.0) at which `Peer::health` still reports the connection as
+    /// healthy (default: 0.05).
+    pub health_max_loss_rate: f32,
+    /// Packets that must be acknowledged after an unacknowledged one before it can be declared
+    /// lost; raised automatically to one more than the reordering observed on the path
+    /// (default: 3).
+    pub loss_reorder_threshold: u16,
+    /// Fraction of the smoothed RTT that must also have elapsed since an unacknowledged packet
+    /// was sent before it can be declared lost (default: 1.125).
+    pub loss_time_threshold: f32,
     /// Size of the event buffer for socket events.
     pub socket_event_buffer_size: usize,
     /// How long to block when polling socket events.
     pub socket_polling_timeout: Option<Duration>,
+    /// Max datagrams read from the socket in one poll before timers and sessions are served
+    /// (default: 0, unlimited). The rest stay queued in the socket until the next poll.
+    pub max_datagrams_per_tick: usize,
     /// Max reliable packets in flight before dropping a connection.
     pub max_packets_in_flight: u16,
     /// Max number of unestablished connections to prevent DoS.
@@ -50,18 +199,70 @@ pub struct Config {
     pub incoming_bandwidth_limit: u32,
     /// Outgoing bandwidth limit in bytes/sec (0 = unlimited).
     pub outgoing_bandwidth_limit: u32,
-    /// Enable CRC32 checksums for data integrity verification (default: false).
+    /// Pace datagrams evenly at `outgoing_bandwidth_limit`, allowing bursts of up to this many
+    /// full-size datagrams after idle periods (0 = no pacing, default). With 1 pacing is strict.
+    /// Has no effect while `outgoing_bandwidth_limit` is 0, unless `use_pacing` is set.
+    pub pacing_burst_packets: u32,
+    /// Pace datagrams at `pacing_gain` times the congestion window (the flow-control window
+    /// without a congestion controller) per smoothed RTT, capped at a nonzero
+    /// `outgoing_bandwidth_limit` (default: false). Bursts follow `pacing_burst_packets`,
+    /// strict pacing if it is 0.
+    pub use_pacing: bool,
+    /// Multiplier of the window-based pacing rate (default: 1.25). Above 1 so pacing does not
+    /// keep the window from being used up within an RTT.
+    pub pacing_gain: f32,
+    /// Enable checksums for data integrity verification (default: true). When false,
+    /// datagrams carry no checksum whatever `checksum` is set to.
     pub use_checksums: bool,
+    /// Checksum algorithm used when `use_checksums` is enabled (default: Crc32). Both ends
+    /// must use the same algorithm.
+    pub checksum: ChecksumAlgorithm,
+    /// Which received commands trigger an acknowledgment (default: reliable data only).
+    pub ack_policy: AckPolicy,
+    /// Delay before acknowledging received reliable data, so several ACKs coalesce into one
+    /// (0 = acknowledge immediately). Datagrams flagged for immediate ACK bypass the delay.
+    pub ack_delay_ms: u32,
+    /// Most ranges a selective acknowledgment (SACK) lists (0 = disabled, the default). When
+    /// set, ACKs of data received with gaps are followed by a SACK of the received ranges
+    /// among the last 256 sequence numbers, so the sender only retransmits the gaps.
+    pub sack_max_ranges: u8,
+    /// Only checksum datagrams that carry data commands (default: false). Control-only
+    /// datagrams skip the `checksum` (4 or 8 bytes, see [`ChecksumAlgorithm::len_bytes`]) and
+    /// flag this in their header byte.
+    pub checksum_data_only: bool,
     /// Compression algorithm to use (default: None).
     pub compression: CompressionAlgorithm,
-    /// Minimum packet size to compress in bytes (default: 128). Packets smaller than this won't be compressed.
+    /// Minimum packet size to compress in bytes (default: 128). Packets smaller than this won't be
+    /// compressed whatever the algorithm, and carry only the 1-byte uncompressed marker.
     pub compression_threshold: usize,
+    /// Number of recent datagrams over which the compression ratio is averaged to decide
+    /// whether compressing pays off (default: 0, always compress). Compression is suspended
+    /// while the ratio stays above `adaptive_compression_max_ratio`.
+    pub adaptive_compression_window: usize,
+    /// Compression ratio (compressed / original size) above which adaptive compression
+    /// suspends itself (default: 0.95).
+    pub adaptive_compression_max_ratio: f32,
+    /// Treat the path as trusted, e.g. loopback (default: false). Datagrams then carry the
+    /// bare command packet: no compression marker or header flags, and no checksum whatever
+    /// `compression` and `use_checksums` are set to. Both ends must agree on this.
+    pub trusted_path: bool,
     /// Use formal 3-way connection handshake for enhanced security (default: false).
     /// When enabled, uses Connect->VerifyConnect->ACK handshake with session IDs.
     pub use_connection_handshake: bool,
+    /// Maximum application data (bytes) buffered per peer while the handshake is in progress
+    /// (0 = no buffering, data is sent alongside the handshake). Buffered data is flushed once
+    /// the connection is established; sends beyond the limit are refused.
+    pub early_data_max_bytes: usize,
     /// Maximum buffered packet data per peer in bytes (0 = unlimited).
     /// Prevents memory exhaustion from malicious/buggy clients.
     pub max_waiting_data: usize,
+    /// Queued data (bytes) to accumulate before sending, so that small writes are coalesced
+    /// into full datagrams (0 = send immediately). Queued control commands and explicit
+    /// flushes send held data regardless.
+    pub flush_threshold_bytes: usize,
+    /// Aggregate bytes of send queues and reassembly buffers across all peers of a host
+    /// (0 = unlimited). New connections are refused while the budget is exhausted.
+    pub global_memory_budget: usize,
     /// Enable advanced packet throttling with acceleration/deceleration.
     /// When enabled, uses dynamic throttle adjustment based on packet loss.
     pub use_advanced_throttling: bool,
@@ -73,6 +274,9 @@ pub struct Config {
     pub throttle_deceleration: u32,
     /// Interval for throttle updates in milliseconds.
     pub throttle_interval: u32,
+    /// Let the throttle switch between reacting to loss and to RTT inflation, whichever the
+    /// path responds to, with hysteresis against flapping (default: false, loss only).
+    pub adaptive_congestion_signal: bool,
     /// Enable dynamic window-based flow control.
     /// When enabled, uses adaptive window sizing based on network conditions.
     pub use_window_flow_control: bool,
@@ -82,9 +286,21 @@ pub struct Config {
     pub min_window_size: u32,
     /// Maximum window size (in packets).
     pub max_window_size: u32,
+    /// Interval in milliseconds between window probes sent while the flow-control window is
+    /// full (0 = never probe). Recovers from a lost window update instead of stalling forever.
+    pub flow_control_probe_interval_ms: u32,
+    /// Congestion controller gating reliable sends on bytes in flight (default: None). A custom
+    /// controller can be installed with `Peer::set_congestion_controller`.
+    pub congestion_controller: CongestionControllerKind,
     /// Maximum number of connections allowed from the same IP address (0 = unlimited).
     /// Useful for NAT scenarios where multiple clients share the same public IP.
     pub max_duplicate_peers: u16,
+    /// Time in milliseconds a new source address of a connection has to answer a path
+    /// challenge before it is abandoned (default: 3000; 0 = never migrate).
+    pub path_validation_timeout_ms: u32,
+    /// Bytes that may be sent to an address under validation per byte received from it
+    /// (default: 3), so a spoofed source address cannot amplify traffic at a victim.
+    pub path_amplification_factor: u32,
     /// Socket receive buffer size in bytes (None = use system default).
     /// Corresponds to SO_RCVBUF socket option.
     pub socket_recv_buffer_size: Option<usize>,
@@ -106,8 +322,75 @@ pub struct Config {
     pub pmtu_max: u16,
     /// Interval between PMTU probes in milliseconds.
     pub pmtu_interval_ms: u32,
+    /// Randomizes each wait between PMTU probes by up to this percentage of the interval either
+    /// way, so peers started together do not probe in lockstep; 25 is a typical value
+    /// (0 = fixed interval, default).
+    pub pmtu_interval_jitter_pct: u8,
     /// Threshold (bytes) at which PMTU search is considered converged.
     pub pmtu_converge_threshold: u16,
+    /// Convergence threshold as a percentage of the current search midpoint (0 = disabled).
+    /// The search converges when `high - low <= max(pmtu_converge_threshold, mid * pct / 100)`.
+    pub pmtu_converge_pct: u8,
+    /// Overall PMTU discovery timeout in milliseconds (0 = no timeout).
+    /// If the search has not converged by then, discovery gives up and pins the fragment size.
+    pub pmtu_discovery_timeout_ms: u32,
+    /// Fragment size (bytes) pinned when discovery gives up without any confirmed probe.
+    pub pmtu_safe_floor: u16,
+    /// Well-known MTU sizes preferred over binary-search midpoints when probing
+    /// (e.g. 1500, 1492 for PPPoE, 1400 for VPNs, 1280 for IPv6). Empty = pure binary search.
+    /// With `PmtuSearchMode::Schedule` these are the only sizes probed.
+    pub pmtu_candidate_sizes: Vec<u16>,
+    /// Round PMTU probe sizes to a multiple of this many bytes, trading granularity for fewer
+    /// distinct probes (0 = no rounding). The convergence threshold is never below the quantum.
+    pub pmtu_probe_quantum: u16,
+    /// How probe sizes are chosen; `Exponential` reaches MTUs near `pmtu_max` in fewer round
+    /// trips (default: binary search).
+    pub pmtu_search_mode: PmtuSearchMode,
+    /// Content used to fill PMTU probe payloads (default: random bytes).
+    pub pmtu_probe_payload_mode: PmtuProbePayload,
+    /// Use an incrementing counter instead of random values for PMTU probe tokens (default: false).
+    /// Tokens only need to be unique within a connection; a counter is cheaper and deterministic.
+    pub pmtu_sequential_tokens: bool,
+    /// Send the first PMTU probe on the first `handle_pmtu` call instead of waiting a full
+    /// `pmtu_interval_ms` (default: false).
+    pub pmtu_probe_on_start: bool,
+    /// Experimental: pair each PMTU probe with a same-sized probe the transport sends with the
+    /// DF bit cleared, to tell "too big" apart from generic loss (default: false).
+    pub pmtu_paired_probes: bool,
+    /// Once the PMTU search converges, confirm `low` with one more probe before adopting it as
+    /// the fragment size; a failed confirmation reopens the search below it (default: false).
+    pub pmtu_confirm_on_converge: bool,
+    /// Packets reported lost in one burst that indicate a PMTU black hole, dropping the fragment
+    /// size to `pmtu_safe_floor` and restarting discovery (0 = detection disabled, default).
+    pub pmtu_blackhole_loss_threshold: u32,
+    /// Minimum time between black-hole resets, so a flapping path does not thrash the fragment
+    /// size (default: 10000).
+    pub pmtu_blackhole_cooldown_ms: u32,
+    /// Consecutive losses of datagrams larger than `pmtu_safe_floor`, reported once the search
+    /// has converged, that restart the search below the converged size (0 = disabled,
+    /// default: 3).
+    pub pmtu_reprobe_loss_threshold: u32,
+    /// Cap for the probe interval, which doubles from `pmtu_interval_ms` after each consecutive
+    /// probe timeout and returns to it on the next reply (0 = no backoff, default).
+    pub pmtu_backoff_max_ms: u32,
+    /// Most PMTU replies and reflected probes sent per second, so a storm of probes is not
+    /// amplified into a storm of replies; probes beyond the limit go unanswered (0 = unlimited,
+    /// default: 10).
+    pub pmtu_max_replies_per_sec: u32,
+    /// Times a timed-out PMTU probe is re-sent at the same size before the size is judged
+    /// too big, so transient loss does not shrink the search (default: 2).
+    pub pmtu_probe_retries: u8,
+    /// Ask the remote to send PMTU probes back instead of sending them, for peers that cannot
+    /// send DF-marked datagrams themselves; this tests the path from the remote (default: false).
+    pub pmtu_reflected_probes: bool,
+    /// Whether received PMTU probes, replies and probe requests reset the idle timer, letting
+    /// probing double as keepalive (default: true). When false, only other traffic keeps the
+    /// connection alive.
+    pub pmtu_probes_count_as_activity: bool,
+    /// Seed for per-peer randomness: session IDs, peer IDs, PMTU probe tokens and payloads
+    /// (None = seeded from OS entropy). Each peer mixes in its remote address, so a fixed seed
+    /// makes a multi-peer scenario reproducible while peers still differ from one another.
+    pub rng_seed: Option<u64>,
 }
 
 impl Default for Config {
@@ -115,36 +398,66 @@ impl Default for Config {
         Self {
             blocking_mode: false,
             idle_connection_timeout: Duration::from_secs(5),
+            disconnect_timeout: Duration::from_secs(1),
+            ping_timeout: Duration::from_secs(1),
             heartbeat_interval: None,
+            heartbeat_on_send_idle: false,
             max_packet_size: (MAX_FRAGMENTS_DEFAULT * FRAGMENT_SIZE_DEFAULT) as usize,
             max_fragments: MAX_FRAGMENTS_DEFAULT as u8,
             fragment_size: FRAGMENT_SIZE_DEFAULT,
             fragment_reassembly_buffer_size: 64,
             receive_buffer_max_size: DEFAULT_MTU as usize,
+            max_reassembly_bytes: 1024 * 1024, // 1 MB of incomplete fragments per connection
             rtt_smoothing_factor: 0.10,
             rtt_max_value: 250,
+            rto_min_ms: 0,           // No floor beyond SRTT + 4 * RTTVAR
+            rto_max_ms: 60_000,      // RFC 6298 ceiling
+            rto_backoff_factor: 2.0, // Exponential backoff
+            health_max_loss_rate: 0.05,
+            loss_reorder_threshold: 3,
+            loss_time_threshold: 1.125,
             socket_event_buffer_size: 1024,
             socket_polling_timeout: Some(Duration::from_millis(1)),
+            max_datagrams_per_tick: 0, // Drain the socket on every poll
             max_packets_in_flight: 512,
             max_unestablished_connections: 50,
             channel_count: 1, // Default to single channel like most simple uses
             incoming_bandwidth_limit: 2_097_152, // 2 MB/s - DoS protection (0 = unlimited)
             outgoing_bandwidth_limit: 2_097_152, // 2 MB/s - prevents bandwidth abuse (0 = unlimited)
+            pacing_burst_packets: 0,             // Limit per second only, without pacing
+            use_pacing: false,                   // Send as fast as the windows allow
+            pacing_gain: 1.25,                   // Pace a little faster than window per RTT
             use_checksums: true,                 // Enabled for data integrity protection
+            checksum: ChecksumAlgorithm::Crc32,
+            checksum_data_only: false,           // Checksum every datagram
+            ack_policy: AckPolicy::ReliableOnly, // Only reliable data elicits ACKs
+            ack_delay_ms: 0,                     // Acknowledge immediately
+            sack_max_ranges: 0,                  // Bitfield acknowledgments only
             compression: CompressionAlgorithm::None, // Disabled by default
             compression_threshold: 128,          // Don't compress packets smaller than 128 bytes
+            adaptive_compression_window: 0,      // Always compress
+            adaptive_compression_max_ratio: 0.95,
+            trusted_path: false,
             use_connection_handshake: true, // Enabled for enhanced security with 3-way handshake
+            early_data_max_bytes: 0,        // Send data alongside the handshake
             max_waiting_data: 32 * 1024 * 1024, // 32 MB - prevents memory exhaustion
+            flush_threshold_bytes: 0,       // Send every write immediately
+            global_memory_budget: 0,        // Unlimited by default
             use_advanced_throttling: false, // Disabled by default for backward compatibility
             throttle_scale: 32,             // Default scale
             throttle_acceleration: 2,       // Default acceleration
             throttle_deceleration: 2,       // Default deceleration
             throttle_interval: 5000,        // 5 seconds (default)
+            adaptive_congestion_signal: false, // React to loss only
             use_window_flow_control: false, // Disabled by default for backward compatibility
             initial_window_size: 512, // Start with 512 packets (matches max_packets_in_flight)
             min_window_size: 64,      // Minimum 64 packets
             max_window_size: 4096,    // Maximum 4096 packets
+            flow_control_probe_interval_ms: 1000, // Probe once per second while blocked
+            congestion_controller: CongestionControllerKind::None, // Throttle and flow control only
             max_duplicate_peers: 0,   // Unlimited by default
+            path_validation_timeout_ms: 3000, // Give a new address three seconds to answer
+            path_amplification_factor: 3, // Send at most 3x what a new address sent
             socket_recv_buffer_size: None, // Use system default
             socket_send_buffer_size: None, // Use system default
             socket_ttl: None,         // Use system default
@@ -153,7 +466,432 @@ impl Default for Config {
             pmtu_min: 576,
             pmtu_max: 1400,
             pmtu_interval_ms: 5000,
+            pmtu_interval_jitter_pct: 0,
             pmtu_converge_threshold: 64,
+            pmtu_converge_pct: 0,             // Absolute threshold only
+            pmtu_discovery_timeout_ms: 0,     // No overall timeout by default
+            pmtu_safe_floor: 576,             // IPv4 minimum reassembly size
+            pmtu_candidate_sizes: Vec::new(), // Pure binary search by default
+            pmtu_probe_quantum: 0,            // Probe exact sizes
+            pmtu_search_mode: PmtuSearchMode::Binary,
+            pmtu_probe_payload_mode: PmtuProbePayload::Random,
+            pmtu_sequential_tokens: false,
+            pmtu_probe_on_start: false,
+            pmtu_paired_probes: false,
+            pmtu_confirm_on_converge: false,
+            pmtu_blackhole_loss_threshold: 0, // No black-hole detection by default
+            pmtu_blackhole_cooldown_ms: 10000,
+            pmtu_reprobe_loss_threshold: 3,
+            pmtu_backoff_max_ms: 0,       // Fixed probe interval by default
+            pmtu_max_replies_per_sec: 10, // Enough for discovery, not for a flood
+            pmtu_probe_retries: 2,
+            pmtu_reflected_probes: false,
+            pmtu_probes_count_as_activity: true,
+            rng_seed: None,
+        }
+    }
+}
+
+impl Config {
+    /// Returns a builder starting from the default configuration.
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    /// Returns the checksum datagrams carry: `checksum`, or `ChecksumAlgorithm::None` when
+    /// `use_checksums` is disabled or the path is trusted.
+    pub fn checksum_algorithm(&self) -> ChecksumAlgorithm {
+        if self.use_checksums && !self.trusted_path {
+            self.checksum
+        } else {
+            ChecksumAlgorithm::None
+        }
+    }
+
+    /// Checks that the options are consistent, naming the first offending field.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.idle_connection_timeout.is_zero() {
+            return Err(ConfigError::IdleConnectionTimeout);
+        }
+        if self.heartbeat_interval.is_some_and(|interval| interval.is_zero()) {
+            return Err(ConfigError::HeartbeatInterval);
+        }
+        if self.max_fragments == 0 {
+            return Err(ConfigError::MaxFragments);
+        }
+        if self.fragment_size == 0 || self.fragment_size as usize > self.receive_buffer_max_size {
+            return Err(ConfigError::FragmentSize);
+        }
+        if !(self.rtt_smoothing_factor > 0.0 && self.rtt_smoothing_factor < 1.0) {
+            return Err(ConfigError::RttSmoothingFactor);
+        }
+        if self.rto_min_ms > self.rto_max_ms {
+            return Err(ConfigError::RtoMinMs);
+        }
+        if !(self.rto_backoff_factor >= 1.0) {
+            return Err(ConfigError::RtoBackoffFactor);
+        }
+        if !(self.pacing_gain > 0.0) {
+            return Err(ConfigError::PacingGain);
+        }
+        if !(0.0..=1.0).contains(&self.health_max_loss_rate) {
+            return Err(ConfigError::HealthMaxLossRate);
+        }
+        if !(self.adaptive_compression_max_ratio > 0.0) {
+            return Err(ConfigError::AdaptiveCompressionMaxRatio);
+        }
+        if self.channel_count == 0 {
+            return Err(ConfigError::ChannelCount);
+        }
+        if self.throttle_interval == 0 {
+            return Err(ConfigError::ThrottleInterval);
+        }
+        if self.min_window_size > self.max_window_size {
+            return Err(ConfigError::MinWindowSize);
         }
+        if !(self.min_window_size..=self.max_window_size).contains(&self.initial_window_size) {
+            return Err(ConfigError::InitialWindowSize);
+        }
+        if self.pmtu_min > self.pmtu_max {
+            return Err(ConfigError::PmtuMin);
+        }
+        if self.pmtu_interval_ms == 0 {
+            return Err(ConfigError::PmtuIntervalMs);
+        }
+        if self.pmtu_interval_jitter_pct > 100 {
+            return Err(ConfigError::PmtuIntervalJitterPct);
+        }
+        if self.pmtu_converge_threshold > self.pmtu_max - self.pmtu_min {
+            return Err(ConfigError::PmtuConvergeThreshold);
+        }
+        if self.pmtu_search_mode == PmtuSearchMode::Schedule && self.pmtu_candidate_sizes.is_empty()
+        {
+            return Err(ConfigError::PmtuCandidateSizes);
+        }
+        Ok(())
+    }
+}
+
+/// A [`Config`] option that is out of range or inconsistent with another, as reported by
+/// [`Config::validate`] and [`ConfigBuilder::build`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// `idle_connection_timeout` is zero
+    IdleConnectionTimeout,
+    /// `heartbeat_interval` is set to zero
+    HeartbeatInterval,
+    /// `max_fragments` is zero
+    MaxFragments,
+    /// `fragment_size` is zero or larger than `receive_buffer_max_size`
+    FragmentSize,
+    /// `rtt_smoothing_factor` is not strictly between 0 and 1
+    RttSmoothingFactor,
+    /// `rto_min_ms` is larger than `rto_max_ms`
+    RtoMinMs,
+    /// `rto_backoff_factor` is below 1
+    RtoBackoffFactor,
+    /// `pacing_gain` is not positive
+    PacingGain,
+    /// `health_max_loss_rate` is not between 0 and 1
+    HealthMaxLossRate,
+    /// `adaptive_compression_max_ratio` is not positive
+    AdaptiveCompressionMaxRatio,
+    /// `channel_count` is zero
+    ChannelCount,
+    /// `throttle_interval` is zero
+    ThrottleInterval,
+    /// `min_window_size` is larger than `max_window_size`
+    MinWindowSize,
+    /// `initial_window_size` is outside `min_window_size..=max_window_size`
+    InitialWindowSize,
+    /// `pmtu_min` is larger than `pmtu_max`
+    PmtuMin,
+    /// `pmtu_interval_ms` is zero
+    PmtuIntervalMs,
+    /// `pmtu_interval_jitter_pct` is above 100
+    PmtuIntervalJitterPct,
+    /// `pmtu_converge_threshold` is larger than the whole `pmtu_min..=pmtu_max` range
+    PmtuConvergeThreshold,
+    /// `pmtu_search_mode` is `Schedule` but `pmtu_candidate_sizes` is empty
+    PmtuCandidateSizes,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::IdleConnectionTimeout => {
+                write!(fmt, "idle_connection_timeout must not be zero.")
+            }
+            ConfigError::HeartbeatInterval => write!(fmt, "heartbeat_interval must not be zero."),
+            ConfigError::MaxFragments => write!(fmt, "max_fragments must not be zero."),
+            ConfigError::FragmentSize => {
+                write!(fmt, "fragment_size must be non-zero and at most receive_buffer_max_size.")
+            }
+            ConfigError::RttSmoothingFactor => {
+                write!(fmt, "rtt_smoothing_factor must be between 0 and 1 (exclusive).")
+            }
+            ConfigError::RtoMinMs => write!(fmt, "rto_min_ms must not exceed rto_max_ms."),
+            ConfigError::RtoBackoffFactor => {
+                write!(fmt, "rto_backoff_factor must be at least 1.")
+            }
+            ConfigError::PacingGain => write!(fmt, "pacing_gain must be positive."),
+            ConfigError::HealthMaxLossRate => {
+                write!(fmt, "health_max_loss_rate must be between 0 and 1.")
+            }
+            ConfigError::AdaptiveCompressionMaxRatio => {
+                write!(fmt, "adaptive_compression_max_ratio must be positive.")
+            }
+            ConfigError::ChannelCount => write!(fmt, "channel_count must not be zero."),
+            ConfigError::ThrottleInterval => write!(fmt, "throttle_interval must not be zero."),
+            ConfigError::MinWindowSize => {
+                write!(fmt, "min_window_size must not exceed max_window_size.")
+            }
+            ConfigError::InitialWindowSize => write!(
+                fmt,
+                "initial_window_size must be between min_window_size and max_window_size."
+            ),
+            ConfigError::PmtuMin => write!(fmt, "pmtu_min must not exceed pmtu_max."),
+            ConfigError::PmtuIntervalMs => write!(fmt, "pmtu_interval_ms must not be zero."),
+            ConfigError::PmtuIntervalJitterPct => {
+                write!(fmt, "pmtu_interval_jitter_pct must not exceed 100.")
+            }
+            ConfigError::PmtuConvergeThreshold => write!(
+                fmt,
+                "pmtu_converge_threshold must not exceed the range between pmtu_min and pmtu_max."
+            ),
+            ConfigError::PmtuCandidateSizes => write!(
+                fmt,
+                "pmtu_candidate_sizes must not be empty with the Schedule pmtu_search_mode."
+            ),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// Builds a validated [`Config`], starting from the defaults.
+///
+/// ```
+/// use bitfold_core::config::Config;
+///
+/// let config = Config::builder().pmtu_min(1200).pmtu_max(1400).build().unwrap();
+/// assert_eq!(config.pmtu_min, 1200);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+/// Generates a setter for each listed `Config` field.
+macro_rules! builder_setters {
+    ($($field:ident: $ty:ty,)*) => {
+        $(
+            #[doc = concat!("Sets [`Config::", stringify!($field), "`].")]
+            pub fn $field(mut self, value: $ty) -> Self {
+                self.config.$field = value;
+                self
+            }
+        )*
+    };
+}
+
+impl ConfigBuilder {
+    builder_setters! {
+        blocking_mode: bool,
+        idle_connection_timeout: Duration,
+        disconnect_timeout: Duration,
+        ping_timeout: Duration,
+        heartbeat_interval: Option<Duration>,
+        heartbeat_on_send_idle: bool,
+        max_packet_size: usize,
+        max_fragments: u8,
+        fragment_size: u16,
+        fragment_reassembly_buffer_size: u16,
+        receive_buffer_max_size: usize,
+        max_reassembly_bytes: usize,
+        rtt_smoothing_factor: f32,
+        rtt_max_value: u16,
+        rto_min_ms: u32,
+        rto_max_ms: u32,
+        rto_backoff_factor: f32,
+        health_max_loss_rate: f32,
+        loss_reorder_threshold: u16,
+        loss_time_threshold: f32,
+        socket_event_buffer_size: usize,
+        socket_polling_timeout: Option<Duration>,
+        max_datagrams_per_tick: usize,
+        max_packets_in_flight: u16,
+        max_unestablished_connections: u16,
+        channel_count: u8,
+        incoming_bandwidth_limit: u32,
+        outgoing_bandwidth_limit: u32,
+        pacing_burst_packets: u32,
+        use_pacing: bool,
+        pacing_gain: f32,
+        use_checksums: bool,
+        checksum: ChecksumAlgorithm,
+        ack_policy: AckPolicy,
+        ack_delay_ms: u32,
+        sack_max_ranges: u8,
+        checksum_data_only: bool,
+        compression: CompressionAlgorithm,
+        compression_threshold: usize,
+        adaptive_compression_window: usize,
+        adaptive_compression_max_ratio: f32,
+        trusted_path: bool,
+        use_connection_handshake: bool,
+        early_data_max_bytes: usize,
+        max_waiting_data: usize,
+        flush_threshold_bytes: usize,
+        global_memory_budget: usize,
+        use_advanced_throttling: bool,
+        throttle_scale: u32,
+        throttle_acceleration: u32,
+        throttle_deceleration: u32,
+        throttle_interval: u32,
+        adaptive_congestion_signal: bool,
+        use_window_flow_control: bool,
+        initial_window_size: u32,
+        min_window_size: u32,
+        max_window_size: u32,
+        flow_control_probe_interval_ms: u32,
+        congestion_controller: CongestionControllerKind,
+        max_duplicate_peers: u16,
+        path_validation_timeout_ms: u32,
+        path_amplification_factor: u32,
+        socket_recv_buffer_size: Option<usize>,
+        socket_send_buffer_size: Option<usize>,
+        socket_ttl: Option<u32>,
+        socket_broadcast: bool,
+        use_pmtu_discovery: bool,
+        pmtu_min: u16,
+        pmtu_max: u16,
+        pmtu_interval_ms: u32,
+        pmtu_interval_jitter_pct: u8,
+        pmtu_converge_threshold: u16,
+        pmtu_converge_pct: u8,
+        pmtu_discovery_timeout_ms: u32,
+        pmtu_safe_floor: u16,
+        pmtu_candidate_sizes: Vec<u16>,
+        pmtu_probe_quantum: u16,
+        pmtu_search_mode: PmtuSearchMode,
+        pmtu_probe_payload_mode: PmtuProbePayload,
+        pmtu_sequential_tokens: bool,
+        pmtu_probe_on_start: bool,
+        pmtu_paired_probes: bool,
+        pmtu_confirm_on_converge: bool,
+        pmtu_blackhole_loss_threshold: u32,
+        pmtu_blackhole_cooldown_ms: u32,
+        pmtu_reprobe_loss_threshold: u32,
+        pmtu_backoff_max_ms: u32,
+        pmtu_max_replies_per_sec: u32,
+        pmtu_probe_retries: u8,
+        pmtu_reflected_probes: bool,
+        pmtu_probes_count_as_activity: bool,
+        rng_seed: Option<u64>,
+    }
+
+    /// Validates the options and returns the config (see [`Config::validate`]).
+    pub fn build(self) -> Result<Config, ConfigError> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_is_valid() {
+        assert_eq!(Config::default().validate(), Ok(()));
+        assert!(Config::builder().build().is_ok());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_default_config_round_trips_through_toml() {
+        let config = Config::default();
+        let text = toml::to_string(&config).unwrap();
+        assert_eq!(toml::from_str::<Config>(&text).unwrap(), config);
+    }
+
+    #[test]
+    fn test_builder_sets_fields() {
+        let config = Config::builder()
+            .pmtu_min(1200)
+            .pmtu_max(1500)
+            .channel_count(4)
+            .heartbeat_interval(Some(Duration::from_millis(250)))
+            .rng_seed(Some(7))
+            .build()
+            .unwrap();
+        assert_eq!((config.pmtu_min, config.pmtu_max), (1200, 1500));
+        assert_eq!(config.channel_count, 4);
+        assert_eq!(config.heartbeat_interval, Some(Duration::from_millis(250)));
+        assert_eq!(config.rng_seed, Some(7));
+    }
+
+    #[test]
+    fn test_builder_rejects_inconsistent_options() {
+        let cases = [
+            (Config::builder().pmtu_min(1500).pmtu_max(1400), ConfigError::PmtuMin),
+            (Config::builder().pmtu_converge_threshold(1000), ConfigError::PmtuConvergeThreshold),
+            (Config::builder().fragment_size(2000), ConfigError::FragmentSize),
+            (Config::builder().fragment_size(0), ConfigError::FragmentSize),
+            (Config::builder().pmtu_interval_ms(0), ConfigError::PmtuIntervalMs),
+            (Config::builder().throttle_interval(0), ConfigError::ThrottleInterval),
+            (
+                Config::builder().idle_connection_timeout(Duration::ZERO),
+                ConfigError::IdleConnectionTimeout,
+            ),
+            (
+                Config::builder().heartbeat_interval(Some(Duration::ZERO)),
+                ConfigError::HeartbeatInterval,
+            ),
+            (Config::builder().channel_count(0), ConfigError::ChannelCount),
+            (Config::builder().initial_window_size(8), ConfigError::InitialWindowSize),
+            (Config::builder().min_window_size(8192), ConfigError::MinWindowSize),
+            (Config::builder().rtt_smoothing_factor(1.5), ConfigError::RttSmoothingFactor),
+            (Config::builder().rto_min_ms(120_000), ConfigError::RtoMinMs),
+            (Config::builder().rto_backoff_factor(0.5), ConfigError::RtoBackoffFactor),
+            (Config::builder().pacing_gain(0.0), ConfigError::PacingGain),
+            (Config::builder().health_max_loss_rate(-0.1), ConfigError::HealthMaxLossRate),
+            (
+                Config::builder().adaptive_compression_max_ratio(0.0),
+                ConfigError::AdaptiveCompressionMaxRatio,
+            ),
+            (Config::builder().pmtu_interval_jitter_pct(101), ConfigError::PmtuIntervalJitterPct),
+            (
+                Config::builder().pmtu_search_mode(PmtuSearchMode::Schedule),
+                ConfigError::PmtuCandidateSizes,
+            ),
+            (Config::builder().max_fragments(0), ConfigError::MaxFragments),
+        ];
+        for (builder, error) in cases {
+            assert_eq!(builder.build().unwrap_err(), error);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_deserialize_partial_config_fills_defaults() {
+        use serde::{
+            de::{
+                value::{Error, MapDeserializer},
+                IntoDeserializer,
+            },
+            Deserialize,
+        };
+
+        let fields = [("channel_count", 4u32), ("max_window_size", 8)];
+        let config = Config::deserialize(MapDeserializer::<_, Error>::new(fields.into_iter()));
+        let mut expected = Config::default();
+        expected.channel_count = 4;
+        expected.max_window_size = 8;
+        assert_eq!(config, Ok(expected));
+
+        let compression = CompressionAlgorithm::deserialize("lz4".into_deserializer());
+        assert_eq!(compression, Ok::<_, Error>(CompressionAlgorithm::Lz4));
     }
 }
@@ -26,6 +26,10 @@ pub enum ErrorKind {
     ProtocolVersionMismatch,
     /// Expected header but could not be read from buffer.
     CouldNotReadHeader(String),
+    /// The connection's time-to-live elapsed and it is being closed.
+    TtlExpired,
+    /// An application ping was not answered within `Config::ping_timeout`.
+    PingTimeout,
 }
 
 impl Display for ErrorKind {
@@ -52,6 +56,12 @@ impl Display for ErrorKind {
             ErrorKind::CouldNotReadHeader(header) => {
                 write!(fmt, "Expected {} header but could not be read from buffer.", header)
             }
+            ErrorKind::TtlExpired => {
+                write!(fmt, "The connection time-to-live expired.")
+            }
+            ErrorKind::PingTimeout => {
+                write!(fmt, "The ping was not answered in time.")
+            }
         }
     }
 }
@@ -51,6 +51,24 @@ pub trait Interceptor: Send {
     /// * `true` - Continue sending the packet
     /// * `false` - Drop the packet (do not send)
     fn on_send(&mut self, addr: &SocketAddr, data: &mut Vec<u8>) -> bool;
+
+    /// Called instead of [`Interceptor::on_receive`] by the host; may also shrink the
+    /// datagram, e.g. to strip padding added by [`Interceptor::on_send`].
+    ///
+    /// # Returns
+    /// * `Some(len)` - Continue processing the first `len` bytes of `data`
+    /// * `None` - Drop the packet (do not process)
+    fn on_receive_trimmed(&mut self, addr: &SocketAddr, data: &mut [u8]) -> Option<usize> {
+        self.on_receive(addr, data).then_some(data.len())
+    }
+
+    /// Maximum number of bytes [`Interceptor::on_send`] adds to a datagram (default: 0).
+    ///
+    /// The host reserves this much of every datagram so transformed datagrams still fit
+    /// within the path MTU.
+    fn send_overhead(&self) -> usize {
+        0
+    }
 }
 
 /// No-op interceptor that passes all packets through unchanged.
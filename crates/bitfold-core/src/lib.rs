@@ -54,6 +54,8 @@ pub mod either;
 pub mod error;
 /// Packet interception for custom processing.
 pub mod interceptor;
+/// Shared memory budget accounting across connections.
+pub mod memory_budget;
 /// Packet pooling for memory efficiency.
 pub mod packet_pool;
 /// Shared, reference-counted byte slices with zero-copy slicing.
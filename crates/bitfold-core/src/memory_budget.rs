@@ -0,0 +1,143 @@
+//! Shared memory accounting across connections.
+//!
+//! A `MemoryBudget` is a cheaply clonable handle to a shared byte counter. Every peer of a
+//! host reports its send queue and reassembly buffer usage into the same budget, so the
+//! aggregate across all connections stays bounded.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+#[derive(Debug)]
+struct BudgetInner {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+/// Shared byte budget that connections reserve from and release back into.
+#[derive(Debug, Clone)]
+pub struct MemoryBudget {
+    inner: Arc<BudgetInner>,
+}
+
+impl MemoryBudget {
+    /// Creates a budget of `limit` bytes (0 = unlimited).
+    pub fn new(limit: usize) -> Self {
+        Self { inner: Arc::new(BudgetInner { limit, used: AtomicUsize::new(0) }) }
+    }
+
+    /// Creates a budget without a limit that only tracks usage.
+    pub fn unlimited() -> Self {
+        Self::new(0)
+    }
+
+    /// Returns the configured limit in bytes (0 = unlimited).
+    pub fn limit(&self) -> usize {
+        self.inner.limit
+    }
+
+    /// Returns the number of bytes currently reserved.
+    pub fn used(&self) -> usize {
+        self.inner.used.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes that can still be reserved.
+    pub fn available(&self) -> usize {
+        if self.inner.limit == 0 {
+            return usize::MAX;
+        }
+        self.inner.limit.saturating_sub(self.used())
+    }
+
+    /// Returns true if the budget is limited and fully reserved.
+    pub fn is_exhausted(&self) -> bool {
+        self.inner.limit > 0 && self.used() >= self.inner.limit
+    }
+
+    /// Reserves `bytes` if they fit within the limit. Returns false and reserves nothing otherwise.
+    pub fn try_reserve(&self, bytes: usize) -> bool {
+        let limit = self.inner.limit;
+        self.inner
+            .used
+            .fetch_update(Ordering::AcqRel, Ordering::Relaxed, |used| {
+                let new_used = used.checked_add(bytes)?;
+                if limit > 0 && new_used > limit {
+                    None
+                } else {
+                    Some(new_used)
+                }
+            })
+            .is_ok()
+    }
+
+    /// Reserves `bytes` regardless of the limit, e.g. when moving existing buffers between budgets.
+    pub fn force_reserve(&self, bytes: usize) {
+        self.inner.used.fetch_add(bytes, Ordering::AcqRel);
+    }
+
+    /// Releases previously reserved `bytes` back into the budget.
+    pub fn release(&self, bytes: usize) {
+        let _ = self.inner.used.fetch_update(Ordering::AcqRel, Ordering::Relaxed, |used| {
+            Some(used.saturating_sub(bytes))
+        });
+    }
+}
+
+impl Default for MemoryBudget {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve_within_limit() {
+        let budget = MemoryBudget::new(100);
+        assert!(budget.try_reserve(60));
+        assert!(budget.try_reserve(40));
+        assert_eq!(budget.used(), 100);
+        assert_eq!(budget.available(), 0);
+        assert!(budget.is_exhausted());
+    }
+
+    #[test]
+    fn test_reserve_refused_when_exceeding_limit() {
+        let budget = MemoryBudget::new(100);
+        assert!(budget.try_reserve(80));
+        assert!(!budget.try_reserve(21));
+        assert_eq!(budget.used(), 80);
+    }
+
+    #[test]
+    fn test_release_readmits() {
+        let budget = MemoryBudget::new(100);
+        assert!(budget.try_reserve(100));
+        assert!(!budget.try_reserve(1));
+
+        budget.release(50);
+        assert!(!budget.is_exhausted());
+        assert!(budget.try_reserve(50));
+    }
+
+    #[test]
+    fn test_clones_share_usage() {
+        let budget = MemoryBudget::new(100);
+        let other = budget.clone();
+        assert!(budget.try_reserve(70));
+        assert!(!other.try_reserve(31));
+        other.release(70);
+        assert_eq!(budget.used(), 0);
+    }
+
+    #[test]
+    fn test_unlimited_never_exhausts() {
+        let budget = MemoryBudget::unlimited();
+        assert!(budget.try_reserve(usize::MAX / 2));
+        assert!(!budget.is_exhausted());
+        assert_eq!(budget.available(), usize::MAX);
+    }
+}
@@ -1,4 +1,7 @@
-use std::sync::Arc;
+use std::{
+    ops::{Bound, RangeBounds},
+    sync::Arc,
+};
 
 /// SharedBytes is a reference-counted, sliceable byte buffer.
 ///
@@ -25,12 +28,41 @@ impl SharedBytes {
         Self { data, start: 0, len }
     }
 
-    /// Creates a sub-slice view into the current buffer without copying.
+    /// Creates a sub-slice view of `range` within the current view without copying.
     /// Panics if the requested range is out of bounds.
-    pub fn slice(&self, start: usize, len: usize) -> Self {
-        assert!(start <= self.len, "slice start out of bounds");
-        assert!(start + len <= self.len, "slice end out of bounds");
-        Self { data: self.data.clone(), start: self.start + start, len }
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Self {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.checked_add(1).expect("slice start overflows"),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.checked_add(1).expect("slice end overflows"),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.len,
+        };
+        assert!(start <= end, "slice start {} is past its end {}", start, end);
+        assert!(end <= self.len, "slice end {} out of bounds for length {}", end, self.len);
+        Self { data: self.data.clone(), start: self.start + start, len: end - start }
+    }
+
+    /// Splits off and returns the first `at` bytes, leaving the rest in `self`.
+    /// Both views share the same buffer. Panics if `at` is past the end.
+    pub fn split_to(&mut self, at: usize) -> Self {
+        assert!(at <= self.len, "split_to {} out of bounds for length {}", at, self.len);
+        let head = self.slice(..at);
+        self.start += at;
+        self.len -= at;
+        head
+    }
+
+    /// Splits off and returns the bytes from `at` onwards, leaving the first `at` in `self`.
+    /// Both views share the same buffer. Panics if `at` is past the end.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(at <= self.len, "split_off {} out of bounds for length {}", at, self.len);
+        let tail = self.slice(at..);
+        self.len = at;
+        tail
     }
 
     /// Returns the current view as a byte slice.
@@ -75,3 +107,82 @@ impl AsRef<[u8]> for SharedBytes {
         self.as_slice()
     }
 }
+
+/// Serializes the current view only, as a plain byte sequence.
+#[cfg(feature = "serde")]
+impl serde::Serialize for SharedBytes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_slice().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SharedBytes {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<u8>::deserialize(deserializer).map(Self::from_vec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shares_buffer(a: &SharedBytes, b: &SharedBytes) -> bool {
+        Arc::ptr_eq(&a.data, &b.data)
+    }
+
+    #[test]
+    fn test_slice_aliases_buffer() {
+        let bytes = SharedBytes::from_vec((0..10).collect());
+        let middle = bytes.slice(2..5);
+        assert_eq!(middle.as_slice(), &[2, 3, 4]);
+        assert!(shares_buffer(&bytes, &middle));
+        assert_eq!(middle.as_slice().as_ptr(), bytes.as_slice()[2..].as_ptr());
+        assert_eq!(Arc::strong_count(&bytes.data), 2);
+
+        assert_eq!(middle.slice(1..=1).as_slice(), &[3]);
+        assert_eq!(bytes.slice(..).as_slice(), bytes.as_slice());
+        assert!(bytes.slice(10..).is_empty());
+    }
+
+    #[test]
+    fn test_split_to_and_split_off_alias_buffer() {
+        let original = SharedBytes::from_vec((0..10).collect());
+        let mut rest = original.clone();
+        let header = rest.split_to(3);
+        assert_eq!(header.as_slice(), &[0, 1, 2]);
+        assert_eq!(rest.as_slice(), &[3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(rest.as_slice().as_ptr(), original.as_slice()[3..].as_ptr());
+
+        let tail = rest.split_off(4);
+        assert_eq!(rest.as_slice(), &[3, 4, 5, 6]);
+        assert_eq!(tail.as_slice(), &[7, 8, 9]);
+        assert!(shares_buffer(&original, &header) && shares_buffer(&original, &tail));
+        assert_eq!(Arc::strong_count(&original.data), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "slice end 11 out of bounds for length 10")]
+    fn test_slice_out_of_range_panics() {
+        SharedBytes::from_vec(vec![0; 10]).slice(5..11);
+    }
+
+    #[test]
+    #[should_panic(expected = "slice start 6 is past its end 5")]
+    fn test_slice_inverted_range_panics() {
+        let (start, end) = (6, 5);
+        SharedBytes::from_vec(vec![0; 10]).slice(start..end);
+    }
+
+    #[test]
+    #[should_panic(expected = "split_to 4 out of bounds for length 3")]
+    fn test_split_to_out_of_range_panics() {
+        SharedBytes::from_vec(vec![0; 3]).split_to(4);
+    }
+
+    #[test]
+    #[should_panic(expected = "split_off 4 out of bounds for length 3")]
+    fn test_split_off_out_of_range_panics() {
+        SharedBytes::from_vec(vec![0; 3]).split_off(4);
+    }
+}
@@ -0,0 +1,155 @@
+//! Connection ID registry guarding which address a connection is bound to.
+//!
+//! Each connection ID is bound to the address that first presented it. A different
+//! address presenting the same ID is only associated with it once its path has been
+//! validated; unvalidated attempts are rejected so they cannot take over the connection.
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+};
+
+/// Outcome of associating an address with a connection ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Association {
+    /// The connection ID was unknown and is now bound to the address.
+    New,
+    /// The connection ID is already bound to this address.
+    Existing,
+    /// The connection ID moved to this address after its path was validated.
+    Migrated,
+    /// The connection ID is bound to another address and this path is not validated.
+    Rejected,
+}
+
+/// Maps connection IDs to the address they are bound to.
+#[derive(Debug, Default)]
+pub struct ConnectionRegistry {
+    bindings: HashMap<u32, SocketAddr>,
+    validated: HashSet<(u32, SocketAddr)>,
+}
+
+impl ConnectionRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Associates `addr` with `connection_id`.
+    ///
+    /// Unknown IDs are bound to `addr`. An ID bound to another address is only moved
+    /// when `addr` has been validated for it with [`ConnectionRegistry::validate_path`].
+    pub fn associate(&mut self, connection_id: u32, addr: SocketAddr) -> Association {
+        match self.bindings.get(&connection_id) {
+            None => {
+                self.bindings.insert(connection_id, addr);
+                Association::New
+            }
+            Some(bound) if *bound == addr => Association::Existing,
+            Some(_) => {
+                if self.validated.remove(&(connection_id, addr)) {
+                    self.bindings.insert(connection_id, addr);
+                    Association::Migrated
+                } else {
+                    Association::Rejected
+                }
+            }
+        }
+    }
+
+    /// Returns whether [`ConnectionRegistry::associate`] would accept `addr` for `connection_id`.
+    pub fn can_associate(&self, connection_id: u32, addr: &SocketAddr) -> bool {
+        match self.bindings.get(&connection_id) {
+            None => true,
+            Some(bound) => bound == addr || self.validated.contains(&(connection_id, *addr)),
+        }
+    }
+
+    /// Marks `addr` as a validated path for `connection_id`.
+    pub fn validate_path(&mut self, connection_id: u32, addr: SocketAddr) {
+        self.validated.insert((connection_id, addr));
+    }
+
+    /// Returns whether `addr` is a validated but not yet associated path for `connection_id`.
+    pub fn is_path_validated(&self, connection_id: u32, addr: &SocketAddr) -> bool {
+        self.validated.contains(&(connection_id, *addr))
+    }
+
+    /// Returns the address `connection_id` is bound to.
+    pub fn address_of(&self, connection_id: u32) -> Option<SocketAddr> {
+        self.bindings.get(&connection_id).copied()
+    }
+
+    /// Removes the binding for `connection_id` if it is bound to `addr`.
+    pub fn remove(&mut self, connection_id: u32, addr: &SocketAddr) {
+        if self.bindings.get(&connection_id) == Some(addr) {
+            self.bindings.remove(&connection_id);
+            self.validated.retain(|(id, _)| *id != connection_id);
+        }
+    }
+
+    /// Returns the number of bound connection IDs.
+    pub fn len(&self) -> usize {
+        self.bindings.len()
+    }
+
+    /// Returns whether no connection IDs are bound.
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn test_first_address_binds_connection_id() {
+        let mut registry = ConnectionRegistry::new();
+        assert_eq!(registry.associate(7, addr(1000)), Association::New);
+        assert_eq!(registry.associate(7, addr(1000)), Association::Existing);
+        assert_eq!(registry.address_of(7), Some(addr(1000)));
+    }
+
+    #[test]
+    fn test_unvalidated_address_is_rejected() {
+        let mut registry = ConnectionRegistry::new();
+        registry.associate(7, addr(1000));
+
+        assert!(!registry.can_associate(7, &addr(2000)));
+        assert_eq!(registry.associate(7, addr(2000)), Association::Rejected);
+        assert_eq!(registry.address_of(7), Some(addr(1000)));
+    }
+
+    #[test]
+    fn test_validated_address_migrates_once() {
+        let mut registry = ConnectionRegistry::new();
+        registry.associate(7, addr(1000));
+        registry.validate_path(7, addr(2000));
+        assert!(registry.is_path_validated(7, &addr(2000)));
+
+        assert_eq!(registry.associate(7, addr(2000)), Association::Migrated);
+        assert_eq!(registry.address_of(7), Some(addr(2000)));
+        assert!(!registry.is_path_validated(7, &addr(2000)));
+
+        // Validation is consumed by the migration; the old address must validate again
+        assert_eq!(registry.associate(7, addr(1000)), Association::Rejected);
+    }
+
+    #[test]
+    fn test_remove_only_unbinds_owner() {
+        let mut registry = ConnectionRegistry::new();
+        registry.associate(7, addr(1000));
+
+        registry.remove(7, &addr(2000));
+        assert_eq!(registry.len(), 1);
+
+        registry.remove(7, &addr(1000));
+        assert!(registry.is_empty());
+        assert_eq!(registry.associate(7, addr(2000)), Association::New);
+    }
+}
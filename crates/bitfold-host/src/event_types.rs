@@ -7,6 +7,7 @@
 
 use std::net::SocketAddr;
 
+use bitfold_peer::CloseReason;
 use bitfold_protocol::packet::Packet;
 
 /// Actions that connections can request from the runtime.
@@ -15,6 +16,9 @@ use bitfold_protocol::packet::Packet;
 pub enum Action<E> {
     /// Send the given bytes to the connection's remote address
     Send(Vec<u8>),
+    /// Send the given bytes to another address, e.g. a path challenge to an address the
+    /// connection may migrate to
+    SendTo(SocketAddr, Vec<u8>),
     /// Emit an event to the user
     Emit(E),
 }
@@ -31,4 +35,6 @@ pub enum SocketEvent {
     Timeout(SocketAddr),
     /// The established connection to a client has timed out.
     Disconnect(SocketAddr),
+    /// A connection was closed and removed, and why.
+    Closed(SocketAddr, CloseReason),
 }
@@ -2,6 +2,8 @@
 
 //! Host socket and session manager for transport and peer coordination.
 
+/// Connection ID registry requiring path validation for new addresses.
+pub mod connection_registry;
 /// Event and action types (Action, SocketEvent).
 pub mod event_types;
 /// Session trait for managing a peer lifecycle.
@@ -17,6 +19,7 @@ pub mod time;
 
 mod peer_session;
 
+pub use connection_registry::{Association, ConnectionRegistry};
 pub use event_types::{Action, SocketEvent};
 pub use session::{Session, SessionEventAddress};
 pub use session_manager::SessionManager;
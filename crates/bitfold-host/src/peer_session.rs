@@ -1,8 +1,11 @@
 use std::{net::SocketAddr, time::Instant};
 
 use bitfold_core::error::ErrorKind;
-use bitfold_peer::{Peer, PeerState};
-use bitfold_protocol::packet::{DeliveryGuarantee, OrderingGuarantee, Packet};
+use bitfold_peer::{CloseReason, Peer, PeerState, DRAIN_REASON};
+use bitfold_protocol::{
+    command_codec::CommandEncoder,
+    packet::{DeliveryGuarantee, OrderingGuarantee, Packet},
+};
 use tracing::error;
 
 use super::{
@@ -19,6 +22,7 @@ impl SessionEventAddress for SocketEvent {
             SocketEvent::Connect(addr) => *addr,
             SocketEvent::Timeout(addr) => *addr,
             SocketEvent::Disconnect(addr) => *addr,
+            SocketEvent::Closed(addr, _) => *addr,
         }
     }
 }
@@ -31,6 +35,122 @@ impl SessionEventAddress for Packet {
     }
 }
 
+/// Returns the event reporting why `peer` closed.
+fn closed_event(peer: &Peer) -> Action<SocketEvent> {
+    // Every close path records a reason before the peer is dropped
+    let reason = peer.close_reason().unwrap_or(CloseReason::ProtocolError);
+    Action::Emit(SocketEvent::Closed(peer.remote_address, reason))
+}
+
+/// Converts a user packet into protocol commands on the peer's queue.
+fn enqueue_user_packet(peer: &mut Peer, event: Packet) {
+    // Convert user packet to command
+    let channel_id = event.channel_id();
+    let ordering = event.order_guarantee();
+    if event.immediate_ack_requested() {
+        peer.request_immediate_ack();
+    }
+    if peer.stream_mode(channel_id).is_some() {
+        peer.enqueue_stream_data(channel_id, event.payload_arc());
+        return;
+    }
+
+    match event.delivery_guarantee() {
+        DeliveryGuarantee::Reliable => {
+            // Use enqueue_reliable_data which handles fragmentation
+            // Reliable unordered when ordering is None, otherwise ordered
+            let ordered = !matches!(ordering, OrderingGuarantee::None);
+            peer.enqueue_reliable_data(channel_id, event.payload_arc(), ordered);
+        }
+        DeliveryGuarantee::Unreliable => {
+            use bitfold_protocol::packet::OrderingGuarantee;
+
+            match ordering {
+                OrderingGuarantee::Unsequenced => {
+                    // Unsequenced: prevents duplicates without ordering.
+                    // Chunk into multiple unsequenced commands if needed to fit MTU budget.
+                    let datagram_cap = peer.datagram_cap();
+                    let per_packet_overhead =
+                        CommandEncoder::packet_overhead_for_config(peer.config());
+                    let send_unsequenced_header =
+                        1 /* type */ + 1 /* channel */ + 2 /* unseq group */ + 2 /* len */; // = 6
+                    let max_payload_unseq = std::cmp::max(
+                        1,
+                        datagram_cap
+                            .saturating_sub(per_packet_overhead)
+                            .saturating_sub(2 /* len prefix */)
+                            .saturating_sub(send_unsequenced_header),
+                    );
+
+                    let base = bitfold_core::shared::SharedBytes::from_arc(event.payload_arc());
+                    let mut offset = 0usize;
+                    while offset < base.len() {
+                        let len = std::cmp::min(max_payload_unseq, base.len() - offset);
+                        let chunk = base.slice(offset..offset + len);
+                        let unsequenced_group = peer.next_unsequenced_group();
+                        peer.enqueue_command(
+                            bitfold_protocol::command::ProtocolCommand::SendUnsequenced {
+                                channel_id,
+                                unsequenced_group,
+                                data: chunk,
+                            },
+                        );
+                        offset += len;
+                    }
+                }
+                _ => {
+                    // Regular unreliable (no sequencing or ordering); allow fragmentation
+                    peer.enqueue_unreliable_data(channel_id, event.payload_arc());
+                }
+            }
+        }
+    }
+}
+
+/// Processes a received datagram, whichever address it came from (see
+/// [`Peer::on_datagram_from`]).
+fn receive_datagram(peer: &mut Peer, payload: &[u8], time: Instant) -> Vec<Action<SocketEvent>> {
+    let mut actions = Vec::new();
+    if !payload.is_empty() {
+        // Update inbound bandwidth window and enforce incoming bandwidth limit
+        peer.update_bandwidth_window(time);
+        if !peer.can_receive_within_bandwidth() {
+            // Over incoming bandwidth limit: drop packet for this window
+            tracing::warn!(
+                labels = ?peer.labels(),
+                "Dropping packet ({} bytes) from {} due to incoming bandwidth limit (utilization {:.2})",
+                payload.len(),
+                peer.remote_address,
+                peer.incoming_bandwidth_utilization()
+            );
+            return actions; // No actions emitted
+        }
+
+        // Track bytes received for bandwidth monitoring (only after passing the limit check)
+        peer.record_bytes_received(payload.len() as u32);
+
+        // Process command packet
+        match peer.process_command_packet(payload, time) {
+            Ok(packets) => {
+                if peer.record_recv() {
+                    actions.push(Action::Emit(SocketEvent::Connect(peer.remote_address)));
+                }
+                for incoming in packets {
+                    actions.push(Action::Emit(SocketEvent::Packet(incoming.0)));
+                }
+                // Release data buffered during the handshake; flushed on the next update
+                for early in peer.take_early_data() {
+                    enqueue_user_packet(peer, early);
+                }
+            }
+            Err(err) => error!("Error occurred processing command packet: {:?}", err),
+        }
+    } else {
+        error!("Error processing packet: {}", ErrorKind::ReceivedDataToShort);
+    }
+    actions
+}
+
 impl Session for Peer {
     type SendEvent = Packet;
     type ReceiveEvent = SocketEvent;
@@ -43,6 +163,16 @@ impl Session for Peer {
         Peer::new(address, config, time)
     }
 
+    fn drain_refusal(
+        config: &bitfold_core::config::Config,
+        address: SocketAddr,
+        time: Instant,
+    ) -> Option<Vec<u8>> {
+        let mut peer = Peer::new(address, config, time);
+        peer.disconnect_with_reason(DRAIN_REASON);
+        peer.encode_queued_commands().ok()
+    }
+
     fn is_established(&self) -> bool {
         self.is_established()
     }
@@ -53,63 +183,64 @@ impl Session for Peer {
         // Check if peer received disconnect command (zombie state)
         if self.state() == PeerState::Zombie {
             actions.push(Action::Emit(SocketEvent::Disconnect(self.remote_address)));
+            actions.push(closed_event(self));
+            return (true, actions);
+        }
+
+        // Give up on a close handshake the remote never completed
+        if self.is_shutdown_timed_out(time) {
+            actions.push(Action::Emit(SocketEvent::Disconnect(self.remote_address)));
+            actions.push(closed_event(self));
             return (true, actions);
         }
 
         // Check for timeout or too many packets in flight
         let should_drop = self.packets_in_flight() > self.config().max_packets_in_flight
-            || self.last_heard(time) >= self.config().idle_connection_timeout;
+            || self.last_heard(time) >= self.idle_timeout();
 
         if should_drop {
             actions.push(Action::Emit(SocketEvent::Timeout(self.remote_address)));
             if self.is_established() {
+                self.set_close_reason(CloseReason::IdleTimeout);
                 actions.push(Action::Emit(SocketEvent::Disconnect(self.remote_address)));
+            } else {
+                self.set_close_reason(CloseReason::HandshakeFailed);
             }
+            actions.push(closed_event(self));
         }
         (should_drop, actions)
     }
 
     fn process_packet(&mut self, payload: &[u8], time: Instant) -> Vec<Action<Self::ReceiveEvent>> {
-        let mut actions = Vec::new();
-        if !payload.is_empty() {
-            // Update inbound bandwidth window and enforce incoming bandwidth limit
-            self.update_bandwidth_window(time);
-            if !self.can_receive_within_bandwidth() {
-                // Over incoming bandwidth limit: drop packet for this window
-                tracing::warn!(
-                    "Dropping packet ({} bytes) from {} due to incoming bandwidth limit (utilization {:.2})",
-                    payload.len(),
-                    self.remote_address,
-                    self.incoming_bandwidth_utilization()
-                );
-                return actions; // No actions emitted
-            }
+        self.on_datagram_from(self.remote_address);
+        receive_datagram(self, payload, time)
+    }
 
-            // Track bytes received for bandwidth monitoring (only after passing the limit check)
-            self.record_bytes_received(payload.len() as u32);
+    fn address(&self) -> Option<SocketAddr> {
+        Some(self.remote_address)
+    }
 
-            // Process command packet
-            match self.process_command_packet(payload, time) {
-                Ok(packets) => {
-                    if self.record_recv() {
-                        actions.push(Action::Emit(SocketEvent::Connect(self.remote_address)));
-                    }
-                    for incoming in packets {
-                        actions.push(Action::Emit(SocketEvent::Packet(incoming.0)));
-                    }
-                }
-                Err(err) => error!("Error occurred processing command packet: {:?}", err),
-            }
-        } else {
-            error!("Error processing packet: {}", ErrorKind::ReceivedDataToShort);
-        }
-        actions
+    fn datagram_connection_id(
+        config: &bitfold_core::config::Config,
+        payload: &[u8],
+    ) -> Option<u32> {
+        Peer::datagram_connection_id(config, payload)
+    }
+
+    fn process_packet_from(
+        &mut self,
+        source: SocketAddr,
+        payload: &[u8],
+        time: Instant,
+    ) -> Vec<Action<Self::ReceiveEvent>> {
+        self.on_datagram_from(source);
+        receive_datagram(self, payload, time)
     }
 
     fn process_event(
         &mut self,
         event: Self::SendEvent,
-        _time: Instant,
+        time: Instant,
     ) -> Vec<Action<Self::ReceiveEvent>> {
         let mut actions = Vec::new();
         let addr = self.remote_address;
@@ -117,79 +248,31 @@ impl Session for Peer {
             actions.push(Action::Emit(SocketEvent::Connect(addr)));
         }
 
-        // Convert user packet to command
-        let channel_id = event.channel_id();
-        let ordering = event.order_guarantee();
-
-        match event.delivery_guarantee() {
-            DeliveryGuarantee::Reliable => {
-                // Use enqueue_reliable_data which handles fragmentation
-                // Reliable unordered when ordering is None, otherwise ordered
-                let ordered = !matches!(ordering, OrderingGuarantee::None);
-                self.enqueue_reliable_data(channel_id, event.payload_arc(), ordered);
-            }
-            DeliveryGuarantee::Unreliable => {
-                use bitfold_protocol::packet::OrderingGuarantee;
-
-                match ordering {
-                    OrderingGuarantee::Unsequenced => {
-                        // Unsequenced: prevents duplicates without ordering.
-                        // Chunk into multiple unsequenced commands if needed to fit MTU budget.
-                        let datagram_cap = std::cmp::min(
-                            self.current_fragment_size() as usize,
-                            self.config().receive_buffer_max_size,
-                        );
-                        let compression_overhead = match self.config().compression {
-                            bitfold_core::config::CompressionAlgorithm::Lz4 => 5,
-                            _ => 1,
-                        };
-                        let checksum_overhead = if self.config().use_checksums { 4 } else { 0 };
-                        let per_packet_overhead =
-                            1 /* command count */ + compression_overhead + checksum_overhead;
-                        let send_unsequenced_header =
-                            1 /* type */ + 1 /* channel */ + 2 /* unseq group */ + 2 /* len */; // = 6
-                        let max_payload_unseq = std::cmp::max(
-                            1,
-                            datagram_cap
-                                .saturating_sub(per_packet_overhead)
-                                .saturating_sub(2 /* len prefix */)
-                                .saturating_sub(send_unsequenced_header),
-                        );
-
-                        let base = bitfold_core::shared::SharedBytes::from_arc(event.payload_arc());
-                        let mut offset = 0usize;
-                        while offset < base.len() {
-                            let len = std::cmp::min(max_payload_unseq, base.len() - offset);
-                            let chunk = base.slice(offset, len);
-                            let unsequenced_group = self.next_unsequenced_group();
-                            self.enqueue_command(
-                                bitfold_protocol::command::ProtocolCommand::SendUnsequenced {
-                                    channel_id,
-                                    unsequenced_group,
-                                    data: chunk,
-                                },
-                            );
-                            offset += len;
-                        }
-                    }
-                    _ => {
-                        // Regular unreliable (no sequencing or ordering); allow fragmentation
-                        self.enqueue_unreliable_data(channel_id, event.payload_arc());
-                    }
-                }
-            }
+        // Hold application data until the handshake completes (`early_data_max_bytes`)
+        if self.state().is_disconnecting() {
+            // Draining after a disconnect: in-flight data completes, but nothing new is sent
+            tracing::debug!("Dropping packet to {}: connection is closing", addr);
+        } else if self.should_buffer_early_data() {
+            self.buffer_early_data(event);
+        } else {
+            enqueue_user_packet(self, event);
         }
-
-        // Flush commands immediately if within bandwidth, splitting into MTU-sized datagrams
-        while self.has_queued_commands() && self.can_send_within_bandwidth() {
-            let cap = std::cmp::min(
-                self.current_fragment_size() as usize,
-                self.config().receive_buffer_max_size,
-            );
+        // Let a silent remote recognize us in case our address changed
+        self.identify_if_silent(time);
+
+        // Flush commands immediately if within bandwidth, splitting into MTU-sized datagrams.
+        // Small writes are held until `flush_threshold_bytes` accumulate.
+        while self.has_queued_commands()
+            && self.should_flush()
+            && self.can_send_within_bandwidth()
+            && self.can_send_paced(time)
+        {
+            let cap = self.datagram_cap();
             match self.encode_queued_commands_bounded(cap) {
                 Ok(Some(bytes)) => {
-                    // Track bytes for bandwidth throttling
+                    // Track bytes for bandwidth throttling and pacing
                     self.record_bytes_sent(bytes.len() as u32);
+                    self.record_paced_send(bytes.len(), time);
                     actions.push(Action::Send(bytes));
                 }
                 Ok(None) => break,
@@ -204,18 +287,48 @@ impl Session for Peer {
         actions
     }
 
+    fn can_accept_event(&self, event: &Self::SendEvent) -> bool {
+        !self.should_buffer_early_data() || self.can_buffer_early_data(event.payload().len())
+    }
+
+    fn connection_id(&self) -> Option<u32> {
+        self.connection_id()
+    }
+
+    fn attach_memory_budget(&mut self, budget: bitfold_core::memory_budget::MemoryBudget) {
+        self.set_memory_budget(budget);
+    }
+
+    fn set_datagram_overhead(&mut self, bytes: usize) {
+        self.set_datagram_overhead(bytes);
+    }
+
+    fn max_receive_datagram_size(&self) -> Option<usize> {
+        Some(self.max_receive_datagram_size())
+    }
+
     fn update(&mut self, time: Instant) -> Vec<Action<Self::ReceiveEvent>> {
         let mut actions = Vec::new();
 
         // Update bandwidth tracking window
         self.update_bandwidth_window(time);
 
+        // Close connections whose TTL elapsed; the close handshake is flushed below
+        if let Err(err) = self.handle_ttl(time) {
+            tracing::info!(labels = ?self.labels(), "Closing {}: {}", self.remote_address, err);
+        }
+        self.handle_ping_timeouts(time);
+        // Reset the connection if a Connect with a new connect ID showed it is gone
+        self.handle_restart_check(time);
+
         // Enqueue ping for keepalive if needed
         if self.is_established() {
             if let Some(heartbeat_interval) = self.config().heartbeat_interval {
-                // Only send heartbeat when both directions have been idle long enough.
+                // Only send heartbeat when both directions have been idle long enough,
+                // unless outbound idleness alone is enough to keep NAT mappings open
                 if self.last_sent(time) >= heartbeat_interval
-                    && self.last_heard(time) >= heartbeat_interval
+                    && (self.config().heartbeat_on_send_idle
+                        || self.last_heard(time) >= heartbeat_interval)
                 {
                     // Use command-based Ping for keepalive
                     self.enqueue_ping_command(time.elapsed().as_millis() as u32);
@@ -223,16 +336,23 @@ impl Session for Peer {
             }
         }
 
+        // Enqueue a delayed ACK whose deadline has passed
+        self.handle_delayed_ack(time);
+        // Let a silent remote recognize us in case our address changed
+        self.identify_if_silent(time);
+
         // Flush any queued commands (ACKs, Pongs, Pings, etc.) if within bandwidth,
         // splitting into MTU-sized datagrams
-        while self.has_queued_commands() && self.can_send_within_bandwidth() {
-            let cap = std::cmp::min(
-                self.current_fragment_size() as usize,
-                self.config().receive_buffer_max_size,
-            );
+        while self.has_queued_commands()
+            && self.should_flush()
+            && self.can_send_within_bandwidth()
+            && self.can_send_paced(time)
+        {
+            let cap = self.datagram_cap();
             match self.encode_queued_commands_bounded(cap) {
                 Ok(Some(bytes)) => {
                     self.record_bytes_sent(bytes.len() as u32);
+                    self.record_paced_send(bytes.len(), time);
                     actions.push(Action::Send(bytes));
                 }
                 Ok(None) => break,
@@ -244,7 +364,15 @@ impl Session for Peer {
         }
 
         // Application-level PMTU discovery & per-peer fragment size tuning
-        self.handle_pmtu(time);
+        self.handle_pmtu_batch(time);
+
+        // Probe the remote's window if we've been blocked on a full one for too long
+        self.handle_window_probe(time);
+
+        // Challenge a new address the connection may migrate to
+        if let Some((candidate, challenge)) = self.poll_path_challenge(time) {
+            actions.push(Action::SendTo(candidate, challenge));
+        }
 
         actions
     }
@@ -296,6 +424,29 @@ mod tests {
         }));
     }
 
+    #[test]
+    fn heartbeat_sent_on_send_idle_despite_recent_inbound() {
+        let mut cfg = bitfold_core::config::Config::default();
+        cfg.heartbeat_interval = Some(Duration::from_millis(50));
+        cfg.heartbeat_on_send_idle = true;
+        cfg.use_checksums = false;
+        cfg.use_connection_handshake = false;
+        let start = Instant::now();
+
+        let mut conn = Peer::new("127.0.0.1:0".parse().unwrap(), &cfg, start);
+        conn.record_send();
+        conn.record_recv();
+
+        conn.last_sent = start - Duration::from_millis(55);
+        conn.last_heard = start - Duration::from_millis(10);
+
+        let actions = conn.update(start);
+        assert!(actions.iter().any(|a| match a {
+            Action::Send(bytes) => is_ping(bytes),
+            _ => false,
+        }));
+    }
+
     #[test]
     fn heartbeat_sent_when_bi_idle() {
         let mut cfg = bitfold_core::config::Config::default();
@@ -346,4 +497,250 @@ mod tests {
         let actions2 = <Peer as Session>::process_packet(&mut server, &encoded, start);
         assert!(actions2.iter().all(|a| !matches!(a, Action::Emit(SocketEvent::Packet(_)))));
     }
+
+    fn decode_commands(bytes: &[u8]) -> Vec<ProtocolCommand> {
+        let payload = CommandDecoder::validate_and_strip_checksum(bytes).unwrap();
+        let decompressed = CommandDecoder::decompress(payload).unwrap();
+        CommandDecoder::decode_packet(&decompressed).unwrap().commands
+    }
+
+    fn sent(actions: &[Action<SocketEvent>]) -> Vec<Vec<u8>> {
+        actions
+            .iter()
+            .filter_map(|a| match a {
+                Action::Send(bytes) => Some(bytes.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn early_data_delivered_after_handshake() {
+        let mut cfg = bitfold_core::config::Config::default();
+        cfg.early_data_max_bytes = 1024;
+        let start = Instant::now();
+        let client_addr = "127.0.0.1:4000".parse().unwrap();
+        let server_addr = "127.0.0.1:5000".parse().unwrap();
+        let mut client = Peer::new(server_addr, &cfg, start);
+        let mut server = Peer::new(client_addr, &cfg, start);
+
+        // Data sent during the handshake is held back; only the Connect goes out
+        let packet = Packet::reliable_ordered(server_addr, vec![7; 100], None);
+        let connect = sent(&client.process_event(packet, start));
+        assert_eq!(connect.len(), 1);
+        assert!(decode_commands(&connect[0]).iter().all(|cmd| !cmd.is_data()));
+        assert_eq!(client.early_data_bytes(), 100);
+
+        let actions = <Peer as Session>::process_packet(&mut server, &connect[0], start);
+        assert!(actions.iter().all(|a| !matches!(a, Action::Emit(SocketEvent::Packet(_)))));
+        let verify = sent(&server.update(start));
+        assert_eq!(verify.len(), 1);
+
+        // Handshake completes: buffered data is flushed and delivered
+        <Peer as Session>::process_packet(&mut client, &verify[0], start);
+        assert!(client.is_established());
+        assert_eq!(client.early_data_bytes(), 0);
+        let delivered: Vec<_> = sent(&client.update(start))
+            .iter()
+            .flat_map(|bytes| <Peer as Session>::process_packet(&mut server, bytes, start))
+            .filter_map(|a| match a {
+                Action::Emit(SocketEvent::Packet(packet)) => Some(packet.payload().to_vec()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(delivered, vec![vec![7; 100]]);
+    }
+
+    #[test]
+    fn small_writes_held_until_flush_threshold() {
+        let mut cfg = bitfold_core::config::Config::default();
+        cfg.use_connection_handshake = false;
+        cfg.flush_threshold_bytes = 100;
+        let start = Instant::now();
+        let addr = "127.0.0.1:5000".parse().unwrap();
+        let mut client = Peer::new(addr, &cfg, start);
+
+        // 80 bytes queued: held across updates
+        for _ in 0..2 {
+            let packet = Packet::unreliable(addr, vec![1; 40]);
+            assert!(sent(&client.process_event(packet, start)).is_empty());
+        }
+        assert!(sent(&client.update(start)).is_empty());
+
+        // Crossing the threshold sends everything coalesced into one datagram
+        let datagrams = sent(&client.process_event(Packet::unreliable(addr, vec![1; 40]), start));
+        assert_eq!(datagrams.len(), 1);
+        assert_eq!(decode_commands(&datagrams[0]).len(), 3);
+        assert!(!client.has_queued_commands());
+
+        // An explicit flush sends held data below the threshold
+        assert!(
+            sent(&client.process_event(Packet::unreliable(addr, vec![1; 40]), start)).is_empty()
+        );
+        client.request_flush();
+        let datagrams = sent(&client.update(start));
+        assert_eq!(datagrams.len(), 1);
+        assert_eq!(decode_commands(&datagrams[0]).len(), 1);
+        assert!(!client.should_flush());
+    }
+
+    fn closed_reason(actions: &[Action<SocketEvent>]) -> Option<CloseReason> {
+        actions.iter().find_map(|a| match a {
+            Action::Emit(SocketEvent::Closed(_, reason)) => Some(*reason),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn close_event_reports_timeouts() {
+        let cfg = bitfold_core::config::Config::default();
+        let start = Instant::now();
+        let idle = start + cfg.idle_connection_timeout;
+        let addr = "127.0.0.1:5000".parse().unwrap();
+
+        // The handshake never completes
+        let mut connecting = Peer::new(addr, &cfg, start);
+        connecting.record_send();
+        let (dropped, actions) = connecting.should_drop(idle);
+        assert!(dropped);
+        assert_eq!(closed_reason(&actions), Some(CloseReason::HandshakeFailed));
+
+        // An established connection goes quiet
+        let mut established = Peer::new(addr, &cfg, start);
+        established.record_send();
+        let connect = sent(&established.update(start));
+        let mut server = Peer::new(addr, &cfg, start);
+        <Peer as Session>::process_packet(&mut server, &connect[0], start);
+        let verify = sent(&server.update(start));
+        <Peer as Session>::process_packet(&mut established, &verify[0], start);
+        assert!(established.is_established());
+        let (dropped, actions) = established.should_drop(idle);
+        assert!(dropped);
+        assert_eq!(closed_reason(&actions), Some(CloseReason::IdleTimeout));
+        assert_eq!(established.close_reason(), Some(CloseReason::IdleTimeout));
+    }
+
+    #[test]
+    fn idle_timeout_uses_the_negotiated_value() {
+        let start = Instant::now();
+        let addr = "127.0.0.1:5000".parse().unwrap();
+        let client_cfg = bitfold_core::config::Config {
+            idle_connection_timeout: Duration::from_secs(30),
+            ..Default::default()
+        };
+        let server_cfg = bitfold_core::config::Config {
+            idle_connection_timeout: Duration::from_secs(10),
+            ..Default::default()
+        };
+
+        let mut client = Peer::new(addr, &client_cfg, start);
+        client.record_send();
+        let connect = sent(&client.update(start));
+        let mut server = Peer::new(addr, &server_cfg, start);
+        <Peer as Session>::process_packet(&mut server, &connect[0], start);
+        let verify = sent(&server.update(start));
+        <Peer as Session>::process_packet(&mut client, &verify[0], start);
+
+        // The client drops at the server's shorter timeout rather than its own
+        assert!(!client.should_drop(start + Duration::from_secs(9)).0);
+        assert!(client.should_drop(start + Duration::from_secs(10)).0);
+    }
+
+    #[test]
+    fn close_event_reports_remote_close() {
+        let cfg = bitfold_core::config::Config::default();
+        let start = Instant::now();
+        let addr = "127.0.0.1:5000".parse().unwrap();
+        let mut client = Peer::new(addr, &cfg, start);
+        let mut server = Peer::new(addr, &cfg, start);
+
+        client.disconnect_with_reason(3);
+        let datagrams = sent(&client.update(start));
+        <Peer as Session>::process_packet(&mut server, &datagrams[0], start);
+        let (dropped, actions) = server.should_drop(start);
+        assert!(dropped);
+        assert_eq!(closed_reason(&actions), Some(CloseReason::RemoteClose { code: 3 }));
+    }
+
+    #[test]
+    fn disconnect_sends_code_and_refuses_new_data() {
+        let mut cfg = bitfold_core::config::Config::default();
+        cfg.use_connection_handshake = false;
+        let start = Instant::now();
+        let addr = "127.0.0.1:5000".parse().unwrap();
+        let mut client = Peer::new(addr, &cfg, start);
+        client.record_send();
+        client.record_recv();
+
+        client.disconnect_with_reason(CloseReason::APPLICATION_CLOSE);
+        let packet = Packet::reliable_ordered(addr, vec![9; 10], None);
+        let datagrams = sent(&client.process_event(packet, start));
+        let commands: Vec<_> = datagrams.iter().flat_map(|bytes| decode_commands(bytes)).collect();
+        assert!(commands.iter().all(|cmd| !cmd.is_data()));
+        assert!(commands.iter().any(|cmd| matches!(cmd, ProtocolCommand::Disconnect {
+            reason: CloseReason::APPLICATION_CLOSE
+        })));
+        assert!(!client.has_queued_commands());
+    }
+
+    #[test]
+    fn pacing_allows_burst_then_spaces_datagrams() {
+        let mut cfg = bitfold_core::config::Config::default();
+        cfg.use_connection_handshake = false;
+        cfg.fragment_size = 1000;
+        cfg.outgoing_bandwidth_limit = 100_000;
+        cfg.pacing_burst_packets = 2;
+        let start = Instant::now();
+        let addr = "127.0.0.1:5000".parse().unwrap();
+        let mut client = Peer::new(addr, &cfg, start);
+        client.record_send();
+        client.record_recv();
+
+        // Five full datagrams of data after an idle period: two go out as a burst
+        let idle = start + Duration::from_secs(1);
+        let packet = Packet::unreliable(addr, vec![7; 4500]);
+        assert_eq!(sent(&client.process_event(packet, idle)).len(), 2);
+        assert!(client.has_queued_commands());
+        assert_eq!(sent(&client.update(idle)).len(), 0);
+
+        // The rest follow one per 10ms of credit
+        let delay = client.pacing_delay(idle);
+        assert_eq!(delay, Duration::from_millis(10));
+        assert_eq!(sent(&client.update(idle + delay)).len(), 1);
+        assert_eq!(sent(&client.update(idle + delay)).len(), 0);
+    }
+
+    #[test]
+    fn paused_sending_holds_data_but_keeps_connection_alive() {
+        let mut cfg = bitfold_core::config::Config::default();
+        cfg.use_connection_handshake = false;
+        cfg.heartbeat_interval = Some(Duration::from_millis(50));
+        let start = Instant::now();
+        let addr = "127.0.0.1:5000".parse().unwrap();
+        let mut client = Peer::new(addr, &cfg, start);
+        client.record_send();
+        client.record_recv();
+
+        client.pause_sending();
+        let packet = Packet::reliable_ordered(addr, vec![9; 10], None);
+        assert!(sent(&client.process_event(packet, start)).is_empty());
+        assert!(client.has_queued_commands());
+
+        // Keepalives still go out, without the held data
+        let later = start + Duration::from_millis(60);
+        let datagrams = sent(&client.update(later));
+        assert_eq!(datagrams.len(), 1);
+        let commands = decode_commands(&datagrams[0]);
+        assert!(commands.iter().all(|cmd| !cmd.is_data()));
+        assert!(commands.iter().any(|cmd| matches!(cmd, ProtocolCommand::Ping { .. })));
+
+        // Resuming flushes the held data
+        client.resume_sending();
+        let datagrams = sent(&client.update(later));
+        assert_eq!(datagrams.len(), 1);
+        assert!(decode_commands(&datagrams[0])
+            .iter()
+            .any(|cmd| matches!(cmd, ProtocolCommand::SendReliable { .. })));
+        assert!(!client.has_queued_commands());
+    }
 }
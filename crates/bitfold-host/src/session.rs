@@ -1,6 +1,6 @@
 use std::{fmt::Debug, net::SocketAddr, time::Instant};
 
-use bitfold_core::config::Config;
+use bitfold_core::{config::Config, memory_budget::MemoryBudget};
 
 use super::event_types::Action;
 
@@ -21,6 +21,12 @@ pub trait Session: Debug {
     /// Creates new session and initialize it.
     fn create_session(config: &Config, address: SocketAddr, time: Instant) -> Self;
 
+    /// Returns the datagram sent to a remote whose connection attempt is refused because the
+    /// host is draining, or `None` to drop the attempt silently.
+    fn drain_refusal(_config: &Config, _address: SocketAddr, _time: Instant) -> Option<Vec<u8>> {
+        None
+    }
+
     /// Sessions are considered established once they have both had a send and a receive.
     fn is_established(&self) -> bool;
 
@@ -30,6 +36,32 @@ pub trait Session: Debug {
     /// Processes a received packet: parse it and emit an event.
     fn process_packet(&mut self, payload: &[u8], time: Instant) -> Vec<Action<Self::ReceiveEvent>>;
 
+    /// Returns the address the session sends to, for sessions whose address changes when the
+    /// connection migrates (see [`Session::process_packet_from`]). The default, `None`, keeps
+    /// the address the session was created with.
+    fn address(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    /// Returns the connection ID a datagram from an unknown address identifies itself with,
+    /// if it decodes and carries one, so it can reach its session after the remote's address
+    /// changed (e.g. a NAT rebinding).
+    fn datagram_connection_id(_config: &Config, _payload: &[u8]) -> Option<u32> {
+        None
+    }
+
+    /// Processes a packet from `source`, an address other than the session's, that
+    /// [`Session::datagram_connection_id`] matched to this session. The session may migrate
+    /// to `source` once it validated the address.
+    fn process_packet_from(
+        &mut self,
+        _source: SocketAddr,
+        _payload: &[u8],
+        _time: Instant,
+    ) -> Vec<Action<Self::ReceiveEvent>> {
+        Vec::new()
+    }
+
     /// Processes a received event and send a packet.
     fn process_event(
         &mut self,
@@ -37,6 +69,30 @@ pub trait Session: Debug {
         time: Instant,
     ) -> Vec<Action<Self::ReceiveEvent>>;
 
+    /// Returns false if the session cannot take `event` yet, e.g. because the data it holds
+    /// until its handshake completes reached its limit. The event is then held back and
+    /// offered again on the next poll.
+    fn can_accept_event(&self, _event: &Self::SendEvent) -> bool {
+        true
+    }
+
     /// Processes session-related tasks: resend dropped packets, send heartbeat, etc.
     fn update(&mut self, time: Instant) -> Vec<Action<Self::ReceiveEvent>>;
+
+    /// Returns the connection ID this session is bound to, if one has been negotiated.
+    fn connection_id(&self) -> Option<u32> {
+        None
+    }
+
+    /// Attaches the host-wide memory budget that session buffers are reserved from.
+    fn attach_memory_budget(&mut self, _budget: MemoryBudget) {}
+
+    /// Reserves `bytes` of every outgoing datagram for the interceptor's transform.
+    fn set_datagram_overhead(&mut self, _bytes: usize) {}
+
+    /// Returns the largest datagram this session expects to receive, if it knows, so the
+    /// receive buffer can be sized to avoid truncation.
+    fn max_receive_datagram_size(&self) -> Option<usize> {
+        None
+    }
 }
@@ -8,13 +8,15 @@ use std::{
 use bitfold_core::{
     config::Config,
     interceptor::{Interceptor, NoOpInterceptor},
+    memory_budget::MemoryBudget,
     packet_pool::PacketAllocator,
     transport::Socket as TransportSocket,
 };
 use crossbeam_channel::{unbounded, Receiver, Sender};
-use tracing::error;
+use tracing::{error, warn};
 
 use super::{
+    connection_registry::{Association, ConnectionRegistry},
     event_types::Action,
     session::{Session, SessionEventAddress},
 };
@@ -96,6 +98,7 @@ impl<TSocket: TransportSocket, ReceiveEvent: Debug>
         for action in actions {
             match action {
                 Action::Send(bytes) => self.pending_sends.push((*address, bytes)),
+                Action::SendTo(to, bytes) => self.pending_sends.push((to, bytes)),
                 Action::Emit(ev) => self.pending_events.push(ev),
             }
         }
@@ -129,6 +132,8 @@ pub struct SessionManager<TSocket: TransportSocket, TSession: Session> {
     sessions: HashMap<SocketAddr, TSession>,
     receive_buffer: Vec<u8>,
     user_event_receiver: Receiver<TSession::SendEvent>,
+    /// User events a session could not take yet, offered again on the next poll
+    deferred_events: Vec<TSession::SendEvent>,
     messenger: SocketEventSenderAndConfig<TSocket, TSession::ReceiveEvent>,
     event_receiver: Receiver<TSession::ReceiveEvent>,
     user_event_sender: Sender<TSession::SendEvent>,
@@ -137,6 +142,12 @@ pub struct SessionManager<TSocket: TransportSocket, TSession: Session> {
     duplicate_peer_count: HashMap<IpAddr, usize>,
     /// Maximum number of duplicate peers allowed (0 = unlimited)
     max_duplicate_peers: u16,
+    /// Binds connection IDs to addresses; new addresses require path validation
+    connection_registry: ConnectionRegistry,
+    /// Budget shared by all sessions for queued and reassembly bytes
+    memory_budget: MemoryBudget,
+    /// Whether new connection attempts are refused (see [`SessionManager::drain`])
+    draining: bool,
 }
 
 impl<TSocket: TransportSocket, TSession: Session> SessionManager<TSocket, TSession> {
@@ -155,6 +166,7 @@ impl<TSocket: TransportSocket, TSession: Session> SessionManager<TSocket, TSessi
         let (user_event_sender, user_event_receiver) = unbounded();
         let max_unestablished_sessions = config.max_unestablished_connections;
         let max_duplicate_peers = config.max_duplicate_peers;
+        let memory_budget = MemoryBudget::new(config.global_memory_budget);
 
         let interceptor = interceptor.unwrap_or_else(|| Box::new(NoOpInterceptor));
 
@@ -162,34 +174,46 @@ impl<TSocket: TransportSocket, TSession: Session> SessionManager<TSocket, TSessi
             receive_buffer: vec![0; config.receive_buffer_max_size],
             sessions: Default::default(),
             user_event_receiver,
+            deferred_events: Vec::new(),
             messenger: SocketEventSenderAndConfig::new(config, socket, event_sender, interceptor),
             user_event_sender,
             event_receiver,
             max_unestablished_sessions,
             duplicate_peer_count: HashMap::new(),
             max_duplicate_peers,
+            connection_registry: ConnectionRegistry::new(),
+            memory_budget,
+            draining: false,
         }
     }
 
     /// Polls for network I/O and processes all sessions.
     pub fn manual_poll(&mut self, time: Instant) {
         let mut unestablished_sessions = self.unestablished_session_count();
+        let max_datagrams = self.messenger.config.max_datagrams_per_tick;
+        let mut datagrams = 0;
 
         loop {
+            // Leave the rest queued so one busy tick cannot starve timers and other peers
+            if max_datagrams > 0 && datagrams >= max_datagrams {
+                break;
+            }
             match self.messenger.socket.receive_packet(self.receive_buffer.as_mut()) {
                 Ok((payload, address)) => {
+                    datagrams += 1;
                     let payload_len = payload.len();
 
-                    // Call interceptor on received data
-                    let should_process = {
+                    // Call interceptor on received data; it may strip bytes added by the sender
+                    let processed_len = {
                         let buf_slice = &mut self.receive_buffer[..payload_len];
-                        self.messenger.interceptor.on_receive(&address, buf_slice)
+                        self.messenger.interceptor.on_receive_trimmed(&address, buf_slice)
                     };
 
-                    if !should_process {
+                    let Some(processed_len) = processed_len else {
                         // Interceptor dropped the packet
                         continue;
-                    }
+                    };
+                    let payload_len = processed_len.min(payload_len);
 
                     // Re-get payload reference after interceptor potentially modified it
                     let payload = &self.receive_buffer[..payload_len];
@@ -197,19 +221,84 @@ impl<TSocket: TransportSocket, TSession: Session> SessionManager<TSocket, TSessi
                     if let Some(session) = self.sessions.get_mut(&address) {
                         let was_est = session.is_established();
                         let actions = session.process_packet(payload, time);
+                        if let Some(id) = session.connection_id() {
+                            if self.connection_registry.associate(id, address)
+                                == Association::Rejected
+                            {
+                                // Connection ID belongs to another path: drop this session
+                                warn!(
+                                    "Rejecting connection ID {} from unvalidated address {}",
+                                    id, address
+                                );
+                                if !was_est {
+                                    unestablished_sessions -= 1;
+                                }
+                                self.sessions.remove(&address);
+                                self.decrement_duplicate_count(&address);
+                                continue;
+                            }
+                        }
                         self.messenger.handle_actions(&address, actions);
                         if !was_est && session.is_established() {
                             unestablished_sessions -= 1;
                         }
+                    } else if let Some(owner) =
+                        TSession::datagram_connection_id(&self.messenger.config, payload)
+                            .and_then(|id| self.connection_registry.address_of(id))
+                            .filter(|owner| self.sessions.contains_key(owner))
+                    {
+                        // A known connection's datagram from a new address, e.g. after a NAT
+                        // rebinding: its session validates the address before migrating
+                        let Some(session) = self.sessions.get_mut(&owner) else {
+                            continue;
+                        };
+                        let actions = session.process_packet_from(address, payload, time);
+                        let current = session.address().unwrap_or(owner);
+                        if current != owner {
+                            self.migrate_session(owner, current);
+                        }
+                        self.messenger.handle_actions(&current, actions);
                     } else {
+                        if self.memory_budget.is_exhausted() {
+                            // Refuse new connections until existing sessions free memory
+                            warn!(
+                                "Refusing new connection from {}: memory budget exhausted",
+                                address
+                            );
+                            continue;
+                        }
+                        if self.draining {
+                            // Tell the remote to go away instead of letting it retry
+                            if let Some(refusal) =
+                                TSession::drain_refusal(&self.messenger.config, address, time)
+                            {
+                                self.messenger.pending_sends.push((address, refusal));
+                            }
+                            continue;
+                        }
                         let mut session =
                             TSession::create_session(&self.messenger.config, address, time);
+                        session.attach_memory_budget(self.memory_budget.clone());
+                        session.set_datagram_overhead(self.messenger.interceptor.send_overhead());
                         let actions = session.process_packet(payload, time);
+                        if let Some(id) = session.connection_id() {
+                            if !self.connection_registry.can_associate(id, &address) {
+                                // Never let an unvalidated address take over an existing ID
+                                warn!(
+                                    "Rejecting connection ID {} from unvalidated address {}",
+                                    id, address
+                                );
+                                continue;
+                            }
+                        }
                         self.messenger.handle_actions(&address, actions);
                         // Check both unestablished limit and duplicate peer limit
                         if unestablished_sessions < self.max_unestablished_sessions as usize
                             && self.can_accept_duplicate(&address)
                         {
+                            if let Some(id) = session.connection_id() {
+                                self.connection_registry.associate(id, address);
+                            }
                             self.sessions.insert(address, session);
                             self.increment_duplicate_count(&address);
                             unestablished_sessions += 1;
@@ -228,8 +317,18 @@ impl<TSocket: TransportSocket, TSession: Session> SessionManager<TSocket, TSessi
             }
         }
 
-        while let Ok(event) = self.user_event_receiver.try_recv() {
+        // Events held back on the last poll go first, keeping each remote's events in order
+        let mut events = std::mem::take(&mut self.deferred_events);
+        events.extend(self.user_event_receiver.try_iter());
+        for event in events {
             let addr = event.address();
+            if self.deferred_events.iter().any(|deferred| deferred.address() == addr)
+                || self.sessions.get(&addr).is_some_and(|session| !session.can_accept_event(&event))
+            {
+                // Hold it until the session can take it, e.g. once its handshake completes
+                self.deferred_events.push(event);
+                continue;
+            }
 
             // Check if session exists and if we can accept a new duplicate
             let is_new_session = !self.sessions.contains_key(&addr);
@@ -254,7 +353,12 @@ impl<TSocket: TransportSocket, TSession: Session> SessionManager<TSocket, TSessi
                 }
                 Entry::Vacant(entry) => {
                     let mut session = TSession::create_session(&self.messenger.config, addr, time);
+                    session.attach_memory_budget(self.memory_budget.clone());
+                    session.set_datagram_overhead(self.messenger.interceptor.send_overhead());
                     let actions = session.process_event(event, time);
+                    if let Some(id) = session.connection_id() {
+                        self.connection_registry.associate(id, addr);
+                    }
                     entry.insert(session);
                     self.messenger.handle_actions(&addr, actions);
                     self.increment_duplicate_count(&addr);
@@ -279,13 +383,34 @@ impl<TSocket: TransportSocket, TSession: Session> SessionManager<TSocket, TSessi
 
         // Remove dropped sessions and decrement duplicate counts
         for addr in to_drop {
-            self.sessions.remove(&addr);
+            if let Some(id) = self.sessions.remove(&addr).and_then(|s| s.connection_id()) {
+                self.connection_registry.remove(id, &addr);
+            }
             self.decrement_duplicate_count(&addr);
         }
 
+        self.resize_receive_buffer();
         self.messenger.flush();
     }
 
+    /// Sizes the receive buffer for the largest datagram any session expects, never below
+    /// `receive_buffer_max_size` so datagrams from new remotes still fit.
+    fn resize_receive_buffer(&mut self) {
+        let size = self
+            .sessions
+            .values()
+            .filter_map(Session::max_receive_datagram_size)
+            .fold(self.messenger.config.receive_buffer_max_size, usize::max);
+        if size != self.receive_buffer.len() {
+            self.receive_buffer.resize(size, 0);
+        }
+    }
+
+    /// Returns the current size of the receive buffer in bytes.
+    pub fn receive_buffer_size(&self) -> usize {
+        self.receive_buffer.len()
+    }
+
     /// Returns the event sender for sending user events to sessions.
     pub fn event_sender(&self) -> &Sender<TSession::SendEvent> {
         &self.user_event_sender
@@ -311,6 +436,24 @@ impl<TSocket: TransportSocket, TSession: Session> SessionManager<TSocket, TSessi
         &mut self.messenger.socket
     }
 
+    /// Stops accepting new connections; existing sessions continue until they close.
+    ///
+    /// Connection attempts from unknown addresses are answered with
+    /// [`Session::drain_refusal`] and no session is created for them.
+    pub fn drain(&mut self) {
+        self.draining = true;
+    }
+
+    /// Returns true once [`SessionManager::drain`] has been called.
+    pub fn is_draining(&self) -> bool {
+        self.draining
+    }
+
+    /// Returns an iterator over mutable references to all sessions, keyed by address.
+    pub fn sessions_mut(&mut self) -> impl Iterator<Item = (&SocketAddr, &mut TSession)> {
+        self.sessions.iter_mut()
+    }
+
     /// Returns the number of active sessions.
     pub fn sessions_count(&self) -> usize {
         self.sessions.len()
@@ -362,8 +505,423 @@ impl<TSocket: TransportSocket, TSession: Session> SessionManager<TSocket, TSessi
         current_count < self.max_duplicate_peers as usize
     }
 
+    /// Moves the session at `from` to `to`, the address its connection migrated to.
+    fn migrate_session(&mut self, from: SocketAddr, to: SocketAddr) {
+        let Some(session) = self.sessions.remove(&from) else {
+            return;
+        };
+        if let Some(id) = session.connection_id() {
+            self.connection_registry.validate_path(id, to);
+            self.connection_registry.associate(id, to);
+        }
+        self.decrement_duplicate_count(&from);
+        self.increment_duplicate_count(&to);
+        self.sessions.insert(to, session);
+    }
+
+    /// Marks `addr` as a validated path for `connection_id`, allowing the ID to move to it.
+    pub fn validate_path(&mut self, connection_id: u32, addr: SocketAddr) {
+        self.connection_registry.validate_path(connection_id, addr);
+    }
+
+    /// Returns the memory budget shared by all sessions.
+    pub fn memory_budget(&self) -> &MemoryBudget {
+        &self.memory_budget
+    }
+
+    /// Returns the registry binding connection IDs to addresses.
+    pub fn connection_registry(&self) -> &ConnectionRegistry {
+        &self.connection_registry
+    }
+
     /// Returns the number of connections from a specific IP address.
     pub fn duplicate_peer_count(&self, addr: &SocketAddr) -> usize {
         self.duplicate_peer_count.get(&addr.ip()).copied().unwrap_or(0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, io};
+
+    use bitfold_peer::{CloseReason, Peer, DRAIN_REASON};
+    use bitfold_protocol::packet::Packet;
+
+    use super::*;
+    use crate::event_types::SocketEvent;
+
+    /// In-memory transport: queued inbound datagrams and recorded outbound ones.
+    #[derive(Debug, Default)]
+    struct MemorySocket {
+        inbound: VecDeque<(Vec<u8>, SocketAddr)>,
+        sent: Vec<(SocketAddr, Vec<u8>)>,
+    }
+
+    impl TransportSocket for MemorySocket {
+        fn send_packet(&mut self, addr: &SocketAddr, payload: &[u8]) -> io::Result<usize> {
+            self.sent.push((*addr, payload.to_vec()));
+            Ok(payload.len())
+        }
+
+        fn receive_packet<'a>(
+            &mut self,
+            buffer: &'a mut [u8],
+        ) -> io::Result<(&'a [u8], SocketAddr)> {
+            match self.inbound.pop_front() {
+                Some((payload, addr)) => {
+                    buffer[..payload.len()].copy_from_slice(&payload);
+                    Ok((&buffer[..payload.len()], addr))
+                }
+                None => Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+
+        fn local_addr(&self) -> io::Result<SocketAddr> {
+            Ok("127.0.0.1:9000".parse().unwrap())
+        }
+
+        fn is_blocking_mode(&self) -> bool {
+            false
+        }
+    }
+
+    fn connect_datagram(config: &Config, time: Instant) -> (Vec<u8>, u32) {
+        let server: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let mut client = Peer::new(server, config, time);
+        client.record_send();
+        let id = client.connection_id().unwrap();
+        (client.encode_queued_commands().unwrap(), id)
+    }
+
+    #[test]
+    fn test_duplicate_connection_id_from_new_address_is_rejected() {
+        let config = Config::default();
+        let time = Instant::now();
+        let mut manager: SessionManager<MemorySocket, Peer> =
+            SessionManager::new(MemorySocket::default(), config.clone());
+
+        let original: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let attacker: SocketAddr = "127.0.0.1:2000".parse().unwrap();
+        let (connect, id) = connect_datagram(&config, time);
+
+        manager.socket_mut().inbound.push_back((connect.clone(), original));
+        manager.manual_poll(time);
+        assert_eq!(manager.connection_registry().address_of(id), Some(original));
+        assert!(manager.session_mut(&original).is_some());
+
+        // Same connection ID replayed from another, unvalidated address
+        manager.socket_mut().sent.clear();
+        manager.socket_mut().inbound.push_back((connect, attacker));
+        manager.manual_poll(time);
+
+        assert!(manager.session_mut(&attacker).is_none());
+        assert!(manager.socket().sent.iter().all(|(addr, _)| *addr != attacker));
+        assert_eq!(manager.connection_registry().address_of(id), Some(original));
+        assert!(manager.session_mut(&original).is_some());
+    }
+
+    #[test]
+    fn test_duplicate_connection_id_accepted_after_path_validation() {
+        let config = Config::default();
+        let time = Instant::now();
+        let mut manager: SessionManager<MemorySocket, Peer> =
+            SessionManager::new(MemorySocket::default(), config.clone());
+
+        let original: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let migrated: SocketAddr = "127.0.0.1:2000".parse().unwrap();
+        let (connect, id) = connect_datagram(&config, time);
+
+        manager.socket_mut().inbound.push_back((connect.clone(), original));
+        manager.manual_poll(time);
+
+        manager.validate_path(id, migrated);
+        manager.socket_mut().inbound.push_back((connect, migrated));
+        manager.manual_poll(time);
+
+        assert!(manager.session_mut(&migrated).is_some());
+        assert_eq!(manager.connection_registry().address_of(id), Some(migrated));
+    }
+
+    #[test]
+    fn test_exhausted_memory_budget_refuses_new_connections() {
+        let mut config = Config::default();
+        config.global_memory_budget = 64;
+        let time = Instant::now();
+        let mut manager: SessionManager<MemorySocket, Peer> =
+            SessionManager::new(MemorySocket::default(), config.clone());
+
+        let first: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let second: SocketAddr = "127.0.0.1:2000".parse().unwrap();
+
+        // Hold the whole budget with bytes buffered outside the host
+        assert!(manager.memory_budget().try_reserve(64));
+        let (connect, _) = connect_datagram(&config, time);
+        manager.socket_mut().inbound.push_back((connect, first));
+        manager.manual_poll(time);
+        assert!(manager.session_mut(&first).is_none());
+
+        // Freeing memory re-admits new connections
+        manager.memory_budget().release(64);
+        let (connect, _) = connect_datagram(&config, time);
+        manager.socket_mut().inbound.push_back((connect, second));
+        manager.manual_poll(time);
+        assert!(manager.session_mut(&second).is_some());
+    }
+
+    /// Sends everything `actions` put on the wire to the manager from `from`.
+    fn deliver(
+        manager: &mut SessionManager<MemorySocket, Peer>,
+        actions: Vec<Action<SocketEvent>>,
+        from: SocketAddr,
+    ) {
+        for action in actions {
+            if let Action::Send(bytes) = action {
+                manager.socket_mut().inbound.push_back((bytes, from));
+            }
+        }
+    }
+
+    /// Feeds every datagram the manager sent to `to` into `peer`.
+    fn receive(manager: &mut SessionManager<MemorySocket, Peer>, peer: &mut Peer, to: SocketAddr) {
+        let sent = std::mem::take(&mut manager.socket_mut().sent);
+        for (_, bytes) in sent.into_iter().filter(|(addr, _)| *addr == to) {
+            <Peer as Session>::process_packet(peer, &bytes, Instant::now());
+        }
+    }
+
+    #[test]
+    fn test_draining_refuses_new_connections_and_keeps_existing_ones() {
+        let config = Config::default();
+        let time = Instant::now();
+        let mut manager: SessionManager<MemorySocket, Peer> =
+            SessionManager::new(MemorySocket::default(), config.clone());
+
+        let server: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let existing: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let newcomer: SocketAddr = "127.0.0.1:2000".parse().unwrap();
+
+        let mut client = Peer::new(server, &config, time);
+        client.record_send();
+        let connect = client.encode_queued_commands().unwrap();
+        manager.socket_mut().inbound.push_back((connect, existing));
+        manager.manual_poll(time);
+        receive(&mut manager, &mut client, existing);
+        assert!(client.is_established());
+
+        manager.drain();
+        assert!(manager.is_draining());
+
+        // A new connection attempt is refused with the drain reason
+        let (connect, _) = connect_datagram(&config, time);
+        manager.socket_mut().inbound.push_back((connect, newcomer));
+        manager.manual_poll(time);
+        assert!(manager.session_mut(&newcomer).is_none());
+        let mut refused = Peer::new(server, &config, time);
+        refused.record_send();
+        receive(&mut manager, &mut refused, newcomer);
+        assert_eq!(refused.close_reason(), Some(CloseReason::RemoteClose { code: DRAIN_REASON }));
+
+        // The existing connection keeps transferring data
+        let packet = Packet::reliable_ordered(server, b"still here".to_vec(), None);
+        let mut actions = client.process_event(packet, time);
+        actions.extend(<Peer as Session>::update(&mut client, time));
+        deliver(&mut manager, actions, existing);
+        manager.manual_poll(time);
+        assert_eq!(delivered(&manager), vec![b"still here".to_vec()]);
+        assert!(manager.session_mut(&existing).is_some());
+    }
+
+    #[test]
+    fn test_max_datagrams_per_tick_defers_the_rest() {
+        let mut config = Config::default();
+        config.max_datagrams_per_tick = 2;
+        let time = Instant::now();
+        let mut manager: SessionManager<MemorySocket, Peer> =
+            SessionManager::new(MemorySocket::default(), config.clone());
+
+        let clients: Vec<SocketAddr> =
+            (0..5).map(|i| format!("127.0.0.1:{}", 1000 + i).parse().unwrap()).collect();
+        for &client in &clients {
+            let (connect, _) = connect_datagram(&config, time);
+            manager.socket_mut().inbound.push_back((connect, client));
+        }
+
+        for (tick, remaining) in [(1, 3), (2, 1), (3, 0)] {
+            manager.manual_poll(time);
+            assert_eq!(manager.socket().inbound.len(), remaining);
+            let served = clients.iter().filter(|&&client| manager.sessions.contains_key(&client));
+            assert_eq!(served.count(), (2 * tick).min(clients.len()));
+        }
+    }
+
+    #[test]
+    fn test_receive_buffer_follows_largest_expected_datagram() {
+        let mut config = Config::default();
+        config.pmtu_max = 9000;
+        let time = Instant::now();
+        let mut manager: SessionManager<MemorySocket, Peer> =
+            SessionManager::new(MemorySocket::default(), config.clone());
+        assert_eq!(manager.receive_buffer_size(), config.receive_buffer_max_size);
+
+        let client: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let (connect, _) = connect_datagram(&config, time);
+        manager.socket_mut().inbound.push_back((connect, client));
+        manager.manual_poll(time);
+        assert_eq!(manager.receive_buffer_size(), config.receive_buffer_max_size);
+
+        // The path turns out to carry jumbo frames
+        let session = manager.session_mut(&client).unwrap();
+        let mut state = session.connection_state();
+        state.pmtu.fragment_size = 8000;
+        session.restore_state(&state, time);
+        manager.manual_poll(time);
+        assert_eq!(manager.receive_buffer_size(), 8000);
+    }
+
+    /// Returns the payloads of the packets the manager delivered to the application.
+    fn delivered(manager: &SessionManager<MemorySocket, Peer>) -> Vec<Vec<u8>> {
+        manager
+            .event_receiver()
+            .try_iter()
+            .filter_map(|event| match event {
+                SocketEvent::Packet(packet) => Some(packet.payload().to_vec()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rebound_client_migrates_its_session() {
+        let config = Config::default();
+        let time = Instant::now();
+        let mut manager: SessionManager<MemorySocket, Peer> =
+            SessionManager::new(MemorySocket::default(), config.clone());
+
+        let server: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let original: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let rebound: SocketAddr = "127.0.0.1:2000".parse().unwrap();
+        let attacker: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+
+        let mut client = Peer::new(server, &config, time);
+        client.record_send();
+        let connect = client.encode_queued_commands().unwrap();
+        manager.socket_mut().inbound.push_back((connect, original));
+        manager.manual_poll(time);
+        receive(&mut manager, &mut client, original);
+        let id = client.connection_id().unwrap();
+        let packet = Packet::reliable_ordered(server, b"hello".to_vec(), None);
+        deliver(&mut manager, client.process_event(packet, time), original);
+        manager.manual_poll(time);
+        assert_eq!(delivered(&manager), vec![b"hello".to_vec()]);
+
+        // The client's NAT rebinds while the server is silent; its next datagram carries
+        // the connection's identity and still reaches the session
+        let later = time + std::time::Duration::from_secs(2);
+        manager.socket_mut().sent.clear();
+        let packet = Packet::reliable_ordered(server, b"moved".to_vec(), None);
+        let actions = client.process_event(packet, later);
+        let identified: Vec<_> = actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::Send(bytes) => Some(bytes.clone()),
+                _ => None,
+            })
+            .collect();
+        deliver(&mut manager, actions, rebound);
+        manager.manual_poll(later);
+        assert_eq!(delivered(&manager), vec![b"moved".to_vec()]);
+        assert_eq!(manager.sessions_count(), 1);
+        assert!(manager.session_mut(&original).is_some());
+
+        // A replay from elsewhere cannot take over the validation of the rebound address
+        for bytes in identified {
+            manager.socket_mut().inbound.push_back((bytes, attacker));
+        }
+        manager.manual_poll(later);
+        assert!(manager.socket().sent.iter().all(|(addr, _)| *addr != attacker));
+
+        // The challenge goes to the new address; the response migrates the session there
+        assert!(manager.socket().sent.iter().any(|(addr, _)| *addr == rebound));
+        receive(&mut manager, &mut client, rebound);
+        deliver(&mut manager, <Peer as Session>::update(&mut client, later), rebound);
+        manager.manual_poll(later);
+        assert!(manager.session_mut(&original).is_none());
+        assert!(manager.session_mut(&rebound).is_some());
+        assert_eq!(manager.sessions_count(), 1);
+        assert_eq!(manager.connection_registry().address_of(id), Some(rebound));
+        assert_eq!(manager.duplicate_peer_count(&rebound), 1);
+
+        // Traffic continues on the new address
+        let packet = Packet::reliable_ordered(server, b"again".to_vec(), None);
+        deliver(&mut manager, client.process_event(packet, later), rebound);
+        manager.manual_poll(later);
+        assert_eq!(delivered(&manager), vec![b"again".to_vec()]);
+        assert!(manager.socket().sent.iter().all(|(addr, _)| *addr == rebound));
+    }
+
+    #[test]
+    fn test_unidentified_datagram_from_new_address_does_not_reach_session() {
+        let config = Config::default();
+        let time = Instant::now();
+        let mut manager: SessionManager<MemorySocket, Peer> =
+            SessionManager::new(MemorySocket::default(), config.clone());
+
+        let server: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let original: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let rebound: SocketAddr = "127.0.0.1:2000".parse().unwrap();
+
+        let mut client = Peer::new(server, &config, time);
+        client.record_send();
+        let connect = client.encode_queued_commands().unwrap();
+        manager.socket_mut().inbound.push_back((connect, original));
+        manager.manual_poll(time);
+        receive(&mut manager, &mut client, original);
+        let packet = Packet::reliable_ordered(server, b"hello".to_vec(), None);
+        deliver(&mut manager, client.process_event(packet, time), original);
+        manager.manual_poll(time);
+        delivered(&manager);
+
+        // Sent before the remote went silent, so without the connection's identity
+        let packet = Packet::reliable_ordered(server, b"lost".to_vec(), None);
+        deliver(&mut manager, client.process_event(packet, time), rebound);
+        manager.manual_poll(time);
+        assert!(delivered(&manager).is_empty());
+        let session = manager.session_mut(&original).unwrap();
+        assert_eq!(session.validating_address(), None);
+    }
+
+    #[test]
+    fn test_early_data_beyond_limit_waits_for_handshake() {
+        let mut config = Config::default();
+        config.early_data_max_bytes = 8;
+        let time = Instant::now();
+        let mut manager: SessionManager<MemorySocket, Peer> =
+            SessionManager::new(MemorySocket::default(), config.clone());
+
+        let server: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let local: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        for byte in 1..=3u8 {
+            let packet = Packet::reliable_ordered(server, vec![byte; 5], None);
+            manager.event_sender().send(packet).unwrap();
+        }
+        manager.manual_poll(time);
+        // Only the first packet fits the early data limit; the others are held, not dropped
+        assert_eq!(manager.session_mut(&server).unwrap().early_data_bytes(), 5);
+
+        let mut remote = Peer::new(local, &config, time);
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            let sent = std::mem::take(&mut manager.socket_mut().sent);
+            for (_, bytes) in sent {
+                for action in <Peer as Session>::process_packet(&mut remote, &bytes, time) {
+                    if let Action::Emit(SocketEvent::Packet(packet)) = action {
+                        received.push(packet.payload().to_vec());
+                    }
+                }
+            }
+            deliver(&mut manager, <Peer as Session>::update(&mut remote, time), server);
+            manager.manual_poll(time);
+        }
+        assert_eq!(received, vec![vec![1; 5], vec![2; 5], vec![3; 5]]);
+    }
+}
@@ -7,9 +7,12 @@ use std::{
 };
 
 use bitfold_core::{
-    config::Config, error::Result, interceptor::Interceptor, transport::Socket as TransportSocket,
+    config::Config,
+    error::{ErrorKind, Result},
+    interceptor::Interceptor,
+    transport::Socket as TransportSocket,
 };
-use bitfold_peer::Peer;
+use bitfold_peer::{ConnectionState, Peer, StreamMode};
 use bitfold_protocol::packet::{DeliveryGuarantee, OrderingGuarantee, Packet};
 use crossbeam_channel::{Receiver, Sender, TryRecvError};
 use socket2::Socket as Socket2;
@@ -20,6 +23,11 @@ use super::{
     time::{Clock, SystemClock},
 };
 
+/// Error for a per-peer operation on an address with no connection.
+fn peer_not_found(addr: SocketAddr) -> ErrorKind {
+    io::Error::new(io::ErrorKind::NotFound, format!("No connection to {}", addr)).into()
+}
+
 /// Applies socket options from configuration to a UdpSocket.
 fn apply_socket_options(socket: &UdpSocket, config: &Config) -> io::Result<()> {
     // Create socket2::Socket from UdpSocket for advanced options
@@ -252,6 +260,82 @@ impl Host {
         Ok(())
     }
 
+    /// Sets a deadline after which the connection to the specified peer is gracefully closed.
+    /// Fails with an `io::ErrorKind::NotFound` error if there is no such peer.
+    pub fn set_ttl(&mut self, addr: SocketAddr, deadline: Instant) -> Result<()> {
+        let session = self.handler.session_mut(&addr).ok_or_else(|| peer_not_found(addr))?;
+        session.set_ttl(deadline);
+        Ok(())
+    }
+
+    /// Sends data held for the specified peer by `flush_threshold_bytes` on the next poll.
+    /// Fails with an `io::ErrorKind::NotFound` error if there is no such peer.
+    pub fn flush(&mut self, addr: SocketAddr) -> Result<()> {
+        let session = self.handler.session_mut(&addr).ok_or_else(|| peer_not_found(addr))?;
+        session.request_flush();
+        Ok(())
+    }
+
+    /// Stops sending application data to the specified peer while keeping the connection
+    /// alive (see [`Peer::pause_sending`]).
+    /// Fails with an `io::ErrorKind::NotFound` error if there is no such peer.
+    pub fn pause_sending(&mut self, addr: SocketAddr) -> Result<()> {
+        let session = self.handler.session_mut(&addr).ok_or_else(|| peer_not_found(addr))?;
+        session.pause_sending();
+        Ok(())
+    }
+
+    /// Resumes sending application data to the specified peer.
+    /// Fails with an `io::ErrorKind::NotFound` error if there is no such peer.
+    pub fn resume_sending(&mut self, addr: SocketAddr) -> Result<()> {
+        let session = self.handler.session_mut(&addr).ok_or_else(|| peer_not_found(addr))?;
+        session.resume_sending();
+        Ok(())
+    }
+
+    /// Opens a stream on `channel_id` of the connection to the specified peer. Packets sent on
+    /// that channel afterwards are delivered according to `mode`, whatever their guarantees.
+    /// Fails with an `io::ErrorKind::NotFound` error if there is no such peer.
+    pub fn open_stream(
+        &mut self,
+        addr: SocketAddr,
+        channel_id: u8,
+        mode: StreamMode,
+    ) -> Result<()> {
+        let session = self.handler.session_mut(&addr).ok_or_else(|| peer_not_found(addr))?;
+        session.open_stream(channel_id, mode);
+        Ok(())
+    }
+
+    /// Gracefully shuts down the connection to the specified peer, returning its
+    /// persistable state (see [`Peer::shutdown`]), or `None` if there is no such peer.
+    pub fn shutdown(&mut self, addr: SocketAddr) -> Option<ConnectionState> {
+        let time = self.clock.now();
+        self.handler.session_mut(&addr).map(|session| session.shutdown(time))
+    }
+
+    /// Stops accepting new connections while existing ones continue, e.g. before a restart.
+    ///
+    /// New connection attempts are refused with a disconnect carrying
+    /// [`DRAIN_REASON`](bitfold_peer::DRAIN_REASON). If `deadline` is given, connections
+    /// still open at that time are gracefully closed, as with [`Host::set_ttl`]; an earlier
+    /// TTL already set on a connection is kept.
+    pub fn drain(&mut self, deadline: Option<Instant>) {
+        self.handler.drain();
+        if let Some(deadline) = deadline {
+            for (_, session) in self.handler.sessions_mut() {
+                if session.ttl().is_none_or(|ttl| deadline < ttl) {
+                    session.set_ttl(deadline);
+                }
+            }
+        }
+    }
+
+    /// Returns true once [`Host::drain`] has been called.
+    pub fn is_draining(&self) -> bool {
+        self.handler.is_draining()
+    }
+
     /// Broadcasts data to all established connections.
     ///
     /// This is a convenience method that sends the same packet to all connected peers.
@@ -391,6 +475,54 @@ mod tests {
         assert_eq!(count, established_count);
     }
 
+    #[test]
+    fn test_set_ttl_for_unknown_peer_fails() {
+        let mut host = Host::bind_any().unwrap();
+        let unknown = "127.0.0.1:9".parse().unwrap();
+
+        let result = host.set_ttl(unknown, Instant::now());
+        assert!(
+            matches!(result, Err(ErrorKind::IOError(e)) if e.kind() == io::ErrorKind::NotFound)
+        );
+    }
+
+    #[test]
+    fn test_flush_for_unknown_peer_fails() {
+        let mut host = Host::bind_any().unwrap();
+        let unknown = "127.0.0.1:9".parse().unwrap();
+
+        let result = host.flush(unknown);
+        assert!(
+            matches!(result, Err(ErrorKind::IOError(e)) if e.kind() == io::ErrorKind::NotFound)
+        );
+    }
+
+    #[test]
+    fn test_pause_and_resume_sending_for_unknown_peer_fail() {
+        let mut host = Host::bind_any().unwrap();
+        let unknown = "127.0.0.1:9".parse().unwrap();
+
+        let result = host.pause_sending(unknown);
+        assert!(
+            matches!(result, Err(ErrorKind::IOError(e)) if e.kind() == io::ErrorKind::NotFound)
+        );
+        let result = host.resume_sending(unknown);
+        assert!(
+            matches!(result, Err(ErrorKind::IOError(e)) if e.kind() == io::ErrorKind::NotFound)
+        );
+    }
+
+    #[test]
+    fn test_open_stream_for_unknown_peer_fails() {
+        let mut host = Host::bind_any().unwrap();
+        let unknown = "127.0.0.1:9".parse().unwrap();
+
+        let result = host.open_stream(unknown, 1, StreamMode::Unreliable);
+        assert!(
+            matches!(result, Err(ErrorKind::IOError(e)) if e.kind() == io::ErrorKind::NotFound)
+        );
+    }
+
     #[test]
     fn test_broadcast_reliable_convenience() {
         let mut host = Host::bind_any().unwrap();
@@ -769,4 +901,82 @@ mod tests {
         // The interceptor modified the packets during transit
         assert!(true, "Interceptor successfully modified packets");
     }
+
+    const PADDING: usize = 16;
+
+    /// Pads outgoing datagrams and strips the padding again on receipt.
+    #[derive(Clone, Default)]
+    struct PaddingInterceptor {
+        /// (encoded, on-the-wire) sizes of sent datagrams
+        sent_sizes: Arc<Mutex<Vec<(usize, usize)>>>,
+    }
+
+    impl Interceptor for PaddingInterceptor {
+        fn on_receive(&mut self, _addr: &SocketAddr, _data: &mut [u8]) -> bool {
+            true
+        }
+
+        fn on_receive_trimmed(&mut self, _addr: &SocketAddr, data: &mut [u8]) -> Option<usize> {
+            let len = data.len().checked_sub(PADDING)?;
+            data[len..].iter().all(|&byte| byte == 0xEE).then_some(len)
+        }
+
+        fn on_send(&mut self, _addr: &SocketAddr, data: &mut Vec<u8>) -> bool {
+            let encoded = data.len();
+            data.resize(encoded + PADDING, 0xEE);
+            self.sent_sizes.lock().unwrap().push((encoded, data.len()));
+            true
+        }
+
+        fn send_overhead(&self) -> usize {
+            PADDING
+        }
+    }
+
+    #[test]
+    fn test_padding_interceptor_round_trip() {
+        let mut config = Config::default();
+        config.blocking_mode = false;
+
+        let padding = PaddingInterceptor::default();
+        let sent_sizes = padding.sent_sizes.clone();
+        let mut server = Host::bind_with_interceptor(
+            "127.0.0.1:0",
+            config.clone(),
+            Box::new(PaddingInterceptor::default()),
+        )
+        .unwrap();
+        let server_addr = server.local_addr().unwrap();
+        let mut client =
+            Host::bind_with_interceptor("127.0.0.1:0", config, Box::new(padding)).unwrap();
+
+        // Large enough to be fragmented: every datagram must still fit the fragment size
+        let payload: Vec<u8> = (0..4000u32).map(|i| i as u8).collect();
+        client.send(Packet::reliable_ordered(server_addr, payload.clone(), None)).unwrap();
+
+        let now = Instant::now();
+        client.manual_poll(now);
+        let mut received = None;
+        for i in 0..20 {
+            server.manual_poll(now + Duration::from_millis(i));
+            while let Some(event) = server.recv() {
+                if let SocketEvent::Packet(packet) = event {
+                    received = Some(packet.payload().to_vec());
+                }
+            }
+            if received.is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(received, Some(payload));
+        let sent_sizes = sent_sizes.lock().unwrap();
+        assert!(sent_sizes.len() > 1);
+        let fragment_size = Config::default().fragment_size as usize;
+        for &(encoded, wire) in sent_sizes.iter() {
+            assert_eq!(wire, encoded + PADDING);
+            assert!(wire <= fragment_size, "{} > {}", wire, fragment_size);
+        }
+    }
 }
@@ -30,6 +30,30 @@ impl ChannelState {
         Self { expected_sequence: 0, buffered_packets: HashMap::new(), latest_sequence: 0 }
     }
 
+    /// Recreates a channel from its sequence counters and buffered out-of-order packets.
+    pub fn from_parts(
+        expected_sequence: u16,
+        latest_sequence: u16,
+        buffered_packets: HashMap<u16, SharedBytes>,
+    ) -> Self {
+        Self { expected_sequence, buffered_packets, latest_sequence }
+    }
+
+    /// Returns the next sequence number expected for ordered delivery.
+    pub fn expected_sequence(&self) -> u16 {
+        self.expected_sequence
+    }
+
+    /// Returns the latest sequence number seen for sequenced delivery.
+    pub fn latest_sequence(&self) -> u16 {
+        self.latest_sequence
+    }
+
+    /// Returns the out-of-order packets waiting for missing sequences.
+    pub fn buffered_packets(&self) -> impl Iterator<Item = (u16, &SharedBytes)> + '_ {
+        self.buffered_packets.iter().map(|(&sequence, data)| (sequence, data))
+    }
+
     /// Process an ordered packet. Returns packets ready for delivery (in order).
     /// Buffers out-of-order packets until the missing sequences arrive.
     pub fn process_ordered(&mut self, sequence: u16, data: SharedBytes) -> Vec<SharedBytes> {
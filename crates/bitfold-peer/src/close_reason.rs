@@ -0,0 +1,40 @@
+/// Disconnect reason code sent to connection attempts refused by a draining host.
+///
+/// A client refused this way closes with `CloseReason::RemoteClose { code: DRAIN_REASON }`.
+pub const DRAIN_REASON: u32 = u32::MAX;
+
+/// Why a connection closed.
+///
+/// Recorded by the peer when the close starts (see [`Peer::close_reason`](crate::Peer::close_reason));
+/// the first cause wins if several apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    /// Nothing was heard from the established remote for `idle_connection_timeout`, or it stopped
+    /// acknowledging (`max_packets_in_flight` exceeded)
+    IdleTimeout,
+    /// The remote sent a disconnect
+    RemoteClose {
+        /// Application-defined reason code sent by the remote
+        code: u32,
+    },
+    /// This side closed the connection (`disconnect`, `shutdown` or an expired TTL)
+    LocalClose {
+        /// Reason code sent to the remote
+        code: u32,
+    },
+    /// The remote started a new connection with a different connect ID, e.g. after a restart
+    Reset,
+    /// The handshake did not complete before `idle_connection_timeout`
+    HandshakeFailed,
+    /// The remote violated the protocol in a way the connection cannot recover from
+    ProtocolError,
+}
+
+impl CloseReason {
+    /// Reason code for an orderly close with nothing wrong (sent by `Peer::disconnect`)
+    pub const NO_ERROR: u32 = 0;
+    /// Reason code for closing because the remote violated the protocol
+    pub const PROTOCOL_VIOLATION: u32 = 1;
+    /// Reason code for a close requested by the application
+    pub const APPLICATION_CLOSE: u32 = 2;
+}
@@ -1,13 +1,15 @@
 use std::collections::VecDeque;
 
+use bitfold_core::config::CompressionAlgorithm;
 use bitfold_protocol::command::ProtocolCommand;
 
 /// Command queue for batching protocol commands before transmission.
 /// Commands are aggregated into larger packets to improve bandwidth utilization.
 #[derive(Debug)]
 pub struct CommandQueue {
-    /// Pending commands to be processed
-    commands: VecDeque<ProtocolCommand>,
+    /// Pending commands to be processed, each with the compression overriding the
+    /// connection's for the datagram carrying it
+    commands: VecDeque<(ProtocolCommand, Option<CompressionAlgorithm>)>,
     /// Maximum commands to queue before forcing a flush
     max_queue_size: usize,
 }
@@ -21,10 +23,25 @@ impl CommandQueue {
     /// Enqueues a protocol command for later processing.
     /// Returns true if the queue should be flushed (reached max size).
     pub fn enqueue(&mut self, command: ProtocolCommand) -> bool {
-        self.commands.push_back(command);
+        self.enqueue_with_compression(command, None)
+    }
+
+    /// Like [`CommandQueue::enqueue`], with a compression algorithm overriding the
+    /// connection's for the datagram carrying the command.
+    pub fn enqueue_with_compression(
+        &mut self,
+        command: ProtocolCommand,
+        compression: Option<CompressionAlgorithm>,
+    ) -> bool {
+        self.commands.push_back((command, compression));
         self.commands.len() >= self.max_queue_size
     }
 
+    /// Places a command at the head of the queue so it is sent before anything else.
+    pub fn enqueue_front(&mut self, command: ProtocolCommand) {
+        self.commands.push_front((command, None));
+    }
+
     /// Returns the number of queued commands.
     pub fn len(&self) -> usize {
         self.commands.len()
@@ -37,12 +54,26 @@ impl CommandQueue {
 
     /// Drains all commands from the queue for processing.
     pub fn drain(&mut self) -> impl Iterator<Item = ProtocolCommand> + '_ {
+        self.commands.drain(..).map(|(command, _)| command)
+    }
+
+    /// Drains all commands with their compression overrides.
+    pub fn drain_with_compression(
+        &mut self,
+    ) -> impl Iterator<Item = (ProtocolCommand, Option<CompressionAlgorithm>)> + '_ {
         self.commands.drain(..)
     }
 
     /// Returns an iterator over the commands without draining.
     pub fn iter(&self) -> impl Iterator<Item = &ProtocolCommand> {
-        self.commands.iter()
+        self.commands.iter().map(|(command, _)| command)
+    }
+
+    /// Returns an iterator over the commands and their compression overrides without draining.
+    pub fn iter_with_compression(
+        &self,
+    ) -> impl Iterator<Item = (&ProtocolCommand, Option<CompressionAlgorithm>)> {
+        self.commands.iter().map(|(command, compression)| (command, *compression))
     }
 
     /// Clears all pending commands.
@@ -121,6 +152,18 @@ mod tests {
         assert!(queue.is_empty());
     }
 
+    #[test]
+    fn test_queue_enqueue_front() {
+        let mut queue = CommandQueue::new(10);
+
+        queue.enqueue(ProtocolCommand::Ping { timestamp: 100 });
+        queue.enqueue_front(ProtocolCommand::Pong { timestamp: 200 });
+
+        let commands: Vec<_> = queue.drain().collect();
+        assert!(matches!(commands[0], ProtocolCommand::Pong { timestamp: 200 }));
+        assert!(matches!(commands[1], ProtocolCommand::Ping { timestamp: 100 }));
+    }
+
     #[test]
     fn test_queue_iter() {
         let mut queue = CommandQueue::new(10);
@@ -132,4 +175,23 @@ mod tests {
         assert_eq!(count, 2);
         assert_eq!(queue.len(), 2); // Iterator shouldn't drain
     }
+
+    #[test]
+    fn test_queue_keeps_compression_overrides() {
+        let mut queue = CommandQueue::new(10);
+
+        queue.enqueue(ProtocolCommand::Ping { timestamp: 100 });
+        queue.enqueue_with_compression(
+            ProtocolCommand::SendUnreliable { channel_id: 0, data: vec![1, 2, 3].into() },
+            Some(CompressionAlgorithm::Zlib),
+        );
+        queue.enqueue_front(ProtocolCommand::Pong { timestamp: 200 });
+
+        let overrides: Vec<_> =
+            queue.iter_with_compression().map(|(_, compression)| compression).collect();
+        assert_eq!(overrides, [None, None, Some(CompressionAlgorithm::Zlib)]);
+        let drained: Vec<_> = queue.drain_with_compression().collect();
+        assert_eq!(drained[2].1, Some(CompressionAlgorithm::Zlib));
+        assert!(queue.is_empty());
+    }
 }
@@ -0,0 +1,196 @@
+//! Pluggable congestion controllers gating reliable sends on bytes in flight.
+//!
+//! A peer built with `Config::congestion_controller` set holds a boxed [`CongestionController`]
+//! that is told about every acknowledged packet and every batch of lost packets, and is asked
+//! before reliable data is sent. Controllers beyond the built-in ones (e.g. BBR) can implement
+//! the trait externally and be installed with `Peer::set_congestion_controller`.
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use bitfold_peer::congestion_controller::{CongestionController, NewReno};
+//!
+//! let mut controller = NewReno::new(1200);
+//! let window = controller.congestion_window();
+//! controller.on_ack(1200, Duration::from_millis(50));
+//! assert_eq!(controller.congestion_window(), window + 1200);
+//! assert!(controller.can_send(window));
+//! ```
+
+use std::{fmt, time::Duration};
+
+use bitfold_core::config::{Config, CongestionControllerKind};
+
+/// Decides how many bytes may be in flight, from acknowledgments and losses.
+pub trait CongestionController: Send + fmt::Debug {
+    /// Called for each packet acknowledged, with its payload size and round-trip time.
+    fn on_ack(&mut self, bytes: usize, rtt: Duration);
+
+    /// Called with the total payload size of a batch of packets declared lost together.
+    fn on_loss(&mut self, bytes: usize);
+
+    /// Returns the congestion window in bytes.
+    fn congestion_window(&self) -> usize;
+
+    /// Returns true if more data may be sent with `bytes_in_flight` unacknowledged.
+    fn can_send(&self, bytes_in_flight: usize) -> bool {
+        bytes_in_flight < self.congestion_window()
+    }
+}
+
+/// Builds the controller selected by `Config::congestion_controller`, using `fragment_size`
+/// as the segment size. Returns None for [`CongestionControllerKind::None`].
+pub fn from_config(config: &Config) -> Option<Box<dyn CongestionController>> {
+    match config.congestion_controller {
+        CongestionControllerKind::None => None,
+        CongestionControllerKind::NewReno => Some(Box::new(NewReno::new(config.fragment_size))),
+        CongestionControllerKind::FixedWindow { bytes } => Some(Box::new(FixedWindow::new(bytes))),
+    }
+}
+
+/// NewReno-style controller: slow start, then additive increase, halving the window on loss.
+#[derive(Debug, Clone)]
+pub struct NewReno {
+    /// Maximum segment size in bytes
+    mss: usize,
+    /// Congestion window in bytes
+    cwnd: usize,
+    /// Slow start threshold in bytes; the window grows exponentially below it
+    ssthresh: usize,
+}
+
+impl NewReno {
+    /// Segments in the initial window (RFC 6928)
+    const INITIAL_SEGMENTS: usize = 10;
+    /// Segments the window never shrinks below
+    const MIN_SEGMENTS: usize = 2;
+
+    /// Creates a controller in slow start with a window of ten `mss`-byte segments.
+    pub fn new(mss: u16) -> Self {
+        let mss = usize::from(mss).max(1);
+        Self { mss, cwnd: mss * Self::INITIAL_SEGMENTS, ssthresh: usize::MAX }
+    }
+
+    /// Returns the slow start threshold in bytes (`usize::MAX` until the first loss).
+    pub fn ssthresh(&self) -> usize {
+        self.ssthresh
+    }
+
+    /// Returns true while the window grows exponentially.
+    pub fn in_slow_start(&self) -> bool {
+        self.cwnd < self.ssthresh
+    }
+}
+
+impl CongestionController for NewReno {
+    fn on_ack(&mut self, bytes: usize, _rtt: Duration) {
+        let increase = if self.in_slow_start() {
+            bytes
+        } else {
+            // Roughly one segment per window of acknowledged data
+            (self.mss * bytes / self.cwnd).max(1)
+        };
+        self.cwnd = self.cwnd.saturating_add(increase);
+    }
+
+    fn on_loss(&mut self, bytes: usize) {
+        if bytes == 0 {
+            return;
+        }
+        self.ssthresh = (self.cwnd / 2).max(self.mss * Self::MIN_SEGMENTS);
+        self.cwnd = self.ssthresh;
+    }
+
+    fn congestion_window(&self) -> usize {
+        self.cwnd
+    }
+}
+
+/// Controller with a window that never changes, for tests.
+#[derive(Debug, Clone)]
+pub struct FixedWindow {
+    window: usize,
+}
+
+impl FixedWindow {
+    /// Creates a controller allowing `window` bytes in flight.
+    pub fn new(window: usize) -> Self {
+        Self { window }
+    }
+}
+
+impl CongestionController for FixedWindow {
+    fn on_ack(&mut self, _bytes: usize, _rtt: Duration) {}
+
+    fn on_loss(&mut self, _bytes: usize) {}
+
+    fn congestion_window(&self) -> usize {
+        self.window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RTT: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn test_new_reno_slow_start_avoidance_and_loss() {
+        let mut controller = NewReno::new(1000);
+        assert_eq!(controller.congestion_window(), 10_000);
+        assert!(controller.in_slow_start());
+
+        // Slow start: every acknowledged byte grows the window by a byte
+        for _ in 0..10 {
+            controller.on_ack(1000, RTT);
+        }
+        assert_eq!(controller.congestion_window(), 20_000);
+
+        // Loss halves the window and ends slow start
+        controller.on_loss(1000);
+        assert_eq!(controller.congestion_window(), 10_000);
+        assert_eq!(controller.ssthresh(), 10_000);
+        assert!(!controller.in_slow_start());
+
+        // Congestion avoidance: a window's worth of acknowledgments adds about one segment
+        for _ in 0..10 {
+            controller.on_ack(1000, RTT);
+        }
+        let window = controller.congestion_window();
+        assert!((10_900..=11_000).contains(&window), "window {window}");
+
+        // Another loss halves again, but never below two segments
+        for _ in 0..10 {
+            controller.on_loss(1000);
+        }
+        assert_eq!(controller.congestion_window(), 2000);
+        assert!(controller.can_send(1999));
+        assert!(!controller.can_send(2000));
+    }
+
+    #[test]
+    fn test_fixed_window_ignores_acks_and_losses() {
+        let mut controller = FixedWindow::new(4096);
+        controller.on_ack(1000, RTT);
+        controller.on_loss(1000);
+        assert_eq!(controller.congestion_window(), 4096);
+        assert!(controller.can_send(4095));
+        assert!(!controller.can_send(4096));
+    }
+
+    #[test]
+    fn test_from_config() {
+        let mut config = Config::default();
+        assert!(from_config(&config).is_none());
+
+        config.congestion_controller = CongestionControllerKind::FixedWindow { bytes: 512 };
+        assert_eq!(from_config(&config).unwrap().congestion_window(), 512);
+
+        config.congestion_controller = CongestionControllerKind::NewReno;
+        config.fragment_size = 1000;
+        assert_eq!(from_config(&config).unwrap().congestion_window(), 10_000);
+    }
+}
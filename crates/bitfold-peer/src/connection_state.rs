@@ -0,0 +1,21 @@
+//! Persistable connection state for warm restarts.
+//!
+//! [`Peer::shutdown`](crate::Peer::shutdown) bundles the state a peer has learned about
+//! its path so that a later process can resume with it instead of starting cold.
+
+use bitfold_protocol::congestion::CongestionSnapshot;
+
+use crate::pmtu_discovery::PmtuSnapshot;
+
+/// State captured from a peer at shutdown, restorable with
+/// [`Peer::restore_state`](crate::Peer::restore_state).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConnectionState {
+    /// Discovered path MTU
+    pub pmtu: PmtuSnapshot,
+    /// Learned RTT and throttle
+    pub congestion: CongestionSnapshot,
+    /// Sequence number for the next outgoing packet, so a resumed connection never reuses one
+    pub next_sequence: u16,
+}
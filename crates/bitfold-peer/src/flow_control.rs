@@ -25,6 +25,23 @@
 //! **Decrease conditions**: Loss rate > 5% or RTT > 500ms
 //! - Window shrinks by ~6% (1/16) per adjustment
 //!
+//! # Window Probes
+//!
+//! A sender whose window is full waits for the receiver to report progress. If that report is
+//! lost the connection would stall, so while blocked the sender periodically sends a window
+//! probe (every `flow_control_probe_interval_ms`). The receiver answers with a window update
+//! carrying the total reliable bytes it has received, which [`FlowControl::apply_window_update`]
+//! uses to release the acknowledged part of the window.
+//!
+//! # Receive Window
+//!
+//! Independently of the sender's own window, the receiver advertises how many more bytes it
+//! can buffer (out-of-order and partially reassembled data) beyond what it has received. A
+//! window update carries that figure, and the sender stops releasing new reliable data once
+//! it has sent the advertised amount past the receiver's `received` count. Until the first
+//! update the sender assumes [`FlowControl::receive_window`]. A zero window blocks the sender
+//! just like a full window, so the probes above double as zero-window probes.
+//!
 //! # Example
 //!
 //! ```
@@ -49,6 +66,8 @@
 //! flow_control.adjust_window_size(&config, loss_rate, rtt_ms);
 //! ```
 
+use std::time::{Duration, Instant};
+
 use bitfold_core::config::Config;
 
 /// Window-based flow control state for managing reliable data transmission.
@@ -61,6 +80,16 @@ pub struct FlowControl {
     window_size: u32,
     /// Reliable data currently in transit (waiting for ACK), in bytes
     reliable_data_in_transit: u32,
+    /// Total reliable bytes ever sent (wrapping), compared against window updates
+    total_sent: u32,
+    /// When the window was first observed full, if it still is
+    blocked_since: Option<Instant>,
+    /// When the last window probe was sent while blocked
+    last_probe: Option<Instant>,
+    /// Receiver's `received` count as of its latest window update
+    remote_received: u32,
+    /// Bytes the receiver could buffer beyond `remote_received` as of its latest window update
+    remote_available: u32,
 }
 
 impl FlowControl {
@@ -80,7 +109,22 @@ impl FlowControl {
     /// let flow_control = FlowControl::new(&config);
     /// ```
     pub fn new(config: &Config) -> Self {
-        Self { window_size: config.initial_window_size, reliable_data_in_transit: 0 }
+        Self {
+            window_size: config.initial_window_size,
+            reliable_data_in_transit: 0,
+            total_sent: 0,
+            blocked_since: None,
+            last_probe: None,
+            remote_received: 0,
+            remote_available: Self::receive_window(config),
+        }
+    }
+
+    /// Returns the receive window: the bytes a receiver can buffer, `max_window_size`
+    /// datagrams of `receive_buffer_max_size`. Senders assume it until the first window update.
+    pub fn receive_window(config: &Config) -> u32 {
+        let datagram = u32::try_from(config.receive_buffer_max_size).unwrap_or(u32::MAX);
+        config.max_window_size.saturating_mul(datagram)
     }
 
     /// Returns the current window size (in packets).
@@ -91,6 +135,12 @@ impl FlowControl {
         self.window_size
     }
 
+    /// Returns the window in bytes: the window size times `fragment_size`, the approximate
+    /// size of a packet.
+    pub fn window_bytes(&self, config: &Config) -> u32 {
+        self.window_size.saturating_mul(config.fragment_size as u32)
+    }
+
     /// Returns the amount of reliable data currently in transit (in bytes).
     ///
     /// This tracks the total bytes of reliable data that have been sent but
@@ -121,6 +171,7 @@ impl FlowControl {
     /// * `data_size` - Size of the data being sent, in bytes
     pub fn record_reliable_data_sent(&mut self, data_size: u32) {
         self.reliable_data_in_transit = self.reliable_data_in_transit.saturating_add(data_size);
+        self.total_sent = self.total_sent.wrapping_add(data_size);
     }
 
     /// Records reliable data being acknowledged (removes from in-transit counter).
@@ -135,11 +186,107 @@ impl FlowControl {
         self.reliable_data_in_transit = self.reliable_data_in_transit.saturating_sub(data_size);
     }
 
+    /// Returns the total reliable bytes ever sent (wrapping).
+    pub fn total_sent(&self) -> u32 {
+        self.total_sent
+    }
+
+    /// Restores window and byte counters captured from a live connection, e.g. when migrating
+    /// it to another process.
+    ///
+    /// # Arguments
+    ///
+    /// * `window_size` - Window size in effect (packets)
+    /// * `reliable_data_in_transit` - Reliable bytes awaiting acknowledgment
+    /// * `total_sent` - Total reliable bytes ever sent (wrapping)
+    pub fn restore(&mut self, window_size: u32, reliable_data_in_transit: u32, total_sent: u32) {
+        self.window_size = window_size;
+        self.reliable_data_in_transit = reliable_data_in_transit;
+        self.total_sent = total_sent;
+        self.blocked_since = None;
+        self.last_probe = None;
+        // The receiver's window is only known again with its next update
+        self.remote_received = total_sent;
+    }
+
+    /// Applies a window update reported by the receiver.
+    ///
+    /// `received` is the total number of reliable bytes the receiver has seen (wrapping).
+    /// Anything sent beyond that is still in transit. The in-transit counter only ever shrinks
+    /// here, so a stale or bogus update never closes the window further.
+    ///
+    /// `available` replaces the advertised receive window unless the update is older than the
+    /// last one applied (a smaller `received`) or claims more than was ever sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `received` - Cumulative reliable bytes received by the remote peer
+    /// * `available` - Further bytes the remote peer can buffer
+    pub fn apply_window_update(&mut self, received: u32, available: u32) {
+        let outstanding = self.total_sent.wrapping_sub(received);
+        self.reliable_data_in_transit = self.reliable_data_in_transit.min(outstanding);
+        let newer = received.wrapping_sub(self.remote_received) <= u32::MAX / 2;
+        if newer && outstanding <= u32::MAX / 2 {
+            self.remote_received = received;
+            self.remote_available = available;
+        }
+        self.blocked_since = None;
+        self.last_probe = None;
+    }
+
+    /// Returns how many more bytes the receiver's advertised window admits.
+    pub fn remote_window_remaining(&self) -> u32 {
+        let outstanding = self.total_sent.wrapping_sub(self.remote_received);
+        self.remote_available.saturating_sub(outstanding)
+    }
+
+    /// Returns true if a window probe should be sent now.
+    ///
+    /// Probing only happens while window flow control is enabled and the window is full. The
+    /// first probe goes out one interval after the window filled up and subsequent probes
+    /// follow at the same interval until the window opens again. Records the probe time when
+    /// returning `true`.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Configuration containing the probe interval
+    /// * `packets_in_flight` - Current number of unacknowledged packets
+    /// * `time` - Current time
+    pub fn should_probe(&mut self, config: &Config, packets_in_flight: u16, time: Instant) -> bool {
+        if !config.use_window_flow_control || config.flow_control_probe_interval_ms == 0 {
+            return false;
+        }
+        if self.can_send_reliable(config, packets_in_flight) {
+            self.blocked_since = None;
+            self.last_probe = None;
+            return false;
+        }
+
+        let blocked_since = *self.blocked_since.get_or_insert(time);
+        let since = self.last_probe.unwrap_or(blocked_since);
+        let interval = Duration::from_millis(config.flow_control_probe_interval_ms as u64);
+        if time.saturating_duration_since(since) < interval {
+            return false;
+        }
+        self.last_probe = Some(time);
+        true
+    }
+
+    /// Returns when the next window probe is due, or None unless the window is known to be
+    /// blocked (as of the last [`FlowControl::should_probe`] call) and probing is enabled.
+    pub fn next_probe_time(&self, config: &Config) -> Option<Instant> {
+        if !config.use_window_flow_control || config.flow_control_probe_interval_ms == 0 {
+            return None;
+        }
+        let since = self.last_probe.or(self.blocked_since)?;
+        Some(since + Duration::from_millis(config.flow_control_probe_interval_ms as u64))
+    }
+
     /// Checks if we can send more data based on window-based flow control.
     ///
-    /// Returns `true` if we have room in the window, `false` if the window is full.
-    /// When window flow control is disabled, this falls back to checking a simple
-    /// packet count limit.
+    /// Returns `true` if we have room in the window and the receiver's advertised window,
+    /// `false` if either is full. When window flow control is disabled, this falls back to
+    /// checking a simple packet count limit.
     ///
     /// # Arguments
     ///
@@ -157,11 +304,8 @@ impl FlowControl {
         }
 
         // Window-based: check if in-transit data is within window size
-        // Approximate packet size for window calculation (MTU-based)
-        let approx_packet_size = config.fragment_size as u32;
-        let window_bytes = self.window_size * approx_packet_size;
-
-        self.reliable_data_in_transit < window_bytes
+        self.reliable_data_in_transit < self.window_bytes(config)
+            && self.remote_window_remaining() > 0
     }
 
     /// Dynamically adjusts the window size based on network conditions.
@@ -398,4 +542,77 @@ mod tests {
         assert_eq!(flow_control.window_size(), default_config.initial_window_size);
         assert_eq!(flow_control.reliable_data_in_transit(), 0);
     }
+
+    #[test]
+    fn test_window_probe_only_while_blocked() {
+        let mut config = Config::default();
+        config.use_window_flow_control = true;
+        config.initial_window_size = 1;
+        config.min_window_size = 1;
+        config.fragment_size = 100;
+        config.flow_control_probe_interval_ms = 50;
+        let mut flow_control = FlowControl::new(&config);
+        let start = Instant::now();
+        let interval = Duration::from_millis(50);
+
+        // Window open: never probe
+        assert!(!flow_control.should_probe(&config, 0, start + interval));
+
+        flow_control.record_reliable_data_sent(100);
+        assert!(!flow_control.should_probe(&config, 0, start));
+        assert!(flow_control.should_probe(&config, 0, start + interval));
+        assert!(!flow_control.should_probe(&config, 0, start + interval + interval / 2));
+        assert!(flow_control.should_probe(&config, 0, start + interval * 2));
+
+        // Probing disabled
+        config.flow_control_probe_interval_ms = 0;
+        assert!(!flow_control.should_probe(&config, 0, start + interval * 10));
+    }
+
+    #[test]
+    fn test_window_update_releases_received_data() {
+        let config = Config::default();
+        let mut flow_control = FlowControl::new(&config);
+
+        flow_control.record_reliable_data_sent(1000);
+        flow_control.record_reliable_data_sent(500);
+
+        // Receiver has seen the first 1000 bytes
+        flow_control.apply_window_update(1000, u32::MAX);
+        assert_eq!(flow_control.reliable_data_in_transit(), 500);
+
+        // A stale update never grows the in-transit counter again
+        flow_control.apply_window_update(200, u32::MAX);
+        assert_eq!(flow_control.reliable_data_in_transit(), 500);
+
+        flow_control.apply_window_update(1500, u32::MAX);
+        assert_eq!(flow_control.reliable_data_in_transit(), 0);
+    }
+
+    #[test]
+    fn test_advertised_window_limits_sending() {
+        let mut config = Config::default();
+        config.use_window_flow_control = true;
+        let mut flow_control = FlowControl::new(&config);
+        assert_eq!(flow_control.remote_window_remaining(), FlowControl::receive_window(&config));
+
+        flow_control.record_reliable_data_sent(1000);
+        flow_control.apply_window_update(400, 1000);
+        // 600 bytes sent past `received` use up most of the advertised 1000
+        assert_eq!(flow_control.remote_window_remaining(), 400);
+        assert!(flow_control.can_send_reliable(&config, 0));
+
+        flow_control.record_reliable_data_sent(400);
+        assert_eq!(flow_control.remote_window_remaining(), 0);
+        assert!(!flow_control.can_send_reliable(&config, 0));
+
+        // An update reordered behind a newer one is ignored
+        flow_control.apply_window_update(1400, 2000);
+        flow_control.apply_window_update(400, 0);
+        assert_eq!(flow_control.remote_window_remaining(), 2000);
+
+        // So is one acknowledging more than was ever sent
+        flow_control.apply_window_update(5000, 0);
+        assert_eq!(flow_control.remote_window_remaining(), 2000);
+    }
 }
@@ -13,6 +13,9 @@
 //! 3. **Timeout**: Incomplete fragment buffers that don't complete within a timeout period
 //!    (default 5 seconds) are cleaned up to prevent memory leaks from packet loss or
 //!    malicious behavior.
+//! 4. **Eviction**: While the incomplete buffers of a connection hold more than
+//!    `max_reassembly_bytes`, the one whose latest fragment arrived longest ago is dropped
+//!    (see [`evict_least_recent_fragments`]).
 //!
 //! # Usage
 //!
@@ -24,7 +27,7 @@
 //! let buffer = CommandFragmentBuffer::new(channel_id, fragment_count, ordered, Instant::now());
 //!
 //! // Add fragments as they arrive
-//! buffer.add_fragment(fragment_id, fragment_data);
+//! buffer.add_fragment(fragment_id, fragment_data, Instant::now());
 //!
 //! // Check if all fragments have been received
 //! if buffer.is_complete() {
@@ -33,7 +36,7 @@
 //! }
 //! ```
 
-use std::{collections::HashMap, sync::Arc, time::Instant};
+use std::{collections::HashMap, error::Error, fmt, sync::Arc, time::Instant};
 
 /// Tracks reassembly of fragmented command packets.
 ///
@@ -52,6 +55,8 @@ pub struct CommandFragmentBuffer {
     fragments: HashMap<u8, Arc<[u8]>>,
     /// Timestamp when first fragment was received (for timeout detection)
     created_at: Instant,
+    /// Timestamp when the latest fragment was received (for eviction)
+    last_arrival: Instant,
 }
 
 impl CommandFragmentBuffer {
@@ -64,7 +69,14 @@ impl CommandFragmentBuffer {
     /// * `ordered` - Whether to deliver in order on receive for this reassembled packet
     /// * `created_at` - Timestamp when the first fragment was received
     pub fn new(channel_id: u8, fragment_count: u8, ordered: bool, created_at: Instant) -> Self {
-        Self { channel_id, fragment_count, ordered, fragments: HashMap::new(), created_at }
+        Self {
+            channel_id,
+            fragment_count,
+            ordered,
+            fragments: HashMap::new(),
+            created_at,
+            last_arrival: created_at,
+        }
     }
 
     /// Returns the channel ID for this fragment group.
@@ -83,8 +95,22 @@ impl CommandFragmentBuffer {
     ///
     /// * `fragment_id` - The ID of this fragment (0-based index)
     /// * `data` - The fragment data
-    pub fn add_fragment(&mut self, fragment_id: u8, data: Arc<[u8]>) {
-        self.fragments.insert(fragment_id, data);
+    /// * `time` - Timestamp when the fragment was received
+    ///
+    /// Returns the previously buffered data if this fragment was already received.
+    pub fn add_fragment(
+        &mut self,
+        fragment_id: u8,
+        data: Arc<[u8]>,
+        time: Instant,
+    ) -> Option<Arc<[u8]>> {
+        self.last_arrival = self.last_arrival.max(time);
+        self.fragments.insert(fragment_id, data)
+    }
+
+    /// Returns the total number of payload bytes currently buffered.
+    pub fn buffered_bytes(&self) -> usize {
+        self.fragments.values().map(|data| data.len()).sum()
     }
 
     /// Checks if all fragments have been received.
@@ -125,6 +151,21 @@ impl CommandFragmentBuffer {
     pub fn created_at(&self) -> Instant {
         self.created_at
     }
+
+    /// Returns the timestamp when the latest fragment was received.
+    pub fn last_arrival(&self) -> Instant {
+        self.last_arrival
+    }
+
+    /// Returns the total number of fragments expected.
+    pub fn fragment_count(&self) -> u8 {
+        self.fragment_count
+    }
+
+    /// Returns the fragments received so far, in no particular order.
+    pub fn fragments(&self) -> impl Iterator<Item = (u8, &[u8])> + '_ {
+        self.fragments.iter().map(|(&fragment_id, data)| (fragment_id, &data[..]))
+    }
 }
 
 /// Default timeout duration for incomplete fragment buffers.
@@ -144,6 +185,8 @@ pub const FRAGMENT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs
 /// * `command_fragments` - Mutable reference to the fragment buffer map
 /// * `time` - Current time for calculating fragment age
 ///
+/// Returns the number of payload bytes freed by the removed buffers.
+///
 /// # Example
 ///
 /// ```ignore
@@ -153,7 +196,7 @@ pub const FRAGMENT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs
 pub fn cleanup_stale_fragments(
     command_fragments: &mut HashMap<u16, CommandFragmentBuffer>,
     time: Instant,
-) {
+) -> usize {
     // Collect sequences of stale buffers
     let stale_sequences: Vec<u16> = command_fragments
         .iter()
@@ -167,6 +210,7 @@ pub fn cleanup_stale_fragments(
         .collect();
 
     // Remove stale buffers and log
+    let mut freed = 0;
     if !stale_sequences.is_empty() {
         tracing::warn!(
             "Cleaning up {} stale fragment buffer(s) that timed out after {:?}",
@@ -174,9 +218,70 @@ pub fn cleanup_stale_fragments(
             FRAGMENT_TIMEOUT
         );
         for seq in stale_sequences {
-            command_fragments.remove(&seq);
+            if let Some(buffer) = command_fragments.remove(&seq) {
+                freed += buffer.buffered_bytes();
+            }
         }
     }
+    freed
+}
+
+/// Why fragment buffers were dropped before completing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReassemblyError {
+    /// The buffers exceeded the reassembly limit, so the least recently updated ones were
+    /// evicted
+    BufferFull {
+        /// Number of fragment groups evicted
+        evicted: usize,
+        /// Payload bytes freed by the evicted groups
+        freed_bytes: usize,
+    },
+}
+
+impl fmt::Display for ReassemblyError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReassemblyError::BufferFull { evicted, freed_bytes } => write!(
+                fmt,
+                "reassembly buffer full: evicted {evicted} fragment group(s) ({freed_bytes} bytes)"
+            ),
+        }
+    }
+}
+
+impl Error for ReassemblyError {}
+
+/// Evicts fragment buffers, least recently updated first, until the rest hold at most
+/// `max_bytes` of payload.
+///
+/// Returns [`ReassemblyError::BufferFull`] describing the evictions if any were needed.
+pub fn evict_least_recent_fragments(
+    command_fragments: &mut HashMap<u16, CommandFragmentBuffer>,
+    max_bytes: usize,
+) -> Result<(), ReassemblyError> {
+    let mut buffered: usize =
+        command_fragments.values().map(|buffer| buffer.buffered_bytes()).sum();
+    let mut evicted = 0;
+    let mut freed_bytes = 0;
+    while buffered > max_bytes {
+        let Some(sequence) = command_fragments
+            .iter()
+            .min_by_key(|(_, buffer)| buffer.last_arrival())
+            .map(|(&sequence, _)| sequence)
+        else {
+            break;
+        };
+        if let Some(buffer) = command_fragments.remove(&sequence) {
+            buffered -= buffer.buffered_bytes();
+            freed_bytes += buffer.buffered_bytes();
+            evicted += 1;
+        }
+    }
+    if evicted == 0 {
+        return Ok(());
+    }
+    Err(ReassemblyError::BufferFull { evicted, freed_bytes })
 }
 
 #[cfg(test)]
@@ -196,15 +301,15 @@ mod tests {
         let mut buffer = CommandFragmentBuffer::new(1, 3, false, Instant::now());
 
         // Add first fragment
-        buffer.add_fragment(0, Arc::from(vec![1, 2, 3]));
+        buffer.add_fragment(0, Arc::from(vec![1, 2, 3]), Instant::now());
         assert!(!buffer.is_complete());
 
         // Add second fragment
-        buffer.add_fragment(1, Arc::from(vec![4, 5, 6]));
+        buffer.add_fragment(1, Arc::from(vec![4, 5, 6]), Instant::now());
         assert!(!buffer.is_complete());
 
         // Add third fragment
-        buffer.add_fragment(2, Arc::from(vec![7, 8, 9]));
+        buffer.add_fragment(2, Arc::from(vec![7, 8, 9]), Instant::now());
         assert!(buffer.is_complete());
     }
 
@@ -213,9 +318,9 @@ mod tests {
         let mut buffer = CommandFragmentBuffer::new(0, 3, true, Instant::now());
 
         // Add all fragments
-        buffer.add_fragment(0, Arc::from(vec![1, 2, 3]));
-        buffer.add_fragment(1, Arc::from(vec![4, 5, 6]));
-        buffer.add_fragment(2, Arc::from(vec![7, 8, 9]));
+        buffer.add_fragment(0, Arc::from(vec![1, 2, 3]), Instant::now());
+        buffer.add_fragment(1, Arc::from(vec![4, 5, 6]), Instant::now());
+        buffer.add_fragment(2, Arc::from(vec![7, 8, 9]), Instant::now());
 
         // Reassemble
         let result = buffer.reassemble().unwrap();
@@ -227,8 +332,8 @@ mod tests {
         let mut buffer = CommandFragmentBuffer::new(0, 3, true, Instant::now());
 
         // Add only 2 of 3 fragments
-        buffer.add_fragment(0, Arc::from(vec![1, 2, 3]));
-        buffer.add_fragment(1, Arc::from(vec![4, 5, 6]));
+        buffer.add_fragment(0, Arc::from(vec![1, 2, 3]), Instant::now());
+        buffer.add_fragment(1, Arc::from(vec![4, 5, 6]), Instant::now());
 
         // Should not be able to reassemble
         assert!(buffer.reassemble().is_none());
@@ -299,4 +404,31 @@ mod tests {
         assert!(fragments.contains_key(&200), "Fresh buffer should still exist");
         assert!(!fragments.contains_key(&100), "Stale buffer should be removed");
     }
+
+    #[test]
+    fn test_evict_least_recent_fragments() {
+        let mut fragments: HashMap<u16, CommandFragmentBuffer> = HashMap::new();
+        let start_time = Instant::now();
+
+        // Four groups of 100 bytes each; the first one created gets its fragment last
+        for seq in 0..4u16 {
+            let created = start_time + std::time::Duration::from_millis(seq.into());
+            let arrival =
+                if seq == 0 { created + std::time::Duration::from_millis(10) } else { created };
+            let mut buffer = CommandFragmentBuffer::new(0, 2, true, created);
+            buffer.add_fragment(0, Arc::from(vec![0; 100]), arrival);
+            fragments.insert(seq, buffer);
+        }
+
+        assert_eq!(evict_least_recent_fragments(&mut fragments, 400), Ok(()));
+        assert_eq!(fragments.len(), 4);
+
+        // Over the limit: the groups whose latest fragment is oldest go first
+        assert_eq!(
+            evict_least_recent_fragments(&mut fragments, 250),
+            Err(ReassemblyError::BufferFull { evicted: 2, freed_bytes: 200 })
+        );
+        assert!(fragments.contains_key(&0), "Recently updated group should remain");
+        assert!(fragments.contains_key(&3));
+    }
 }
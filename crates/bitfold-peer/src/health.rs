@@ -0,0 +1,32 @@
+//! Composite connection health check.
+
+use std::time::Duration;
+
+/// Breakdown of a connection's health, produced by [`Peer::health`](crate::Peer::health).
+///
+/// The connection is healthy when every check passes (see [`PeerHealth::is_healthy`]).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PeerHealth {
+    /// Packet loss rate (0.0 to 1.0)
+    pub loss_rate: f32,
+    /// Whether the loss rate is at most `health_max_loss_rate`
+    pub loss_ok: bool,
+    /// Smoothed round-trip time
+    pub rtt: Duration,
+    /// Round-trip time variance
+    pub rtt_variance: Duration,
+    /// Whether the RTT is within `rtt_max_value` and its variance does not exceed the RTT
+    pub rtt_stable: bool,
+    /// Whether PMTU discovery settled on a size (always true with discovery disabled)
+    pub pmtu_converged: bool,
+    /// Whether the connection is established and the remote was heard from within half the
+    /// idle timeout
+    pub alive: bool,
+}
+
+impl PeerHealth {
+    /// Returns true if every check passes.
+    pub fn is_healthy(&self) -> bool {
+        self.loss_ok && self.rtt_stable && self.pmtu_converged && self.alive
+    }
+}
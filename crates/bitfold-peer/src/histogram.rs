@@ -0,0 +1,161 @@
+//! Fixed-bucket histograms with OpenMetrics exemplars.
+//!
+//! Each bucket keeps the most recent observation that fell into it together with the trace ID
+//! active at the time, so a dashboard can jump from an outlier bucket to the trace that
+//! produced it.
+
+use std::fmt::Write;
+
+/// Upper bounds (seconds) of the PMTU convergence-time histogram.
+pub const PMTU_CONVERGENCE_BUCKETS: &[f64] = &[0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0];
+/// Upper bounds (packets) of the flow-control window histogram.
+pub const WINDOW_SIZE_BUCKETS: &[f64] = &[16.0, 32.0, 64.0, 128.0, 256.0, 512.0, 1024.0];
+
+/// An observation linked to the trace that produced it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exemplar {
+    /// Observed value
+    pub value: f64,
+    /// Trace ID active when the value was observed
+    pub trace_id: String,
+}
+
+/// Cumulative histogram over fixed bucket bounds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// Inclusive upper bound of each bucket, ascending; `+Inf` is implicit
+    bounds: &'static [f64],
+    /// Observations per bucket (not cumulative), with the `+Inf` bucket last
+    counts: Vec<u64>,
+    /// Latest exemplar per bucket, with the `+Inf` bucket last
+    exemplars: Vec<Option<Exemplar>>,
+    /// Sum of all observed values
+    sum: f64,
+}
+
+impl Histogram {
+    /// Creates an empty histogram with the given ascending bucket upper bounds.
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            exemplars: vec![None; bounds.len() + 1],
+            sum: 0.0,
+        }
+    }
+
+    /// Records `value`, attaching it as the bucket's exemplar if a `trace_id` is given.
+    pub fn observe(&mut self, value: f64, trace_id: Option<&str>) {
+        let bucket = self.bucket_index(value);
+        self.counts[bucket] += 1;
+        self.sum += value;
+        if let Some(trace_id) = trace_id {
+            self.exemplars[bucket] = Some(Exemplar { value, trace_id: trace_id.to_owned() });
+        }
+    }
+
+    /// Returns the index of the first bucket whose upper bound is at least `value`.
+    pub fn bucket_index(&self, value: f64) -> usize {
+        self.bounds.iter().position(|&bound| value <= bound).unwrap_or(self.bounds.len())
+    }
+
+    /// Returns the bucket upper bounds.
+    pub fn bounds(&self) -> &[f64] {
+        self.bounds
+    }
+
+    /// Returns the number of observations in each bucket (not cumulative), `+Inf` last.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Returns the latest exemplar recorded in bucket `index`.
+    pub fn exemplar(&self, index: usize) -> Option<&Exemplar> {
+        self.exemplars.get(index).and_then(Option::as_ref)
+    }
+
+    /// Returns the total number of observations.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the sum of all observed values.
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Appends this histogram to `out` in OpenMetrics text format as metric `name`, with
+    /// `labels` (already formatted as `key="value"` pairs) on every sample.
+    pub fn write_openmetrics(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        let label_set = if labels.is_empty() { String::new() } else { format!("{{{}}}", labels) };
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        let mut cumulative = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            cumulative += count;
+            let le = match self.bounds.get(index) {
+                Some(bound) => bound.to_string(),
+                None => "+Inf".to_owned(),
+            };
+            let _ = write!(
+                out,
+                "{}_bucket{{{}{}le=\"{}\"}} {}",
+                name, labels, separator, le, cumulative
+            );
+            if let Some(exemplar) = self.exemplar(index) {
+                let _ = write!(
+                    out,
+                    " # {{trace_id=\"{}\"}} {}",
+                    escape_label_value(&exemplar.trace_id),
+                    exemplar.value
+                );
+            }
+            out.push('\n');
+        }
+        let _ = writeln!(out, "{}_count{} {}", name, label_set, self.count());
+        let _ = writeln!(out, "{}_sum{} {}", name, label_set, self.sum);
+    }
+}
+
+/// Escapes a label value for the OpenMetrics text format.
+pub fn escape_label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_observations_land_in_first_fitting_bucket() {
+        let mut histogram = Histogram::new(&[1.0, 5.0]);
+        histogram.observe(1.0, None);
+        histogram.observe(3.0, Some("abc"));
+        histogram.observe(9.0, None);
+
+        assert_eq!(histogram.counts(), &[1, 1, 1]);
+        assert_eq!(histogram.count(), 3);
+        assert_eq!(histogram.sum(), 13.0);
+        assert_eq!(histogram.exemplar(1), Some(&Exemplar { value: 3.0, trace_id: "abc".into() }));
+        assert_eq!(histogram.exemplar(2), None);
+    }
+
+    #[test]
+    fn test_openmetrics_buckets_are_cumulative_with_exemplars() {
+        let mut histogram = Histogram::new(&[1.0, 5.0]);
+        histogram.observe(0.5, None);
+        histogram.observe(2.5, Some("4bf92f"));
+
+        let mut out = String::new();
+        histogram.write_openmetrics(&mut out, "rtt_seconds", "peer=\"a\"");
+        assert_eq!(
+            out,
+            "# TYPE rtt_seconds histogram\n\
+             rtt_seconds_bucket{peer=\"a\",le=\"1\"} 1\n\
+             rtt_seconds_bucket{peer=\"a\",le=\"5\"} 2 # {trace_id=\"4bf92f\"} 2.5\n\
+             rtt_seconds_bucket{peer=\"a\",le=\"+Inf\"} 2\n\
+             rtt_seconds_count{peer=\"a\"} 2\n\
+             rtt_seconds_sum{peer=\"a\"} 3\n"
+        );
+    }
+}
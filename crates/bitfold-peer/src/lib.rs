@@ -5,23 +5,70 @@
 /// Bandwidth throttling and utilization tracking.
 pub mod bandwidth_throttle;
 mod channel_state;
+/// Causes of a connection closing.
+pub mod close_reason;
 /// Command queue for batching operations.
 pub mod command_queue;
+/// Pluggable congestion controllers.
+pub mod congestion_controller;
+/// Persistable connection state for warm restarts.
+pub mod connection_state;
 /// Window-based flow control for reliable data transmission.
 pub mod flow_control;
 /// Fragment reassembly management for command packets.
 mod fragment_buffer;
+/// Composite connection health check.
+pub mod health;
+/// Fixed-bucket histograms with OpenMetrics exemplars.
+pub mod histogram;
+/// Live connection state for migrating a connection between processes.
+pub mod migration;
+/// Notifications of changes to the effective send MTU.
+pub mod mtu_changes;
+/// Connection parameters agreed during the handshake.
+pub mod negotiated_params;
+/// Diagnostic view of packets awaiting acknowledgment.
+#[cfg(any(test, feature = "diagnostics"))]
+pub mod outstanding;
+/// Send pacing with a burst allowance.
+pub mod pacer;
+mod path_validation;
 mod peer;
 mod peer_state;
+/// On-demand round-trip time measurement with application pings.
+pub mod ping;
 /// Path MTU discovery implementation.
 pub mod pmtu_discovery;
+/// Per-message send options.
+pub mod send_options;
 /// Peer connection statistics tracking.
 pub mod statistics;
+/// Per-stream reliability modes.
+pub mod stream_mode;
+/// Diagnostic view of a peer's armed timers.
+#[cfg(any(test, feature = "diagnostics"))]
+pub mod timers;
 /// Unsequenced packet duplicate detection.
 pub mod unsequenced;
 
 pub use bandwidth_throttle::BandwidthThrottle;
+pub use close_reason::{CloseReason, DRAIN_REASON};
+pub use congestion_controller::CongestionController;
+pub use connection_state::ConnectionState;
 pub use flow_control::FlowControl;
+pub use health::PeerHealth;
+pub use histogram::Histogram;
+pub use migration::{LiveConnectionState, QueuedCommand};
+pub use mtu_changes::MtuChanges;
+pub use negotiated_params::NegotiatedParams;
+#[cfg(any(test, feature = "diagnostics"))]
+pub use outstanding::OutstandingInfo;
+pub use pacer::Pacer;
 pub use peer::Peer;
 pub use peer_state::PeerState;
-pub use statistics::PeerStatistics;
+pub use ping::PingFuture;
+pub use send_options::SendOptions;
+pub use statistics::{CompressionStats, PeerMetricsSnapshot, PeerStatistics};
+pub use stream_mode::StreamMode;
+#[cfg(any(test, feature = "diagnostics"))]
+pub use timers::TimerKind;
@@ -0,0 +1,145 @@
+//! Live connection state for migrating a connection between processes.
+//!
+//! Unlike [`ConnectionState`], which only carries what a peer learned about its path,
+//! [`LiveConnectionState`] captures everything needed to continue an established connection
+//! mid-flight: session identity, sequence numbers, unacknowledged packets, receive windows,
+//! partially reassembled messages and commands not yet sent. It is produced by
+//! [`Peer::export_live_state`](crate::Peer::export_live_state) and consumed by
+//! [`Peer::import_live_state`](crate::Peer::import_live_state).
+//!
+//! All fields are plain data and times are stored relative to the moment of export, so the
+//! state can be serialized (with the `serde` feature) in whatever format the application uses
+//! and restored in a new process.
+
+use std::time::Duration;
+
+use bitfold_core::config::CompressionAlgorithm;
+use bitfold_protocol::{
+    command::ProtocolCommand,
+    packet::{OrderingGuarantee, PacketType},
+};
+
+use crate::{connection_state::ConnectionState, negotiated_params::NegotiatedParams, PeerState};
+
+/// Complete state of a live connection, restorable in another process.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LiveConnectionState {
+    /// Path MTU, congestion state and next outgoing sequence number
+    pub connection: ConnectionState,
+    /// Connection lifecycle state
+    pub state: PeerState,
+    /// Peer ID assigned during the handshake
+    pub peer_id: u16,
+    /// Session ID chosen by the remote peer
+    pub incoming_session_id: u16,
+    /// Session ID chosen by this peer
+    pub outgoing_session_id: u16,
+    /// Connect ID used for replay protection
+    pub connect_id: u32,
+    /// Protocol version agreed with the remote peer
+    pub protocol_version: u16,
+    /// Channel count agreed with the remote peer
+    pub negotiated_channels: u8,
+    /// MTU agreed with the remote peer
+    pub negotiated_mtu: u16,
+    /// Idle timeout agreed with the remote peer
+    pub negotiated_idle_timeout: Duration,
+    /// Parameters recorded when the handshake completed
+    pub negotiated_params: Option<NegotiatedParams>,
+    /// Most recent local sequence number acknowledged by the remote
+    pub remote_ack_sequence: u16,
+    /// Remote sequence numbers still covered by outgoing ACKs and SACKs, oldest first
+    pub received_sequences: Vec<u16>,
+    /// Packets sent but not yet acknowledged
+    pub in_flight: Vec<InFlightPacket>,
+    /// Sequence number for the next reliable message
+    pub next_reliable_sequence: u16,
+    /// Sequence number for the next fragmented unreliable message
+    pub next_unreliable_sequence: u16,
+    /// Base of the unsequenced duplicate-detection window
+    pub incoming_unsequenced_group: u16,
+    /// Unsequenced duplicate-detection window bits
+    pub unsequenced_window: [u32; 32],
+    /// Next outgoing unsequenced group
+    pub outgoing_unsequenced_group: u16,
+    /// Base of the reliable duplicate-detection window
+    pub incoming_reliable_sequence: u16,
+    /// Reliable duplicate-detection window bits
+    pub reliable_window: [u32; 32],
+    /// Flow-control window size (packets)
+    pub window_size: u32,
+    /// Reliable bytes sent and awaiting acknowledgment
+    pub reliable_data_in_transit: u32,
+    /// Total reliable bytes sent (wrapping)
+    pub total_reliable_sent: u32,
+    /// Total reliable bytes received (wrapping), reported in window updates
+    pub reliable_bytes_received: u32,
+    /// Per-channel ordering state
+    pub channels: Vec<ChannelSnapshot>,
+    /// Partially reassembled fragmented messages
+    pub reassembly: Vec<ReassemblySnapshot>,
+    /// Commands queued but not yet sent, in send order
+    pub queued_commands: Vec<QueuedCommand>,
+}
+
+/// A packet awaiting acknowledgment at the time of export.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InFlightPacket {
+    /// Sequence number the packet was sent with
+    pub sequence: u16,
+    /// Type of packet sent
+    pub packet_type: PacketType,
+    /// Payload of the packet
+    pub payload: Vec<u8>,
+    /// Ordering guarantee specified for the packet
+    pub ordering: OrderingGuarantee,
+    /// Optional identifier for ordering/sequencing
+    pub item_identifier: Option<u16>,
+    /// Time since the packet was sent
+    pub age: Duration,
+}
+
+/// A command queued but not yet sent at the time of export.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueuedCommand {
+    /// The command
+    pub command: ProtocolCommand,
+    /// Compression overriding the connection's for the datagram carrying the command, as
+    /// given to [`Peer::send_with_options`](crate::Peer::send_with_options)
+    pub compression: Option<CompressionAlgorithm>,
+}
+
+/// Ordering state of one channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelSnapshot {
+    /// Channel ID
+    pub channel_id: u8,
+    /// Next sequence number expected for ordered delivery
+    pub expected_sequence: u16,
+    /// Latest sequence number seen for sequenced delivery
+    pub latest_sequence: u16,
+    /// Out-of-order packets waiting for missing sequences, by sequence number
+    pub buffered: Vec<(u16, Vec<u8>)>,
+}
+
+/// A fragmented message that has not been fully received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReassemblySnapshot {
+    /// Sequence number shared by the fragments
+    pub sequence: u16,
+    /// Channel ID of the message
+    pub channel_id: u8,
+    /// Total number of fragments expected
+    pub fragment_count: u8,
+    /// Whether the message is delivered in order
+    pub ordered: bool,
+    /// Fragments received so far, by fragment ID
+    pub fragments: Vec<(u8, Vec<u8>)>,
+    /// Time since the first fragment arrived
+    pub age: Duration,
+}
@@ -0,0 +1,133 @@
+//! Notifications of changes to a peer's effective send MTU.
+
+use std::{
+    collections::VecDeque,
+    future::poll_fn,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+#[derive(Debug, Default)]
+struct MtuSlot {
+    values: VecDeque<u16>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+/// Receives each new effective send MTU of a peer, subscribed with
+/// [`Peer::mtu_changes`](crate::Peer::mtu_changes).
+///
+/// [`MtuChanges::recv`] resolves with the next value, or `None` once the peer is dropped and
+/// every value has been received. Like [`PingFuture`](crate::PingFuture), it needs no
+/// particular executor; it is woken from whichever thread drives the peer.
+#[derive(Debug)]
+pub struct MtuChanges {
+    slot: Arc<Mutex<MtuSlot>>,
+}
+
+impl MtuChanges {
+    /// Waits for the next effective send MTU, or `None` once the peer is gone.
+    pub async fn recv(&mut self) -> Option<u16> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Polls for the next effective send MTU, registering `cx` to be woken when one arrives.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<u16>> {
+        let mut slot = self.slot.lock().unwrap();
+        match slot.values.pop_front() {
+            Some(mtu) => Poll::Ready(Some(mtu)),
+            None if slot.closed => Poll::Ready(None),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Returns the next effective send MTU if one is waiting, without blocking.
+    pub fn try_recv(&mut self) -> Option<u16> {
+        self.slot.lock().unwrap().values.pop_front()
+    }
+}
+
+/// Subscribers to a peer's effective send MTU.
+#[derive(Debug, Default)]
+pub(crate) struct MtuSubscribers {
+    subscribers: Vec<Arc<Mutex<MtuSlot>>>,
+}
+
+impl MtuSubscribers {
+    /// Registers a new subscriber, returning the receiver it reads from.
+    pub(crate) fn subscribe(&mut self) -> MtuChanges {
+        let slot = Arc::new(Mutex::new(MtuSlot::default()));
+        self.subscribers.push(Arc::clone(&slot));
+        MtuChanges { slot }
+    }
+
+    /// Sends `mtu` to every subscriber, unsubscribing those whose receiver was dropped.
+    pub(crate) fn notify(&mut self, mtu: u16) {
+        self.subscribers.retain(|slot| {
+            if Arc::strong_count(slot) == 1 {
+                return false;
+            }
+            let mut slot = slot.lock().unwrap();
+            slot.values.push_back(mtu);
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+            true
+        });
+    }
+
+    /// Returns true if nobody subscribed.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.subscribers.is_empty()
+    }
+
+    /// Number of subscribers.
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.subscribers.len()
+    }
+}
+
+impl Drop for MtuSubscribers {
+    fn drop(&mut self) {
+        for slot in &self.subscribers {
+            let mut slot = slot.lock().unwrap();
+            slot.closed = true;
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{future::Future, pin::pin};
+
+    use super::*;
+
+    #[test]
+    fn test_dropped_subscribers_close_receivers() {
+        let mut subscribers = MtuSubscribers::default();
+        let mut changes = subscribers.subscribe();
+        subscribers.notify(1200);
+        drop(subscribers);
+
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(pin!(changes.recv()).poll(&mut cx), Poll::Ready(Some(1200)));
+        assert_eq!(pin!(changes.recv()).poll(&mut cx), Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_dropped_receiver_is_unsubscribed() {
+        let mut subscribers = MtuSubscribers::default();
+        let changes = subscribers.subscribe();
+        let _kept = subscribers.subscribe();
+        drop(changes);
+        subscribers.notify(1200);
+        assert_eq!(subscribers.len(), 1);
+    }
+}
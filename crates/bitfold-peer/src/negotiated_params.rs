@@ -0,0 +1,30 @@
+//! Connection parameters agreed during the handshake.
+
+use std::time::Duration;
+
+use bitfold_core::config::CompressionAlgorithm;
+
+/// Definitive record of how a connection was set up, captured once when the
+/// handshake completes.
+///
+/// Values reflect what both sides agreed on, which may differ from the local `Config`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NegotiatedParams {
+    /// Protocol version in use
+    pub protocol_version: u16,
+    /// Number of channels (independent streams) available
+    pub channels: u8,
+    /// MTU agreed in the handshake (bytes)
+    pub mtu: u16,
+    /// Flow-control window size (packets)
+    pub window_size: u32,
+    /// Fragment size in effect when the connection was established (bytes)
+    pub initial_fragment_size: u16,
+    /// Idle timeout both sides enforce: the smaller of the two advertised
+    pub idle_timeout: Duration,
+    /// Compression algorithm applied to outgoing packets
+    pub compression: CompressionAlgorithm,
+    /// Whether checksums are appended to packets
+    pub checksums: bool,
+}
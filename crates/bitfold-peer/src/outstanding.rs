@@ -0,0 +1,15 @@
+//! Diagnostic view of packets awaiting acknowledgment.
+
+use std::time::Instant;
+
+/// A sent packet that has not been acknowledged yet, as reported by
+/// [`Peer::outstanding_packets`](crate::Peer::outstanding_packets).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutstandingInfo {
+    /// Sequence number the packet was sent with
+    pub sequence: u16,
+    /// Time the packet was sent
+    pub sent_time: Instant,
+    /// Payload size (bytes)
+    pub size: usize,
+}
@@ -0,0 +1,205 @@
+//! Send pacing with a burst allowance.
+//!
+//! The bandwidth throttle caps the bytes sent per one-second window but lets them all go out
+//! at once. A [`Pacer`] spreads datagrams evenly at the same rate instead, using a token
+//! bucket: credit accrues at the pacing rate up to a burst allowance, and each datagram
+//! spends its size. After an idle period the bucket is full, so up to the allowance goes out
+//! immediately (like TCP's initial burst) before the steady rate applies.
+//!
+//! A datagram may be sent once the credit covers a full-size datagram. With an allowance of
+//! one datagram this is strict pacing.
+//!
+//! The rate is either fixed or follows the congestion window: [`Pacer::window_rate`] sends a
+//! window per smoothed RTT, scaled by a gain, so the packets of a window are spaced
+//! `RTT / (gain * window / packet size)` apart. Following the window, delays below the timer
+//! granularity are not worth waiting for, so at high rates datagrams are released immediately;
+//! a fixed rate always waits for the credit.
+
+use std::time::{Duration, Instant};
+
+/// Delays shorter than this are not waited for while following the window.
+const PACING_GRANULARITY: Duration = Duration::from_millis(1);
+
+/// Token bucket pacing outgoing datagrams.
+#[derive(Debug, Clone)]
+pub struct Pacer {
+    /// Pacing rate in bytes per second
+    rate: u32,
+    /// Size of a full datagram in bytes
+    packet_bytes: f64,
+    /// Most credit the bucket holds, in bytes
+    burst_bytes: f64,
+    /// Credit as of `last_update`, in bytes; negative after a datagram larger than
+    /// `packet_bytes`
+    credit: f64,
+    last_update: Instant,
+    /// Whether the rate follows the window (see [`Pacer::set_window_rate`]) rather than being
+    /// fixed
+    follows_window: bool,
+}
+
+impl Pacer {
+    /// Creates a pacer sending `rate` bytes per second (at least 1) that allows bursts of
+    /// `burst_packets` (at least 1) datagrams of `packet_bytes`. The bucket starts full.
+    pub fn new(rate: u32, burst_packets: u32, packet_bytes: usize, time: Instant) -> Self {
+        let packet_bytes = packet_bytes.max(1) as f64;
+        let burst_bytes = packet_bytes * burst_packets.max(1) as f64;
+        Self {
+            rate: rate.max(1),
+            packet_bytes,
+            burst_bytes,
+            credit: burst_bytes,
+            last_update: time,
+            follows_window: false,
+        }
+    }
+
+    /// Returns the rate sending `window` bytes per `rtt`, multiplied by `gain`, in bytes per
+    /// second (at least 1).
+    pub fn window_rate(window: usize, rtt: Duration, gain: f32) -> u32 {
+        let rtt = rtt.max(PACING_GRANULARITY).as_secs_f64();
+        (window as f64 * f64::from(gain) / rtt).clamp(1.0, u32::MAX as f64) as u32
+    }
+
+    /// Returns the pacing rate in bytes per second.
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    /// Changes the pacing rate to `rate` bytes per second (at least 1) from `time` on. Credit
+    /// accrued until then is kept.
+    pub fn set_rate(&mut self, rate: u32, time: Instant) {
+        self.credit = self.credit_at(time);
+        self.last_update = self.last_update.max(time);
+        self.rate = rate.max(1);
+    }
+
+    /// Like [`Pacer::set_rate`], for a rate following the window (see [`Pacer::window_rate`]).
+    /// From then on delays below the timer granularity are not waited for.
+    pub fn set_window_rate(&mut self, rate: u32, time: Instant) {
+        self.set_rate(rate, time);
+        self.follows_window = true;
+    }
+
+    /// Returns the spacing of full-size datagrams at the pacing rate.
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f64(self.packet_bytes / self.rate as f64)
+    }
+
+    /// Returns the credit available at `time`.
+    fn credit_at(&self, time: Instant) -> f64 {
+        let elapsed = time.saturating_duration_since(self.last_update).as_secs_f64();
+        (self.credit + elapsed * self.rate as f64).min(self.burst_bytes)
+    }
+
+    /// Returns true if a datagram may be sent at `time`.
+    pub fn can_send(&self, time: Instant) -> bool {
+        self.time_to_send_next(time).is_none()
+    }
+
+    /// Returns how long after `now` the next datagram has to wait, or `None` if it may go
+    /// now because the credit covers it or, following the window, the wait is below the timer
+    /// granularity.
+    pub fn time_to_send_next(&self, now: Instant) -> Option<Duration> {
+        let min_delay = if self.follows_window { PACING_GRANULARITY } else { Duration::ZERO };
+        Some(self.delay(now)).filter(|delay| !delay.is_zero() && *delay >= min_delay)
+    }
+
+    /// Records a datagram of `bytes` sent at `time`.
+    pub fn on_sent(&mut self, bytes: usize, time: Instant) {
+        self.credit = self.credit_at(time) - bytes as f64;
+        self.last_update = self.last_update.max(time);
+    }
+
+    /// Returns how long after `time` the next datagram may be sent (zero if it may go now).
+    pub fn delay(&self, time: Instant) -> Duration {
+        let missing = self.packet_bytes - self.credit_at(time);
+        if missing <= 0.0 {
+            return Duration::ZERO;
+        }
+        // Rounded up so the credit is there when the delay has passed
+        Duration::from_micros((missing * 1e6 / self.rate as f64).ceil() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_after_idle_then_paced() {
+        let start = Instant::now();
+        // 100 KB/s, bursts of four 1000-byte datagrams
+        let mut pacer = Pacer::new(100_000, 4, 1000, start);
+        let idle = start + Duration::from_secs(1);
+
+        for _ in 0..4 {
+            assert!(pacer.can_send(idle));
+            pacer.on_sent(1000, idle);
+        }
+        assert!(!pacer.can_send(idle));
+
+        // Each further datagram waits for its 10ms of credit
+        assert_eq!(pacer.delay(idle).as_micros(), 10_000);
+        assert!(!pacer.can_send(idle + Duration::from_millis(9)));
+        let next = idle + pacer.delay(idle);
+        assert!(pacer.can_send(next));
+        pacer.on_sent(1000, next);
+        assert!(!pacer.can_send(next + Duration::from_millis(9)));
+    }
+
+    #[test]
+    fn test_credit_capped_at_burst() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new(100_000, 2, 1000, start);
+        let later = start + Duration::from_secs(60);
+        pacer.on_sent(1000, later);
+        pacer.on_sent(1000, later);
+        assert!(!pacer.can_send(later));
+        assert!(pacer.delay(later) > Duration::ZERO);
+    }
+
+    #[test]
+    fn test_interval_follows_window_per_rtt() {
+        let start = Instant::now();
+        // 100 KB window per 100ms RTT: 1 MB/s, so 1000-byte datagrams every 1ms
+        let rate = Pacer::window_rate(100_000, Duration::from_millis(100), 1.0);
+        assert_eq!(rate, 1_000_000);
+        let mut pacer = Pacer::new(rate, 1, 1000, start);
+        assert_eq!(pacer.interval(), Duration::from_millis(1));
+
+        // Halving the window doubles the interval, the gain shortens it again
+        pacer.set_window_rate(Pacer::window_rate(50_000, Duration::from_millis(100), 1.0), start);
+        assert_eq!(pacer.interval(), Duration::from_millis(2));
+        pacer.on_sent(1000, start);
+        assert_eq!(pacer.time_to_send_next(start), Some(Duration::from_millis(2)));
+        pacer.set_window_rate(Pacer::window_rate(50_000, Duration::from_millis(100), 2.0), start);
+        assert_eq!(pacer.time_to_send_next(start), Some(Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn test_high_rate_releases_immediately() {
+        let start = Instant::now();
+        // 10 MB window per 10ms RTT: a 1000-byte datagram is due every microsecond
+        let rate = Pacer::window_rate(10_000_000, Duration::from_millis(10), 1.0);
+        let mut pacer = Pacer::new(rate, 1, 1000, start);
+        pacer.set_window_rate(rate, start);
+        for _ in 0..100 {
+            assert_eq!(pacer.time_to_send_next(start), None);
+            assert!(pacer.can_send(start));
+            pacer.on_sent(1000, start);
+        }
+    }
+
+    #[test]
+    fn test_fixed_rate_waits_for_credit() {
+        let start = Instant::now();
+        // A fixed 10 MB/s: the next 1000-byte datagram is due after 100 microseconds
+        let mut pacer = Pacer::new(10_000_000, 1, 1000, start);
+        assert!(pacer.can_send(start));
+        pacer.on_sent(1000, start);
+        assert!(!pacer.can_send(start));
+        assert_eq!(pacer.time_to_send_next(start), Some(Duration::from_micros(100)));
+        assert!(pacer.can_send(start + Duration::from_micros(100)));
+    }
+}
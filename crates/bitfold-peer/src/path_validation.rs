@@ -0,0 +1,125 @@
+//! Path validation for connection migration.
+//!
+//! When datagrams identifying a connection start arriving from a new source address, e.g. after
+//! a NAT rebinding or a switch from Wi-Fi to cellular, the address is not trusted until it
+//! proves it can receive: a path challenge carrying unpredictable data is sent there, and only a path
+//! response echoing that data from the same address lets the connection migrate.
+//!
+//! Until then, the bytes sent to the address are limited to a multiple of the bytes received
+//! from it, so a spoofed source address cannot be used to amplify traffic at a victim.
+
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// Challenges sent per validation, spread evenly over its timeout.
+const CHALLENGES_PER_VALIDATION: u32 = 3;
+
+/// Validation in progress of a candidate remote address.
+#[derive(Debug, Clone)]
+pub(crate) struct PathValidation {
+    /// Address the connection would migrate to
+    candidate: SocketAddr,
+    /// Data the path response must echo
+    challenge: u64,
+    /// When the candidate was first seen
+    started: Instant,
+    /// When the last challenge was sent
+    last_challenge: Option<Instant>,
+    /// Bytes received from the candidate
+    bytes_received: usize,
+    /// Bytes sent to the candidate
+    bytes_sent: usize,
+}
+
+impl PathValidation {
+    /// Starts validating `candidate` with `challenge` as the data to echo.
+    pub(crate) fn new(candidate: SocketAddr, challenge: u64, time: Instant) -> Self {
+        Self {
+            candidate,
+            challenge,
+            started: time,
+            last_challenge: None,
+            bytes_received: 0,
+            bytes_sent: 0,
+        }
+    }
+
+    /// Returns the address under validation.
+    pub(crate) fn candidate(&self) -> SocketAddr {
+        self.candidate
+    }
+
+    /// Returns the data the path response must echo.
+    pub(crate) fn challenge(&self) -> u64 {
+        self.challenge
+    }
+
+    /// Records a datagram of `bytes` received from the candidate.
+    pub(crate) fn record_received(&mut self, bytes: usize) {
+        self.bytes_received = self.bytes_received.saturating_add(bytes);
+    }
+
+    /// Returns true once `timeout` has passed since the candidate was first seen.
+    pub(crate) fn is_expired(&self, time: Instant, timeout: Duration) -> bool {
+        time.saturating_duration_since(self.started) >= timeout
+    }
+
+    /// Returns true if another challenge is due at `time`.
+    pub(crate) fn is_challenge_due(&self, time: Instant, timeout: Duration) -> bool {
+        self.last_challenge.is_none_or(|last| {
+            time.saturating_duration_since(last) >= timeout / CHALLENGES_PER_VALIDATION
+        })
+    }
+
+    /// Records a challenge of `bytes` sent at `time`, unless it would take the bytes sent to
+    /// the candidate above `amplification_factor` times the bytes received from it. Returns
+    /// whether it may be sent.
+    pub(crate) fn try_send_challenge(
+        &mut self,
+        bytes: usize,
+        amplification_factor: u32,
+        time: Instant,
+    ) -> bool {
+        let allowed = self.bytes_received.saturating_mul(amplification_factor as usize);
+        if self.bytes_sent.saturating_add(bytes) > allowed {
+            return false;
+        }
+        self.bytes_sent += bytes;
+        self.last_challenge = Some(time);
+        true
+    }
+
+    /// Returns true if a path response with `data` from `source` completes this validation.
+    pub(crate) fn is_answered_by(&self, source: SocketAddr, data: u64) -> bool {
+        source == self.candidate && data == self.challenge
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_challenges_respect_amplification_limit() {
+        let start = Instant::now();
+        let timeout = Duration::from_millis(300);
+        let candidate: SocketAddr = "127.0.0.1:2000".parse().unwrap();
+        let mut validation = PathValidation::new(candidate, 7, start);
+
+        // Nothing received yet: nothing may be sent
+        assert!(validation.is_challenge_due(start, timeout));
+        assert!(!validation.try_send_challenge(20, 3, start));
+
+        validation.record_received(10);
+        assert!(validation.try_send_challenge(20, 3, start));
+        assert!(!validation.is_challenge_due(start + Duration::from_millis(99), timeout));
+        assert!(validation.is_challenge_due(start + Duration::from_millis(100), timeout));
+        // 20 more would exceed three times the 10 bytes received
+        assert!(!validation.try_send_challenge(20, 3, start + Duration::from_millis(100)));
+
+        assert!(!validation.is_expired(start + Duration::from_millis(299), timeout));
+        assert!(validation.is_expired(start + timeout, timeout));
+    }
+}
@@ -1,23 +1,85 @@
-use std::{collections::VecDeque, time::Instant};
+use std::{borrow::Cow, collections::VecDeque, time::Instant};
 
-use bitfold_core::error::{ErrorKind, Result};
+use bitfold_core::{
+    config::{AckPolicy, ChecksumAlgorithm, Config},
+    error::{ErrorKind, Result},
+};
 use bitfold_protocol::{
-    command::ProtocolCommand,
-    command_codec::CommandDecoder,
+    command::{CommandPacket, ProtocolCommand},
+    command_codec::{self, CommandDecoder},
     packet::{DeliveryGuarantee, IncomingPackets, OrderingGuarantee, Packet, PacketType},
 };
+use rand::Rng;
 
-use super::Peer;
+use super::{Peer, HANDSHAKE_MTU, HANDSHAKE_PROTOCOL_VERSION, MIN_HANDSHAKE_PROTOCOL_VERSION};
 use crate::{
-    channel_state::ChannelState, fragment_buffer::CommandFragmentBuffer, peer_state::PeerState,
-    pmtu_discovery::PmtuDiscovery,
+    channel_state::ChannelState, close_reason::CloseReason, fragment_buffer::CommandFragmentBuffer,
+    peer_state::PeerState, pmtu_discovery::PmtuDiscovery,
 };
 
+/// A received datagram, decoded.
+pub(super) struct DecodedDatagram {
+    /// Commands the datagram carries
+    pub(super) packet: CommandPacket,
+    /// Whether the sender omitted the checksum (see `Config::checksum_data_only`)
+    pub(super) checksum_omitted: bool,
+    /// Whether the sender asked for an immediate ACK
+    pub(super) immediate_ack: bool,
+}
+
 impl Peer {
+    /// Validates the checksum of, decompresses and decodes a datagram sent by a remote
+    /// configured like `config`.
+    pub(super) fn decode_datagram(config: &Config, data: &[u8]) -> Result<DecodedDatagram> {
+        // Validate and strip checksum if enabled (before decompression). Control-only
+        // datagrams may omit it under `checksum_data_only`.
+        let checksum = config.checksum_algorithm();
+        let checksum_omitted = checksum != ChecksumAlgorithm::None
+            && config.checksum_data_only
+            && command_codec::is_checksum_omitted(data);
+        let payload = if checksum != ChecksumAlgorithm::None && !checksum_omitted {
+            CommandDecoder::validate_and_strip_checksum_with(data, checksum)
+                .map_err(|e| ErrorKind::CouldNotReadHeader(e.to_string()))?
+        } else {
+            data
+        };
+
+        let trusted_path = config.trusted_path;
+        let immediate_ack = !trusted_path && command_codec::is_immediate_ack_requested(payload);
+
+        // Decompress if needed. Bounded datagrams never carry more than fits in one receive
+        // buffer before compression
+        let decompressed = if trusted_path {
+            Cow::Borrowed(payload)
+        } else {
+            Cow::Owned(
+                CommandDecoder::decompress_limited(payload, config.receive_buffer_max_size)
+                    .map_err(|e| ErrorKind::CouldNotReadHeader(e.to_string()))?,
+            )
+        };
+
+        // A peer with SACK disabled still accepts (and ignores) any SACK the wire can carry
+        let max_sack_ranges = match config.sack_max_ranges {
+            0 => command_codec::MAX_SACK_RANGES,
+            max => max as usize,
+        };
+        // Nothing a peer sends legitimately exceeds one receive buffer, so probe payloads
+        // declaring more are rejected before they are allocated
+        let packet = CommandDecoder::decode_packet_limited(
+            &decompressed,
+            config.receive_buffer_max_size,
+            max_sack_ranges,
+        )
+        .map_err(|e| ErrorKind::CouldNotReadHeader(e.to_string()))?;
+
+        Ok(DecodedDatagram { packet, checksum_omitted, immediate_ack })
+    }
+
     /// Decodes and processes an incoming command packet.
     /// This is the command-based alternative to `process_incoming`.
     /// Returns all user packets that resulted from processing the commands.
-    /// Validates CRC32 checksum if enabled, then decompresses if needed.
+    /// Validates the configured checksum if enabled, then decompresses if needed. On a trusted
+    /// path the datagram is the bare command packet.
     pub fn process_command_packet(
         &mut self,
         data: &[u8],
@@ -25,24 +87,22 @@ impl Peer {
     ) -> Result<IncomingPackets> {
         // Track bytes received
         self.record_data_received(data.len());
+        self.last_datagram_size = Some(data.len());
 
-        // Validate and strip checksum if enabled (before decompression)
-        let payload = if self.config.use_checksums {
-            CommandDecoder::validate_and_strip_checksum(data)
-                .map_err(|e| ErrorKind::CouldNotReadHeader(e.to_string()))?
-        } else {
-            data
-        };
+        let DecodedDatagram { packet: command_packet, checksum_omitted, immediate_ack } =
+            Self::decode_datagram(&self.config, data)?;
 
-        // Decompress if needed
-        let decompressed = CommandDecoder::decompress(payload)
-            .map_err(|e| ErrorKind::CouldNotReadHeader(e.to_string()))?;
-
-        let command_packet = CommandDecoder::decode_packet(&decompressed)
-            .map_err(|e| ErrorKind::CouldNotReadHeader(e.to_string()))?;
+        // The flag is not covered by a checksum, so never trust it for data
+        if checksum_omitted && command_packet.commands.iter().any(ProtocolCommand::is_data) {
+            return Err(ErrorKind::CouldNotReadHeader(
+                "Data commands in datagram without checksum".to_string(),
+            ));
+        }
 
         // Record packet being received
         self.record_packet_received();
+        // A datagram from a new address identifying this connection may start its validation
+        self.check_datagram_source(&command_packet.commands, data.len(), time);
 
         let mut all_packets = std::collections::VecDeque::new();
 
@@ -53,7 +113,13 @@ impl Peer {
             }
         }
 
-        Ok(IncomingPackets::many(all_packets))
+        // Flush a delayed ACK for this datagram's data right away if the sender asked
+        if immediate_ack && self.ack_deadline.is_some() {
+            self.schedule_ack(time, true);
+        }
+        self.advertise_receive_window();
+
+        Ok(IncomingPackets::many(all_packets).with_datagram_size(data.len()))
     }
 
     /// Processes an incoming protocol command and returns resulting actions.
@@ -64,16 +130,29 @@ impl Peer {
         command: &ProtocolCommand,
         time: Instant,
     ) -> Result<IncomingPackets> {
-        self.last_heard = time;
+        if self.config.pmtu_probes_count_as_activity || !command.is_pmtu() {
+            self.last_heard = time;
+        }
+
+        // Reliable data is acknowledged below once accepted; other ack-eliciting commands
+        // only under `AckPolicy::AllEliciting`
+        if self.config.ack_policy == AckPolicy::AllEliciting
+            && command.is_ack_eliciting()
+            && !matches!(
+                command,
+                ProtocolCommand::SendReliable { .. } | ProtocolCommand::SendFragment { .. }
+            )
+        {
+            self.schedule_ack(time, false);
+        }
 
         match command {
             ProtocolCommand::Acknowledge { sequence, received_mask, .. } => {
-                self.acknowledge_handler.process_incoming(
-                    *sequence,
-                    *sequence,
-                    *received_mask,
-                    time,
-                );
+                self.process_acknowledgment(*sequence, *received_mask, time);
+                Ok(IncomingPackets::zero())
+            }
+            ProtocolCommand::Sack { ranges } => {
+                self.process_sack(ranges, time);
                 Ok(IncomingPackets::zero())
             }
             ProtocolCommand::Ping { timestamp } => {
@@ -81,16 +160,25 @@ impl Peer {
                 self.enqueue_pong_command(*timestamp);
                 Ok(IncomingPackets::zero())
             }
-            ProtocolCommand::Pong { .. } => {
-                // Pong received, RTT calculated in acknowledgment handler
+            ProtocolCommand::Pong { timestamp } => {
+                // Keepalive RTT is calculated in the acknowledgment handler; only pongs
+                // answering `Peer::ping` are resolved here
+                self.pending_pings.resolve(*timestamp, time);
                 Ok(IncomingPackets::zero())
             }
             ProtocolCommand::SendReliable { channel_id, sequence, ordered, data } => {
                 // Process reliable data command
-                self.acknowledge_handler.process_incoming(*sequence, *sequence, 0, time);
+                self.acknowledge_handler.record_received(*sequence);
+                self.record_arrival_order(*sequence);
+                // Retransmitted copies were counted when the first one arrived
+                if !self.reliable_received.is_duplicate(*sequence) {
+                    self.reliable_received.mark_received(*sequence);
+                    self.reliable_bytes_received =
+                        self.reliable_bytes_received.wrapping_add(data.len() as u32);
+                }
 
-                // Automatically enqueue ACK for reliable data
-                self.enqueue_ack_command(None);
+                // Acknowledge reliable data, possibly after `ack_delay_ms`
+                self.schedule_ack(time, false);
                 if *ordered {
                     // Ordered delivery via per-channel buffering
                     let channel_state =
@@ -239,8 +327,20 @@ impl Peer {
                 fragment_count,
                 data,
             } => {
+                // Reserve buffer space first; refused fragments are neither buffered nor acked
+                if !self.reserve_reassembly(data.len()) {
+                    return Ok(IncomingPackets::zero());
+                }
+
                 // Process fragment and reassemble if complete
-                self.acknowledge_handler.process_incoming(*sequence, *sequence, 0, time);
+                self.acknowledge_handler.record_received(*sequence);
+                self.record_arrival_order(*sequence);
+                // A fragment of a message already reassembled is a retransmitted copy
+                if self.reliable_received.is_duplicate(*sequence) {
+                    self.release_reassembly(data.len());
+                    self.schedule_ack(time, false);
+                    return Ok(IncomingPackets::zero());
+                }
 
                 // Get or create fragment buffer for this sequence
                 let buffer = self.command_fragments.entry(*sequence).or_insert_with(|| {
@@ -248,22 +348,38 @@ impl Peer {
                 });
 
                 // Add this fragment
-                buffer.add_fragment(
+                let replaced = buffer.add_fragment(
                     *fragment_id,
                     data.clone().into_full_arc().unwrap_or_else(|| {
                         std::sync::Arc::<[u8]>::from(data.as_slice().to_vec().into_boxed_slice())
                     }),
+                    time,
                 );
+                let is_complete = buffer.is_complete();
+                match replaced {
+                    Some(previous) => self.release_reassembly(previous.len()),
+                    None => {
+                        self.reliable_bytes_received =
+                            self.reliable_bytes_received.wrapping_add(data.len() as u32)
+                    }
+                }
+                if is_complete {
+                    self.reliable_received.mark_received(*sequence);
+                }
+                if !is_complete {
+                    self.enforce_reassembly_limit();
+                }
 
                 // Check if reassembly is complete
-                if buffer.is_complete() {
+                if is_complete {
                     // Remove buffer and reassemble
                     if let Some(buffer) = self.command_fragments.remove(sequence) {
+                        self.release_reassembly(buffer.buffered_bytes());
                         let channel_id = buffer.channel_id();
                         let is_ordered = buffer.is_ordered();
                         if let Some(reassembled) = buffer.reassemble() {
-                            // Automatically enqueue ACK for the complete fragmented packet
-                            self.enqueue_ack_command(None);
+                            // Acknowledge the complete fragmented packet, possibly after `ack_delay_ms`
+                            self.schedule_ack(time, false);
 
                             if is_ordered {
                                 // For ordered: push through channel ordering using the sequence
@@ -358,23 +474,36 @@ impl Peer {
                 data,
             } => {
                 // Process unreliable fragment and reassemble if complete (no ACK needed)
+                if !self.reserve_reassembly(data.len()) {
+                    return Ok(IncomingPackets::zero());
+                }
+
                 // Get or create fragment buffer for this sequence
                 let buffer = self.command_fragments.entry(*sequence).or_insert_with(|| {
                     CommandFragmentBuffer::new(*channel_id, *fragment_count, false, time)
                 });
 
                 // Add this fragment
-                buffer.add_fragment(
+                let replaced = buffer.add_fragment(
                     *fragment_id,
                     data.clone().into_full_arc().unwrap_or_else(|| {
                         std::sync::Arc::<[u8]>::from(data.as_slice().to_vec().into_boxed_slice())
                     }),
+                    time,
                 );
+                let is_complete = buffer.is_complete();
+                if let Some(previous) = replaced {
+                    self.release_reassembly(previous.len());
+                }
+                if !is_complete {
+                    self.enforce_reassembly_limit();
+                }
 
                 // Check if reassembly is complete
-                if buffer.is_complete() {
+                if is_complete {
                     // Remove buffer and reassemble
                     if let Some(buffer) = self.command_fragments.remove(sequence) {
+                        self.release_reassembly(buffer.buffered_bytes());
                         let channel_id = buffer.channel_id();
                         if let Some(reassembled) = buffer.reassemble() {
                             // Return unreliable packet (no ACK)
@@ -395,42 +524,90 @@ impl Peer {
                 // Not complete yet, or reassembly failed
                 Ok(IncomingPackets::zero())
             }
-            ProtocolCommand::Disconnect { reason: _ } => {
+            ProtocolCommand::Disconnect { reason } => {
                 // Mark peer as zombie - session manager will emit disconnect event and clean up
                 self.state = PeerState::Zombie;
+                self.set_close_reason(CloseReason::RemoteClose { code: *reason });
+                Ok(IncomingPackets::zero())
+            }
+            ProtocolCommand::VersionNegotiation { connect_id, supported } => {
+                // Client-side: the server refused our version; retry with a common one. A
+                // refusal not echoing our connect ID is spoofed or stale and ignored.
+                if self.state == PeerState::Connecting && *connect_id == self.connect_id {
+                    let fallback = supported
+                        .iter()
+                        .copied()
+                        .filter(|version| {
+                            (MIN_HANDSHAKE_PROTOCOL_VERSION..=HANDSHAKE_PROTOCOL_VERSION)
+                                .contains(version)
+                        })
+                        .max();
+                    match fallback {
+                        Some(version) if version != self.protocol_version => {
+                            self.protocol_version = version;
+                            self.state = PeerState::Idle;
+                            self.initiate_connect();
+                        }
+                        _ => {
+                            self.state = PeerState::Zombie;
+                            self.set_close_reason(CloseReason::ProtocolError);
+                            return Err(ErrorKind::ProtocolVersionMismatch);
+                        }
+                    }
+                }
                 Ok(IncomingPackets::zero())
             }
             ProtocolCommand::Connect {
                 channels,
-                mtu: _,
-                protocol_version: _,
+                mtu,
+                protocol_version,
                 outgoing_session_id,
                 connect_id,
+                idle_timeout_ms,
             } => {
                 // Server-side: Received CONNECT from client (step 1 of 3-way handshake)
                 // Validate connect_id for replay protection
-                if self.state == PeerState::Idle {
-                    use rand::Rng;
-                    let mut rng = rand::rng();
+                if self.state == PeerState::Connected && *connect_id != self.connect_id {
+                    // The remote may have lost its connection state (e.g. restarted), but the
+                    // Connect is unauthenticated: reset only if the connection stops answering
+                    self.challenge_restart(time);
+                } else if self.state == PeerState::Idle {
+                    if *protocol_version < MIN_HANDSHAKE_PROTOCOL_VERSION {
+                        // Too old to talk to; list what we support so the client can retry
+                        let supported =
+                            (MIN_HANDSHAKE_PROTOCOL_VERSION..=HANDSHAKE_PROTOCOL_VERSION).collect();
+                        self.command_queue.enqueue(ProtocolCommand::VersionNegotiation {
+                            connect_id: *connect_id,
+                            supported,
+                        });
+                        return Err(ErrorKind::ProtocolVersionMismatch);
+                    }
 
                     // Store client's session ID as our incoming
                     self.incoming_session_id = *outgoing_session_id;
                     // Assign a peer ID (in real impl, this would be managed by host)
-                    self.peer_id = rng.random();
+                    self.peer_id = self.rng.random();
                     // Store connect ID for validation
                     self.connect_id = *connect_id;
 
+                    // Negotiate down to what both sides support
+                    self.protocol_version = (*protocol_version).min(HANDSHAKE_PROTOCOL_VERSION);
+                    self.negotiated_channels = (*channels).min(self.config.channel_count);
+                    self.negotiated_mtu = (*mtu).min(HANDSHAKE_MTU);
+                    self.negotiate_idle_timeout(*idle_timeout_ms);
+
                     // Transition to AcknowledgingConnect
                     self.state = PeerState::AcknowledgingConnect;
 
                     // Send VERIFY_CONNECT (step 2 of 3-way handshake)
                     let verify_command = ProtocolCommand::VerifyConnect {
                         peer_id: self.peer_id,
-                        channels: *channels.min(&self.config.channel_count), // Negotiate
-                        mtu: 1400,                                           // Negotiate MTU
+                        channels: self.negotiated_channels,
+                        mtu: self.negotiated_mtu,
                         incoming_session_id: self.incoming_session_id,
                         outgoing_session_id: self.outgoing_session_id,
                         window_size: self.window_size(), // Send our window size
+                        idle_timeout_ms: Self::duration_ms(self.negotiated_idle_timeout),
                     };
                     self.command_queue.enqueue(verify_command);
                 }
@@ -438,32 +615,31 @@ impl Peer {
             }
             ProtocolCommand::VerifyConnect {
                 peer_id,
-                channels: _,
-                mtu: _,
+                channels,
+                mtu,
                 incoming_session_id,
                 outgoing_session_id,
                 window_size,
+                idle_timeout_ms,
             } => {
                 // Client-side: Received VERIFY_CONNECT from server (step 2 of 3-way handshake)
                 if self.state == PeerState::Connecting {
-                    // Store server's session IDs
+                    self.adopt_session_ids(*incoming_session_id, *outgoing_session_id)?;
                     self.peer_id = *peer_id;
-                    self.incoming_session_id = *incoming_session_id;
 
                     // Negotiate window size (take minimum of ours and server's)
                     if self.config.use_window_flow_control {
                         self.set_window_size((*window_size).min(self.window_size()));
                     }
-                    // Verify outgoing session ID matches
-                    if *outgoing_session_id != self.outgoing_session_id {
-                        // Session ID mismatch - potential attack
-                        return Err(ErrorKind::CouldNotReadHeader(
-                            "Session ID mismatch".to_string(),
-                        ));
-                    }
+
+                    // Adopt the channel count and MTU chosen by the server
+                    self.negotiated_channels = *channels;
+                    self.negotiated_mtu = *mtu;
+                    self.negotiate_idle_timeout(*idle_timeout_ms);
 
                     // Transition to ConnectionSucceeded
                     self.state = PeerState::ConnectionSucceeded;
+                    self.complete_handshake();
 
                     // Send ACK (any data packet serves as implicit ACK - step 3)
                     // The next data packet sent will complete the handshake
@@ -498,25 +674,115 @@ impl Peer {
                 );
                 Ok(IncomingPackets::zero())
             }
-            ProtocolCommand::PMTUProbe { size, token, .. } => {
-                // Respond to PMTU probe with a reply (small control)
-                let reply = PmtuDiscovery::create_reply(*size, *token);
-                self.enqueue_command(reply);
+            ProtocolCommand::PMTUProbe { size, token, payload } => {
+                // Respond to PMTU probe with a reply (small control). This is independent of
+                // `use_pmtu_discovery` so a peer with discovery disabled still acts as a responder.
+                if !PmtuDiscovery::verify_probe(&self.config, *size, *token, payload.as_slice()) {
+                    // Answering a damaged probe would confirm a size that did not get through
+                    tracing::debug!("Ignoring PMTU probe {}: payload failed verification", token);
+                } else if self.pmtu.process_reflected_probe(*size, *token, time) {
+                    // The probe answers our own request: it is the reply
+                    self.notify_mtu_change();
+                } else if self.pmtu.allow_reply(time) {
+                    let reply = PmtuDiscovery::create_reply(*size, *token);
+                    self.enqueue_command(reply);
+                } else {
+                    tracing::debug!("Suppressing PMTU reply to probe {}: rate limit", token);
+                }
                 Ok(IncomingPackets::zero())
             }
             ProtocolCommand::PMTUReply { size, token } => {
                 // Process the reply through the PMTU discovery module
                 self.pmtu.process_reply(*size, *token, time);
+                self.notify_mtu_change();
+                Ok(IncomingPackets::zero())
+            }
+            ProtocolCommand::PMTUProbeRequest { size, token, .. } => {
+                // Send the requested probe back, subject to the same rate limit as replies.
+                // Unauthenticated requests must not turn the peer into an amplifier, so only
+                // an established connection answers, within a multiple of the request's size.
+                if self.state != PeerState::Connected {
+                    tracing::debug!("Ignoring PMTU probe request {}: not connected", token);
+                } else if !self.pmtu.allow_reply(time) {
+                    tracing::debug!("Suppressing PMTU probe request {}: rate limit", token);
+                } else {
+                    let request_bytes = self.last_datagram_size.unwrap_or(0);
+                    match self.pmtu.create_reflected_probe(*size, *token, request_bytes) {
+                        Some(probe) => {
+                            self.enqueue_command(probe);
+                        }
+                        None => tracing::debug!(
+                            "Ignoring PMTU probe request {}: {} bytes requested by a {} byte datagram",
+                            token,
+                            size,
+                            request_bytes
+                        ),
+                    }
+                }
+                Ok(IncomingPackets::zero())
+            }
+            ProtocolCommand::WindowProbe => {
+                // Report how much reliable data has arrived so the sender can reopen its window
+                let available = self.receive_window_available();
+                self.advertised_receive_window = available;
+                self.enqueue_command(ProtocolCommand::WindowUpdate {
+                    received: self.reliable_bytes_received,
+                    available,
+                });
+                Ok(IncomingPackets::zero())
+            }
+            ProtocolCommand::WindowUpdate { received, available } => {
+                self.flow_control.apply_window_update(*received, *available);
+                Ok(IncomingPackets::zero())
+            }
+            ProtocolCommand::PathChallenge { data } => {
+                // Echo the data so the remote can validate the path the challenge arrived on.
+                // Only an established connection answers, which also tells a connection that
+                // is alive from one the remote restarted.
+                if self.is_established() {
+                    self.enqueue_command(ProtocolCommand::PathResponse { data: *data });
+                    // Lets the response through to the connection if our address changed
+                    self.enqueue_connection_id();
+                }
                 Ok(IncomingPackets::zero())
             }
+            ProtocolCommand::PathResponse { data } => {
+                if !self.process_restart_response(*data) {
+                    self.process_path_response(*data);
+                }
+                Ok(IncomingPackets::zero())
+            }
+            // Checked for the whole datagram before its commands are processed
+            ProtocolCommand::ConnectionId { .. } => Ok(IncomingPackets::zero()),
+        }
+    }
+
+    /// Checks the session IDs of the server's VerifyConnect and adopts the server's. The server
+    /// fills both from its own side: its incoming session ID is ours echoed back, and its
+    /// outgoing session ID becomes our incoming one.
+    fn adopt_session_ids(
+        &mut self,
+        incoming_session_id: u16,
+        outgoing_session_id: u16,
+    ) -> Result<()> {
+        if incoming_session_id != self.outgoing_session_id {
+            // Session ID mismatch - potential attack
+            self.state = PeerState::Zombie;
+            self.set_close_reason(CloseReason::ProtocolError);
+            return Err(ErrorKind::CouldNotReadHeader("Session ID mismatch".to_string()));
         }
+        self.incoming_session_id = outgoing_session_id;
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use bitfold_core::config::Config;
+    use std::time::Duration;
+
+    use bitfold_core::config::{Config, PmtuProbePayload};
     use bitfold_protocol::command::ProtocolCommand;
+    use rand::SeedableRng;
 
     use super::*;
 
@@ -524,10 +790,93 @@ mod tests {
         Peer::new(get_fake_addr(), &Config::default(), Instant::now())
     }
 
+    #[test]
+    fn test_connect_with_unsupported_version_is_refused() {
+        let time = Instant::now();
+        let mut server = create_virtual_connection();
+        let connect = ProtocolCommand::Connect {
+            channels: 1,
+            mtu: HANDSHAKE_MTU,
+            protocol_version: MIN_HANDSHAKE_PROTOCOL_VERSION - 1,
+            outgoing_session_id: 7,
+            connect_id: 9,
+            idle_timeout_ms: 5000,
+        };
+        assert!(matches!(
+            server.process_command(&connect, time),
+            Err(ErrorKind::ProtocolVersionMismatch)
+        ));
+        assert_eq!(server.state(), PeerState::Idle);
+        let commands: Vec<_> = server.drain_commands().collect();
+        assert_eq!(commands, vec![ProtocolCommand::VersionNegotiation {
+            connect_id: 9,
+            supported: (MIN_HANDSHAKE_PROTOCOL_VERSION..=HANDSHAKE_PROTOCOL_VERSION).collect(),
+        }]);
+    }
+
+    #[test]
+    fn test_version_negotiation_without_common_version_closes() {
+        let time = Instant::now();
+        let mut client = create_virtual_connection();
+        client.initiate_connect();
+        client.drain_commands().for_each(drop);
+
+        let refusal = ProtocolCommand::VersionNegotiation {
+            connect_id: client.connect_id,
+            supported: vec![HANDSHAKE_PROTOCOL_VERSION + 1],
+        };
+        assert!(matches!(
+            client.process_command(&refusal, time),
+            Err(ErrorKind::ProtocolVersionMismatch)
+        ));
+        assert_eq!(client.state(), PeerState::Zombie);
+        assert_eq!(client.close_reason(), Some(CloseReason::ProtocolError));
+    }
+
+    #[test]
+    fn test_spoofed_version_negotiation_is_ignored() {
+        let time = Instant::now();
+        let mut client = create_virtual_connection();
+        client.initiate_connect();
+        client.drain_commands().for_each(drop);
+
+        // Neither a refusal nor a downgrade takes effect without the client's connect ID
+        for supported in [vec![HANDSHAKE_PROTOCOL_VERSION + 1], vec![0]] {
+            let spoofed = ProtocolCommand::VersionNegotiation {
+                connect_id: client.connect_id.wrapping_add(1),
+                supported,
+            };
+            client.process_command(&spoofed, time).unwrap();
+            assert_eq!(client.state(), PeerState::Connecting);
+            assert_eq!(client.protocol_version, HANDSHAKE_PROTOCOL_VERSION);
+            assert!(!client.has_queued_commands());
+        }
+        assert_eq!(client.close_reason(), None);
+    }
+
     fn get_fake_addr() -> std::net::SocketAddr {
         "127.0.0.1:0".parse().unwrap()
     }
 
+    #[test]
+    fn test_verify_connect_exchanges_session_ids() {
+        let time = Instant::now();
+        let config = Config::default();
+        let mut client = Peer::new(get_fake_addr(), &config, time);
+        let mut server = Peer::new(get_fake_addr(), &config, time);
+        client.record_send();
+        for command in client.drain_commands().collect::<Vec<_>>() {
+            server.process_command(&command, time).unwrap();
+        }
+        for command in server.drain_commands().collect::<Vec<_>>() {
+            client.process_command(&command, time).unwrap();
+        }
+
+        assert_eq!(client.state, PeerState::ConnectionSucceeded);
+        assert_eq!(server.incoming_session_id, client.outgoing_session_id);
+        assert_eq!(client.incoming_session_id, server.outgoing_session_id);
+    }
+
     #[test]
     fn test_command_queue_integration() {
         let mut peer = create_virtual_connection();
@@ -615,6 +964,421 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_pmtu_probe_resets_idle_timer_when_counted_as_activity() {
+        for counts_as_activity in [true, false] {
+            let mut config = Config::default();
+            config.pmtu_probes_count_as_activity = counts_as_activity;
+            let start = Instant::now();
+            let mut peer = Peer::new(get_fake_addr(), &config, start);
+            let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+            let probe = PmtuDiscovery::build_probe(&config, 1200, 9, &mut rng);
+
+            let time = start + Duration::from_secs(3);
+            peer.process_command(&probe, time).unwrap();
+            assert!(peer
+                .drain_commands()
+                .any(|cmd| matches!(cmd, ProtocolCommand::PMTUReply { .. })));
+            let expected = if counts_as_activity { Duration::ZERO } else { Duration::from_secs(3) };
+            assert_eq!(peer.last_heard(time), expected);
+
+            // Other traffic always counts
+            peer.process_command(&ProtocolCommand::Ping { timestamp: 1 }, time).unwrap();
+            assert_eq!(peer.last_heard(time), Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn test_truncated_verifiable_probe_gets_no_reply() {
+        let mut config = Config::default();
+        config.pmtu_probe_payload_mode = PmtuProbePayload::Verifiable;
+        let time = Instant::now();
+        let mut peer = Peer::new(get_fake_addr(), &config, time);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+
+        let probe = PmtuDiscovery::build_probe(&config, 1200, 9, &mut rng);
+        let ProtocolCommand::PMTUProbe { size, token, payload } = &probe else {
+            panic!("Expected PMTUProbe");
+        };
+        let truncated = ProtocolCommand::PMTUProbe {
+            size: *size,
+            token: *token,
+            payload: payload.slice(..payload.len() - 8),
+        };
+        peer.process_command(&truncated, time).unwrap();
+        assert!(peer.drain_commands().all(|cmd| !matches!(cmd, ProtocolCommand::PMTUReply { .. })));
+
+        peer.process_command(&probe, time).unwrap();
+        let replies: Vec<_> = peer
+            .drain_commands()
+            .filter(|cmd| matches!(cmd, ProtocolCommand::PMTUReply { .. }))
+            .collect();
+        assert_eq!(replies, vec![ProtocolCommand::PMTUReply { size: 1200, token: 9 }]);
+    }
+
+    #[test]
+    fn test_pmtu_probe_storm_gets_bounded_replies() {
+        let mut config = Config::default();
+        config.pmtu_max_replies_per_sec = 5;
+        let time = Instant::now();
+        let mut peer = Peer::new(get_fake_addr(), &config, time);
+
+        // The same probe retransmitted rapidly
+        let probe =
+            ProtocolCommand::PMTUProbe { size: 1200, token: 7, payload: vec![0u8; 16].into() };
+        for step in 0..100 {
+            let at = time + std::time::Duration::from_millis(step);
+            peer.process_command(&probe, at).unwrap();
+        }
+
+        let replies = peer
+            .drain_commands()
+            .filter(|command| matches!(command, ProtocolCommand::PMTUReply { .. }))
+            .count();
+        assert_eq!(replies, 5);
+    }
+
+    #[test]
+    fn test_pmtu_probe_reply_with_discovery_disabled() {
+        let mut config = Config::default();
+        config.use_pmtu_discovery = false;
+        let time = Instant::now();
+        let mut peer = Peer::new(get_fake_addr(), &config, time);
+
+        // Disabled peer never probes on its own
+        peer.handle_pmtu(time + std::time::Duration::from_secs(60));
+        assert!(!peer.has_queued_commands());
+
+        let probe =
+            ProtocolCommand::PMTUProbe { size: 1200, token: 7, payload: vec![0u8; 16].into() };
+        peer.process_command(&probe, time).unwrap();
+
+        let commands: Vec<_> = peer.drain_commands().collect();
+        assert!(matches!(commands[..], [ProtocolCommand::PMTUReply { size: 1200, token: 7 }]));
+    }
+
+    #[test]
+    fn test_pmtu_discovery_converges_against_disabled_responder() {
+        let mut prober_config = Config::default();
+        prober_config.pmtu_interval_ms = 100;
+        let mut responder_config = Config::default();
+        responder_config.use_pmtu_discovery = false;
+
+        let start = Instant::now();
+        let mut prober = Peer::new(get_fake_addr(), &prober_config, start);
+        let mut responder = Peer::new(get_fake_addr(), &responder_config, start);
+
+        let time = start + std::time::Duration::from_millis(150);
+        prober.handle_pmtu(time);
+        let (size, _, _, _) = prober.pmtu.outstanding_probe().unwrap();
+
+        let probe_packet = prober.encode_queued_commands().unwrap();
+        responder.process_command_packet(&probe_packet, time).unwrap();
+        let reply_packet = responder.encode_queued_commands().unwrap();
+        prober.process_command_packet(&reply_packet, time).unwrap();
+
+        assert!(!prober.pmtu.has_outstanding_probe());
+        assert_eq!(prober.current_fragment_size(), size);
+    }
+
+    /// Decodes the commands of a datagram encoded under the default config.
+    fn decode_datagram(datagram: &[u8]) -> Vec<ProtocolCommand> {
+        let payload = CommandDecoder::validate_and_strip_checksum(datagram).unwrap();
+        let decompressed = CommandDecoder::decompress(payload).unwrap();
+        CommandDecoder::decode_packet(&decompressed).unwrap().commands
+    }
+
+    #[test]
+    fn test_pmtu_probe_request_elicits_sized_probe() {
+        let time = Instant::now();
+        let mut requester = create_virtual_connection();
+        let mut responder = create_virtual_connection();
+
+        let request =
+            |padding| ProtocolCommand::PMTUProbeRequest { size: 1100, token: 42, padding };
+
+        // Requests are only answered on an established connection
+        requester.enqueue_command(request(367));
+        let request_packet = requester.encode_queued_commands().unwrap();
+        responder.process_command_packet(&request_packet, time).unwrap();
+        assert!(!responder.has_queued_commands());
+        responder.state = PeerState::Connected;
+
+        // An unpadded request would amplify its size by far more than three
+        requester.enqueue_command(request(0));
+        let small_packet = requester.encode_queued_commands().unwrap();
+        responder.process_command_packet(&small_packet, time).unwrap();
+        assert!(!responder.has_queued_commands());
+
+        responder.process_command_packet(&request_packet, time).unwrap();
+        let probe_packet = responder.encode_queued_commands().unwrap();
+        assert_eq!(probe_packet.len(), 1100);
+
+        let mut decoded = decode_datagram(&probe_packet);
+        assert!(matches!(decoded[..], [ProtocolCommand::PMTUProbe { size: 1100, token: 42, .. }]));
+
+        // Without reflected probes configured, the requester answers it like any other probe
+        requester.process_command(&decoded.remove(0), time).unwrap();
+        let commands: Vec<_> = requester.drain_commands().collect();
+        assert_eq!(commands, vec![ProtocolCommand::PMTUReply { size: 1100, token: 42 }]);
+    }
+
+    #[test]
+    fn test_reflected_pmtu_probes_converge() {
+        let mut requester_config = Config::default();
+        requester_config.pmtu_interval_ms = 100;
+        requester_config.pmtu_reflected_probes = true;
+        let mut responder_config = Config::default();
+        responder_config.use_pmtu_discovery = false;
+        let path_mtu = 1300;
+
+        let start = Instant::now();
+        let mut requester = Peer::new(get_fake_addr(), &requester_config, start);
+        let mut responder = Peer::new(get_fake_addr(), &responder_config, start);
+        responder.state = PeerState::Connected;
+
+        let mut time = start;
+        for _ in 0..100 {
+            if requester.pmtu.is_converged() {
+                break;
+            }
+            time += Duration::from_millis(500);
+            requester.handle_pmtu(time);
+            if !requester.has_queued_commands() {
+                continue;
+            }
+            let request_packet = requester.encode_queued_commands().unwrap();
+            let request = decode_datagram(&request_packet);
+            assert!(matches!(request[..], [ProtocolCommand::PMTUProbeRequest { .. }]));
+            responder.process_command_packet(&request_packet, time).unwrap();
+
+            // The reverse path drops datagrams above its MTU
+            let probe_packet = responder.encode_queued_commands().unwrap();
+            if probe_packet.len() <= path_mtu {
+                requester.process_command_packet(&probe_packet, time).unwrap();
+                // The reflected probe is consumed, not replied to
+                assert!(!requester.has_queued_commands());
+            }
+        }
+
+        assert!(requester.pmtu.is_converged());
+        let size = requester.current_fragment_size() as usize;
+        assert!(size <= path_mtu);
+        assert!(path_mtu - size <= requester_config.pmtu_converge_threshold as usize);
+    }
+
+    #[test]
+    fn test_window_probe_recovers_from_lost_window_update() {
+        let mut config = Config::default();
+        config.use_window_flow_control = true;
+        config.min_window_size = 1;
+        config.initial_window_size = 2;
+        config.fragment_size = 100;
+        config.flow_control_probe_interval_ms = 200;
+        let interval = std::time::Duration::from_millis(200);
+
+        let start = Instant::now();
+        let mut sender = Peer::new(get_fake_addr(), &config, start);
+        let mut receiver = Peer::new(get_fake_addr(), &config, start);
+
+        // Fill the sender's 200-byte window; the receiver gets the data but its ack is lost
+        sender.enqueue_command(ProtocolCommand::SendReliable {
+            channel_id: 0,
+            sequence: 0,
+            ordered: false,
+            data: vec![0u8; 250].into(),
+        });
+        let data_packet = sender.encode_queued_commands().unwrap();
+        receiver.process_command_packet(&data_packet, start).unwrap();
+        receiver.drain_commands().for_each(drop);
+        assert!(!sender.can_send_reliable());
+
+        // No probe until the window has been blocked for a full interval
+        sender.handle_window_probe(start);
+        assert!(!sender.has_queued_commands());
+
+        // First probe is answered, but the window update is lost as well
+        let time = start + interval;
+        sender.handle_window_probe(time);
+        let probe_packet = sender.encode_queued_commands().unwrap();
+        receiver.process_command_packet(&probe_packet, time).unwrap();
+        let lost: Vec<_> = receiver.drain_commands().collect();
+        assert!(matches!(lost[..], [ProtocolCommand::WindowUpdate { received: 250, .. }]));
+        assert!(!sender.can_send_reliable());
+
+        // Probing repeats on the interval rather than waiting forever
+        sender.handle_window_probe(time + interval / 2);
+        assert!(!sender.has_queued_commands());
+        let time = time + interval;
+        sender.handle_window_probe(time);
+        let probe_packet = sender.encode_queued_commands().unwrap();
+        receiver.process_command_packet(&probe_packet, time).unwrap();
+        let update_packet = receiver.encode_queued_commands().unwrap();
+        sender.process_command_packet(&update_packet, time).unwrap();
+
+        assert_eq!(sender.reliable_data_in_transit(), 0);
+        assert!(sender.can_send_reliable());
+        sender.handle_window_probe(time + interval * 10);
+        assert!(!sender.has_queued_commands());
+    }
+
+    #[test]
+    fn test_receive_window_shrinks_closes_and_reopens() {
+        let mut receiver_config = Config::default();
+        receiver_config.use_window_flow_control = true;
+        receiver_config.min_window_size = 1;
+        receiver_config.initial_window_size = 2;
+        receiver_config.max_window_size = 2;
+        receiver_config.receive_buffer_max_size = 1000;
+        receiver_config.fragment_size = 1000;
+        receiver_config.flow_control_probe_interval_ms = 200;
+        // The sender's own window is far larger, so only the advertised window holds it back
+        let mut sender_config = receiver_config.clone();
+        sender_config.initial_window_size = 8;
+        sender_config.max_window_size = 8;
+        let interval = Duration::from_millis(200);
+
+        let start = Instant::now();
+        let mut sender = Peer::new(get_fake_addr(), &sender_config, start);
+        let mut receiver = Peer::new(get_fake_addr(), &receiver_config, start);
+        assert_eq!(receiver.receive_window_available(), 2000);
+
+        let reliable = |sequence: u16, len: usize| ProtocolCommand::SendReliable {
+            channel_id: 0,
+            sequence,
+            ordered: true,
+            data: vec![0u8; len].into(),
+        };
+        let window_updates = |peer: &mut Peer| -> Vec<(u32, u32)> {
+            peer.drain_commands()
+                .filter_map(|command| match command {
+                    ProtocolCommand::WindowUpdate { received, available } => {
+                        Some((received, available))
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // Sequence 0 is delayed, so the receiver buffers 700 bytes and advertises the rest
+        sender.enqueue_command(reliable(0, 100));
+        let delayed = sender.encode_queued_commands_bounded(1000).unwrap().unwrap();
+        sender.enqueue_command(reliable(1, 700));
+        let packet = sender.encode_queued_commands_bounded(1000).unwrap().unwrap();
+        receiver.process_command_packet(&packet, start).unwrap();
+        assert_eq!(window_updates(&mut receiver), [(700, 1300)]);
+        sender
+            .process_command(
+                &ProtocolCommand::WindowUpdate { received: 700, available: 1300 },
+                start,
+            )
+            .unwrap();
+
+        // Two more fill the advertised window and the receiver's buffer
+        for sequence in 2..4 {
+            assert!(sender.can_send_reliable());
+            sender.enqueue_command(reliable(sequence, 700));
+            let packet = sender.encode_queued_commands_bounded(1000).unwrap().unwrap();
+            receiver.process_command_packet(&packet, start).unwrap();
+        }
+        assert!(!sender.can_send_reliable());
+        assert_eq!(receiver.receive_window_available(), 0);
+        assert_eq!(window_updates(&mut receiver), [(1400, 600), (2100, 0)]);
+
+        // New data stays queued behind the zero window; after an interval only a probe goes out
+        sender.enqueue_command(reliable(4, 100));
+        sender.handle_window_probe(start);
+        let time = start + interval;
+        sender.handle_window_probe(time);
+        let packet = sender.encode_queued_commands_bounded(1000).unwrap().unwrap();
+        assert!(matches!(decode_datagram(&packet)[..], [ProtocolCommand::WindowProbe]));
+        receiver.process_command_packet(&packet, time).unwrap();
+        let update = receiver.encode_queued_commands().unwrap();
+        sender.process_command_packet(&update, time).unwrap();
+        assert!(!sender.can_send_reliable());
+
+        // The delayed packet drains the buffer and the receiver reopens its window unprompted
+        receiver.process_command_packet(&delayed, time).unwrap();
+        assert_eq!(window_updates(&mut receiver), [(2200, 2000)]);
+        sender
+            .process_command(
+                &ProtocolCommand::WindowUpdate { received: 2200, available: 2000 },
+                time,
+            )
+            .unwrap();
+        assert!(sender.can_send_reliable());
+        let packet = sender.encode_queued_commands_bounded(1000).unwrap().unwrap();
+        assert!(decode_datagram(&packet)
+            .iter()
+            .any(|command| matches!(command, ProtocolCommand::SendReliable { sequence: 4, .. })));
+    }
+
+    #[test]
+    fn test_duplicate_reliable_data_keeps_window_open() {
+        let mut config = Config::default();
+        config.use_window_flow_control = true;
+        config.initial_window_size = 2;
+        config.max_window_size = 2;
+        config.receive_buffer_max_size = 1000;
+        config.fragment_size = 1000;
+
+        let time = Instant::now();
+        let mut sender = Peer::new(get_fake_addr(), &config, time);
+        let mut receiver = Peer::new(get_fake_addr(), &config, time);
+        let reliable = |sequence: u16, len: usize| ProtocolCommand::SendReliable {
+            channel_id: 0,
+            sequence,
+            ordered: false,
+            data: vec![0u8; len].into(),
+        };
+
+        // Fill the 2000-byte windows; the receiver's window updates are lost
+        for (sequence, len) in [(0, 700), (1, 700), (2, 600)] {
+            sender.enqueue_command(reliable(sequence, len));
+            let packet = sender.encode_queued_commands_bounded(1000).unwrap().unwrap();
+            receiver.process_command_packet(&packet, time).unwrap();
+        }
+        receiver.drain_commands().for_each(drop);
+        assert!(!sender.can_send_reliable());
+
+        // The last packet is retransmitted and arrives a second time
+        let packet = sender.encode_retransmission(reliable(2, 600), 1000).unwrap().unwrap();
+        receiver.process_command_packet(&packet, time).unwrap();
+        receiver.drain_commands().for_each(drop);
+
+        // The duplicate is not counted, so the next update is accepted and reopens the window
+        receiver.process_command(&ProtocolCommand::WindowProbe, time).unwrap();
+        let update = receiver.encode_queued_commands().unwrap();
+        assert!(matches!(decode_datagram(&update)[..], [ProtocolCommand::WindowUpdate {
+            received: 2000,
+            available: 2000
+        }]));
+        sender.process_command_packet(&update, time).unwrap();
+        assert!(sender.can_send_reliable());
+    }
+
+    #[test]
+    fn test_received_data_acknowledges_nothing_we_sent() {
+        use bitfold_protocol::packet::{OrderingGuarantee, PacketType};
+
+        let mut peer = create_virtual_connection();
+        let time = Instant::now();
+        peer.acknowledge_handler.process_outgoing(
+            PacketType::Packet,
+            &[0u8; 10],
+            OrderingGuarantee::None,
+            None,
+            time,
+        );
+
+        // The remote's data happens to carry the sequence of our packet in flight
+        let later = time + std::time::Duration::from_millis(30);
+        peer.process_command(&reliable(0), later).unwrap();
+        assert_eq!(peer.packets_in_flight(), 1);
+        assert_eq!(peer.acknowledge_handler.congestion().min_rtt(), None);
+        assert_eq!(peer.acknowledge_handler.received_sequences(), vec![0]);
+    }
+
     #[test]
     fn test_automatic_ack_response() {
         let mut peer = create_virtual_connection();
@@ -647,6 +1411,122 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_pure_ack_datagram_does_not_elicit_ack() {
+        for policy in [AckPolicy::ReliableOnly, AckPolicy::AllEliciting] {
+            let mut config = Config::default();
+            config.ack_policy = policy;
+            let time = Instant::now();
+            let mut sender = Peer::new(get_fake_addr(), &config, time);
+            let mut receiver = Peer::new(get_fake_addr(), &config, time);
+
+            // Reliable data is acknowledged
+            sender.enqueue_command(ProtocolCommand::SendReliable {
+                channel_id: 0,
+                sequence: 0,
+                ordered: true,
+                data: vec![1, 2, 3].into(),
+            });
+            let data = sender.encode_queued_commands_bounded(1000).unwrap().unwrap();
+            receiver.process_command_packet(&data, time).unwrap();
+            let ack = receiver.encode_queued_commands().unwrap();
+
+            // The pure ACK does not itself trigger an ACK
+            sender.process_command_packet(&ack, time).unwrap();
+            assert!(!sender.has_queued_commands(), "{:?}", policy);
+        }
+    }
+
+    fn reliable(sequence: u16) -> ProtocolCommand {
+        ProtocolCommand::SendReliable {
+            channel_id: 0,
+            sequence,
+            ordered: true,
+            data: vec![1].into(),
+        }
+    }
+
+    #[test]
+    fn test_delayed_ack_coalesces_until_deadline() {
+        let mut config = Config::default();
+        config.ack_delay_ms = 40;
+        let time = Instant::now();
+        let mut sender = Peer::new(get_fake_addr(), &config, time);
+        let mut receiver = Peer::new(get_fake_addr(), &config, time);
+
+        for sequence in 0..2 {
+            sender.enqueue_command(reliable(sequence));
+            let data = sender.encode_queued_commands_bounded(1000).unwrap().unwrap();
+            receiver.process_command_packet(&data, time).unwrap();
+        }
+        assert!(!receiver.has_queued_commands());
+        assert_eq!(receiver.ack_deadline(), Some(time + Duration::from_millis(40)));
+
+        receiver.handle_delayed_ack(time + Duration::from_millis(39));
+        assert!(!receiver.has_queued_commands());
+        receiver.handle_delayed_ack(time + Duration::from_millis(40));
+        let commands: Vec<_> = receiver.drain_commands().collect();
+        assert!(matches!(commands[..], [ProtocolCommand::Acknowledge { sequence: 1, .. }]));
+        assert_eq!(receiver.ack_deadline(), None);
+    }
+
+    #[test]
+    fn test_immediate_ack_bypasses_delay() {
+        let mut config = Config::default();
+        config.ack_delay_ms = 40;
+        let time = Instant::now();
+        let mut sender = Peer::new(get_fake_addr(), &config, time);
+        let mut receiver = Peer::new(get_fake_addr(), &config, time);
+
+        // A delayed ACK is pending for earlier data
+        sender.enqueue_command(reliable(0));
+        let data = sender.encode_queued_commands_bounded(1000).unwrap().unwrap();
+        receiver.process_command_packet(&data, time).unwrap();
+        assert!(receiver.ack_deadline().is_some());
+
+        // Only the datagram carrying the flagged data requests an immediate ACK
+        sender.request_immediate_ack();
+        sender.enqueue_command(ProtocolCommand::Ping { timestamp: 1 });
+        let ping = sender.encode_queued_commands_bounded(1000).unwrap().unwrap();
+        assert!(!command_codec::is_immediate_ack_requested(&ping));
+        sender.enqueue_command(reliable(1));
+        let data = sender.encode_queued_commands_bounded(1000).unwrap().unwrap();
+        assert!(command_codec::is_immediate_ack_requested(&data));
+
+        let later = time + Duration::from_millis(1);
+        receiver.process_command_packet(&data, later).unwrap();
+        let commands: Vec<_> = receiver.drain_commands().collect();
+        assert!(matches!(commands[..], [ProtocolCommand::Acknowledge { sequence: 1, .. }]));
+        assert_eq!(receiver.ack_deadline(), None);
+
+        // The flag is consumed by one datagram
+        sender.enqueue_command(reliable(2));
+        let data = sender.encode_queued_commands_bounded(1000).unwrap().unwrap();
+        assert!(!command_codec::is_immediate_ack_requested(&data));
+    }
+
+    #[test]
+    fn test_ack_policy_all_eliciting_acks_control_commands() {
+        let time = Instant::now();
+        let control = ProtocolCommand::BandwidthLimit { incoming: 0, outgoing: 0 };
+
+        let mut peer = create_virtual_connection();
+        peer.process_command(&control, time).unwrap();
+        assert!(!peer.has_queued_commands());
+
+        let mut config = Config::default();
+        config.ack_policy = AckPolicy::AllEliciting;
+        let mut peer = Peer::new(get_fake_addr(), &config, time);
+        peer.process_command(&control, time).unwrap();
+        let commands: Vec<_> = peer.drain_commands().collect();
+        assert!(matches!(commands[..], [ProtocolCommand::Acknowledge { .. }]));
+
+        // Keepalives are answered with a pong only
+        peer.process_command(&ProtocolCommand::Ping { timestamp: 1 }, time).unwrap();
+        let commands: Vec<_> = peer.drain_commands().collect();
+        assert!(matches!(commands[..], [ProtocolCommand::Pong { timestamp: 1 }]));
+    }
+
     #[test]
     fn test_round_trip_ping_pong() {
         let mut peer1 = create_virtual_connection();
@@ -1037,4 +1917,26 @@ mod tests {
         let result0_again = peer.process_command(&cmd0, time).unwrap();
         assert_eq!(result0_again.into_iter().count(), 0); // Dropped as old/duplicate
     }
+
+    #[test]
+    fn test_received_datagram_size_is_reported() {
+        let mut config = Config::default();
+        config.use_checksums = true;
+        let time = Instant::now();
+        let mut sender = Peer::new(get_fake_addr(), &config, time);
+        let mut receiver = Peer::new(get_fake_addr(), &config, time);
+        assert_eq!(receiver.last_datagram_size(), None);
+
+        sender.enqueue_command(ProtocolCommand::SendUnreliable {
+            channel_id: 0,
+            data: vec![7u8; 300].into(),
+        });
+        let datagram = sender.encode_queued_commands_bounded(1000).unwrap().unwrap();
+        let packets = receiver.process_command_packet(&datagram, time).unwrap();
+
+        // The raw size, checksum included, not the size of the decoded commands
+        assert_eq!(packets.datagram_size(), Some(datagram.len()));
+        assert_eq!(receiver.last_datagram_size(), Some(datagram.len()));
+        assert_eq!(packets.into_iter().count(), 1);
+    }
 }
@@ -1,5 +1,6 @@
+use bitfold_core::config::{ChecksumAlgorithm, CompressionAlgorithm};
 use bitfold_protocol::{
-    command::CommandPacket,
+    command::{CommandPacket, ProtocolCommand},
     command_codec::{self, CommandEncoder},
 };
 
@@ -7,13 +8,42 @@ use super::Peer;
 
 impl Peer {
     /// Encodes all queued commands into a CommandPacket and returns the bytes.
-    /// Drains the command queue in the process.
-    /// Applies compression if enabled, then appends CRC32 checksum if enabled.
+    /// Drains the command queue in the process, except that data queued with another
+    /// compression override (see [`Peer::send_with_options`]) than the first data stays queued
+    /// for a datagram of its own, like [`Peer::encode_queued_commands_bounded`] does. New data
+    /// held by [`Peer::pause_sending`] or a full window stays queued the same way.
+    /// Applies compression if enabled (or the override), then appends the configured checksum
+    /// if enabled.
     pub fn encode_queued_commands(&mut self) -> std::io::Result<Vec<u8>> {
+        let retransmission_queued = std::mem::take(&mut self.retransmission_queued);
+        let sendable = self.sendable_commands(retransmission_queued);
         let mut packet = CommandPacket::new();
-        for command in self.drain_commands() {
-            packet.add_command(command);
+        let mut compression = None;
+        let mut split = false;
+        let mut reliable_sent = 0;
+        let drained: Vec<_> = self.drain_commands_with_compression().collect();
+        for (index, (command, command_compression)) in drained.into_iter().enumerate() {
+            if !sendable[index] {
+                self.enqueue_command_with_compression(command, command_compression);
+                continue;
+            }
+            if command.is_data() && !split {
+                split = compression.is_some_and(|selected| selected != command_compression);
+                compression = compression.or(Some(command_compression));
+            }
+            if split {
+                self.enqueue_command_with_compression(command, command_compression);
+            } else {
+                if Self::is_new_reliable_data(index, &command, retransmission_queued) {
+                    reliable_sent += Self::command_data_size(&command) as u32;
+                }
+                packet.add_command(command);
+            }
         }
+        if self.config.use_window_flow_control && reliable_sent > 0 {
+            self.flow_control.record_reliable_data_sent(reliable_sent);
+        }
+        let compression = compression.flatten();
 
         // Encode into a pooled scratch buffer to avoid intermediate Vec allocations per step
         let mut scratch = self.tx_pool.allocate();
@@ -21,13 +51,8 @@ impl Peer {
         CommandEncoder::encode_packet_into(&mut scratch, &packet)?;
 
         // Apply compression using pooled buffer to reduce allocations
-        let compression_buffer = self.compression_pool.acquire();
-        let mut final_data = command_codec::compress_with_buffer(
-            &scratch,
-            self.config.compression,
-            self.config.compression_threshold,
-            compression_buffer,
-        )?;
+        let mut final_data = self.compress_datagram(&scratch, compression)?;
+        self.apply_immediate_ack(&packet, &mut final_data);
 
         // Return scratch to pool
         self.tx_pool.deallocate(scratch);
@@ -36,9 +61,7 @@ impl Peer {
         self.record_packet_sent();
 
         // Append checksum if enabled (after compression) in-place
-        if self.config.use_checksums {
-            command_codec::append_checksum_in_place(&mut final_data);
-        }
+        self.apply_checksum(&packet, &mut final_data);
 
         // Track bytes sent
         self.record_data_sent(final_data.len());
@@ -46,6 +69,115 @@ impl Peer {
         Ok(final_data)
     }
 
+    /// Encodes `command` alone into a datagram, compressed and checksummed like queued
+    /// commands, without touching the command queue. For datagrams addressed somewhere other
+    /// than the remote address, such as path challenges.
+    pub(super) fn encode_standalone_command(
+        &mut self,
+        command: ProtocolCommand,
+    ) -> std::io::Result<Vec<u8>> {
+        let packet = CommandPacket::single(command);
+        let encoded = CommandEncoder::encode_packet(&packet)?;
+        let mut final_data = self.compress_datagram(&encoded, None)?;
+        self.apply_checksum(&packet, &mut final_data);
+        self.record_data_sent(final_data.len());
+        Ok(final_data)
+    }
+
+    /// Compresses an encoded datagram into a pooled buffer, unless adaptive compression has
+    /// suspended it, and records the outcome. `compression` overrides the connection's
+    /// algorithm, regardless of adaptive compression. On a trusted path the datagram is copied
+    /// as is, without a compression marker.
+    fn compress_datagram(
+        &mut self,
+        encoded: &[u8],
+        compression: Option<CompressionAlgorithm>,
+    ) -> std::io::Result<Vec<u8>> {
+        if self.config.trusted_path {
+            let mut final_data = self.compression_pool.acquire();
+            final_data.clear();
+            final_data.extend_from_slice(encoded);
+            return Ok(final_data);
+        }
+        let configured = compression.unwrap_or(self.config.compression);
+        let attempted = configured != CompressionAlgorithm::None
+            && encoded.len() >= self.config.compression_threshold;
+        let adaptive = attempted && compression.is_none();
+        let compress = !adaptive
+            || self
+                .adaptive_compression
+                .as_mut()
+                .is_none_or(|adaptive| adaptive.should_compress(encoded));
+        let algorithm = if compress { configured } else { CompressionAlgorithm::None };
+
+        let compression_buffer = self.compression_pool.acquire();
+        let final_data = command_codec::compress_with_buffer(
+            encoded,
+            algorithm,
+            self.config.compression_threshold,
+            compression_buffer,
+        )?;
+        if adaptive && compress {
+            if let Some(adaptive) = self.adaptive_compression.as_mut() {
+                adaptive.record(encoded.len(), final_data.len());
+            }
+        }
+        self.statistics.record_compression(configured, encoded.len(), final_data.len());
+        Ok(final_data)
+    }
+
+    /// Sets the immediate-ACK header flag on the first datagram carrying data after
+    /// [`Peer::request_immediate_ack`]. Must run before the checksum is appended. Datagrams on
+    /// a trusted path have no header byte to carry it.
+    fn apply_immediate_ack(&mut self, packet: &CommandPacket, data: &mut [u8]) {
+        if !self.config.trusted_path
+            && self.immediate_ack_requested
+            && packet.commands.iter().any(ProtocolCommand::is_data)
+        {
+            command_codec::mark_immediate_ack(data);
+            self.immediate_ack_requested = false;
+        }
+    }
+
+    /// Appends the configured checksum to an encoded datagram if checksums are enabled.
+    /// With `checksum_data_only`, control-only datagrams are flagged instead.
+    fn apply_checksum(&self, packet: &CommandPacket, data: &mut Vec<u8>) {
+        let checksum = self.config.checksum_algorithm();
+        if checksum == ChecksumAlgorithm::None {
+            return;
+        }
+        if self.config.checksum_data_only && !packet.commands.iter().any(ProtocolCommand::is_data) {
+            command_codec::mark_checksum_omitted(data);
+        } else {
+            command_codec::append_checksum_in_place_with(data, checksum);
+        }
+    }
+
+    /// Encodes a retransmitted command into a datagram of at most `max_size` bytes,
+    /// coalescing it with as many queued commands (new data, ACKs) as fit in the spare space.
+    ///
+    /// The retransmission always goes first; queued commands that do not fit stay queued.
+    /// Returns `Ok(None)` if the retransmission alone does not fit within `max_size`.
+    /// Commands that should not be retransmitted (see [`Peer::should_retransmit`]) are
+    /// discarded and only queued commands are encoded.
+    pub fn encode_retransmission(
+        &mut self,
+        command: ProtocolCommand,
+        max_size: usize,
+    ) -> std::io::Result<Option<Vec<u8>>> {
+        if !self.should_retransmit(&command) {
+            return self.encode_queued_commands_bounded(max_size);
+        }
+        // Already-sent data is not subject to the admission limits of new data
+        let data_size = Self::command_data_size(&command);
+        self.memory_budget.force_reserve(data_size);
+        self.total_waiting_data += data_size;
+        self.command_queue.enqueue_front(command);
+        // Retransmissions go out even while sending is paused
+        self.retransmission_queued = true;
+        self.encode_queued_commands_bounded(max_size)
+    }
+
     /// Encodes up to `max_size` bytes worth of queued commands into a single datagram.
     ///
     /// - Returns `Ok(None)` if there are no queued commands.
@@ -53,6 +185,9 @@ impl Peer {
     ///
     /// This prevents producing UDP payloads larger than the configured receive buffer (and typical MTUs),
     /// avoiding OS-level EMSGSIZE errors and IP fragmentation.
+    ///
+    /// With `use_window_flow_control`, new reliable data stays queued while the flow-control
+    /// window is full, and reliable data that goes out is counted as in transit.
     pub fn encode_queued_commands_bounded(
         &mut self,
         max_size: usize,
@@ -75,20 +210,32 @@ impl Peer {
         // - per-command 2-byte length prefix
         // - compression marker/header (1 byte; LZ4 adds extra 4 bytes to store original size)
         // - optional checksum (4 bytes)
-        let compression_overhead = match self.config.compression {
-            bitfold_core::config::CompressionAlgorithm::Lz4 => 5, // 1 marker + 4 original size
-            _ => 1,                                               // 1 marker for None/Zlib
-        };
-        let checksum_overhead = if self.config.use_checksums { 4 } else { 0 };
-        let static_overhead = 1 /* command count */ + compression_overhead + checksum_overhead;
+        // On a trusted path only the command count remains
+        let static_overhead = CommandEncoder::packet_overhead_for_config(&self.config);
+
+        let retransmission_queued = std::mem::take(&mut self.retransmission_queued);
+        let sendable = self.sendable_commands(retransmission_queued);
 
         // Select as many commands as will fit within max_size when encoded
         let mut selected_count = 0usize;
+        let mut selected = Vec::with_capacity(self.command_queue.len());
         let mut aggregated_len = 0; // track only command bytes (static_overhead already includes command count)
 
         // Pre-encode commands individually to know precise sizes
         let mut per_command_sizes: Vec<usize> = Vec::new();
-        for cmd in self.command_queue.iter() {
+        // Compression override of the data selected so far; data with another stays queued
+        let mut datagram_compression = None;
+        for (index, (cmd, compression)) in self.command_queue.iter_with_compression().enumerate() {
+            if !sendable[index] {
+                selected.push(false);
+                continue;
+            }
+            if cmd.is_data() {
+                if datagram_compression.is_some_and(|selected| selected != compression) {
+                    break;
+                }
+                datagram_compression = Some(compression);
+            }
             let encoded = bitfold_protocol::command_codec::CommandEncoder::encode_command(cmd)?;
             let cmd_total = 2 /* length prefix */ + encoded.len();
 
@@ -99,13 +246,16 @@ impl Peer {
 
             aggregated_len += cmd_total;
             per_command_sizes.push(cmd_total);
+            selected.push(true);
             selected_count += 1;
         }
 
         if selected_count == 0 {
             // First command is too large to fit within max_size
             // This can happen with very large data payloads or very small MTU
-            if let Some(first_cmd) = self.command_queue.iter().next() {
+            let first_sendable =
+                self.command_queue.iter().enumerate().find(|&(index, _)| sendable[index]);
+            if let Some((_, first_cmd)) = first_sendable {
                 // Don't warn for PMTU probes - they're expected to exceed normal MTU
                 if !first_is_pmtu_probe {
                     let encoded =
@@ -126,14 +276,25 @@ impl Peer {
             return Ok(None);
         }
 
-        // Drain exactly selected_count commands, requeue the rest to preserve order
-        let drained: Vec<_> = self.drain_commands().collect();
+        // Drain the selected commands, requeue the rest to preserve order
+        let drained: Vec<_> = self.drain_commands_with_compression().collect();
         let mut packet = CommandPacket::new();
-        for cmd in drained.iter().take(selected_count) {
-            packet.add_command(cmd.clone());
+        let mut reliable_sent = 0;
+        for (index, (cmd, compression)) in drained.into_iter().enumerate() {
+            if selected.get(index).copied().unwrap_or(false) {
+                if Self::is_new_reliable_data(index, &cmd, retransmission_queued) {
+                    reliable_sent += Self::command_data_size(&cmd) as u32;
+                }
+                packet.add_command(cmd);
+            } else {
+                self.enqueue_command_with_compression(cmd, compression);
+            }
         }
-        for cmd in drained.into_iter().skip(selected_count) {
-            self.enqueue_command(cmd);
+        if !self.has_queued_commands() {
+            self.flush_requested = false;
+        }
+        if self.config.use_window_flow_control && reliable_sent > 0 {
+            self.flow_control.record_reliable_data_sent(reliable_sent);
         }
 
         // Encode into pooled scratch buffer
@@ -142,21 +303,14 @@ impl Peer {
         bitfold_protocol::command_codec::CommandEncoder::encode_packet_into(&mut scratch, &packet)?;
 
         // Apply compression using pooled buffer
-        let compression_buffer = self.compression_pool.acquire();
-        let mut final_data = command_codec::compress_with_buffer(
-            &scratch,
-            self.config.compression,
-            self.config.compression_threshold,
-            compression_buffer,
-        )?;
+        let mut final_data = self.compress_datagram(&scratch, datagram_compression.flatten())?;
+        self.apply_immediate_ack(&packet, &mut final_data);
         self.tx_pool.deallocate(scratch);
 
         // Record packet and bytes sent
         self.record_packet_sent();
 
-        if self.config.use_checksums {
-            command_codec::append_checksum_in_place(&mut final_data);
-        }
+        self.apply_checksum(&packet, &mut final_data);
 
         // Track bytes sent (full encoded size after compression/checksum)
         self.record_data_sent(final_data.len());
@@ -177,16 +331,78 @@ impl Peer {
 
         Ok(Some(final_data))
     }
+
+    /// Returns whether the queued command at `index` carries reliable data not sent before.
+    fn is_new_reliable_data(
+        index: usize,
+        cmd: &ProtocolCommand,
+        retransmission_queued: bool,
+    ) -> bool {
+        cmd.is_reliable() && cmd.is_data() && !(index == 0 && retransmission_queued)
+    }
+
+    /// Flags, by queue position, the commands that may go out now. While sending is paused,
+    /// new data stays queued; control commands and a queued retransmission still go out.
+    /// New reliable data blocked by a window (see [`Peer::window_blocked_commands`]) stays
+    /// queued as well.
+    fn sendable_commands(&self, retransmission_queued: bool) -> Vec<bool> {
+        let window_blocked = self.window_blocked_commands(retransmission_queued);
+        self.command_queue
+            .iter()
+            .enumerate()
+            .map(|(index, cmd)| {
+                (!self.sending_paused || !cmd.is_data() || (index == 0 && retransmission_queued))
+                    && !window_blocked.get(index).copied().unwrap_or(false)
+            })
+            .collect()
+    }
+
+    /// Flags, by queue position, the new reliable data that does not fit in the flow-control
+    /// window, the receiver's advertised window or the congestion window. Empty unless
+    /// `use_window_flow_control` is enabled or a congestion controller is installed.
+    fn window_blocked_commands(&self, retransmission_queued: bool) -> Vec<bool> {
+        let flow_control = self.config.use_window_flow_control;
+        if !flow_control && self.congestion_controller.is_none() {
+            return Vec::new();
+        }
+        let window_bytes = self.flow_control.window_bytes(&self.config);
+        let mut in_transit = self.flow_control.reliable_data_in_transit();
+        let mut remote_remaining = self.flow_control.remote_window_remaining();
+        let mut bytes_in_flight = self.bytes_in_flight();
+        self.command_queue
+            .iter()
+            .enumerate()
+            .map(|(index, cmd)| {
+                if !Self::is_new_reliable_data(index, cmd, retransmission_queued) {
+                    return false;
+                }
+                if flow_control && (in_transit >= window_bytes || remote_remaining == 0) {
+                    return true;
+                }
+                if let Some(controller) = &self.congestion_controller {
+                    if !controller.can_send(bytes_in_flight) {
+                        return true;
+                    }
+                }
+                let size = Self::command_data_size(cmd);
+                in_transit = in_transit.saturating_add(size as u32);
+                remote_remaining = remote_remaining.saturating_sub(size as u32);
+                bytes_in_flight = bytes_in_flight.saturating_add(size);
+                false
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::time::Instant;
 
-    use bitfold_core::config::{CompressionAlgorithm, Config};
-    use bitfold_protocol::command::ProtocolCommand;
+    use bitfold_core::config::{ChecksumAlgorithm, CompressionAlgorithm, Config};
+    use bitfold_protocol::command_codec::CommandDecoder;
 
     use super::*;
+    use crate::{SendOptions, StreamMode};
 
     fn create_virtual_connection() -> Peer {
         Peer::new(get_fake_addr(), &Config::default(), Instant::now())
@@ -249,6 +465,158 @@ mod tests {
         assert!(result.is_ok()); // Should succeed with valid checksum
     }
 
+    #[test]
+    fn test_checksum_algorithms_end_to_end() {
+        for checksum in [
+            ChecksumAlgorithm::None,
+            ChecksumAlgorithm::Crc32,
+            ChecksumAlgorithm::XxHash32,
+            ChecksumAlgorithm::XxHash64,
+        ] {
+            let config = Config { checksum, ..Config::default() };
+            let mut peer1 = Peer::new(get_fake_addr(), &config, Instant::now());
+            let mut peer2 = Peer::new(get_fake_addr(), &config, Instant::now());
+            let data = vec![1, 2, 3, 4, 5];
+            peer1.enqueue_command(ProtocolCommand::SendUnreliable {
+                channel_id: 0,
+                data: data.clone().into(),
+            });
+
+            let mut encoded = peer1.encode_queued_commands().unwrap();
+            let overhead = CommandEncoder::packet_overhead(config.compression, checksum);
+            assert_eq!(encoded.len(), overhead + 2 + 4 + data.len());
+
+            let packets = peer2.process_command_packet(&encoded, Instant::now()).unwrap();
+            let payloads: Vec<_> =
+                packets.into_iter().map(|(packet, _)| packet.payload().to_vec()).collect();
+            assert_eq!(payloads, vec![data]);
+
+            // A corrupted payload byte is caught by every checksum
+            if checksum != ChecksumAlgorithm::None {
+                encoded[6] ^= 0x01;
+                assert!(peer2.process_command_packet(&encoded, Instant::now()).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_disabled_checksums_ignore_algorithm() {
+        let config = Config {
+            use_checksums: false,
+            checksum: ChecksumAlgorithm::XxHash64,
+            ..Config::default()
+        };
+        assert_eq!(config.checksum_algorithm(), ChecksumAlgorithm::None);
+
+        let mut peer = Peer::new(get_fake_addr(), &config, Instant::now());
+        peer.enqueue_command(ProtocolCommand::Ping { timestamp: 1234 });
+        let encoded = peer.encode_queued_commands().unwrap();
+        let none = Config { checksum: ChecksumAlgorithm::None, ..config };
+        let mut expected = Peer::new(get_fake_addr(), &none, Instant::now());
+        expected.enqueue_command(ProtocolCommand::Ping { timestamp: 1234 });
+        assert_eq!(encoded, expected.encode_queued_commands().unwrap());
+    }
+
+    #[test]
+    fn test_trusted_path_sends_bare_command_packets() {
+        let config = Config {
+            trusted_path: true,
+            compression: CompressionAlgorithm::Lz4,
+            compression_threshold: 0,
+            checksum: ChecksumAlgorithm::XxHash64,
+            ..Config::default()
+        };
+        assert_eq!(CommandEncoder::packet_overhead_for_config(&config), 1);
+        let time = Instant::now();
+        let mut sender = Peer::new(get_fake_addr(), &config, time);
+        let mut receiver = Peer::new(get_fake_addr(), &config, time);
+        let untrusted = Peer::new(get_fake_addr(), &Config::default(), time);
+        assert_eq!(
+            sender.usable_payload_for_mtu(1200),
+            untrusted.usable_payload_for_mtu(1200)
+                + CommandEncoder::packet_overhead_for_config(untrusted.config()) as u16
+                - 1
+        );
+
+        let data = vec![7u8; 300];
+        sender.enqueue_command(ProtocolCommand::SendUnreliable {
+            channel_id: 0,
+            data: data.clone().into(),
+        });
+        sender.request_immediate_ack();
+        let encoded = sender.encode_queued_commands().unwrap();
+
+        // Neither a compression marker nor a checksum: the datagram is the command packet
+        let mut packet = CommandPacket::new();
+        packet.add_command(ProtocolCommand::SendUnreliable {
+            channel_id: 0,
+            data: data.clone().into(),
+        });
+        assert_eq!(encoded, CommandEncoder::encode_packet(&packet).unwrap());
+
+        let packets = receiver.process_command_packet(&encoded, time).unwrap();
+        let payloads: Vec<_> =
+            packets.into_iter().map(|(packet, _)| packet.payload().to_vec()).collect();
+        assert_eq!(payloads, vec![data]);
+    }
+
+    #[test]
+    fn test_send_with_options_overrides_compression() {
+        let time = Instant::now();
+        let text: std::sync::Arc<[u8]> = b"compressible ".repeat(40).into();
+        let zlib = SendOptions { compression: Some(CompressionAlgorithm::Zlib) };
+        let uncompressed = SendOptions { compression: Some(CompressionAlgorithm::None) };
+        let marker = |datagram: &[u8]| datagram[0] & 0x3F;
+
+        for default in [CompressionAlgorithm::None, CompressionAlgorithm::Lz4] {
+            let config = Config { compression: default, ..Config::default() };
+            let mut sender = Peer::new(get_fake_addr(), &config, time);
+            // The receiver's own default plays no part in decoding
+            let mut receiver = Peer::new(get_fake_addr(), &Config::default(), time);
+            sender.open_stream(0, StreamMode::Unreliable);
+
+            // Each override gets a datagram of its own, marked with the algorithm used
+            sender.send_with_options(0, text.clone(), zlib);
+            sender.send_with_options(0, text.clone(), uncompressed);
+            sender.send_with_options(0, text.clone(), SendOptions::default());
+            let mut markers = Vec::new();
+            let mut payloads = Vec::new();
+            while let Some(datagram) = sender.encode_queued_commands_bounded(1200).unwrap() {
+                markers.push(marker(&datagram));
+                let packets = receiver.process_command_packet(&datagram, time).unwrap();
+                payloads.extend(packets.into_iter().map(|(packet, _)| packet.payload().to_vec()));
+            }
+            let default_marker = if default == CompressionAlgorithm::Lz4 { 2 } else { 0 };
+            assert_eq!(markers, [1, 0, default_marker], "default {default:?}");
+            assert_eq!(payloads, vec![text.to_vec(); 3]);
+        }
+    }
+
+    #[test]
+    fn test_unbounded_encoder_splits_on_compression_override() {
+        let time = Instant::now();
+        let text: std::sync::Arc<[u8]> = b"compressible ".repeat(40).into();
+        let zlib = SendOptions { compression: Some(CompressionAlgorithm::Zlib) };
+        let marker = |datagram: &[u8]| datagram[0] & 0x3F;
+        let mut sender = Peer::new(get_fake_addr(), &Config::default(), time);
+        let mut receiver = Peer::new(get_fake_addr(), &Config::default(), time);
+        sender.open_stream(0, StreamMode::Unreliable);
+
+        sender.send_with_options(0, text.clone(), SendOptions::default());
+        sender.send_with_options(0, text.clone(), zlib);
+        sender.send_with_options(0, text.clone(), zlib);
+        let mut markers = Vec::new();
+        let mut payloads = Vec::new();
+        while sender.has_queued_commands() {
+            let datagram = sender.encode_queued_commands().unwrap();
+            markers.push(marker(&datagram));
+            let packets = receiver.process_command_packet(&datagram, time).unwrap();
+            payloads.extend(packets.into_iter().map(|(packet, _)| packet.payload().to_vec()));
+        }
+        assert_eq!(markers, [0, 1]);
+        assert_eq!(payloads, vec![text.to_vec(); 3]);
+    }
+
     #[test]
     fn test_checksum_detects_corruption_in_peer() {
         let mut config = Config::default();
@@ -276,6 +644,59 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_checksum_data_only_skips_control_datagrams() {
+        let mut config = Config::default();
+        config.use_checksums = true;
+        config.checksum_data_only = true;
+        let time = Instant::now();
+        let mut sender = Peer::new(get_fake_addr(), &config, time);
+        let mut receiver = Peer::new(get_fake_addr(), &config, time);
+
+        // Control-only datagram: flagged, no trailing checksum
+        sender.enqueue_command(ProtocolCommand::Ping { timestamp: 1234 });
+        let control = sender.encode_queued_commands().unwrap();
+        let mut full_checksum_peer = Peer::new(get_fake_addr(), &Config::default(), time);
+        full_checksum_peer.enqueue_command(ProtocolCommand::Ping { timestamp: 1234 });
+        let checksummed = full_checksum_peer.encode_queued_commands().unwrap();
+        assert!(command_codec::is_checksum_omitted(&control));
+        assert_eq!(control.len() + 4, checksummed.len());
+        receiver.process_command_packet(&control, time).unwrap();
+
+        // Data datagram: checksummed as usual
+        sender.enqueue_command(ProtocolCommand::Ping { timestamp: 1234 });
+        sender.enqueue_command(ProtocolCommand::SendUnreliable {
+            channel_id: 0,
+            data: vec![1, 2, 3].into(),
+        });
+        let data = sender.encode_queued_commands().unwrap();
+        assert!(!command_codec::is_checksum_omitted(&data));
+        assert!(CommandDecoder::validate_and_strip_checksum(&data).is_ok());
+        let packets = receiver.process_command_packet(&data, time).unwrap();
+        assert_eq!(packets.into_iter().count(), 1);
+    }
+
+    #[test]
+    fn test_checksum_data_only_rejects_unchecksummed_data() {
+        let mut config = Config::default();
+        config.use_checksums = true;
+        config.checksum_data_only = true;
+        let time = Instant::now();
+        let mut receiver = Peer::new(get_fake_addr(), &config, time);
+
+        // A data datagram claiming to carry no checksum is not trusted
+        let mut unchecked =
+            Peer::new(get_fake_addr(), &Config { use_checksums: false, ..config }, time);
+        unchecked.enqueue_command(ProtocolCommand::SendUnreliable {
+            channel_id: 0,
+            data: vec![1, 2, 3].into(),
+        });
+        let mut forged = unchecked.encode_queued_commands().unwrap();
+        command_codec::mark_checksum_omitted(&mut forged);
+
+        assert!(receiver.process_command_packet(&forged, time).is_err());
+    }
+
     #[test]
     fn test_checksum_disabled_backward_compatibility() {
         let mut config = Config::default();
@@ -351,6 +772,100 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_payload_below_threshold_stored_uncompressed_with_lz4() {
+        let mut config = Config::default();
+        config.compression = CompressionAlgorithm::Lz4;
+
+        let mut peer1 = Peer::new(get_fake_addr(), &config, Instant::now());
+        let mut peer2 = Peer::new(get_fake_addr(), &config, Instant::now());
+        let payload = vec![7; 10];
+        peer1.enqueue_command(ProtocolCommand::SendUnreliable {
+            channel_id: 0,
+            data: payload.clone().into(),
+        });
+
+        // Uncompressed marker, no LZ4 size field
+        let encoded = peer1.encode_queued_commands().unwrap();
+        assert_eq!(encoded[0], 0);
+        let overhead = CommandEncoder::packet_overhead_for_size(
+            config.compression,
+            config.compression_threshold,
+            config.checksum_algorithm(),
+            encoded.len(),
+        );
+        assert_eq!(
+            overhead,
+            CommandEncoder::packet_overhead(CompressionAlgorithm::None, config.checksum)
+        );
+        assert_eq!(encoded.len(), overhead + 2 + 4 + payload.len());
+
+        let packets = peer2.process_command_packet(&encoded, Instant::now()).unwrap();
+        let payloads: Vec<_> =
+            packets.into_iter().map(|(packet, _)| packet.payload().to_vec()).collect();
+        assert_eq!(payloads, vec![payload]);
+    }
+
+    #[test]
+    fn test_adaptive_compression_suspends_on_random_payloads() {
+        use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+        let mut config = Config::default();
+        config.compression = CompressionAlgorithm::Lz4;
+        config.adaptive_compression_window = 4;
+        let mut peer1 = Peer::new(get_fake_addr(), &config, Instant::now());
+        let mut peer2 = Peer::new(get_fake_addr(), &config, Instant::now());
+        let mut rng = StdRng::seed_from_u64(3);
+
+        let mut send = |peer1: &mut Peer| {
+            let mut data = vec![0; 400];
+            rng.fill_bytes(&mut data);
+            peer1.enqueue_command(ProtocolCommand::SendUnreliable {
+                channel_id: 0,
+                data: data.clone().into(),
+            });
+            let encoded = peer1.encode_queued_commands().unwrap();
+            let packets = peer2.process_command_packet(&encoded, Instant::now()).unwrap();
+            let payloads: Vec<_> =
+                packets.into_iter().map(|(packet, _)| packet.payload().to_vec()).collect();
+            assert_eq!(payloads, vec![data]);
+        };
+        for _ in 0..4 {
+            assert!(!peer1.is_compression_suspended());
+            send(&mut peer1);
+        }
+        assert!(peer1.is_compression_suspended());
+        send(&mut peer1);
+        assert!(peer1.is_compression_suspended());
+    }
+
+    #[test]
+    fn test_compression_stats_accumulate_totals() {
+        let mut config = Config::default();
+        config.compression = CompressionAlgorithm::Zlib;
+        config.compression_threshold = 10;
+        // The checksum is appended after compression and is not counted
+        config.use_checksums = false;
+        let mut peer = Peer::new(get_fake_addr(), &config, Instant::now());
+
+        let (mut original, mut compressed) = (0u64, 0u64);
+        for data in [vec![7u8; 400], vec![1, 2, 3], (0..200u32).map(|i| (i * 31) as u8).collect()] {
+            let command = ProtocolCommand::SendUnreliable { channel_id: 0, data: data.into() };
+            let mut packet = CommandPacket::new();
+            packet.add_command(command.clone());
+            original += CommandEncoder::encode_packet(&packet).unwrap().len() as u64;
+
+            peer.enqueue_command(command);
+            compressed += peer.encode_queued_commands().unwrap().len() as u64;
+        }
+
+        let stats = peer.compression_stats();
+        assert_eq!(stats.original, original);
+        assert_eq!(stats.compressed, compressed);
+        assert!((stats.ratio() - compressed as f64 / original as f64).abs() < 1e-9);
+        assert!(stats.ratio() < 1.0);
+    }
+
     #[test]
     fn test_compression_disabled_by_default() {
         let config = Config::default();
@@ -479,4 +994,195 @@ mod tests {
         // Command should remain queued
         assert!(peer.has_queued_commands());
     }
+
+    fn decode(peer: &Peer, bytes: &[u8]) -> Vec<ProtocolCommand> {
+        let payload = if peer.config.use_checksums {
+            CommandDecoder::validate_and_strip_checksum(bytes).unwrap()
+        } else {
+            bytes
+        };
+        let decompressed = CommandDecoder::decompress(payload).unwrap();
+        CommandDecoder::decode_packet(&decompressed).unwrap().commands
+    }
+
+    #[test]
+    fn test_retransmission_coalesced_with_queued_data() {
+        let mut peer = create_virtual_connection();
+        let mtu = peer.current_fragment_size() as usize;
+
+        peer.enqueue_ack_command(None);
+        peer.enqueue_command(ProtocolCommand::SendReliable {
+            channel_id: 0,
+            sequence: 8,
+            ordered: true,
+            data: vec![2u8; 100].into(),
+        });
+
+        let retransmit = ProtocolCommand::SendReliable {
+            channel_id: 0,
+            sequence: 3,
+            ordered: true,
+            data: vec![1u8; 200].into(),
+        };
+        let bytes = peer.encode_retransmission(retransmit.clone(), mtu).unwrap().unwrap();
+        assert!(bytes.len() <= mtu);
+
+        // One datagram carries the retransmission first, then the ACK and new data
+        let commands = decode(&peer, &bytes);
+        assert_eq!(commands.len(), 3);
+        assert_eq!(commands[0], retransmit);
+        assert!(matches!(commands[1], ProtocolCommand::Acknowledge { .. }));
+        assert!(matches!(commands[2], ProtocolCommand::SendReliable { sequence: 8, .. }));
+        assert!(!peer.has_queued_commands());
+        assert_eq!(peer.buffered_bytes(), 0);
+    }
+
+    #[test]
+    fn test_retransmission_leaves_overflow_queued() {
+        let mut peer = create_virtual_connection();
+        let mtu = peer.current_fragment_size() as usize;
+        let payload = peer.usable_payload_for_mtu(mtu as u16) as usize;
+
+        let fresh = ProtocolCommand::SendUnreliable { channel_id: 0, data: vec![2u8; 64].into() };
+        peer.enqueue_command(fresh.clone());
+
+        // A full-size retransmission leaves no room for the new data
+        let retransmit = ProtocolCommand::SendReliable {
+            channel_id: 0,
+            sequence: 3,
+            ordered: true,
+            data: vec![1u8; payload].into(),
+        };
+        let bytes = peer.encode_retransmission(retransmit.clone(), mtu).unwrap().unwrap();
+        assert_eq!(decode(&peer, &bytes), vec![retransmit]);
+        assert_eq!(peer.drain_commands().collect::<Vec<_>>(), vec![fresh]);
+    }
+
+    #[test]
+    fn test_retransmission_sent_while_paused() {
+        let mut peer = create_virtual_connection();
+        peer.pause_sending();
+        peer.enqueue_command(ProtocolCommand::SendUnreliable {
+            channel_id: 0,
+            data: vec![1; 8].into(),
+        });
+        let retransmit = ProtocolCommand::SendReliable {
+            channel_id: 0,
+            sequence: 4,
+            ordered: true,
+            data: vec![2; 8].into(),
+        };
+
+        let mtu = peer.current_fragment_size() as usize;
+        let bytes = peer.encode_retransmission(retransmit.clone(), mtu).unwrap().unwrap();
+        assert_eq!(decode(&peer, &bytes), vec![retransmit]);
+        assert_eq!(peer.queued_commands_count(), 1);
+        assert_eq!(peer.encode_queued_commands_bounded(mtu).unwrap(), None);
+    }
+
+    #[test]
+    fn test_unbounded_encoding_holds_data_while_paused() {
+        let mut peer = create_virtual_connection();
+        peer.pause_sending();
+        let data = ProtocolCommand::SendUnreliable { channel_id: 0, data: vec![1; 8].into() };
+        peer.enqueue_command(data.clone());
+        peer.enqueue_command(ProtocolCommand::Ping { timestamp: 7 });
+
+        let bytes = peer.encode_queued_commands().unwrap();
+        assert_eq!(decode(&peer, &bytes), vec![ProtocolCommand::Ping { timestamp: 7 }]);
+        assert_eq!(peer.queued_commands_count(), 1);
+
+        peer.resume_sending();
+        let bytes = peer.encode_queued_commands().unwrap();
+        assert_eq!(decode(&peer, &bytes), vec![data]);
+        assert!(!peer.has_queued_commands());
+    }
+
+    #[test]
+    fn test_unbounded_encoding_respects_window() {
+        let mut config = Config::default();
+        config.use_window_flow_control = true;
+        let mut peer = Peer::new(get_fake_addr(), &config, Instant::now());
+        peer.set_flow_window(1000);
+        for sequence in 0..3 {
+            peer.enqueue_command(ProtocolCommand::SendReliable {
+                channel_id: 0,
+                sequence,
+                ordered: true,
+                data: vec![0; 600].into(),
+            });
+        }
+
+        // The window admits data while it has room, so the second command still goes out
+        let bytes = peer.encode_queued_commands().unwrap();
+        assert_eq!(decode(&peer, &bytes).len(), 2);
+        assert_eq!(peer.queued_commands_count(), 1);
+        let bytes = peer.encode_queued_commands().unwrap();
+        assert!(decode(&peer, &bytes).is_empty());
+        assert_eq!(peer.queued_commands_count(), 1);
+    }
+
+    #[test]
+    fn test_small_flow_window_limits_reliable_datagrams() {
+        let mut config = Config::default();
+        config.use_window_flow_control = true;
+        config.fragment_size = 1000;
+        let mut peer = Peer::new(get_fake_addr(), &config, Instant::now());
+        peer.set_flow_window(3000);
+        assert_eq!(peer.flow_window(), 3000);
+
+        for sequence in 0..5 {
+            peer.enqueue_command(ProtocolCommand::SendReliable {
+                channel_id: 0,
+                sequence,
+                ordered: true,
+                data: vec![0u8; 1000].into(),
+            });
+        }
+        peer.enqueue_command(ProtocolCommand::Ping { timestamp: 1 });
+
+        // Three datagrams of data fill the window; the ping is not held back with the rest
+        let mut datagrams = 0;
+        while peer.encode_queued_commands_bounded(1400).unwrap().is_some() {
+            datagrams += 1;
+        }
+        assert_eq!(datagrams, 3);
+        assert_eq!(peer.reliable_data_in_transit(), 3000);
+        assert_eq!(peer.queued_commands_count(), 2);
+        assert!(!peer.can_send_reliable());
+
+        // The receiver reports the data arrived: the rest goes out
+        peer.process_command(
+            &ProtocolCommand::WindowUpdate { received: 3000, available: u32::MAX },
+            Instant::now(),
+        )
+        .unwrap();
+        let mut datagrams = 0;
+        while peer.encode_queued_commands_bounded(1400).unwrap().is_some() {
+            datagrams += 1;
+        }
+        assert_eq!(datagrams, 2);
+    }
+
+    #[test]
+    fn test_flow_window_ignored_without_window_flow_control() {
+        let mut config = Config::default();
+        config.fragment_size = 1000;
+        let mut peer = Peer::new(get_fake_addr(), &config, Instant::now());
+        peer.set_flow_window(1000);
+
+        for sequence in 0..3 {
+            peer.enqueue_command(ProtocolCommand::SendReliable {
+                channel_id: 0,
+                sequence,
+                ordered: true,
+                data: vec![0u8; 1000].into(),
+            });
+        }
+        let mut datagrams = 0;
+        while peer.encode_queued_commands_bounded(1400).unwrap().is_some() {
+            datagrams += 1;
+        }
+        assert_eq!(datagrams, 3);
+    }
 }
@@ -1,29 +1,23 @@
 use std::sync::Arc;
 
 use bitfold_core::shared::SharedBytes;
-use bitfold_protocol::command::ProtocolCommand;
+use bitfold_protocol::{command::ProtocolCommand, command_codec::CommandEncoder};
 
 use super::Peer;
+use crate::{send_options::SendOptions, stream_mode::StreamMode};
 
 impl Peer {
     /// Enqueues reliable data, automatically fragmenting if necessary.
     /// Returns the sequence number used for the packet(s).
     pub fn enqueue_reliable_data(&mut self, channel_id: u8, data: Arc<[u8]>, ordered: bool) -> u16 {
-        let sequence = self.acknowledge_handler.local_sequence_num();
+        let sequence = self.outgoing_reliable_sequence;
+        self.outgoing_reliable_sequence = self.outgoing_reliable_sequence.wrapping_add(1);
 
         // Compute datagram cap and per-command payload budget so a single
         // SendReliable or SendFragment fits within one UDP datagram when encoded.
-        let datagram_cap = std::cmp::min(
-            self.current_fragment_size() as usize,
-            self.config.receive_buffer_max_size,
-        );
+        let datagram_cap = self.datagram_cap();
         // Overheads common to any datagram containing exactly one command
-        let compression_overhead = match self.config.compression {
-            bitfold_core::config::CompressionAlgorithm::Lz4 => 5, // marker + original size
-            _ => 1,                                               // marker only
-        };
-        let checksum_overhead = if self.config.use_checksums { 4 } else { 0 };
-        let per_packet_overhead = 1 /* command count */ + compression_overhead + checksum_overhead;
+        let per_packet_overhead = CommandEncoder::packet_overhead_for_config(&self.config);
 
         // Header sizes for commands (not including the 2-byte length prefix)
         let send_reliable_header = 1 /* type */ + 1 /* channel */ + 2 /* sequence */
@@ -40,6 +34,7 @@ impl Peer {
         // Validate MTU is large enough for minimum payload
         if max_payload_reliable < 1 {
             tracing::error!(
+                labels = ?self.labels,
                 "MTU too small: datagram_cap={}, overhead={}, can't fit minimum payload",
                 datagram_cap,
                 per_packet_overhead + 2 + send_reliable_header
@@ -65,6 +60,7 @@ impl Peer {
 
             if fragment_payload < 1 {
                 tracing::error!(
+                    labels = ?self.labels,
                     "MTU too small for fragmentation: datagram_cap={}, overhead={}, can't fit minimum fragment payload",
                     datagram_cap,
                     per_packet_overhead + 2 + send_fragment_header
@@ -76,6 +72,7 @@ impl Peer {
             let total_fragments_usize = data.len().div_ceil(fragment_payload);
             if total_fragments_usize > u8::MAX as usize {
                 tracing::warn!(
+                    labels = ?self.labels,
                     "Payload {} bytes too large to fragment: would require {} fragments (max {}), dropping packet",
                     data.len(),
                     total_fragments_usize,
@@ -87,13 +84,14 @@ impl Peer {
 
             if total_fragments > self.config.max_fragments {
                 tracing::warn!(
+                    labels = ?self.labels,
                     "Payload requires {} fragments but max allowed is {}, sending first fragment only",
                     total_fragments,
                     self.config.max_fragments
                 );
                 // Too many fragments - send the first fragment only (best-effort)
                 let base = SharedBytes::from_arc(data);
-                let fragment_data = base.slice(0, fragment_payload.min(base.len()));
+                let fragment_data = base.slice(..fragment_payload.min(base.len()));
                 self.enqueue_command(ProtocolCommand::SendFragment {
                     channel_id,
                     sequence,
@@ -116,7 +114,7 @@ impl Peer {
                 let start = (fragment_id as usize) * fragment_payload;
                 let end = ((fragment_id as usize + 1) * fragment_payload).min(data.len());
                 let base = SharedBytes::from_arc(data.clone());
-                let fragment_data = base.slice(start, end - start);
+                let fragment_data = base.slice(start..end);
                 self.enqueue_command(ProtocolCommand::SendFragment {
                     channel_id,
                     sequence,
@@ -131,6 +129,53 @@ impl Peer {
         sequence
     }
 
+    /// Opens a stream on `channel_id` whose data is sent according to `mode`, replacing any
+    /// mode set before.
+    pub fn open_stream(&mut self, channel_id: u8, mode: StreamMode) {
+        self.stream_modes.insert(channel_id, mode);
+    }
+
+    /// Returns the mode of the stream opened on `channel_id`, if any.
+    pub fn stream_mode(&self, channel_id: u8) -> Option<StreamMode> {
+        self.stream_modes.get(&channel_id).copied()
+    }
+
+    /// Enqueues data on the stream opened on `channel_id` according to its mode, fragmenting if
+    /// necessary. Channels without an opened stream are treated as `ReliableOrdered`.
+    /// Returns the sequence number used for the packet(s).
+    pub fn enqueue_stream_data(&mut self, channel_id: u8, data: Arc<[u8]>) -> u16 {
+        match self.stream_mode(channel_id).unwrap_or_default() {
+            StreamMode::ReliableOrdered => self.enqueue_reliable_data(channel_id, data, true),
+            StreamMode::ReliableUnordered => self.enqueue_reliable_data(channel_id, data, false),
+            StreamMode::Unreliable => self.enqueue_unreliable_data(channel_id, data),
+        }
+    }
+
+    /// Like [`Peer::enqueue_stream_data`], with per-message options. A compression override
+    /// applies to the datagrams first carrying the message, which carry no data without the
+    /// same override; retransmissions use the connection's compression.
+    pub fn send_with_options(
+        &mut self,
+        channel_id: u8,
+        data: Arc<[u8]>,
+        options: SendOptions,
+    ) -> u16 {
+        let previous = std::mem::replace(&mut self.send_compression, options.compression);
+        let sequence = self.enqueue_stream_data(channel_id, data);
+        self.send_compression = previous;
+        sequence
+    }
+
+    /// Returns whether `command` should be retransmitted when lost: reliable commands are,
+    /// unless they were sent on a stream opened as `Unreliable`.
+    pub fn should_retransmit(&self, command: &ProtocolCommand) -> bool {
+        command.is_reliable()
+            && command
+                .channel_id()
+                .and_then(|channel_id| self.stream_mode(channel_id))
+                .is_none_or(|mode| mode.is_reliable())
+    }
+
     /// Enqueues unreliable data, automatically fragmenting if necessary.
     /// Returns the sequence number used for reassembly.
     pub fn enqueue_unreliable_data(&mut self, channel_id: u8, data: Arc<[u8]>) -> u16 {
@@ -140,16 +185,8 @@ impl Peer {
 
         // Compute datagram cap and per-command payload budget so a single
         // SendUnreliable or SendUnreliableFragment fits within one UDP datagram when encoded.
-        let datagram_cap = std::cmp::min(
-            self.current_fragment_size() as usize,
-            self.config.receive_buffer_max_size,
-        );
-        let compression_overhead = match self.config.compression {
-            bitfold_core::config::CompressionAlgorithm::Lz4 => 5,
-            _ => 1,
-        };
-        let checksum_overhead = if self.config.use_checksums { 4 } else { 0 };
-        let per_packet_overhead = 1 /* command count */ + compression_overhead + checksum_overhead;
+        let datagram_cap = self.datagram_cap();
+        let per_packet_overhead = CommandEncoder::packet_overhead_for_config(&self.config);
 
         // Header sizes (without the 2-byte length prefix)
         let send_unrel_header = 1 /* type */ + 1 /* channel */ + 2 /* payload len */; // = 4
@@ -164,6 +201,7 @@ impl Peer {
         // Validate MTU is large enough for minimum payload
         if max_payload_unreliable < 1 {
             tracing::error!(
+                labels = ?self.labels,
                 "MTU too small: datagram_cap={}, overhead={}, can't fit minimum unreliable payload",
                 datagram_cap,
                 per_packet_overhead + 2 + send_unrel_header
@@ -186,6 +224,7 @@ impl Peer {
 
             if fragment_payload < 1 {
                 tracing::error!(
+                    labels = ?self.labels,
                     "MTU too small for unreliable fragmentation: datagram_cap={}, overhead={}, can't fit minimum fragment payload",
                     datagram_cap,
                     per_packet_overhead + 2 + send_unrel_frag_header
@@ -197,6 +236,7 @@ impl Peer {
             let total_fragments_usize = data.len().div_ceil(fragment_payload);
             if total_fragments_usize > u8::MAX as usize {
                 tracing::warn!(
+                    labels = ?self.labels,
                     "Unreliable payload {} bytes too large to fragment: would require {} fragments (max {}), dropping packet",
                     data.len(),
                     total_fragments_usize,
@@ -208,13 +248,14 @@ impl Peer {
 
             if total_fragments > self.config.max_fragments {
                 tracing::warn!(
+                    labels = ?self.labels,
                     "Unreliable payload requires {} fragments but max allowed is {}, sending first fragment only",
                     total_fragments,
                     self.config.max_fragments
                 );
                 // Too many fragments - send first fragment only (best-effort)
                 let base = SharedBytes::from_arc(data);
-                let fragment_data = base.slice(0, fragment_payload.min(base.len()));
+                let fragment_data = base.slice(..fragment_payload.min(base.len()));
                 self.enqueue_command(ProtocolCommand::SendUnreliableFragment {
                     channel_id,
                     sequence,
@@ -236,7 +277,7 @@ impl Peer {
                 let start = (fragment_id as usize) * fragment_payload;
                 let end = ((fragment_id as usize + 1) * fragment_payload).min(data.len());
                 let base = SharedBytes::from_arc(data.clone());
-                let fragment_data = base.slice(start, end - start);
+                let fragment_data = base.slice(start..end);
 
                 self.enqueue_command(ProtocolCommand::SendUnreliableFragment {
                     channel_id,
@@ -635,6 +676,48 @@ mod tests {
         assert_eq!(peer.command_fragments.len(), 0, "Stale fragment buffer should be cleaned up");
     }
 
+    #[test]
+    fn test_reassembly_limit_evicts_least_recent_group() {
+        let mut config = Config::default();
+        config.max_reassembly_bytes = 250;
+        let start_time = Instant::now();
+        let mut peer = Peer::new(get_fake_addr(), &config, start_time);
+        let first_half = |sequence| ProtocolCommand::SendFragment {
+            channel_id: 0,
+            sequence,
+            ordered: false,
+            fragment_id: 0,
+            fragment_count: 2,
+            data: vec![sequence as u8; 100].into(),
+        };
+
+        // Five half-received 100-byte groups against room for two
+        for (i, sequence) in (100..105).enumerate() {
+            let time = start_time + std::time::Duration::from_millis(i as u64);
+            peer.process_command(&first_half(sequence), time).unwrap();
+        }
+        let mut buffered: Vec<_> = peer.command_fragments.keys().copied().collect();
+        buffered.sort_unstable();
+        assert_eq!(buffered, [103, 104], "Oldest groups should be evicted");
+        assert_eq!(peer.reassembly_bytes, 200);
+
+        // The surviving groups still complete
+        let time = start_time + std::time::Duration::from_millis(10);
+        let second_half = ProtocolCommand::SendFragment {
+            channel_id: 0,
+            sequence: 103,
+            ordered: false,
+            fragment_id: 1,
+            fragment_count: 2,
+            data: vec![0; 100].into(),
+        };
+        let packets: Vec<_> =
+            peer.process_command(&second_half, time).unwrap().into_iter().collect();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].0.payload().len(), 200);
+        assert_eq!(peer.reassembly_bytes, 100);
+    }
+
     #[test]
     fn test_complete_fragments_not_cleaned() {
         let config = Config::default();
@@ -697,4 +780,37 @@ mod tests {
             "All stale fragment buffers should be cleaned up"
         );
     }
+
+    #[test]
+    fn test_stream_modes_control_retransmission() {
+        let mut peer = create_virtual_connection();
+        peer.open_stream(1, StreamMode::ReliableOrdered);
+        peer.open_stream(2, StreamMode::Unreliable);
+        assert_eq!(peer.stream_mode(2), Some(StreamMode::Unreliable));
+        assert_eq!(peer.stream_mode(3), None);
+
+        peer.enqueue_stream_data(1, Arc::from(&b"state"[..]));
+        peer.enqueue_stream_data(2, Arc::from(&b"voice"[..]));
+        let mut sent: Vec<_> = peer.drain_commands().collect();
+        assert!(matches!(sent[0], ProtocolCommand::SendReliable {
+            channel_id: 1,
+            ordered: true,
+            ..
+        }));
+        assert!(matches!(sent[1], ProtocolCommand::SendUnreliable { channel_id: 2, .. }));
+
+        // Both were lost; only the reliable stream's data goes out again
+        let mtu = peer.datagram_cap();
+        let unreliable = sent.pop().unwrap();
+        let reliable = sent.pop().unwrap();
+        assert!(!peer.should_retransmit(&unreliable));
+        assert!(peer.encode_retransmission(unreliable, mtu).unwrap().is_none());
+
+        let bytes = peer.encode_retransmission(reliable, mtu).unwrap().unwrap();
+        let mut receiver = create_virtual_connection();
+        let packets = receiver.process_command_packet(&bytes, Instant::now()).unwrap();
+        let payloads: Vec<_> =
+            packets.into_iter().map(|(packet, _)| packet.payload().to_vec()).collect();
+        assert_eq!(payloads, vec![b"state".to_vec()]);
+    }
 }
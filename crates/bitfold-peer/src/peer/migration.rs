@@ -0,0 +1,371 @@
+use std::{net::SocketAddr, time::Instant};
+
+use bitfold_core::{config::Config, shared::SharedBytes};
+use bitfold_protocol::SentPacket;
+
+use super::Peer;
+use crate::{
+    channel_state::ChannelState,
+    fragment_buffer::CommandFragmentBuffer,
+    migration::{
+        ChannelSnapshot, InFlightPacket, LiveConnectionState, QueuedCommand, ReassemblySnapshot,
+    },
+    unsequenced::UnsequencedState,
+};
+
+impl Peer {
+    /// Captures the complete live state of this connection for migration to another process.
+    ///
+    /// Unlike [`Peer::connection_state`], the result lets the connection continue mid-flight
+    /// (see [`Peer::import_live_state`]). Early data, labels, statistics and the TTL are not
+    /// included.
+    pub fn export_live_state(&self, time: Instant) -> LiveConnectionState {
+        let mut in_flight: Vec<_> = self
+            .acknowledge_handler
+            .sent_packets()
+            .map(|(sequence, packet)| InFlightPacket {
+                sequence,
+                packet_type: packet.packet_type,
+                payload: packet.payload.to_vec(),
+                ordering: packet.ordering_guarantee,
+                item_identifier: packet.item_identifier,
+                age: time.saturating_duration_since(packet.sent_time),
+            })
+            .collect();
+        // Oldest first across wraparound: every in-flight sequence precedes the next one
+        let next_sequence = self.acknowledge_handler.local_sequence_num();
+        in_flight.sort_unstable_by_key(|packet| packet.sequence.wrapping_sub(next_sequence));
+
+        let mut channels: Vec<_> = self
+            .channel_states
+            .iter()
+            .map(|(&channel_id, channel)| {
+                let mut buffered: Vec<_> = channel
+                    .buffered_packets()
+                    .map(|(sequence, data)| (sequence, data.as_slice().to_vec()))
+                    .collect();
+                // Buffered packets all follow the next expected sequence
+                let expected = channel.expected_sequence();
+                buffered.sort_unstable_by_key(|&(sequence, _)| sequence.wrapping_sub(expected));
+                ChannelSnapshot {
+                    channel_id,
+                    expected_sequence: channel.expected_sequence(),
+                    latest_sequence: channel.latest_sequence(),
+                    buffered,
+                }
+            })
+            .collect();
+        channels.sort_unstable_by_key(|channel| channel.channel_id);
+
+        let mut reassembly: Vec<_> = self
+            .command_fragments
+            .iter()
+            .map(|(&sequence, buffer)| {
+                let mut fragments: Vec<_> = buffer
+                    .fragments()
+                    .map(|(fragment_id, data)| (fragment_id, data.to_vec()))
+                    .collect();
+                fragments.sort_unstable_by_key(|&(fragment_id, _)| fragment_id);
+                ReassemblySnapshot {
+                    sequence,
+                    channel_id: buffer.channel_id(),
+                    fragment_count: buffer.fragment_count(),
+                    ordered: buffer.is_ordered(),
+                    fragments,
+                    age: time.saturating_duration_since(buffer.created_at()),
+                }
+            })
+            .collect();
+        // Relative to the message that started reassembling first, across wraparound
+        if let Some(oldest) =
+            reassembly.iter().max_by_key(|message| message.age).map(|m| m.sequence)
+        {
+            reassembly.sort_unstable_by_key(|message| message.sequence.wrapping_sub(oldest));
+        }
+
+        LiveConnectionState {
+            connection: self.connection_state(),
+            state: self.state,
+            peer_id: self.peer_id,
+            incoming_session_id: self.incoming_session_id,
+            outgoing_session_id: self.outgoing_session_id,
+            connect_id: self.connect_id,
+            protocol_version: self.protocol_version,
+            negotiated_channels: self.negotiated_channels,
+            negotiated_mtu: self.negotiated_mtu,
+            negotiated_idle_timeout: self.negotiated_idle_timeout,
+            negotiated_params: self.negotiated_params,
+            remote_ack_sequence: self.acknowledge_handler.remote_ack_sequence_num(),
+            received_sequences: self.acknowledge_handler.received_sequences(),
+            in_flight,
+            next_reliable_sequence: self.outgoing_reliable_sequence,
+            next_unreliable_sequence: self.next_unreliable_sequence,
+            incoming_unsequenced_group: self.unsequenced_state.incoming_group(),
+            unsequenced_window: self.unsequenced_state.window(),
+            outgoing_unsequenced_group: self.unsequenced_state.outgoing_group(),
+            incoming_reliable_sequence: self.reliable_received.incoming_group(),
+            reliable_window: self.reliable_received.window(),
+            window_size: self.flow_control.window_size(),
+            reliable_data_in_transit: self.flow_control.reliable_data_in_transit(),
+            total_reliable_sent: self.flow_control.total_sent(),
+            reliable_bytes_received: self.reliable_bytes_received,
+            channels,
+            reassembly,
+            queued_commands: self
+                .command_queue
+                .iter_with_compression()
+                .map(|(command, compression)| QueuedCommand {
+                    command: command.clone(),
+                    compression,
+                })
+                .collect(),
+        }
+    }
+
+    /// Recreates a connection from state captured by [`Peer::export_live_state`], possibly in
+    /// another process, so that it continues where the exported peer left off.
+    ///
+    /// Queued commands and reassembly buffers are admitted regardless of memory limits, as
+    /// they were already accepted by the exporting peer.
+    pub fn import_live_state(
+        addr: SocketAddr,
+        config: &Config,
+        state: &LiveConnectionState,
+        time: Instant,
+    ) -> Peer {
+        let mut peer = Peer::new(addr, config, time);
+        peer.restore_state(&state.connection, time);
+
+        peer.state = state.state;
+        peer.peer_id = state.peer_id;
+        peer.incoming_session_id = state.incoming_session_id;
+        peer.outgoing_session_id = state.outgoing_session_id;
+        peer.connect_id = state.connect_id;
+        peer.protocol_version = state.protocol_version;
+        peer.negotiated_channels = state.negotiated_channels;
+        peer.negotiated_mtu = state.negotiated_mtu;
+        peer.negotiated_idle_timeout = state.negotiated_idle_timeout;
+        peer.negotiated_params = state.negotiated_params;
+
+        peer.acknowledge_handler
+            .restore_received(state.remote_ack_sequence, &state.received_sequences);
+        for packet in &state.in_flight {
+            peer.acknowledge_handler.restore_sent_packet(packet.sequence, SentPacket {
+                packet_type: packet.packet_type,
+                payload: packet.payload.clone().into_boxed_slice(),
+                ordering_guarantee: packet.ordering,
+                item_identifier: packet.item_identifier,
+                sent_time: time.checked_sub(packet.age).unwrap_or(time),
+            });
+        }
+
+        peer.outgoing_reliable_sequence = state.next_reliable_sequence;
+        peer.next_unreliable_sequence = state.next_unreliable_sequence;
+        peer.unsequenced_state = UnsequencedState::from_parts(
+            state.incoming_unsequenced_group,
+            state.unsequenced_window,
+            state.outgoing_unsequenced_group,
+        );
+        peer.reliable_received = UnsequencedState::from_parts(
+            state.incoming_reliable_sequence,
+            state.reliable_window,
+            0,
+        );
+        peer.flow_control.restore(
+            state.window_size,
+            state.reliable_data_in_transit,
+            state.total_reliable_sent,
+        );
+        peer.reliable_bytes_received = state.reliable_bytes_received;
+
+        for channel in &state.channels {
+            let buffered = channel
+                .buffered
+                .iter()
+                .map(|(sequence, data)| (*sequence, SharedBytes::from_vec(data.clone())))
+                .collect();
+            peer.channel_states.insert(
+                channel.channel_id,
+                ChannelState::from_parts(
+                    channel.expected_sequence,
+                    channel.latest_sequence,
+                    buffered,
+                ),
+            );
+        }
+
+        for message in &state.reassembly {
+            let mut buffer = CommandFragmentBuffer::new(
+                message.channel_id,
+                message.fragment_count,
+                message.ordered,
+                time.checked_sub(message.age).unwrap_or(time),
+            );
+            for (fragment_id, data) in &message.fragments {
+                peer.memory_budget.force_reserve(data.len());
+                peer.reassembly_bytes += data.len();
+                buffer.add_fragment(*fragment_id, data.as_slice().into(), buffer.created_at());
+            }
+            peer.command_fragments.insert(message.sequence, buffer);
+        }
+        peer.reassembly_peak_bytes = peer.reassembly_bytes;
+
+        for queued in &state.queued_commands {
+            let data_size = Self::command_data_size(&queued.command);
+            peer.memory_budget.force_reserve(data_size);
+            peer.total_waiting_data += data_size;
+            peer.command_queue.enqueue_with_compression(queued.command.clone(), queued.compression);
+        }
+
+        peer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitfold_core::config::CompressionAlgorithm;
+    use bitfold_protocol::command::ProtocolCommand;
+
+    use super::*;
+    use crate::PeerState;
+
+    fn get_fake_addr() -> SocketAddr {
+        "127.0.0.1:0".parse().unwrap()
+    }
+
+    /// Runs the handshake between a fresh client and server.
+    fn connected_pair(config: &Config, time: Instant) -> (Peer, Peer) {
+        let mut client = Peer::new(get_fake_addr(), config, time);
+        let mut server = Peer::new(get_fake_addr(), config, time);
+        client.record_send();
+        for command in client.drain_commands().collect::<Vec<_>>() {
+            server.process_command(&command, time).unwrap();
+        }
+        for command in server.drain_commands().collect::<Vec<_>>() {
+            client.process_command(&command, time).unwrap();
+        }
+        server.record_recv();
+        (client, server)
+    }
+
+    fn reliable(sequence: u16, data: &[u8]) -> ProtocolCommand {
+        ProtocolCommand::SendReliable {
+            channel_id: 1,
+            sequence,
+            ordered: true,
+            data: data.to_vec().into(),
+        }
+    }
+
+    fn fragment(fragment_id: u8, data: &[u8]) -> ProtocolCommand {
+        ProtocolCommand::SendFragment {
+            channel_id: 1,
+            sequence: 3,
+            ordered: true,
+            fragment_id,
+            fragment_count: 2,
+            data: data.to_vec().into(),
+        }
+    }
+
+    /// Processes `command` and returns the payloads delivered to the application.
+    fn deliver(peer: &mut Peer, command: &ProtocolCommand, time: Instant) -> Vec<Vec<u8>> {
+        let packets = peer.process_command(command, time).unwrap();
+        packets.into_iter().map(|(packet, _)| packet.payload().to_vec()).collect()
+    }
+
+    #[test]
+    fn test_migrated_receiver_continues_mid_flight() {
+        let config = Config::default();
+        let time = Instant::now();
+        let (_client, mut server) = connected_pair(&config, time);
+
+        // Sequence 1 and the second fragment of message 3 are still on their way
+        assert_eq!(deliver(&mut server, &reliable(0, b"zero"), time), vec![b"zero".to_vec()]);
+        assert!(deliver(&mut server, &reliable(2, b"two"), time).is_empty());
+        assert!(deliver(&mut server, &fragment(0, b"thr"), time).is_empty());
+        server.enqueue_command(ProtocolCommand::Ping { timestamp: 7 });
+
+        let later = time + std::time::Duration::from_millis(10);
+        let state = server.export_live_state(later);
+        let mut migrated = Peer::import_live_state(get_fake_addr(), &config, &state, later);
+        assert_eq!(migrated.export_live_state(later), state);
+        assert_eq!(migrated.state(), PeerState::Connected);
+        assert_eq!(migrated.reassembly_bytes, server.reassembly_bytes);
+
+        // Both continue identically from here
+        for peer in [&mut server, &mut migrated] {
+            assert_eq!(deliver(peer, &reliable(1, b"one"), later), vec![
+                b"one".to_vec(),
+                b"two".to_vec()
+            ]);
+            assert_eq!(deliver(peer, &fragment(1, b"ee"), later), vec![b"three".to_vec()]);
+        }
+        let expected: Vec<_> = server.drain_commands().collect();
+        let commands: Vec<_> = migrated.drain_commands().collect();
+        assert_eq!(commands, expected);
+        assert!(commands.contains(&ProtocolCommand::Ping { timestamp: 7 }));
+        assert!(matches!(
+            commands.last(),
+            Some(ProtocolCommand::Acknowledge { sequence: 3, received_mask: 0b111, .. })
+        ));
+    }
+
+    #[test]
+    fn test_exported_sequences_ordered_across_wraparound() {
+        use std::collections::HashMap;
+
+        use bitfold_protocol::packet::{OrderingGuarantee, PacketType};
+
+        let config = Config::default();
+        let time = Instant::now();
+        let (mut client, _server) = connected_pair(&config, time);
+        client.acknowledge_handler.set_local_sequence_num(u16::MAX - 1);
+        for _ in 0..3 {
+            client.acknowledge_handler.process_outgoing(
+                PacketType::Packet,
+                b"data",
+                OrderingGuarantee::None,
+                None,
+                time,
+            );
+        }
+        let buffered: HashMap<_, _> =
+            [u16::MAX, 0, 1].into_iter().map(|sequence| (sequence, vec![1].into())).collect();
+        client.channel_states.insert(1, ChannelState::from_parts(u16::MAX - 1, 0, buffered));
+
+        let state = client.export_live_state(time);
+        let in_flight: Vec<_> = state.in_flight.iter().map(|packet| packet.sequence).collect();
+        assert_eq!(in_flight, vec![u16::MAX - 1, u16::MAX, 0]);
+        let channel = state.channels.iter().find(|channel| channel.channel_id == 1).unwrap();
+        let buffered: Vec<_> = channel.buffered.iter().map(|&(sequence, _)| sequence).collect();
+        assert_eq!(buffered, vec![u16::MAX, 0, 1]);
+    }
+
+    #[test]
+    fn test_migrated_sender_flushes_queued_data() {
+        let config = Config::default();
+        let time = Instant::now();
+        let (mut client, mut server) = connected_pair(&config, time);
+
+        client.enqueue_command(reliable(0, b"queued before migration"));
+        let zlib = Some(CompressionAlgorithm::Zlib);
+        client.enqueue_command_with_compression(reliable(1, &[7; 256]), zlib);
+        let state = client.export_live_state(time);
+        assert_eq!(state.queued_commands[1].compression, zlib);
+        let mut migrated = Peer::import_live_state(get_fake_addr(), &config, &state, time);
+        assert_eq!(migrated.export_live_state(time), state);
+        assert_eq!(migrated.queued_commands_count(), 2);
+        assert_eq!(migrated.total_waiting_data, client.total_waiting_data);
+
+        // The override still puts the second message in a compressed datagram of its own
+        let mut payloads = Vec::new();
+        for marker in [0, 1] {
+            let datagram = migrated.encode_queued_commands().unwrap();
+            assert_eq!(datagram[0], marker);
+            let packets = server.process_command_packet(&datagram, time).unwrap();
+            payloads.extend(packets.into_iter().map(|(packet, _)| packet.payload().to_vec()));
+        }
+        assert_eq!(payloads, vec![b"queued before migration".to_vec(), vec![7; 256]]);
+    }
+}
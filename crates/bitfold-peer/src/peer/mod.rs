@@ -1,28 +1,71 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, VecDeque},
     fmt,
+    hash::{DefaultHasher, Hash, Hasher},
     net::SocketAddr,
     time::{Duration, Instant},
 };
 
-use bitfold_core::{config::Config, packet_pool::PacketAllocator};
-use bitfold_protocol::{command::ProtocolCommand, AcknowledgmentHandler, SentPacket};
+use bitfold_core::{
+    config::{ChecksumAlgorithm, CompressionAlgorithm, Config},
+    error::{ErrorKind, Result},
+    memory_budget::MemoryBudget,
+    packet_pool::PacketAllocator,
+};
+use bitfold_protocol::{
+    command::ProtocolCommand,
+    command_codec::{AdaptiveCompression, CommandEncoder, COMMAND_LENGTH_PREFIX_SIZE},
+    packet::Packet,
+    sequence_buffer::sequence_greater_than,
+    AckedPacket, AcknowledgmentHandler, RttEstimator, SentPacket,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use super::{
     bandwidth_throttle::BandwidthThrottle,
     channel_state::ChannelState,
+    close_reason::CloseReason,
     command_queue::CommandQueue,
+    congestion_controller::{self, CongestionController},
+    connection_state::ConnectionState,
     flow_control::FlowControl,
-    fragment_buffer::{cleanup_stale_fragments, CommandFragmentBuffer},
+    fragment_buffer::{
+        cleanup_stale_fragments, evict_least_recent_fragments, CommandFragmentBuffer,
+        ReassemblyError,
+    },
+    health::PeerHealth,
+    histogram::{Histogram, PMTU_CONVERGENCE_BUCKETS, WINDOW_SIZE_BUCKETS},
+    mtu_changes::{MtuChanges, MtuSubscribers},
+    negotiated_params::NegotiatedParams,
+    pacer::Pacer,
+    path_validation::PathValidation,
     peer_state::PeerState,
-    pmtu_discovery::PmtuDiscovery,
-    statistics::PeerStatistics,
+    ping::{PendingPings, PingFuture},
+    pmtu_discovery::{PmtuAddressFamily, PmtuDiscovery, PmtuProbeLoss, PmtuState, PmtuStats},
+    statistics::{CompressionStats, PeerMetricsSnapshot, PeerStatistics, TRACE_ID_LABEL},
+    stream_mode::StreamMode,
     unsequenced::UnsequencedState,
 };
 
 mod command_processor;
 mod encoder;
 mod fragmenter;
+mod migration;
+mod path_migration;
+mod restart_check;
+
+use restart_check::RestartCheck;
+
+/// Protocol version advertised in the connection handshake.
+const HANDSHAKE_PROTOCOL_VERSION: u16 = 2;
+/// Oldest protocol version accepted in the connection handshake (version 2 added the idle
+/// timeout to Connect and VerifyConnect).
+const MIN_HANDSHAKE_PROTOCOL_VERSION: u16 = 2;
+/// MTU advertised in the connection handshake.
+const HANDSHAKE_MTU: u16 = 1400;
+/// Size of the largest unfragmented data command header (SendReliable), excluding its payload.
+const SEND_RELIABLE_HEADER_SIZE: usize = 1 /* type */ + 1 /* channel */ + 2 /* sequence */
+    + 1 /* ordered flag */ + 2 /* payload len */;
 
 /// Represents a remote peer in the network.
 /// Tracks network quality, processes packets, and manages connection state.
@@ -36,6 +79,8 @@ pub struct Peer {
 
     /// Current connection state
     state: PeerState,
+    /// Why the connection closed, once it started closing
+    close_reason: Option<CloseReason>,
 
     // Connection handshake tracking
     /// Assigned peer ID (from server)
@@ -46,9 +91,19 @@ pub struct Peer {
     outgoing_session_id: u16,
     /// Connect ID for replay protection
     connect_id: u32,
-
-    /// Outgoing reliable packet sequence number
-    #[allow(dead_code)]
+    /// Protocol version agreed with the remote peer
+    protocol_version: u16,
+    /// Channel count agreed with the remote peer
+    negotiated_channels: u8,
+    /// MTU agreed with the remote peer
+    negotiated_mtu: u16,
+    /// Idle timeout agreed with the remote peer (the smaller of both sides')
+    negotiated_idle_timeout: Duration,
+    /// Record of negotiated parameters, set once the handshake completes
+    negotiated_params: Option<NegotiatedParams>,
+
+    /// Sequence number of the next reliable message (increments for each message, fragmented
+    /// or not)
     outgoing_reliable_sequence: u16,
     /// Outgoing unreliable packet sequence number
     #[allow(dead_code)]
@@ -64,6 +119,9 @@ pub struct Peer {
 
     /// Unsequenced packet duplicate detection state
     unsequenced_state: UnsequencedState,
+    /// Duplicate detection of incoming reliable sequences, so retransmitted copies are not
+    /// counted as newly received data
+    reliable_received: UnsequencedState,
 
     /// Handler for reliable delivery acknowledgments and congestion control
     acknowledge_handler: AcknowledgmentHandler,
@@ -77,53 +135,143 @@ pub struct Peer {
     total_waiting_data: usize,
     /// Fragment reassembly buffer for command-based fragments (indexed by sequence number)
     command_fragments: HashMap<u16, CommandFragmentBuffer>,
+    /// Total bytes of fragment payloads buffered for reassembly
+    reassembly_bytes: usize,
+    /// High-water mark of `reassembly_bytes` since creation or the last reset
+    reassembly_peak_bytes: usize,
+    /// Shared budget that queued and reassembly bytes are reserved from
+    memory_budget: MemoryBudget,
     /// Per-channel ordering/sequencing state
     channel_states: HashMap<u8, ChannelState>,
+    /// Reliability mode of each opened stream, by channel
+    stream_modes: HashMap<u8, StreamMode>,
+    /// Application packets sent before the handshake completed
+    early_data: VecDeque<Packet>,
+    /// Total payload bytes held in `early_data`
+    early_data_bytes: usize,
 
     // Window-based flow control
     /// Flow control state for reliable data transmission
     flow_control: FlowControl,
+    /// Byte-based congestion window gating reliable sends (see `Config::congestion_controller`)
+    congestion_controller: Option<Box<dyn CongestionController>>,
+    /// Total reliable payload bytes received (wrapping), reported in window updates
+    reliable_bytes_received: u32,
+    /// Receive window space last advertised to the remote, in bytes
+    advertised_receive_window: u32,
+    /// Size of the most recent raw datagram handed to `process_command_packet`
+    last_datagram_size: Option<usize>,
+    /// Highest reliable sequence number received so far
+    highest_received_sequence: Option<u16>,
+    /// Largest distance a reliable packet arrived behind `highest_received_sequence`
+    reorder_extent: u16,
+
+    // Acknowledgments
+    /// When a delayed ACK for received reliable data is due (`ack_delay_ms`)
+    ack_deadline: Option<Instant>,
+    /// Whether the next datagram carrying data should request an immediate ACK
+    immediate_ack_requested: bool,
+    /// Compression overriding the connection's for data enqueued by
+    /// [`Peer::send_with_options`] while it runs
+    send_compression: Option<CompressionAlgorithm>,
+    /// Whether queued data should be sent even below `flush_threshold_bytes`
+    flush_requested: bool,
+    /// Whether new application data is held in the queue (see `pause_sending`)
+    sending_paused: bool,
+    /// Whether the front of the queue is a retransmission, which bypasses a pause
+    retransmission_queued: bool,
 
     // Bandwidth throttling
     /// Bandwidth tracking and limiting
     bandwidth_throttle: BandwidthThrottle,
+    /// Spreads datagrams at the outgoing limit or the window rate (see
+    /// `Config::pacing_burst_packets` and `Config::use_pacing`)
+    pacer: Option<Pacer>,
 
     // Statistics tracking
     /// Comprehensive statistics for this peer
     statistics: PeerStatistics,
+    /// Time each PMTU search took to settle (seconds)
+    pmtu_convergence_histogram: Histogram,
+    /// Start of the PMTU search whose convergence was last recorded
+    pmtu_convergence_recorded: Option<Instant>,
+    /// Flow-control window size after each adjustment (packets)
+    window_histogram: Histogram,
 
     /// Scratch buffer pool for encoding/compression to reduce heap allocations
     tx_pool: PacketAllocator,
     /// Compression output buffer pool for reducing compression allocations
     compression_pool: bitfold_core::packet_pool::CompressionBufferPool,
+    /// Suspends compression on incompressible traffic (see `Config::adaptive_compression_window`)
+    adaptive_compression: Option<AdaptiveCompression>,
 
     /// Path MTU discovery manager
     pmtu: PmtuDiscovery,
+    /// Bytes of each datagram reserved for transforms applied after encoding (interceptors)
+    datagram_overhead: usize,
+    /// Subscribers notified when the effective send MTU changes (see `mtu_changes`)
+    mtu_subscribers: MtuSubscribers,
+    /// Effective send MTU last reported to `mtu_subscribers`
+    reported_mtu: u16,
+
+    /// Application-provided labels (e.g. user ID, tenant) for logging and metrics grouping
+    labels: BTreeMap<String, String>,
+
+    /// Time `shutdown()` started the close handshake
+    shutdown_started: Option<Instant>,
+    /// Deadline after which the connection closes itself
+    ttl: Option<Instant>,
+    /// Application pings awaiting their pong
+    pending_pings: PendingPings,
+    /// Source address of the datagram being processed, as reported by `on_datagram_from`
+    datagram_source: Option<SocketAddr>,
+    /// Validation of a new source address the connection may migrate to
+    path_validation: Option<PathValidation>,
+    /// When the connection was last identified to a silent remote
+    last_identified: Option<Instant>,
+    /// Liveness check after a Connect with a new connect ID on the established connection
+    restart_check: Option<RestartCheck>,
+    /// Source of all randomness for this connection (see `Config::rng_seed`)
+    rng: StdRng,
 }
 
 impl Peer {
     /// Creates and returns a new peer for the provided socket address.
     pub fn new(addr: SocketAddr, config: &Config, time: Instant) -> Peer {
-        use rand::Rng;
-        let mut rng = rand::rng();
+        let mut rng = match config.rng_seed {
+            Some(seed) => StdRng::seed_from_u64(Self::connection_seed(seed, addr)),
+            None => StdRng::from_os_rng(),
+        };
+        let pmtu_rng = StdRng::seed_from_u64(rng.random());
+        let mut pmtu = PmtuDiscovery::with_rng(config, time, pmtu_rng);
+        pmtu.set_address_family(PmtuAddressFamily::of(addr));
 
-        Peer {
+        let mut peer = Peer {
             last_heard: time,
             last_sent: time,
             remote_address: addr,
             state: PeerState::Idle,
+            close_reason: None,
             peer_id: 0, // Will be assigned during handshake
             incoming_session_id: 0,
             outgoing_session_id: rng.random(), // Random session ID for security
             connect_id: rng.random(),          // Random connect ID for replay protection
+            protocol_version: HANDSHAKE_PROTOCOL_VERSION,
+            negotiated_channels: config.channel_count,
+            negotiated_mtu: HANDSHAKE_MTU,
+            negotiated_idle_timeout: config.idle_connection_timeout,
+            negotiated_params: None,
             outgoing_reliable_sequence: 0,
             outgoing_unreliable_sequence: 0,
             incoming_reliable_sequence: 0,
             incoming_unreliable_sequence: 0,
             next_unreliable_sequence: 0,
             unsequenced_state: UnsequencedState::new(),
+            reliable_received: UnsequencedState::new(),
             acknowledge_handler: {
                 let mut handler = AcknowledgmentHandler::new();
+                handler
+                    .set_loss_thresholds(config.loss_reorder_threshold, config.loss_time_threshold);
                 // Configure advanced throttling if enabled
                 if config.use_advanced_throttling {
                     handler.congestion_mut().enable_advanced_throttling(
@@ -133,24 +281,85 @@ impl Peer {
                         config.throttle_interval,
                     );
                 }
+                handler.congestion_mut().set_adaptive_signal(config.adaptive_congestion_signal);
+                *handler.congestion_mut().rtt_estimator_mut() = RttEstimator::from_config(config);
                 handler
             },
             config: config.to_owned(),
             command_queue: CommandQueue::default(),
             total_waiting_data: 0,
             command_fragments: HashMap::new(),
+            reassembly_bytes: 0,
+            reassembly_peak_bytes: 0,
+            memory_budget: MemoryBudget::unlimited(),
             channel_states: HashMap::new(),
+            stream_modes: HashMap::new(),
+            early_data: VecDeque::new(),
+            early_data_bytes: 0,
             flow_control: FlowControl::new(config),
+            congestion_controller: congestion_controller::from_config(config),
+            reliable_bytes_received: 0,
+            advertised_receive_window: FlowControl::receive_window(config),
+            last_datagram_size: None,
+            highest_received_sequence: None,
+            reorder_extent: 0,
+            ack_deadline: None,
+            immediate_ack_requested: false,
+            send_compression: None,
+            flush_requested: false,
+            sending_paused: false,
+            retransmission_queued: false,
             bandwidth_throttle: BandwidthThrottle::new(
                 config.outgoing_bandwidth_limit,
                 config.incoming_bandwidth_limit,
                 time,
             ),
+            pacer: (config.use_pacing
+                || (config.pacing_burst_packets > 0 && config.outgoing_bandwidth_limit > 0))
+                .then(|| {
+                    Pacer::new(
+                        config.outgoing_bandwidth_limit,
+                        config.pacing_burst_packets,
+                        config.fragment_size as usize,
+                        time,
+                    )
+                }),
             statistics: PeerStatistics::default(),
+            pmtu_convergence_histogram: Histogram::new(PMTU_CONVERGENCE_BUCKETS),
+            pmtu_convergence_recorded: None,
+            window_histogram: Histogram::new(WINDOW_SIZE_BUCKETS),
             tx_pool: PacketAllocator::new(config.max_packet_size, 256),
             compression_pool: bitfold_core::packet_pool::CompressionBufferPool::default(),
-            pmtu: PmtuDiscovery::new(config, time),
-        }
+            adaptive_compression: (config.adaptive_compression_window > 0).then(|| {
+                AdaptiveCompression::new(
+                    config.adaptive_compression_window,
+                    config.adaptive_compression_max_ratio,
+                )
+            }),
+            pmtu,
+            datagram_overhead: 0,
+            mtu_subscribers: MtuSubscribers::default(),
+            reported_mtu: 0,
+            labels: BTreeMap::new(),
+            shutdown_started: None,
+            ttl: None,
+            pending_pings: PendingPings::default(),
+            datagram_source: None,
+            path_validation: None,
+            last_identified: None,
+            restart_check: None,
+            rng,
+        };
+        peer.update_pacing_rate(time);
+        peer
+    }
+
+    /// Derives a peer's RNG seed from the configured seed and its remote address.
+    fn connection_seed(seed: u64, addr: SocketAddr) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        addr.hash(&mut hasher);
+        hasher.finish()
     }
 
     /// Records that this connection has sent a packet. Returns whether the connection has
@@ -176,6 +385,7 @@ impl Peer {
             _ => {}
         }
 
+        self.complete_handshake();
         !was_est && self.is_established()
     }
 
@@ -208,9 +418,46 @@ impl Peer {
             _ => {}
         }
 
+        self.complete_handshake();
         !was_est && self.is_established()
     }
 
+    /// Records the negotiated parameters the first time the connection is established.
+    pub(crate) fn complete_handshake(&mut self) {
+        if self.negotiated_params.is_some() || !self.is_established() {
+            return;
+        }
+        let params = NegotiatedParams {
+            protocol_version: self.protocol_version,
+            channels: self.negotiated_channels,
+            mtu: self.negotiated_mtu,
+            window_size: self.window_size(),
+            initial_fragment_size: self.current_fragment_size(),
+            idle_timeout: self.negotiated_idle_timeout,
+            compression: self.config.compression,
+            checksums: self.config.checksum_algorithm() != ChecksumAlgorithm::None,
+        };
+        tracing::info!(
+            labels = ?self.labels,
+            remote = %self.remote_address,
+            protocol_version = params.protocol_version,
+            channels = params.channels,
+            mtu = params.mtu,
+            window_size = params.window_size,
+            initial_fragment_size = params.initial_fragment_size,
+            idle_timeout = ?params.idle_timeout,
+            compression = ?params.compression,
+            checksums = params.checksums,
+            "Connection established with negotiated parameters"
+        );
+        self.negotiated_params = Some(params);
+    }
+
+    /// Returns the parameters negotiated during the handshake, once the connection is established.
+    pub fn negotiated_params(&self) -> Option<&NegotiatedParams> {
+        self.negotiated_params.as_ref()
+    }
+
     /// Returns if the connection has been established
     pub fn is_established(&self) -> bool {
         self.state.is_established()
@@ -221,14 +468,128 @@ impl Peer {
         self.state
     }
 
+    /// Returns the handshake connect ID once a connection handshake has started.
+    pub fn connection_id(&self) -> Option<u32> {
+        if self.config.use_connection_handshake && self.state != PeerState::Idle {
+            Some(self.connect_id)
+        } else {
+            None
+        }
+    }
+
     /// Initiates graceful disconnect
     pub fn disconnect(&mut self) {
+        self.disconnect_with_reason(CloseReason::NO_ERROR);
+    }
+
+    /// Initiates graceful disconnect, sending an application-defined `reason` code to the remote.
+    /// [`CloseReason`] defines standard codes, e.g. [`CloseReason::APPLICATION_CLOSE`]. The
+    /// remote reports it as [`CloseReason::RemoteClose`].
+    ///
+    /// Until the peer is dropped, queued data still goes out and received data is still
+    /// acknowledged, but the host accepts no new application packets for it.
+    ///
+    /// A delayed ACK is sent right away, so it shares the datagram carrying the disconnect.
+    pub fn disconnect_with_reason(&mut self, reason: u32) {
         if !self.state.is_disconnecting() {
             self.state = PeerState::Disconnecting;
-            self.command_queue.enqueue(ProtocolCommand::Disconnect { reason: 0 });
+            self.set_close_reason(CloseReason::LocalClose { code: reason });
+            if self.ack_deadline.take().is_some() {
+                self.enqueue_ack_command(None);
+            }
+            self.command_queue.enqueue(ProtocolCommand::Disconnect { reason });
+        }
+    }
+
+    /// Returns why the connection closed, or `None` while it is open.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        self.close_reason
+    }
+
+    /// Records why the connection closed. Only the first reason recorded is kept.
+    pub fn set_close_reason(&mut self, reason: CloseReason) {
+        self.close_reason.get_or_insert(reason);
+    }
+
+    /// Starts a graceful shutdown and returns the state needed to warm-restart later.
+    ///
+    /// Queues the close handshake; the peer is dropped once the remote confirms or
+    /// `disconnect_timeout` elapses (see [`Peer::is_shutdown_timed_out`]).
+    pub fn shutdown(&mut self, time: Instant) -> ConnectionState {
+        self.disconnect();
+        self.shutdown_started.get_or_insert(time);
+        self.connection_state()
+    }
+
+    /// Returns whether the close handshake started by `shutdown()` has exceeded its timeout.
+    pub fn is_shutdown_timed_out(&self, time: Instant) -> bool {
+        self.shutdown_started
+            .is_some_and(|started| time.duration_since(started) >= self.config.disconnect_timeout)
+    }
+
+    /// Sets a deadline after which the connection is gracefully closed (see [`Peer::handle_ttl`]).
+    pub fn set_ttl(&mut self, deadline: Instant) {
+        self.ttl = Some(deadline);
+    }
+
+    /// Returns the connection deadline, if one is set.
+    pub fn ttl(&self) -> Option<Instant> {
+        self.ttl
+    }
+
+    /// Starts a graceful close once the TTL has passed. Call this periodically.
+    ///
+    /// Returns `Err(ErrorKind::TtlExpired)` once, on the call that initiates the close.
+    pub fn handle_ttl(&mut self, time: Instant) -> Result<()> {
+        match self.ttl {
+            Some(deadline) if time >= deadline => {
+                self.ttl = None;
+                self.shutdown(time);
+                Err(ErrorKind::TtlExpired)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Sends a ping and returns a future resolving with the round-trip time once its pong
+    /// arrives.
+    ///
+    /// Independent of keepalive pings; several pings may be in flight at once, each matched
+    /// to its pong by token. The future fails with `ErrorKind::PingTimeout` if no pong arrives
+    /// within `Config::ping_timeout` (see [`Peer::handle_ping_timeouts`]).
+    pub fn ping(&mut self, time: Instant) -> PingFuture {
+        let (token, future) = self.pending_pings.register(time);
+        self.enqueue_ping_command(token);
+        future
+    }
+
+    /// Number of pings sent by [`Peer::ping`] that are still awaiting their pong.
+    pub fn pending_pings(&self) -> usize {
+        self.pending_pings.len()
+    }
+
+    /// Fails pings that went unanswered for `Config::ping_timeout`. Call this periodically.
+    pub fn handle_ping_timeouts(&mut self, time: Instant) {
+        self.pending_pings.expire(time, self.config.ping_timeout);
+    }
+
+    /// Returns the persistable path, congestion and sequence state of this connection.
+    pub fn connection_state(&self) -> ConnectionState {
+        ConnectionState {
+            pmtu: self.pmtu.snapshot(),
+            congestion: self.acknowledge_handler.congestion().snapshot(),
+            next_sequence: self.acknowledge_handler.local_sequence_num(),
         }
     }
 
+    /// Resumes from state returned by `shutdown()` in an earlier process.
+    pub fn restore_state(&mut self, state: &ConnectionState, time: Instant) {
+        self.pmtu.restore(&state.pmtu, time);
+        self.acknowledge_handler.congestion_mut().restore(&state.congestion);
+        self.acknowledge_handler.set_local_sequence_num(state.next_sequence);
+        self.notify_mtu_change();
+    }
+
     // ===== Connection Handshake (3-way) =====
 
     /// Initiates a connection handshake by sending CONNECT command (step 1 of 3).
@@ -238,20 +599,80 @@ impl Peer {
             self.state = PeerState::Connecting;
             let connect_command = ProtocolCommand::Connect {
                 channels: self.config.channel_count,
-                mtu: 1400,           // Default MTU
-                protocol_version: 1, // Protocol version
+                mtu: HANDSHAKE_MTU,
+                protocol_version: self.protocol_version,
                 outgoing_session_id: self.outgoing_session_id,
                 connect_id: self.connect_id,
+                idle_timeout_ms: Self::duration_ms(self.negotiated_idle_timeout),
             };
             self.command_queue.enqueue(connect_command);
         }
     }
 
+    /// Returns the idle timeout both sides enforce: `idle_connection_timeout`, lowered to the
+    /// remote's during the handshake if that is smaller.
+    pub fn idle_timeout(&self) -> Duration {
+        self.negotiated_idle_timeout
+    }
+
+    /// Adopts the remote's advertised idle timeout if it is smaller than the current one. A
+    /// remote advertising 0 has no preference, as no connection could survive it.
+    fn negotiate_idle_timeout(&mut self, remote_ms: u32) {
+        if remote_ms == 0 {
+            return;
+        }
+        let remote = Duration::from_millis(remote_ms as u64);
+        self.negotiated_idle_timeout = self.negotiated_idle_timeout.min(remote);
+    }
+
+    /// Converts `duration` to whole milliseconds for the wire, saturating at `u32::MAX`.
+    fn duration_ms(duration: Duration) -> u32 {
+        duration.as_millis().min(u32::MAX as u128) as u32
+    }
+
     /// Returns the current number of not yet acknowledged packets
     pub fn packets_in_flight(&self) -> u16 {
         self.acknowledge_handler.packets_in_flight()
     }
 
+    /// Returns the payload bytes of the packets not yet acknowledged.
+    pub fn bytes_in_flight(&self) -> usize {
+        self.acknowledge_handler.bytes_in_flight()
+    }
+
+    /// Processes an acknowledgment of `sequence` and the packets in `received_mask`, feeding
+    /// the newly acknowledged packets to the congestion controller.
+    pub(crate) fn process_acknowledgment(
+        &mut self,
+        sequence: u16,
+        received_mask: u32,
+        time: Instant,
+    ) {
+        let acked =
+            self.acknowledge_handler.process_incoming(sequence, sequence, received_mask, time);
+        self.on_acked(acked);
+    }
+
+    /// Processes a selective acknowledgment of inclusive `(first, last)` sequence ranges (see
+    /// [`AcknowledgmentHandler::process_sack`]). Ignored when selective acknowledgment is
+    /// disabled (`sack_max_ranges` is 0).
+    pub(crate) fn process_sack(&mut self, ranges: &[(u16, u16)], time: Instant) {
+        if self.config.sack_max_ranges == 0 {
+            return;
+        }
+        let acked = self.acknowledge_handler.process_sack(ranges, time);
+        self.on_acked(acked);
+    }
+
+    /// Feeds newly acknowledged packets to the congestion controller.
+    fn on_acked(&mut self, acked: Vec<AckedPacket>) {
+        if let Some(controller) = self.congestion_controller.as_mut() {
+            for packet in acked {
+                controller.on_ack(packet.bytes, packet.rtt);
+            }
+        }
+    }
+
     /// Returns a [Duration] representing the interval since we last heard from the client
     pub fn last_heard(&self, time: Instant) -> Duration {
         time.duration_since(self.last_heard)
@@ -262,6 +683,51 @@ impl Peer {
         time.duration_since(self.last_sent)
     }
 
+    /// Returns a breakdown of this connection's health at `time`: loss rate, RTT stability,
+    /// PMTU convergence and liveness.
+    pub fn health(&self, time: Instant) -> PeerHealth {
+        let loss_rate = self.loss_rate();
+        let rtt = self.rtt();
+        let rtt_variance = self.acknowledge_handler.congestion().rtt_variance();
+        let rtt_max = Duration::from_millis(self.config.rtt_max_value as u64);
+        PeerHealth {
+            loss_rate,
+            loss_ok: loss_rate <= self.config.health_max_loss_rate,
+            rtt,
+            rtt_variance,
+            rtt_stable: rtt <= rtt_max && rtt_variance <= rtt,
+            pmtu_converged: !self.config.use_pmtu_discovery || self.pmtu.is_settled(),
+            alive: self.is_established() && self.last_heard(time) < self.idle_timeout() / 2,
+        }
+    }
+
+    /// Returns true if every check in [`Peer::health`] passes at `time`.
+    pub fn is_healthy(&self, time: Instant) -> bool {
+        self.health(time).is_healthy()
+    }
+
+    /// Returns the size in bytes of the most recent datagram received from the transport, as
+    /// handed to [`Peer::process_command_packet`], including datagrams that failed to decode.
+    pub fn last_datagram_size(&self) -> Option<usize> {
+        self.last_datagram_size
+    }
+
+    /// Returns the largest number of sequence numbers by which a received reliable packet
+    /// trailed the highest one already received, i.e. how far out of order the path delivers.
+    pub fn reorder_extent(&self) -> u16 {
+        self.reorder_extent
+    }
+
+    /// Updates the reorder extent with the arrival of reliable `sequence`.
+    fn record_arrival_order(&mut self, sequence: u16) {
+        match self.highest_received_sequence {
+            Some(highest) if !sequence_greater_than(sequence, highest) => {
+                self.reorder_extent = self.reorder_extent.max(highest.wrapping_sub(sequence));
+            }
+            _ => self.highest_received_sequence = Some(sequence),
+        }
+    }
+
     /// Returns the current round-trip time for this connection.
     pub fn rtt(&self) -> Duration {
         self.acknowledge_handler.rtt()
@@ -272,6 +738,12 @@ impl Peer {
         self.acknowledge_handler.rto()
     }
 
+    /// Backs the retransmission timeout off by `rto_backoff_factor` after a retransmission
+    /// timer expired; the next RTT sample resets it (see [`RttEstimator::on_timeout`]).
+    pub fn on_retransmission_timeout(&mut self) {
+        self.acknowledge_handler.congestion_mut().rtt_estimator_mut().on_timeout();
+    }
+
     /// Returns the current packet loss rate (0.0 to 1.0).
     pub fn loss_rate(&self) -> f32 {
         self.acknowledge_handler.loss_rate()
@@ -292,18 +764,109 @@ impl Peer {
         &self.config
     }
 
-    /// Gathers dropped packets from the acknowledgment handler.
-    pub fn gather_dropped_packets(&mut self) -> Vec<SentPacket> {
-        let dropped = self.acknowledge_handler.dropped_packets();
+    /// Gathers packets the acknowledgment handler declares lost at `time` (see
+    /// [`AcknowledgmentHandler::lost_packets`]). Packets lost together are reported as a
+    /// burst to PMTU black-hole detection (see [`Peer::report_loss_burst`]).
+    pub fn gather_dropped_packets(&mut self, time: Instant) -> Vec<SentPacket> {
+        let dropped = self.acknowledge_handler.lost_packets(time);
 
         // Track packet loss
         for _ in &dropped {
             self.record_packet_lost();
         }
+        if let Some(controller) = self.congestion_controller.as_mut() {
+            let bytes: usize = dropped.iter().map(|packet| packet.payload.len()).sum();
+            controller.on_loss(bytes);
+        }
+        if !dropped.is_empty() {
+            self.report_loss_burst(dropped.len(), time);
+        }
 
         dropped
     }
 
+    /// Lists the packets awaiting acknowledgment, oldest sequence number first, across
+    /// sequence number wraparound.
+    ///
+    /// Allocates on every call, so it is only available with the `diagnostics` feature and is
+    /// meant for inspecting stalled connections rather than regular use.
+    #[cfg(any(test, feature = "diagnostics"))]
+    pub fn outstanding_packets(&self) -> Vec<crate::OutstandingInfo> {
+        let mut packets: Vec<_> = self
+            .acknowledge_handler
+            .sent_packets()
+            .map(|(sequence, packet)| crate::OutstandingInfo {
+                sequence,
+                sent_time: packet.sent_time,
+                size: packet.payload.len(),
+            })
+            .collect();
+        // Every outstanding sequence lies within half the sequence space before the next one
+        let next = self.acknowledge_handler.local_sequence_num();
+        packets.sort_unstable_by_key(|info| info.sequence.wrapping_sub(next));
+        packets
+    }
+
+    /// Lists the armed timers and when each fires, soonest first.
+    ///
+    /// Times are computed from the current state, so a timer whose time has passed fires on
+    /// the next update. Only available with the `diagnostics` feature.
+    #[cfg(any(test, feature = "diagnostics"))]
+    pub fn timers(&self) -> Vec<(crate::TimerKind, Instant)> {
+        use crate::TimerKind;
+
+        let mut timers = vec![(TimerKind::Idle, self.last_heard + self.idle_timeout())];
+        if let Some(interval) = self.config.heartbeat_interval.filter(|_| self.is_established()) {
+            let idle_since = if self.config.heartbeat_on_send_idle {
+                self.last_sent
+            } else {
+                self.last_sent.max(self.last_heard)
+            };
+            timers.push((TimerKind::Keepalive, idle_since + interval));
+        }
+        let optional = [
+            (TimerKind::LossDetection, self.acknowledge_handler.loss_time()),
+            (TimerKind::PmtuProbe, self.pmtu.next_probe_time()),
+            (TimerKind::PmtuProbeTimeout, self.pmtu.probe_timeout_time(self.rto())),
+            (TimerKind::PmtuDiscoveryTimeout, self.pmtu.discovery_timeout_time()),
+            (TimerKind::AckDelay, self.ack_deadline),
+            (TimerKind::FlowControlProbe, self.flow_control.next_probe_time(&self.config)),
+            (
+                TimerKind::PingTimeout,
+                self.pending_pings.oldest().map(|sent| sent + self.config.ping_timeout),
+            ),
+            (TimerKind::Ttl, self.ttl),
+            (
+                TimerKind::ShutdownTimeout,
+                self.shutdown_started.map(|started| started + self.config.disconnect_timeout),
+            ),
+        ];
+        timers.extend(optional.into_iter().filter_map(|(kind, time)| Some((kind, time?))));
+        timers.sort_by_key(|&(_, time)| time);
+        timers
+    }
+
+    // ===== Labels =====
+
+    /// Sets an application label on this connection, returning the previous value if any.
+    pub fn set_label(
+        &mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Option<String> {
+        self.labels.insert(key.into(), value.into())
+    }
+
+    /// Removes an application label, returning its value if it was set.
+    pub fn remove_label(&mut self, key: &str) -> Option<String> {
+        self.labels.remove(key)
+    }
+
+    /// Returns the application labels attached to this connection.
+    pub fn labels(&self) -> &BTreeMap<String, String> {
+        &self.labels
+    }
+
     // ===== Statistics =====
 
     /// Returns a reference to the peer's statistics.
@@ -311,11 +874,33 @@ impl Peer {
         &self.statistics
     }
 
+    /// Returns true while adaptive compression has suspended compressing incompressible traffic.
+    pub fn is_compression_suspended(&self) -> bool {
+        self.adaptive_compression.as_ref().is_some_and(AdaptiveCompression::is_suspended)
+    }
+
+    /// Returns cumulative original vs compressed bytes for the configured compression algorithm.
+    pub fn compression_stats(&self) -> CompressionStats {
+        self.statistics.compression_stats(self.config.compression)
+    }
+
     /// Returns a mutable reference to the peer's statistics.
     pub fn statistics_mut(&mut self) -> &mut PeerStatistics {
         &mut self.statistics
     }
 
+    /// Returns a metrics snapshot for this peer, including its labels.
+    pub fn metrics_snapshot(&self) -> PeerMetricsSnapshot {
+        PeerMetricsSnapshot {
+            statistics: self.statistics.clone(),
+            rtt: self.rtt(),
+            packet_loss_rate: self.statistics.packet_loss_rate(),
+            labels: self.labels.clone(),
+            pmtu_convergence: self.pmtu_convergence_histogram.clone(),
+            window_size: self.window_histogram.clone(),
+        }
+    }
+
     /// Records a packet being sent.
     fn record_packet_sent(&mut self) {
         self.statistics.packets_sent += 1;
@@ -346,12 +931,182 @@ impl Peer {
         self.pmtu.current_fragment_size()
     }
 
-    /// Handles PMTU probing state machine (enqueue probes, process timeouts).
+    /// Returns the bytes of a datagram not available to application data: packet header,
+    /// compression marker, checksum and the framing of a single data command.
+    fn message_overhead(&self) -> u16 {
+        (CommandEncoder::packet_overhead_for_config(&self.config)
+            + COMMAND_LENGTH_PREFIX_SIZE
+            + SEND_RELIABLE_HEADER_SIZE) as u16
+    }
+
+    /// Returns the largest message payload that fits unfragmented in a datagram of `mtu` bytes.
+    pub fn usable_payload_for_mtu(&self, mtu: u16) -> u16 {
+        mtu.saturating_sub(self.message_overhead())
+    }
+
+    /// Returns the largest message payload that currently fits in one datagram: the PMTU-derived
+    /// fragment size (capped at `receive_buffer_max_size`) minus the overhead of the active
+    /// compression and checksum settings.
+    pub fn effective_send_mtu(&self) -> u16 {
+        self.usable_payload_for_mtu(self.datagram_cap().min(u16::MAX as usize) as u16)
+    }
+
+    /// Returns the largest datagram the remote is expected to send: the MTU agreed in the
+    /// handshake or, if larger, the size PMTU discovery found for this path, plus the bytes
+    /// transforms add after encoding. Receive buffers this large avoid truncation.
+    pub fn max_receive_datagram_size(&self) -> usize {
+        (self.negotiated_mtu.max(self.current_fragment_size()) as usize) + self.datagram_overhead
+    }
+
+    /// Returns the largest encoded datagram to produce: the PMTU-derived fragment size, capped
+    /// at `receive_buffer_max_size`, minus the bytes reserved for post-encoding transforms.
+    pub fn datagram_cap(&self) -> usize {
+        (self.current_fragment_size() as usize)
+            .min(self.config.receive_buffer_max_size)
+            .saturating_sub(self.datagram_overhead)
+    }
+
+    /// Reserves `bytes` of every datagram for a transform applied after encoding (e.g. an
+    /// interceptor adding padding), so transformed datagrams still fit the path MTU.
+    pub fn set_datagram_overhead(&mut self, bytes: usize) {
+        self.datagram_overhead = bytes;
+        self.pmtu.set_datagram_overhead(bytes.min(u16::MAX as usize) as u16);
+        self.notify_mtu_change();
+    }
+
+    /// Subscribes to changes of the effective send MTU (see [`Peer::effective_send_mtu`]).
+    ///
+    /// The returned receiver yields each new value as PMTU discovery, a black-hole reset or a
+    /// change in datagram overhead moves it, so applications can adjust packetization; await
+    /// [`MtuChanges::recv`] or poll it without blocking. Values are only produced while the
+    /// peer is driven; a dropped receiver is unsubscribed on the next change.
+    pub fn mtu_changes(&mut self) -> MtuChanges {
+        self.reported_mtu = self.effective_send_mtu();
+        self.mtu_subscribers.subscribe()
+    }
+
+    /// Registers `callback` to be called with the old and new PMTU fragment size whenever
+    /// discovery moves it, so send buffers can be resized without polling.
+    pub fn on_fragment_size_change(&mut self, callback: impl FnMut(u16, u16) + Send + 'static) {
+        self.pmtu.on_fragment_size_change(callback);
+    }
+
+    /// Sends the effective send MTU to subscribers if it changed since last reported.
+    fn notify_mtu_change(&mut self) {
+        if self.mtu_subscribers.is_empty() {
+            return;
+        }
+        let mtu = self.effective_send_mtu();
+        if mtu != self.reported_mtu {
+            self.reported_mtu = mtu;
+            self.mtu_subscribers.notify(mtu);
+        }
+    }
+
+    /// Returns the datagram size needed to carry a `payload`-byte message unfragmented.
+    /// Inverse of [`Peer::usable_payload_for_mtu`].
+    pub fn mtu_for_payload(&self, payload: u16) -> u16 {
+        payload.saturating_add(self.message_overhead())
+    }
+
+    /// Handles PMTU probing state machine (enqueue probes, process timeouts). New probes are
+    /// deferred while congestion control recovers from losses.
     pub fn handle_pmtu(&mut self, time: Instant) {
         let rto = self.rto();
-        if let Some(probe_cmd) = self.pmtu.handle_pmtu(time, rto) {
+        self.defer_pmtu_during_recovery();
+        let retries = self.pmtu.stats().retries;
+        let probe = self.pmtu.handle_pmtu(time, rto);
+        self.after_pmtu(probe, retries, time);
+    }
+
+    /// Like [`Peer::handle_pmtu`], but independent of how often it is called: a call after a
+    /// long gap enqueues the one probe a regular tick would have sent by now (see
+    /// [`PmtuDiscovery::handle_pmtu_batch`]).
+    pub fn handle_pmtu_batch(&mut self, time: Instant) {
+        let rto = self.rto();
+        self.defer_pmtu_during_recovery();
+        let retries = self.pmtu.stats().retries;
+        let probe = self.pmtu.handle_pmtu_batch(time, rto);
+        self.after_pmtu(probe, retries, time);
+    }
+
+    /// Holds back PMTU probes while congestion control is recovering from losses, so probing
+    /// does not add to the congestion.
+    fn defer_pmtu_during_recovery(&mut self) {
+        let recovering = self.acknowledge_handler.congestion().is_recovering();
+        self.pmtu.set_probes_deferred(recovering);
+    }
+
+    /// Enqueues a probe produced by PMTU discovery and records any resulting change. A probe
+    /// re-sent after its timer expired (more than `retries_before` retries) backs the RTO off.
+    fn after_pmtu(&mut self, probe: Option<ProtocolCommand>, retries_before: u64, time: Instant) {
+        if let Some(probe_cmd) = probe {
             self.enqueue_command(probe_cmd);
         }
+        if self.pmtu.stats().retries > retries_before {
+            self.on_retransmission_timeout();
+        }
+        let started = self.pmtu.started_at();
+        if self.pmtu.is_settled() && self.pmtu_convergence_recorded != Some(started) {
+            self.pmtu_convergence_recorded = Some(started);
+            let seconds = time.saturating_duration_since(started).as_secs_f64();
+            // The `trace_id` label links the observation to the trace as an exemplar
+            let trace_id = self.labels.get(TRACE_ID_LABEL).map(String::as_str);
+            self.pmtu_convergence_histogram.observe(seconds, trace_id);
+        }
+        self.notify_mtu_change();
+    }
+
+    /// Takes the fragmentable twin of the outstanding PMTU probe (`pmtu_paired_probes`).
+    ///
+    /// The transport must encode it into a datagram of its own and send it with the DF bit
+    /// cleared; see [`PmtuDiscovery::take_fragmentable_probe`].
+    pub fn take_fragmentable_pmtu_probe(&mut self) -> Option<ProtocolCommand> {
+        self.pmtu.take_fragmentable_probe()
+    }
+
+    /// Returns the diagnosis of the most recent paired PMTU probe that timed out.
+    pub fn last_pmtu_probe_loss(&self) -> Option<PmtuProbeLoss> {
+        self.pmtu.last_probe_loss()
+    }
+
+    /// Returns PMTU discovery counters and state (see [`PmtuDiscovery::stats`]).
+    pub fn pmtu_stats(&self) -> PmtuStats {
+        self.pmtu.stats()
+    }
+
+    /// Reports `lost` packets detected lost together. A burst of at least
+    /// `pmtu_blackhole_loss_threshold` packets is treated as a PMTU black hole (see
+    /// [`PmtuDiscovery::handle_blackhole`]).
+    ///
+    /// Returns `true` if the fragment size was reset.
+    pub fn report_loss_burst(&mut self, lost: usize, time: Instant) -> bool {
+        let threshold = self.config.pmtu_blackhole_loss_threshold as usize;
+        let reset = threshold > 0 && lost >= threshold && self.pmtu.handle_blackhole(time);
+        self.notify_mtu_change();
+        reset
+    }
+
+    /// Reports that a datagram of `size` bytes was lost, restarting a converged PMTU search
+    /// below the converged size after repeated losses (see
+    /// [`PmtuDiscovery::report_loss_at_size`]).
+    ///
+    /// Returns `true` if the search was restarted.
+    pub fn report_loss_at_size(&mut self, size: u16, time: Instant) -> bool {
+        let restarted = self.pmtu.report_loss_at_size(size, time);
+        self.notify_mtu_change();
+        restarted
+    }
+
+    /// Returns the phase of the PMTU search.
+    pub fn pmtu_state(&self) -> PmtuState {
+        self.pmtu.state()
+    }
+
+    /// Restarts PMTU discovery, e.g. after it gave up on a pathological path.
+    pub fn reset_pmtu(&mut self, time: Instant) {
+        self.pmtu.reset(time);
+        self.notify_mtu_change();
     }
 
     // ===== Window-based Flow Control =====
@@ -371,6 +1126,28 @@ impl Peer {
         self.flow_control.set_window_size(&self.config, window_size);
     }
 
+    /// Returns the flow-control window in bytes, which limits reliable data in transit when
+    /// `use_window_flow_control` is enabled. This is not the congestion window of an installed
+    /// [`CongestionController`].
+    #[cfg(any(test, feature = "diagnostics"))]
+    pub fn flow_window(&self) -> u32 {
+        self.flow_control.window_bytes(&self.config)
+    }
+
+    /// Forces the flow-control window to `bytes`, rounded up to whole `fragment_size` packets
+    /// and ignoring the configured window bounds, so tests can exercise the send path under a
+    /// specific window without driving acknowledgments. Has no effect on sending unless
+    /// `use_window_flow_control` is enabled.
+    #[cfg(any(test, feature = "diagnostics"))]
+    pub fn set_flow_window(&mut self, bytes: u32) {
+        let window_size = bytes.div_ceil((self.config.fragment_size as u32).max(1));
+        self.flow_control.restore(
+            window_size,
+            self.flow_control.reliable_data_in_transit(),
+            self.flow_control.total_sent(),
+        );
+    }
+
     /// Records reliable data being sent (adds to in-transit counter).
     pub fn record_reliable_data_sent(&mut self, data_size: u32) {
         self.flow_control.record_reliable_data_sent(data_size);
@@ -381,10 +1158,25 @@ impl Peer {
         self.flow_control.record_reliable_data_acked(data_size);
     }
 
-    /// Checks if we can send more data based on window-based flow control.
-    /// Returns true if we have room in the window.
+    /// Checks if we can send more data based on window-based flow control and the congestion
+    /// controller, if any. Returns true if we have room in both windows.
     pub fn can_send_reliable(&self) -> bool {
         self.flow_control.can_send_reliable(&self.config, self.packets_in_flight())
+            && self
+                .congestion_controller
+                .as_ref()
+                .is_none_or(|controller| controller.can_send(self.bytes_in_flight()))
+    }
+
+    /// Returns the congestion controller, if one is configured.
+    pub fn congestion_controller(&self) -> Option<&dyn CongestionController> {
+        self.congestion_controller.as_deref()
+    }
+
+    /// Replaces the congestion controller chosen by `Config::congestion_controller`, e.g. with
+    /// one implemented outside this crate.
+    pub fn set_congestion_controller(&mut self, controller: Box<dyn CongestionController>) {
+        self.congestion_controller = Some(controller);
     }
 
     /// Dynamically adjusts the window size based on network conditions.
@@ -393,6 +1185,51 @@ impl Peer {
         let loss_rate = self.loss_rate();
         let rtt_ms = self.rtt().as_millis() as u32;
         self.flow_control.adjust_window_size(&self.config, loss_rate, rtt_ms);
+        let window_size = self.flow_control.window_size() as f64;
+        let trace_id = self.labels.get(TRACE_ID_LABEL).map(String::as_str);
+        self.window_histogram.observe(window_size, trace_id);
+    }
+
+    /// Enqueues a window probe if the flow-control window has been full for a probe interval.
+    /// The remote answers with a window update, so a lost update cannot stall the sender.
+    pub fn handle_window_probe(&mut self, time: Instant) {
+        let packets_in_flight = self.packets_in_flight();
+        if self.flow_control.should_probe(&self.config, packets_in_flight, time) {
+            self.enqueue_command(ProtocolCommand::WindowProbe);
+        }
+    }
+
+    /// Returns how many more reliable bytes this peer can buffer: the receive window less the
+    /// bytes held for reassembly or waiting for earlier packets on ordered channels.
+    pub fn receive_window_available(&self) -> u32 {
+        let buffered = self.reassembly_bytes
+            + self
+                .channel_states
+                .values()
+                .flat_map(ChannelState::buffered_packets)
+                .map(|(_, data)| data.len())
+                .sum::<usize>();
+        let buffered = u32::try_from(buffered).unwrap_or(u32::MAX);
+        FlowControl::receive_window(&self.config).saturating_sub(buffered)
+    }
+
+    /// Enqueues a window update if the receive window opened from or closed to zero, or moved
+    /// by a quarter of its size since last advertised. Only with `use_window_flow_control`.
+    fn advertise_receive_window(&mut self) {
+        if !self.config.use_window_flow_control {
+            return;
+        }
+        let available = self.receive_window_available();
+        let last = self.advertised_receive_window;
+        let step = (FlowControl::receive_window(&self.config) / 4).max(1);
+        if (available == 0) == (last == 0) && available.abs_diff(last) < step {
+            return;
+        }
+        self.advertised_receive_window = available;
+        self.enqueue_command(ProtocolCommand::WindowUpdate {
+            received: self.reliable_bytes_received,
+            available,
+        });
     }
 
     // ===== Bandwidth Throttling =====
@@ -419,6 +1256,55 @@ impl Peer {
         self.bandwidth_throttle.can_send_within_bandwidth()
     }
 
+    /// Checks if pacing lets a datagram go out at `time`. Always true without pacing.
+    pub fn can_send_paced(&self, time: Instant) -> bool {
+        self.pacer.as_ref().is_none_or(|pacer| pacer.can_send(time))
+    }
+
+    /// Records a datagram of `bytes` sent at `time` against the pacing budget.
+    pub fn record_paced_send(&mut self, bytes: usize, time: Instant) {
+        self.update_pacing_rate(time);
+        if let Some(pacer) = self.pacer.as_mut() {
+            pacer.on_sent(bytes, time);
+        }
+    }
+
+    /// Returns how long after `time` pacing holds back the next datagram (zero without pacing).
+    pub fn pacing_delay(&self, time: Instant) -> Duration {
+        self.time_to_send_next(time).unwrap_or(Duration::ZERO)
+    }
+
+    /// Returns how long after `now` pacing holds back the next datagram, or `None` if it may
+    /// go now (always without pacing).
+    pub fn time_to_send_next(&self, now: Instant) -> Option<Duration> {
+        self.pacer.as_ref().and_then(|pacer| pacer.time_to_send_next(now))
+    }
+
+    /// Returns the pacing rate in bytes per second, if pacing is enabled.
+    pub fn pacing_rate(&self) -> Option<u32> {
+        self.pacer.as_ref().map(Pacer::rate)
+    }
+
+    /// Moves the pacing rate to `pacing_gain` times the congestion window (the flow-control
+    /// window without a controller) per smoothed RTT, capped at the outgoing limit. Only with
+    /// `use_pacing`; otherwise the pacer keeps the outgoing limit as its rate.
+    fn update_pacing_rate(&mut self, time: Instant) {
+        if !self.config.use_pacing {
+            return;
+        }
+        let window = match self.congestion_controller.as_ref() {
+            Some(controller) => controller.congestion_window(),
+            None => self.flow_control.window_bytes(&self.config) as usize,
+        };
+        let mut rate = Pacer::window_rate(window, self.rtt(), self.config.pacing_gain);
+        if self.config.outgoing_bandwidth_limit > 0 {
+            rate = rate.min(self.config.outgoing_bandwidth_limit);
+        }
+        if let Some(pacer) = self.pacer.as_mut() {
+            pacer.set_window_rate(rate, time);
+        }
+    }
+
     /// Returns current bandwidth utilization (0.0 to 1.0+).
     /// Returns 0.0 if bandwidth limiting is disabled.
     pub fn bandwidth_utilization(&self) -> f32 {
@@ -462,7 +1348,125 @@ impl Peer {
     /// Call this periodically (e.g., once per second) to prevent accumulation of stale buffers.
     /// Default timeout is 5 seconds after the first fragment is received.
     pub fn cleanup_stale_fragments(&mut self, time: Instant) {
-        cleanup_stale_fragments(&mut self.command_fragments, time);
+        let freed = cleanup_stale_fragments(&mut self.command_fragments, time);
+        self.release_reassembly(freed);
+        self.advertise_receive_window();
+    }
+
+    /// Evicts the least recently updated incomplete fragment groups while reassembly holds
+    /// more than `max_reassembly_bytes`.
+    fn enforce_reassembly_limit(&mut self) {
+        let limit = self.config.max_reassembly_bytes;
+        if limit == 0 || self.reassembly_bytes <= limit {
+            return;
+        }
+        if let Err(error) = evict_least_recent_fragments(&mut self.command_fragments, limit) {
+            tracing::warn!(labels = ?self.labels, "Dropping fragments: {}", error);
+            let ReassemblyError::BufferFull { freed_bytes, .. } = error;
+            self.release_reassembly(freed_bytes);
+        }
+    }
+
+    // ===== Memory Budget =====
+
+    /// Attaches a shared memory budget, moving bytes already buffered by this peer into it.
+    pub fn set_memory_budget(&mut self, budget: MemoryBudget) {
+        let held = self.buffered_bytes();
+        self.memory_budget.release(held);
+        budget.force_reserve(held);
+        self.memory_budget = budget;
+    }
+
+    /// Returns the memory budget this peer reserves from.
+    pub fn memory_budget(&self) -> &MemoryBudget {
+        &self.memory_budget
+    }
+
+    /// Returns the bytes this peer holds in its send queue and reassembly buffers.
+    pub fn buffered_bytes(&self) -> usize {
+        self.total_waiting_data + self.reassembly_bytes
+    }
+
+    /// Returns the most bytes held in reassembly buffers at once since creation or the
+    /// last [`Peer::reset_reassembly_peak`].
+    pub fn reassembly_peak_bytes(&self) -> usize {
+        self.reassembly_peak_bytes
+    }
+
+    /// Restarts peak tracking from the bytes currently held in reassembly buffers.
+    pub fn reset_reassembly_peak(&mut self) {
+        self.reassembly_peak_bytes = self.reassembly_bytes;
+    }
+
+    /// Reserves fragment bytes for reassembly. Returns false if the memory budget is exhausted.
+    fn reserve_reassembly(&mut self, bytes: usize) -> bool {
+        if !self.memory_budget.try_reserve(bytes) {
+            tracing::warn!(
+                labels = ?self.labels,
+                "Dropping fragment: memory budget exhausted ({} of {} bytes used)",
+                self.memory_budget.used(),
+                self.memory_budget.limit()
+            );
+            return false;
+        }
+        self.reassembly_bytes += bytes;
+        self.reassembly_peak_bytes = self.reassembly_peak_bytes.max(self.reassembly_bytes);
+        true
+    }
+
+    /// Releases fragment bytes that left the reassembly buffers.
+    fn release_reassembly(&mut self, bytes: usize) {
+        self.reassembly_bytes = self.reassembly_bytes.saturating_sub(bytes);
+        self.memory_budget.release(bytes);
+    }
+
+    // ===== Early Data =====
+
+    /// Returns true if application data should be held until the handshake completes.
+    pub fn should_buffer_early_data(&self) -> bool {
+        self.config.early_data_max_bytes > 0
+            && self.config.use_connection_handshake
+            && !self.is_established()
+    }
+
+    /// Returns true if `bytes` more of early data fit within `early_data_max_bytes`.
+    pub fn can_buffer_early_data(&self, bytes: usize) -> bool {
+        self.early_data_bytes + bytes <= self.config.early_data_max_bytes
+    }
+
+    /// Buffers an application packet sent during the handshake.
+    /// Returns false and drops the packet if it would exceed `early_data_max_bytes`; check
+    /// [`Peer::can_buffer_early_data`] first to hold it back instead.
+    pub fn buffer_early_data(&mut self, packet: Packet) -> bool {
+        let size = packet.payload().len();
+        if !self.can_buffer_early_data(size) {
+            tracing::warn!(
+                labels = ?self.labels,
+                "Dropping early data: would exceed early_data_max_bytes limit ({} + {} > {})",
+                self.early_data_bytes,
+                size,
+                self.config.early_data_max_bytes
+            );
+            return false;
+        }
+        self.early_data_bytes += size;
+        self.early_data.push_back(packet);
+        true
+    }
+
+    /// Returns the payload bytes currently buffered as early data.
+    pub fn early_data_bytes(&self) -> usize {
+        self.early_data_bytes
+    }
+
+    /// Takes the buffered early data once the connection is established, in send order.
+    /// Returns nothing while the handshake is still in progress.
+    pub fn take_early_data(&mut self) -> Vec<Packet> {
+        if !self.is_established() {
+            return Vec::new();
+        }
+        self.early_data_bytes = 0;
+        self.early_data.drain(..).collect()
     }
 
     // ===== Command-based API =====
@@ -485,6 +1489,17 @@ impl Peer {
     /// Returns true if the queue should be flushed (reached max size).
     /// Returns false and drops the command if it would exceed max_waiting_data limit.
     pub fn enqueue_command(&mut self, command: ProtocolCommand) -> bool {
+        let compression = if command.is_data() { self.send_compression } else { None };
+        self.enqueue_command_with_compression(command, compression)
+    }
+
+    /// Like [`Peer::enqueue_command`], with a compression algorithm overriding the
+    /// connection's for the datagram carrying the command.
+    fn enqueue_command_with_compression(
+        &mut self,
+        command: ProtocolCommand,
+        compression: Option<CompressionAlgorithm>,
+    ) -> bool {
         let data_size = Self::command_data_size(&command);
 
         // Check if adding this command would exceed the limit (0 = unlimited)
@@ -493,6 +1508,7 @@ impl Peer {
             if new_total > self.config.max_waiting_data {
                 // Drop the command to prevent memory exhaustion
                 tracing::warn!(
+                    labels = ?self.labels,
                     "Dropping command: would exceed max_waiting_data limit ({} + {} > {})",
                     self.total_waiting_data,
                     data_size,
@@ -502,12 +1518,26 @@ impl Peer {
             }
         }
 
+        // Check the budget shared with other peers
+        if !self.memory_budget.try_reserve(data_size) {
+            tracing::warn!(
+                labels = ?self.labels,
+                "Dropping command: memory budget exhausted ({} + {} > {})",
+                self.memory_budget.used(),
+                data_size,
+                self.memory_budget.limit()
+            );
+            return false;
+        }
+
         self.total_waiting_data += data_size;
-        self.command_queue.enqueue(command)
+        self.command_queue.enqueue_with_compression(command, compression)
     }
 
     /// Generates and enqueues an Acknowledge command based on current state.
     /// This should be called after receiving reliable packets to send ACKs back.
+    ///
+    /// With `sack_max_ranges` set, a Sack of the received ranges follows if there are gaps.
     pub fn enqueue_ack_command(&mut self, sent_time: Option<u32>) {
         let ack_command = ProtocolCommand::Acknowledge {
             sequence: self.acknowledge_handler.remote_sequence_num(),
@@ -515,6 +1545,43 @@ impl Peer {
             sent_time,
         };
         self.enqueue_command(ack_command);
+
+        if self.config.sack_max_ranges > 0 {
+            let ranges = self.acknowledge_handler.sack_ranges(self.config.sack_max_ranges as usize);
+            if ranges.len() > 1 {
+                self.enqueue_command(ProtocolCommand::Sack { ranges });
+            }
+        }
+    }
+
+    /// Acknowledges received data now, or once `ack_delay_ms` has elapsed so that further
+    /// data can share the ACK. `immediate` bypasses the delay.
+    pub fn schedule_ack(&mut self, time: Instant, immediate: bool) {
+        if immediate || self.config.ack_delay_ms == 0 {
+            // The ACK covers everything received so far, including any delayed one
+            self.ack_deadline = None;
+            self.enqueue_ack_command(None);
+        } else if self.ack_deadline.is_none() {
+            self.ack_deadline = Some(time + Duration::from_millis(self.config.ack_delay_ms as u64));
+        }
+    }
+
+    /// Enqueues the delayed ACK if its deadline has passed.
+    pub fn handle_delayed_ack(&mut self, time: Instant) {
+        if self.ack_deadline.is_some_and(|deadline| time >= deadline) {
+            self.ack_deadline = None;
+            self.enqueue_ack_command(None);
+        }
+    }
+
+    /// Returns when the pending delayed ACK is due, if any.
+    pub fn ack_deadline(&self) -> Option<Instant> {
+        self.ack_deadline
+    }
+
+    /// Flags the next datagram carrying data as requesting an immediate ACK from the remote.
+    pub fn request_immediate_ack(&mut self) {
+        self.immediate_ack_requested = true;
     }
 
     /// Generates and enqueues a Ping command with the current timestamp.
@@ -530,10 +1597,20 @@ impl Peer {
     /// Drains all pending commands from the queue.
     /// Resets the total_waiting_data counter since commands are being sent.
     pub fn drain_commands(&mut self) -> impl Iterator<Item = ProtocolCommand> + '_ {
+        self.memory_budget.release(self.total_waiting_data);
         self.total_waiting_data = 0; // Reset since we're draining all commands
         self.command_queue.drain()
     }
 
+    /// Like [`Peer::drain_commands`], with each command's compression override.
+    fn drain_commands_with_compression(
+        &mut self,
+    ) -> impl Iterator<Item = (ProtocolCommand, Option<CompressionAlgorithm>)> + '_ {
+        self.memory_budget.release(self.total_waiting_data);
+        self.total_waiting_data = 0;
+        self.command_queue.drain_with_compression()
+    }
+
     /// Returns the number of queued commands.
     pub fn queued_commands_count(&self) -> usize {
         self.command_queue.len()
@@ -543,22 +1620,69 @@ impl Peer {
     pub fn has_queued_commands(&self) -> bool {
         !self.command_queue.is_empty()
     }
-}
 
-impl fmt::Debug for Peer {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}:{}", self.remote_address.ip(), self.remote_address.port())
+    /// Requests that queued data be sent without waiting for `flush_threshold_bytes` to
+    /// accumulate. The request lasts until the queue has been emptied.
+    pub fn request_flush(&mut self) {
+        self.flush_requested = true;
     }
-}
 
-#[cfg(test)]
+    /// Stops sending new application data until [`Peer::resume_sending`]. Data stays queued,
+    /// while ACKs, keepalives, retransmissions and other control commands are still sent so
+    /// the connection stays alive.
+    pub fn pause_sending(&mut self) {
+        self.sending_paused = true;
+    }
+
+    /// Resumes sending application data held by [`Peer::pause_sending`]; it goes out on the
+    /// next flush.
+    pub fn resume_sending(&mut self) {
+        self.sending_paused = false;
+    }
+
+    /// Returns whether sending of application data is paused.
+    pub fn is_sending_paused(&self) -> bool {
+        self.sending_paused
+    }
+
+    /// Returns whether queued commands should be encoded and sent now.
+    ///
+    /// With `flush_threshold_bytes` set, data is held until that many bytes are queued, unless
+    /// a flush was requested or a control command (ACK, ping, handshake, ...) is waiting.
+    pub fn should_flush(&self) -> bool {
+        let threshold = self.config.flush_threshold_bytes;
+        threshold == 0
+            || self.flush_requested
+            || self.total_waiting_data >= threshold
+            || self.command_queue.iter().any(|command| !command.is_data())
+    }
+}
+
+impl Drop for Peer {
+    fn drop(&mut self) {
+        self.memory_budget.release(self.buffered_bytes());
+    }
+}
+
+impl fmt::Debug for Peer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.remote_address.ip(), self.remote_address.port())
+    }
+}
+
+#[cfg(test)]
 mod tests {
-    use std::time::Instant;
+    use std::time::{Duration, Instant};
 
-    use bitfold_core::config::Config;
+    use bitfold_core::{
+        config::{CompressionAlgorithm, Config},
+        error::ErrorKind,
+        memory_budget::MemoryBudget,
+    };
+    use bitfold_protocol::{command::ProtocolCommand, command_codec::CommandDecoder};
 
     use super::Peer;
-    use crate::peer_state::PeerState;
+    use crate::{close_reason::CloseReason, peer_state::PeerState};
 
     fn get_fake_addr() -> std::net::SocketAddr {
         "127.0.0.1:0".parse().unwrap()
@@ -653,7 +1777,7 @@ mod tests {
         assert!(peer.pmtu.has_outstanding_probe());
 
         // Simulate successful reply (this would update low bound)
-        if let Some((size, token, _)) = peer.pmtu.outstanding_probe() {
+        if let Some((size, token, _, _)) = peer.pmtu.outstanding_probe() {
             let reply = bitfold_protocol::command::ProtocolCommand::PMTUReply { size, token };
             let _ = peer.process_command(&reply, time).unwrap();
 
@@ -665,6 +1789,7 @@ mod tests {
     #[test]
     fn test_pmtu_discovery_timeout() {
         let mut config = Config::default();
+        config.pmtu_probe_retries = 0;
         config.use_pmtu_discovery = true;
         config.pmtu_min = 576;
         config.pmtu_max = 1400;
@@ -692,6 +1817,66 @@ mod tests {
         assert!(peer.pmtu.high_bound() < high_before);
     }
 
+    #[test]
+    fn test_pmtu_probe_retry_backs_off_rto() {
+        let mut config = Config::default();
+        config.pmtu_probe_retries = 2;
+        config.pmtu_interval_ms = 100;
+        let start = Instant::now();
+        let mut peer = Peer::new(get_fake_addr(), &config, start);
+        peer.acknowledge_handler.congestion_mut().update_rtt(std::time::Duration::from_millis(300));
+        let rto = peer.rto();
+        let tick = rto + std::time::Duration::from_millis(1);
+
+        let mut time = start + std::time::Duration::from_millis(150);
+        peer.handle_pmtu(time);
+        assert!(peer.pmtu.has_outstanding_probe());
+
+        // The probe timer expires and the probe is re-sent with a backed-off RTO
+        time += tick;
+        peer.handle_pmtu(time);
+        assert_eq!(peer.pmtu_stats().retries, 1);
+        assert_eq!(peer.rto(), rto * 2);
+
+        // A tick before the backed-off timer expires re-sends nothing
+        time += tick;
+        peer.handle_pmtu(time);
+        assert_eq!(peer.pmtu_stats().retries, 1);
+        assert_eq!(peer.rto(), rto * 2);
+    }
+
+    #[test]
+    fn test_pmtu_probe_deferred_during_congestion_recovery() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        let start = Instant::now();
+        let mut peer = Peer::new(get_fake_addr(), &config, start);
+
+        // 10% loss backs the throttle off
+        for _ in 0..100 {
+            peer.acknowledge_handler.congestion_mut().record_sent();
+        }
+        for _ in 0..10 {
+            peer.acknowledge_handler.congestion_mut().record_loss();
+        }
+        let mut time = start + std::time::Duration::from_secs(2);
+        assert!(peer.update_throttle(time));
+        assert!(peer.acknowledge_handler.congestion().is_recovering());
+
+        peer.handle_pmtu(time);
+        assert!(!peer.has_queued_commands());
+        assert!(!peer.pmtu.has_outstanding_probe());
+
+        // Loss-free intervals end the recovery and probing resumes
+        while peer.acknowledge_handler.congestion().is_recovering() {
+            peer.acknowledge_handler.congestion_mut().record_sent();
+            time += std::time::Duration::from_secs(2);
+            peer.update_throttle(time);
+        }
+        peer.handle_pmtu(time);
+        assert!(peer.pmtu.has_outstanding_probe());
+    }
+
     #[test]
     fn test_pmtu_discovery_enabled_by_default() {
         let config = Config::default();
@@ -710,4 +1895,1139 @@ mod tests {
         peer.handle_pmtu(probe_time);
         assert!(peer.has_queued_commands()); // Should have queued a PMTUProbe command
     }
+
+    #[test]
+    fn test_labels_are_stored_and_retrievable() {
+        let mut peer = Peer::new(get_fake_addr(), &Config::default(), Instant::now());
+        assert!(peer.labels().is_empty());
+
+        assert_eq!(peer.set_label("user_id", "42"), None);
+        assert_eq!(peer.set_label("tenant", "acme"), None);
+        assert_eq!(peer.labels().get("user_id").map(String::as_str), Some("42"));
+        assert_eq!(peer.labels().get("tenant").map(String::as_str), Some("acme"));
+
+        // Overwriting returns the previous value
+        assert_eq!(peer.set_label("user_id", "43"), Some("42".to_string()));
+        assert_eq!(peer.remove_label("tenant"), Some("acme".to_string()));
+        assert_eq!(peer.labels().len(), 1);
+    }
+
+    #[test]
+    fn test_metrics_snapshot_includes_labels() {
+        let mut peer = Peer::new(get_fake_addr(), &Config::default(), Instant::now());
+        peer.set_label("tenant", "acme");
+        peer.statistics.packets_sent = 10;
+
+        let snapshot = peer.metrics_snapshot();
+        assert_eq!(snapshot.labels.get("tenant").map(String::as_str), Some("acme"));
+        assert_eq!(snapshot.statistics.packets_sent, 10);
+        assert_eq!(snapshot.rtt, peer.rtt());
+    }
+
+    #[test]
+    fn test_shutdown_returns_state_and_starts_close_handshake() {
+        let time = Instant::now();
+        let mut peer = Peer::new(get_fake_addr(), &Config::default(), time);
+        peer.state = PeerState::Connected;
+        peer.pmtu.set_fragment_size(1200);
+        peer.acknowledge_handler.congestion_mut().update_rtt(std::time::Duration::from_millis(300));
+        peer.acknowledge_handler.set_local_sequence_num(42);
+
+        let state = peer.shutdown(time);
+        assert_eq!(state.pmtu.fragment_size, 1200);
+        assert_eq!(state.congestion.rtt, peer.rtt());
+        assert_eq!(state.next_sequence, 42);
+
+        // Close handshake was attempted
+        assert_eq!(peer.state(), PeerState::Disconnecting);
+        assert!(peer
+            .drain_commands()
+            .any(|command| matches!(command, ProtocolCommand::Disconnect { .. })));
+
+        // The handshake is abandoned after the disconnect timeout
+        assert!(!peer.is_shutdown_timed_out(time));
+        assert!(peer.is_shutdown_timed_out(time + peer.config().disconnect_timeout));
+
+        // A new peer warm-starts from the persisted state
+        let mut resumed = Peer::new(get_fake_addr(), &Config::default(), time);
+        resumed.restore_state(&state, time);
+        assert_eq!(resumed.connection_state(), state);
+        assert_eq!(resumed.current_fragment_size(), 1200);
+    }
+
+    #[test]
+    fn test_negotiated_params_reflect_handshake() {
+        let time = Instant::now();
+        let mut client_config = Config::default();
+        client_config.channel_count = 8;
+        client_config.use_window_flow_control = true;
+        client_config.initial_window_size = 512;
+        let mut server_config = Config::default();
+        server_config.channel_count = 2;
+        server_config.use_window_flow_control = true;
+        server_config.initial_window_size = 128;
+
+        let mut client = Peer::new(get_fake_addr(), &client_config, time);
+        let mut server = Peer::new(get_fake_addr(), &server_config, time);
+
+        // CONNECT -> VERIFY_CONNECT -> implicit ACK
+        client.record_send();
+        assert!(client.negotiated_params().is_none());
+        for command in client.drain_commands().collect::<Vec<_>>() {
+            server.process_command(&command, time).unwrap();
+        }
+        for command in server.drain_commands().collect::<Vec<_>>() {
+            client.process_command(&command, time).unwrap();
+        }
+        assert!(server.negotiated_params().is_none());
+        server.record_recv();
+
+        let client_params = *client.negotiated_params().unwrap();
+        let server_params = *server.negotiated_params().unwrap();
+        assert_eq!(client_params, server_params);
+
+        // Negotiated down from what the client configured
+        assert_eq!(client_params.channels, 2);
+        assert_eq!(client_params.window_size, 128);
+        assert_eq!(client_params.protocol_version, 2);
+        assert_eq!(client_params.mtu, 1400);
+        assert_eq!(client_params.initial_fragment_size, client.current_fragment_size());
+
+        // Recorded once: later changes do not rewrite the record
+        client.set_window_size(256);
+        client.record_send();
+        assert_eq!(*client.negotiated_params().unwrap(), client_params);
+    }
+
+    /// Runs the handshake between a fresh client and server.
+    fn handshake(client: &mut Peer, server: &mut Peer, time: Instant) {
+        client.record_send();
+        for command in client.drain_commands().collect::<Vec<_>>() {
+            server.process_command(&command, time).unwrap();
+        }
+        for command in server.drain_commands().collect::<Vec<_>>() {
+            client.process_command(&command, time).unwrap();
+        }
+        server.record_recv();
+    }
+
+    #[test]
+    fn test_disconnect_coalesces_final_ack() {
+        for ack_delay_ms in [0, 50] {
+            let mut config = Config::default();
+            config.ack_delay_ms = ack_delay_ms;
+            let time = Instant::now();
+            let mut client = Peer::new(get_fake_addr(), &config, time);
+            let mut server = Peer::new(get_fake_addr(), &config, time);
+            handshake(&mut client, &mut server, time);
+
+            // Data arrives just before the server closes
+            client.enqueue_command(ProtocolCommand::SendReliable {
+                channel_id: 0,
+                sequence: 0,
+                ordered: true,
+                data: vec![1, 2, 3].into(),
+            });
+            let data = client.encode_queued_commands().unwrap();
+            server.process_command_packet(&data, time).unwrap();
+            server.disconnect();
+
+            let datagram = server.encode_queued_commands_bounded(1400).unwrap().unwrap();
+            assert!(!server.has_queued_commands());
+            let payload = CommandDecoder::validate_and_strip_checksum(&datagram).unwrap();
+            let decompressed = CommandDecoder::decompress(payload).unwrap();
+            let commands = CommandDecoder::decode_packet(&decompressed).unwrap().commands;
+            assert!(matches!(commands[..], [
+                ProtocolCommand::Acknowledge { sequence: 0, .. },
+                ProtocolCommand::Disconnect { reason: 0 }
+            ]));
+        }
+    }
+
+    #[test]
+    fn test_reorder_extent_tracks_furthest_late_arrival() {
+        let time = Instant::now();
+        let mut peer = Peer::new(get_fake_addr(), &Config::default(), time);
+        let receive = |peer: &mut Peer, sequence: u16| {
+            let command = ProtocolCommand::SendReliable {
+                channel_id: 0,
+                sequence,
+                ordered: false,
+                data: vec![1].into(),
+            };
+            peer.process_command(&command, time).unwrap();
+        };
+
+        // In order: nothing reordered
+        for sequence in 0..3 {
+            receive(&mut peer, sequence);
+        }
+        assert_eq!(peer.reorder_extent(), 0);
+
+        // 4 overtakes 3 by one
+        receive(&mut peer, 4);
+        receive(&mut peer, 3);
+        assert_eq!(peer.reorder_extent(), 1);
+
+        // 6 arrives three behind 9; a smaller gap later does not lower the extent
+        receive(&mut peer, 9);
+        receive(&mut peer, 6);
+        receive(&mut peer, 8);
+        assert_eq!(peer.reorder_extent(), 3);
+
+        // Measured across the sequence wrap
+        for sequence in [30000, 60000, u16::MAX, 3] {
+            receive(&mut peer, sequence);
+        }
+        assert_eq!(peer.reorder_extent(), 3);
+        receive(&mut peer, 65531);
+        assert_eq!(peer.reorder_extent(), 8);
+    }
+
+    #[test]
+    fn test_health_reports_healthy_connection() {
+        let time = Instant::now();
+        let config = Config::default();
+        let mut client = Peer::new(get_fake_addr(), &config, time);
+        let mut server = Peer::new(get_fake_addr(), &config, time);
+        handshake(&mut client, &mut server, time);
+        let snapshot =
+            crate::pmtu_discovery::PmtuSnapshot { fragment_size: 1300, low: 1300, high: 1320 };
+        client.pmtu.restore(&snapshot, time);
+
+        let health = client.health(time);
+        assert!(health.loss_ok && health.rtt_stable && health.pmtu_converged && health.alive);
+        assert!(client.is_healthy(time));
+
+        // The search has not converged on the server
+        assert!(!server.health(time).pmtu_converged);
+        assert!(!server.is_healthy(time));
+    }
+
+    #[test]
+    fn test_health_reports_degraded_conditions() {
+        let time = Instant::now();
+        let mut config = Config::default();
+        config.use_pmtu_discovery = false;
+        let mut client = Peer::new(get_fake_addr(), &config, time);
+        let mut server = Peer::new(get_fake_addr(), &config, time);
+        handshake(&mut client, &mut server, time);
+        assert!(client.is_healthy(time));
+
+        // 20% loss
+        let congestion = client.acknowledge_handler.congestion_mut();
+        for sent in 0..10 {
+            congestion.record_sent();
+            if sent % 5 == 0 {
+                congestion.record_loss();
+            }
+        }
+        // RTT swings well past `rtt_max_value`
+        for sample in [10, 900, 10, 900] {
+            congestion.update_rtt(std::time::Duration::from_millis(sample));
+        }
+
+        let later = time + config.idle_connection_timeout / 2;
+        let health = client.health(later);
+        assert_eq!(health.loss_rate, 0.2);
+        assert!(!health.loss_ok);
+        assert!(!health.rtt_stable);
+        assert!(health.pmtu_converged);
+        assert!(!health.alive);
+        assert!(!health.is_healthy());
+    }
+
+    #[test]
+    fn test_max_receive_datagram_size_tracks_negotiated_sizes() {
+        let time = Instant::now();
+        let config = Config::default();
+        let mut client = Peer::new(get_fake_addr(), &config, time);
+        let mut server = Peer::new(get_fake_addr(), &config, time);
+        handshake(&mut client, &mut server, time);
+        assert_eq!(client.max_receive_datagram_size(), 1400);
+
+        // A larger discovered path MTU and a transform's overhead both raise it
+        client.pmtu.set_fragment_size(1450);
+        assert_eq!(client.max_receive_datagram_size(), 1450);
+        client.set_datagram_overhead(16);
+        assert_eq!(client.max_receive_datagram_size(), 1466);
+    }
+
+    #[test]
+    fn test_idle_timeout_negotiates_to_smaller_side() {
+        let time = Instant::now();
+        for (client_secs, server_secs) in [(30, 10), (10, 30)] {
+            let mut client_config = Config::default();
+            client_config.idle_connection_timeout = std::time::Duration::from_secs(client_secs);
+            let mut server_config = Config::default();
+            server_config.idle_connection_timeout = std::time::Duration::from_secs(server_secs);
+            let mut client = Peer::new(get_fake_addr(), &client_config, time);
+            let mut server = Peer::new(get_fake_addr(), &server_config, time);
+            handshake(&mut client, &mut server, time);
+
+            let agreed = std::time::Duration::from_secs(10);
+            assert_eq!(client.idle_timeout(), agreed);
+            assert_eq!(server.idle_timeout(), agreed);
+            assert_eq!(client.negotiated_params().unwrap().idle_timeout, agreed);
+            assert_eq!(server.negotiated_params().unwrap().idle_timeout, agreed);
+        }
+    }
+
+    #[test]
+    fn test_zero_idle_timeout_is_no_preference() {
+        let time = Instant::now();
+        let config = Config::default();
+        let mut server = Peer::new(get_fake_addr(), &config, time);
+        let connect = ProtocolCommand::Connect {
+            channels: 1,
+            mtu: super::HANDSHAKE_MTU,
+            protocol_version: super::HANDSHAKE_PROTOCOL_VERSION,
+            outgoing_session_id: 7,
+            connect_id: 1,
+            idle_timeout_ms: 0,
+        };
+        server.process_command(&connect, time).unwrap();
+
+        assert_eq!(server.idle_timeout(), config.idle_connection_timeout);
+        let verify: Vec<_> = server.drain_commands().collect();
+        let [ProtocolCommand::VerifyConnect { idle_timeout_ms, .. }] = verify[..] else {
+            panic!("expected a VerifyConnect, got {verify:?}");
+        };
+        assert_eq!(idle_timeout_ms as u128, config.idle_connection_timeout.as_millis());
+    }
+
+    #[test]
+    fn test_close_reason_distinguishes_local_and_remote_close() {
+        let time = Instant::now();
+        let config = Config::default();
+        let mut client = Peer::new(get_fake_addr(), &config, time);
+        let mut server = Peer::new(get_fake_addr(), &config, time);
+        handshake(&mut client, &mut server, time);
+        assert_eq!(client.close_reason(), None);
+
+        client.disconnect_with_reason(42);
+        assert_eq!(client.close_reason(), Some(CloseReason::LocalClose { code: 42 }));
+        for command in client.drain_commands().collect::<Vec<_>>() {
+            server.process_command(&command, time).unwrap();
+        }
+        assert_eq!(server.state(), PeerState::Zombie);
+        assert_eq!(server.close_reason(), Some(CloseReason::RemoteClose { code: 42 }));
+
+        // A later cause does not overwrite the first
+        client.set_close_reason(CloseReason::IdleTimeout);
+        assert_eq!(client.close_reason(), Some(CloseReason::LocalClose { code: 42 }));
+    }
+
+    #[test]
+    fn test_close_reason_reset_on_new_connect() {
+        let time = Instant::now();
+        let config = Config::default();
+        let mut client = Peer::new(get_fake_addr(), &config, time);
+        let mut server = Peer::new(get_fake_addr(), &config, time);
+        handshake(&mut client, &mut server, time);
+
+        // The client restarts and connects again from the same address
+        let mut restarted = Peer::new(get_fake_addr(), &config, time);
+        restarted.record_send();
+        for command in restarted.drain_commands().collect::<Vec<_>>() {
+            server.process_command(&command, time).unwrap();
+        }
+        assert_eq!(server.state(), PeerState::Connected);
+
+        // The server challenges the connection; the restarted client does not answer
+        let mut time = time;
+        while server.state() == PeerState::Connected {
+            for command in server.drain_commands().collect::<Vec<_>>() {
+                assert!(matches!(command, ProtocolCommand::PathChallenge { .. }));
+                restarted.process_command(&command, time).unwrap();
+            }
+            assert!(!restarted.has_queued_commands());
+            time += server.rto();
+            server.handle_restart_check(time);
+        }
+        assert_eq!(server.state(), PeerState::Zombie);
+        assert_eq!(server.close_reason(), Some(CloseReason::Reset));
+    }
+
+    #[test]
+    fn test_spoofed_connect_does_not_reset() {
+        let time = Instant::now();
+        let config = Config::default();
+        let mut client = Peer::new(get_fake_addr(), &config, time);
+        let mut server = Peer::new(get_fake_addr(), &config, time);
+        handshake(&mut client, &mut server, time);
+
+        let spoofed = ProtocolCommand::Connect {
+            channels: 1,
+            mtu: super::HANDSHAKE_MTU,
+            protocol_version: super::HANDSHAKE_PROTOCOL_VERSION,
+            outgoing_session_id: 7,
+            connect_id: client.connect_id.wrapping_add(1),
+            idle_timeout_ms: 5000,
+        };
+        server.process_command(&spoofed, time).unwrap();
+        let challenge: Vec<_> = server.drain_commands().collect();
+        assert!(matches!(challenge[..], [ProtocolCommand::PathChallenge { .. }]));
+
+        // Repeating the Connect does not renew the check
+        server.process_command(&spoofed, time).unwrap();
+        assert!(!server.has_queued_commands());
+
+        // The live client answers, so the connection survives
+        client.process_command(&challenge[0], time).unwrap();
+        for command in client.drain_commands().collect::<Vec<_>>() {
+            server.process_command(&command, time).unwrap();
+        }
+        let later = time + server.rto() * 10;
+        server.handle_restart_check(later);
+        assert_eq!(server.state(), PeerState::Connected);
+        assert_eq!(server.close_reason(), None);
+        assert!(!server.has_queued_commands());
+    }
+
+    #[test]
+    fn test_close_reason_protocol_error_on_session_mismatch() {
+        let time = Instant::now();
+        let mut client = Peer::new(get_fake_addr(), &Config::default(), time);
+        client.record_send();
+        let verify = ProtocolCommand::VerifyConnect {
+            peer_id: 1,
+            channels: 1,
+            mtu: 1400,
+            incoming_session_id: client.outgoing_session_id.wrapping_add(1),
+            outgoing_session_id: 7,
+            window_size: 512,
+            idle_timeout_ms: 5000,
+        };
+        assert!(client.process_command(&verify, time).is_err());
+        assert_eq!(client.state(), PeerState::Zombie);
+        assert_eq!(client.close_reason(), Some(CloseReason::ProtocolError));
+    }
+
+    #[test]
+    fn test_pmtu_convergence_recorded_in_histogram() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        let start = Instant::now();
+        let mut peer = Peer::new(get_fake_addr(), &config, start);
+        peer.set_label("trace_id", "4bf92f3577b34da6");
+        peer.set_label("tenant", "acme");
+
+        // Every probe gets through; the search settles after four probes, one per second
+        for second in 1..=6 {
+            let time = start + std::time::Duration::from_secs(second);
+            peer.handle_pmtu(time);
+            if let Some((size, token, _, _)) = peer.pmtu.outstanding_probe() {
+                peer.process_command(&ProtocolCommand::PMTUReply { size, token }, time).unwrap();
+            }
+        }
+
+        let snapshot = peer.metrics_snapshot();
+        let histogram = &snapshot.pmtu_convergence;
+        assert_eq!(histogram.count(), 1);
+        assert_eq!(histogram.sum(), 5.0);
+        assert_eq!(histogram.counts()[histogram.bucket_index(5.0)], 1);
+        assert_eq!(histogram.bounds()[histogram.bucket_index(5.0)], 5.0);
+
+        let text = snapshot.to_openmetrics();
+        assert!(text.contains(
+            "bitfold_pmtu_convergence_seconds_bucket{tenant=\"acme\",le=\"5\"} 1 \
+             # {trace_id=\"4bf92f3577b34da6\"} 5\n"
+        ));
+        assert!(
+            text.contains("bitfold_pmtu_convergence_seconds_bucket{tenant=\"acme\",le=\"1\"} 0\n")
+        );
+        assert!(text.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn test_usable_payload_and_mtu_are_inverses() {
+        let configs = [Config::default(), Config {
+            use_checksums: false,
+            compression: CompressionAlgorithm::Lz4,
+            ..Config::default()
+        }];
+        for config in &configs {
+            let peer = Peer::new(get_fake_addr(), config, Instant::now());
+            let overhead = peer.mtu_for_payload(0);
+
+            for mtu in overhead..=u16::MAX {
+                assert_eq!(peer.mtu_for_payload(peer.usable_payload_for_mtu(mtu)), mtu);
+            }
+            for payload in 0..=(u16::MAX - overhead) {
+                assert_eq!(peer.usable_payload_for_mtu(peer.mtu_for_payload(payload)), payload);
+            }
+        }
+    }
+
+    #[test]
+    fn test_usable_payload_fits_single_datagram() {
+        let mut peer = Peer::new(get_fake_addr(), &Config::default(), Instant::now());
+        let mtu = peer.current_fragment_size();
+        let payload = peer.usable_payload_for_mtu(mtu) as usize;
+
+        // Exactly fills one datagram without fragmenting
+        peer.enqueue_reliable_data(0, vec![0u8; payload].into(), true);
+        assert_eq!(peer.queued_commands_count(), 1);
+        let encoded = peer.encode_queued_commands_bounded(mtu as usize).unwrap().unwrap();
+        assert_eq!(encoded.len(), mtu as usize);
+
+        // One more byte needs fragmenting
+        peer.enqueue_reliable_data(0, vec![0u8; payload + 1].into(), true);
+        assert!(peer.queued_commands_count() > 1);
+    }
+
+    #[test]
+    fn test_effective_send_mtu_tracks_overhead_stack() {
+        let compressions =
+            [CompressionAlgorithm::None, CompressionAlgorithm::Zlib, CompressionAlgorithm::Lz4];
+        for compression in compressions {
+            for use_checksums in [false, true] {
+                let config = Config { compression, use_checksums, ..Config::default() };
+                let mut peer = Peer::new(get_fake_addr(), &config, Instant::now());
+                let mtu = peer.current_fragment_size();
+
+                // Header, compression marker (+4 for LZ4), checksum, length prefix, data header
+                let marker = if compression == CompressionAlgorithm::Lz4 { 5 } else { 1 };
+                let checksum = if use_checksums { 4 } else { 0 };
+                let expected = mtu - (1 + marker + checksum + 2 + 7);
+                assert_eq!(
+                    peer.effective_send_mtu(),
+                    expected,
+                    "{:?}/{}",
+                    compression,
+                    use_checksums
+                );
+
+                // The effective size goes out unfragmented within the datagram cap
+                let payload = peer.effective_send_mtu() as usize;
+                peer.enqueue_reliable_data(0, vec![0u8; payload].into(), true);
+                assert_eq!(peer.queued_commands_count(), 1);
+                let encoded = peer.encode_queued_commands_bounded(mtu as usize).unwrap().unwrap();
+                assert!(encoded.len() <= mtu as usize);
+            }
+        }
+
+        // PMTU changes are reflected immediately
+        let mut peer = Peer::new(get_fake_addr(), &Config::default(), Instant::now());
+        let before = peer.effective_send_mtu();
+        peer.pmtu.set_fragment_size(peer.current_fragment_size() - 100);
+        assert_eq!(peer.effective_send_mtu(), before - 100);
+    }
+
+    #[test]
+    fn test_mtu_changes_yield_new_effective_mtu() {
+        use std::{
+            future::Future,
+            sync::{
+                atomic::{AtomicBool, Ordering},
+                Arc,
+            },
+            task::{Context, Poll, Wake, Waker},
+        };
+
+        struct WakeFlag(AtomicBool);
+        impl Wake for WakeFlag {
+            fn wake(self: Arc<Self>) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let mut config = Config::default();
+        config.pmtu_blackhole_loss_threshold = 1;
+        let time = Instant::now();
+        let mut peer = Peer::new(get_fake_addr(), &config, time);
+        let mut changes = peer.mtu_changes();
+        let dropped = peer.mtu_changes();
+        drop(dropped);
+
+        // Nothing is reported until the MTU moves
+        let flag = Arc::new(WakeFlag(AtomicBool::new(false)));
+        let waker = Waker::from(Arc::clone(&flag));
+        let mut cx = Context::from_waker(&waker);
+        {
+            let mut next = std::pin::pin!(changes.recv());
+            peer.handle_pmtu(time);
+            assert!(next.as_mut().poll(&mut cx).is_pending());
+
+            // The awaiting task is woken with the new value
+            assert!(peer.report_loss_burst(1, time));
+            assert!(flag.0.load(Ordering::SeqCst));
+            assert_eq!(next.poll(&mut cx), Poll::Ready(Some(peer.effective_send_mtu())));
+        }
+        assert_eq!(peer.effective_send_mtu(), peer.usable_payload_for_mtu(config.pmtu_safe_floor));
+        assert_eq!(peer.mtu_subscribers.len(), 1);
+
+        // A successful probe raises it again
+        let later = time + std::time::Duration::from_millis(config.pmtu_interval_ms as u64);
+        peer.handle_pmtu(later);
+        let (size, token, _, _) = peer.pmtu.outstanding_probe().unwrap();
+        peer.process_command(&ProtocolCommand::PMTUReply { size, token }, later).unwrap();
+        assert_eq!(changes.try_recv(), Some(peer.usable_payload_for_mtu(size)));
+        assert_eq!(changes.try_recv(), None);
+
+        // Dropping the peer ends the stream
+        drop(peer);
+        assert_eq!(std::pin::pin!(changes.recv()).poll(&mut cx), Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_loss_bursts_within_cooldown_reset_once() {
+        let mut config = Config::default();
+        config.pmtu_blackhole_loss_threshold = 3;
+        config.pmtu_blackhole_cooldown_ms = 1000;
+        let time = Instant::now();
+        let mut peer = Peer::new(get_fake_addr(), &config, time);
+        peer.pmtu.set_fragment_size(1400);
+
+        // Below the threshold: ordinary loss
+        assert!(!peer.report_loss_burst(2, time));
+        assert_eq!(peer.current_fragment_size(), 1400);
+
+        let mut resets = 0;
+        for offset_ms in [0, 300] {
+            peer.pmtu.set_fragment_size(1400);
+            resets += peer.report_loss_burst(5, time + std::time::Duration::from_millis(offset_ms))
+                as u32;
+        }
+        assert_eq!(resets, 1);
+        assert_eq!(peer.current_fragment_size(), 1400);
+
+        assert!(peer.report_loss_burst(5, time + std::time::Duration::from_millis(1000)));
+        assert_eq!(peer.current_fragment_size(), config.pmtu_safe_floor);
+    }
+
+    #[test]
+    fn test_detected_loss_burst_resets_fragment_size() {
+        use bitfold_protocol::packet::{OrderingGuarantee, PacketType};
+
+        let mut config = Config::default();
+        config.pmtu_blackhole_loss_threshold = 3;
+        let start = Instant::now();
+        let mut peer = Peer::new(get_fake_addr(), &config, start);
+        peer.pmtu.set_fragment_size(1400);
+        for _ in 0..40 {
+            peer.acknowledge_handler.process_outgoing(
+                PacketType::Packet,
+                &[0u8; 100],
+                OrderingGuarantee::None,
+                None,
+                start,
+            );
+        }
+
+        // Acknowledging only the last packet leaves those far behind it lost at once
+        let ack = ProtocolCommand::Acknowledge { sequence: 39, received_mask: 0, sent_time: None };
+        peer.process_command(&ack, start + std::time::Duration::from_millis(20)).unwrap();
+        assert!(peer.gather_dropped_packets(start).len() >= 3);
+        assert_eq!(peer.current_fragment_size(), config.pmtu_safe_floor);
+    }
+
+    #[test]
+    fn test_datagram_overhead_reserved_for_transforms() {
+        let mut config = Config::default();
+        config.pmtu_probe_on_start = true;
+        let time = Instant::now();
+        let mut peer = Peer::new(get_fake_addr(), &config, time);
+        let mtu = peer.current_fragment_size();
+        let payload = peer.effective_send_mtu();
+
+        peer.set_datagram_overhead(16);
+        assert_eq!(peer.datagram_cap(), mtu as usize - 16);
+        assert_eq!(peer.effective_send_mtu(), payload - 16);
+
+        // Data is sized so the transformed datagram still fits the fragment size
+        peer.enqueue_reliable_data(0, vec![0u8; payload as usize].into(), true);
+        while let Some(bytes) = peer.encode_queued_commands_bounded(peer.datagram_cap()).unwrap() {
+            assert!(bytes.len() + 16 <= mtu as usize);
+        }
+
+        // PMTU probes advertise their size on the wire, after the transform
+        peer.handle_pmtu(time);
+        let (probe_size, _, _, _) = peer.pmtu.outstanding_probe().unwrap();
+        let probe = peer.encode_queued_commands_bounded(peer.datagram_cap()).unwrap().unwrap();
+        assert_eq!(probe.len() + 16, probe_size as usize);
+    }
+
+    #[test]
+    fn test_ttl_closes_connection_gracefully() {
+        let time = Instant::now();
+        let mut peer = Peer::new(get_fake_addr(), &Config::default(), time);
+        peer.state = PeerState::Connected;
+        let deadline = time + std::time::Duration::from_secs(10);
+        peer.set_ttl(deadline);
+
+        // Stays open before the deadline
+        assert!(peer.handle_ttl(time + std::time::Duration::from_secs(9)).is_ok());
+        assert_eq!(peer.state(), PeerState::Connected);
+        assert!(!peer.has_queued_commands());
+
+        // Closes gracefully once it passes
+        assert!(matches!(peer.handle_ttl(deadline), Err(ErrorKind::TtlExpired)));
+        assert_eq!(peer.state(), PeerState::Disconnecting);
+        assert!(peer
+            .drain_commands()
+            .any(|command| matches!(command, ProtocolCommand::Disconnect { .. })));
+
+        // Expiry is reported once
+        assert!(peer.handle_ttl(deadline).is_ok());
+        assert_eq!(peer.ttl(), None);
+    }
+
+    fn poll_ping(
+        future: std::pin::Pin<&mut crate::PingFuture>,
+    ) -> std::task::Poll<bitfold_core::error::Result<std::time::Duration>> {
+        use std::future::Future;
+        future.poll(&mut std::task::Context::from_waker(std::task::Waker::noop()))
+    }
+
+    #[test]
+    fn test_ping_resolves_with_round_trip_time() {
+        let time = Instant::now();
+        let mut client = Peer::new(get_fake_addr(), &Config::default(), time);
+        let mut server = Peer::new(get_fake_addr(), &Config::default(), time);
+        let mut first = std::pin::pin!(client.ping(time));
+        let second = std::pin::pin!(client.ping(time + std::time::Duration::from_millis(5)));
+        assert!(poll_ping(first.as_mut()).is_pending());
+        assert_eq!(client.pending_pings(), 2);
+
+        let arrival = time + std::time::Duration::from_millis(15);
+        for command in client.drain_commands().collect::<Vec<_>>() {
+            server.process_command(&command, arrival).unwrap();
+        }
+        // Answer the pongs in reverse order; each still resolves its own ping
+        let mut pongs: Vec<_> = server.drain_commands().collect();
+        assert!(pongs.iter().all(|command| matches!(command, ProtocolCommand::Pong { .. })));
+        pongs.reverse();
+        let answered = time + std::time::Duration::from_millis(30);
+        for command in &pongs {
+            client.process_command(command, answered).unwrap();
+        }
+
+        assert!(matches!(
+            poll_ping(first),
+            std::task::Poll::Ready(Ok(rtt)) if rtt == std::time::Duration::from_millis(30)
+        ));
+        assert!(matches!(
+            poll_ping(second),
+            std::task::Poll::Ready(Ok(rtt)) if rtt == std::time::Duration::from_millis(25)
+        ));
+        assert_eq!(client.pending_pings(), 0);
+    }
+
+    #[test]
+    fn test_lost_ping_times_out() {
+        let config = Config::default();
+        let time = Instant::now();
+        let mut client = Peer::new(get_fake_addr(), &config, time);
+        let mut future = std::pin::pin!(client.ping(time));
+        client.drain_commands().for_each(drop);
+
+        client.handle_ping_timeouts(time + config.ping_timeout / 2);
+        assert!(poll_ping(future.as_mut()).is_pending());
+
+        client.handle_ping_timeouts(time + config.ping_timeout);
+        assert!(matches!(poll_ping(future), std::task::Poll::Ready(Err(ErrorKind::PingTimeout))));
+        assert_eq!(client.pending_pings(), 0);
+
+        // A late pong is ignored like a keepalive pong
+        assert!(client
+            .process_command(&ProtocolCommand::Pong { timestamp: 1 << 31 }, time)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_timers_list_armed_timers() {
+        use std::time::Duration;
+
+        use crate::TimerKind;
+
+        let mut config = Config::default();
+        config.use_pmtu_discovery = false;
+        config.ack_delay_ms = 20;
+        config.heartbeat_interval = Some(Duration::from_secs(1));
+        config.idle_connection_timeout = Duration::from_secs(5);
+        config.ping_timeout = Duration::from_secs(3);
+        let time = Instant::now();
+        let mut peer = Peer::new(get_fake_addr(), &config, time);
+        // Only the idle timer runs before the connection is established
+        assert_eq!(peer.timers(), vec![(TimerKind::Idle, time + Duration::from_secs(5))]);
+
+        peer.state = PeerState::Connected;
+        peer.schedule_ack(time, false);
+        peer.set_ttl(time + Duration::from_secs(10));
+        let _pong = peer.ping(time + Duration::from_millis(100));
+
+        assert_eq!(peer.timers(), vec![
+            (TimerKind::AckDelay, time + Duration::from_millis(20)),
+            (TimerKind::Keepalive, time + Duration::from_secs(1)),
+            (TimerKind::PingTimeout, time + Duration::from_millis(3100)),
+            (TimerKind::Idle, time + Duration::from_secs(5)),
+            (TimerKind::Ttl, time + Duration::from_secs(10)),
+        ]);
+
+        // Hearing from the remote pushes back the idle and keepalive timers
+        peer.last_heard = time + Duration::from_millis(500);
+        let timers = peer.timers();
+        assert!(timers.contains(&(TimerKind::Keepalive, time + Duration::from_millis(1500))));
+        assert!(timers.contains(&(TimerKind::Idle, time + Duration::from_millis(5500))));
+    }
+
+    #[test]
+    fn test_reassembly_peak_tracks_interleaved_delivery() {
+        let mut peer = Peer::new(get_fake_addr(), &Config::default(), Instant::now());
+        let time = Instant::now();
+        let fragment =
+            |sequence: u16, fragment_id: u8, len: usize| ProtocolCommand::SendUnreliableFragment {
+                channel_id: 0,
+                sequence,
+                fragment_id,
+                fragment_count: 3,
+                data: vec![fragment_id; len].into(),
+            };
+
+        // Two messages arrive interleaved and out of order
+        for command in [
+            fragment(1, 2, 10),
+            fragment(2, 1, 20),
+            fragment(1, 0, 30),
+            fragment(2, 2, 40),
+            fragment(1, 1, 50), // completes message 1 (90 bytes buffered before freeing)
+            fragment(2, 0, 60), // completes message 2
+        ] {
+            peer.process_command(&command, time).unwrap();
+        }
+
+        assert_eq!(peer.reassembly_bytes, 0);
+        assert_eq!(peer.reassembly_peak_bytes(), 10 + 20 + 30 + 40 + 50);
+
+        peer.reset_reassembly_peak();
+        assert_eq!(peer.reassembly_peak_bytes(), 0);
+
+        peer.process_command(&fragment(3, 0, 5), time).unwrap();
+        assert_eq!(peer.reassembly_peak_bytes(), 5);
+    }
+
+    fn unreliable(len: usize) -> ProtocolCommand {
+        ProtocolCommand::SendUnreliable { channel_id: 0, data: vec![0u8; len].into() }
+    }
+
+    #[test]
+    fn test_memory_budget_shared_by_send_queues() {
+        let budget = MemoryBudget::new(100);
+        let mut first = Peer::new(get_fake_addr(), &Config::default(), Instant::now());
+        let mut second = Peer::new(get_fake_addr(), &Config::default(), Instant::now());
+        first.set_memory_budget(budget.clone());
+        second.set_memory_budget(budget.clone());
+
+        first.enqueue_command(unreliable(60));
+        assert_eq!(budget.used(), 60);
+
+        // Exceeding the aggregate budget refuses new buffering on any peer
+        second.enqueue_command(unreliable(50));
+        assert!(!second.has_queued_commands());
+        assert_eq!(budget.used(), 60);
+
+        // Draining frees memory and re-admits buffering
+        first.drain_commands().for_each(drop);
+        assert_eq!(budget.used(), 0);
+        second.enqueue_command(unreliable(50));
+        assert!(second.has_queued_commands());
+        assert_eq!(budget.used(), 50);
+
+        // Dropping a peer returns whatever it still holds
+        drop(second);
+        assert_eq!(budget.used(), 0);
+    }
+
+    #[test]
+    fn test_memory_budget_bounds_reassembly_buffers() {
+        let budget = MemoryBudget::new(8);
+        let mut peer = Peer::new(get_fake_addr(), &Config::default(), Instant::now());
+        peer.set_memory_budget(budget.clone());
+        let time = Instant::now();
+
+        let fragment = |fragment_id: u8, len: usize| ProtocolCommand::SendUnreliableFragment {
+            channel_id: 0,
+            sequence: 1,
+            fragment_id,
+            fragment_count: 2,
+            data: vec![fragment_id; len].into(),
+        };
+
+        peer.process_command(&fragment(0, 6), time).unwrap();
+        assert_eq!(budget.used(), 6);
+
+        // Second fragment does not fit: refused and not buffered
+        peer.process_command(&fragment(1, 6), time).unwrap();
+        assert_eq!(budget.used(), 6);
+        assert_eq!(peer.command_fragments[&1].buffered_bytes(), 6);
+
+        // A smaller fragment fits; completing reassembly frees the buffer
+        let packets = peer.process_command(&fragment(1, 2), time).unwrap();
+        assert_eq!(packets.into_iter().count(), 1);
+        assert_eq!(budget.used(), 0);
+        assert!(peer.command_fragments.is_empty());
+    }
+
+    #[test]
+    fn test_congestion_controller_follows_acks_and_losses() {
+        use bitfold_core::config::CongestionControllerKind;
+        use bitfold_protocol::packet::{OrderingGuarantee, PacketType};
+
+        use crate::congestion_controller::FixedWindow;
+
+        let start = Instant::now();
+        let mut config = Config::default();
+        config.congestion_controller = CongestionControllerKind::NewReno;
+        config.fragment_size = 100;
+        let mut peer = Peer::new(get_fake_addr(), &config, start);
+        let window = |peer: &Peer| peer.congestion_controller().unwrap().congestion_window();
+        assert_eq!(window(&peer), 1000);
+
+        for _ in 0..40 {
+            peer.acknowledge_handler.process_outgoing(
+                PacketType::Packet,
+                &[0u8; 100],
+                OrderingGuarantee::None,
+                None,
+                start,
+            );
+        }
+        assert_eq!(peer.bytes_in_flight(), 4000);
+        assert!(!peer.can_send_reliable());
+
+        // Acknowledging the first packet grows the window in slow start
+        let ack = ProtocolCommand::Acknowledge { sequence: 0, received_mask: 0, sent_time: None };
+        peer.process_command(&ack, start + std::time::Duration::from_millis(20)).unwrap();
+        assert_eq!(window(&peer), 1100);
+        assert_eq!(peer.bytes_in_flight(), 3900);
+
+        // Acknowledging the last one leaves the packets far behind it lost, halving the window
+        let ack = ProtocolCommand::Acknowledge { sequence: 39, received_mask: 0, sent_time: None };
+        peer.process_command(&ack, start + std::time::Duration::from_millis(20)).unwrap();
+        assert_eq!(window(&peer), 1200);
+        assert_eq!(peer.gather_dropped_packets(start).len(), 6);
+        assert_eq!(window(&peer), 600);
+
+        peer.set_congestion_controller(Box::new(FixedWindow::new(usize::MAX)));
+        assert!(peer.can_send_reliable());
+    }
+
+    #[test]
+    fn test_sack_retransmits_only_the_lost_message() {
+        use bitfold_protocol::packet::{OrderingGuarantee, PacketType};
+
+        let config = Config { sack_max_ranges: 4, ..Config::default() };
+        let start = Instant::now();
+        let mut sender = Peer::new(get_fake_addr(), &config, start);
+        let mut receiver = Peer::new(get_fake_addr(), &config, start);
+        for sequence in 0..=40u16 {
+            sender.acknowledge_handler.process_outgoing(
+                PacketType::Packet,
+                &sequence.to_be_bytes(),
+                OrderingGuarantee::None,
+                None,
+                start,
+            );
+            // The middle message is lost on the way
+            if sequence != 4 {
+                let data = ProtocolCommand::SendReliable {
+                    channel_id: 0,
+                    sequence,
+                    ordered: false,
+                    data: sequence.to_be_bytes().to_vec().into(),
+                };
+                receiver.process_command(&data, start).unwrap();
+            }
+        }
+
+        // Only the last ACK datagram arrives; its bitfield no longer reaches back to 0..=7
+        let commands: Vec<_> = receiver.drain_commands().collect();
+        let [.., ack @ ProtocolCommand::Acknowledge { sequence: 40, .. }, ProtocolCommand::Sack { ranges }] =
+            &commands[..]
+        else {
+            panic!("expected an ACK followed by a SACK, got {commands:?}");
+        };
+        assert_eq!(ranges, &vec![(5, 40), (0, 3)]);
+
+        let acked = start + std::time::Duration::from_millis(40);
+        sender.process_command(ack, acked).unwrap();
+        sender.process_command(&commands[commands.len() - 1], acked).unwrap();
+
+        let lost = sender.gather_dropped_packets(start + std::time::Duration::from_secs(1));
+        assert_eq!(lost.len(), 1);
+        assert_eq!(&*lost[0].payload, &4u16.to_be_bytes());
+        assert_eq!(sender.packets_in_flight(), 0);
+    }
+
+    #[test]
+    fn test_sack_ignored_when_disabled() {
+        use bitfold_protocol::packet::{OrderingGuarantee, PacketType};
+
+        let start = Instant::now();
+        let mut sender = Peer::new(get_fake_addr(), &Config::default(), start);
+        for sequence in 0..4u16 {
+            sender.acknowledge_handler.process_outgoing(
+                PacketType::Packet,
+                &sequence.to_be_bytes(),
+                OrderingGuarantee::None,
+                None,
+                start,
+            );
+        }
+
+        let sack = ProtocolCommand::Sack { ranges: vec![(0, 3)] };
+        sender.process_command(&sack, start + std::time::Duration::from_millis(40)).unwrap();
+        assert_eq!(sender.packets_in_flight(), 4);
+    }
+
+    #[test]
+    fn test_outstanding_packets_lists_unacked_sends() {
+        use bitfold_protocol::packet::{OrderingGuarantee, PacketType};
+
+        let start = Instant::now();
+        let mut peer = Peer::new(get_fake_addr(), &Config::default(), start);
+        assert!(peer.outstanding_packets().is_empty());
+
+        for (offset, size) in [(0u64, 10usize), (5, 20), (9, 30)] {
+            peer.acknowledge_handler.process_outgoing(
+                PacketType::Packet,
+                &vec![0u8; size],
+                OrderingGuarantee::None,
+                None,
+                start + std::time::Duration::from_millis(offset),
+            );
+        }
+
+        let outstanding = peer.outstanding_packets();
+        let summary: Vec<_> = outstanding
+            .iter()
+            .map(|info| {
+                (info.sequence, info.sent_time.duration_since(start).as_millis(), info.size)
+            })
+            .collect();
+        assert_eq!(summary, vec![(0, 0, 10), (1, 5, 20), (2, 9, 30)]);
+        assert_eq!(outstanding.len(), peer.packets_in_flight() as usize);
+    }
+
+    #[test]
+    fn test_outstanding_packets_ordered_across_wraparound() {
+        use bitfold_protocol::packet::{OrderingGuarantee, PacketType};
+
+        let time = Instant::now();
+        let mut peer = Peer::new(get_fake_addr(), &Config::default(), time);
+        peer.acknowledge_handler.set_local_sequence_num(u16::MAX - 1);
+        for _ in 0..4 {
+            peer.acknowledge_handler.process_outgoing(
+                PacketType::Packet,
+                &[0u8; 10],
+                OrderingGuarantee::None,
+                None,
+                time,
+            );
+        }
+
+        let sequences: Vec<_> =
+            peer.outstanding_packets().iter().map(|info| info.sequence).collect();
+        assert_eq!(sequences, vec![u16::MAX - 1, u16::MAX, 0, 1]);
+    }
+
+    /// Runs a handshake plus a PMTU probe exchange and returns every datagram on the wire.
+    fn seeded_scenario_traffic(client_seed: u64, server_seed: u64) -> Vec<Vec<u8>> {
+        let start = Instant::now();
+        let client_addr = "127.0.0.1:4000".parse().unwrap();
+        let server_addr = "127.0.0.1:5000".parse().unwrap();
+        let mut client_config = Config::default();
+        client_config.rng_seed = Some(client_seed);
+        client_config.pmtu_probe_on_start = true;
+        let mut server_config = Config::default();
+        server_config.rng_seed = Some(server_seed);
+
+        let mut client = Peer::new(server_addr, &client_config, start);
+        let mut server = Peer::new(client_addr, &server_config, start);
+        let mut wire = Vec::new();
+
+        client.initiate_connect();
+        wire.push(client.encode_queued_commands().unwrap());
+        server.process_command_packet(wire.last().unwrap(), start).unwrap();
+        wire.push(server.encode_queued_commands().unwrap());
+        client.process_command_packet(wire.last().unwrap(), start).unwrap();
+
+        client.handle_pmtu(start);
+        wire.push(client.encode_queued_commands_bounded(usize::MAX).unwrap().unwrap());
+        server.process_command_packet(wire.last().unwrap(), start).unwrap();
+        wire.push(server.encode_queued_commands().unwrap());
+        wire
+    }
+
+    #[test]
+    fn test_rng_seed_makes_wire_traffic_reproducible() {
+        let first = seeded_scenario_traffic(1, 2);
+        assert_eq!(first, seeded_scenario_traffic(1, 2));
+        assert_ne!(first, seeded_scenario_traffic(1, 3));
+
+        // Peers sharing a seed still differ when their addresses do
+        let mut config = Config::default();
+        config.rng_seed = Some(7);
+        let time = Instant::now();
+        let a = Peer::new("127.0.0.1:1".parse().unwrap(), &config, time);
+        let b = Peer::new("127.0.0.1:2".parse().unwrap(), &config, time);
+        assert_ne!((a.outgoing_session_id, a.connect_id), (b.outgoing_session_id, b.connect_id));
+    }
+
+    #[test]
+    fn test_early_data_limit_refuses_excess() {
+        use bitfold_protocol::packet::Packet;
+
+        let mut config = Config::default();
+        config.early_data_max_bytes = 150;
+        let addr = get_fake_addr();
+        let mut peer = Peer::new(addr, &config, Instant::now());
+        peer.record_send();
+        assert!(peer.should_buffer_early_data());
+
+        assert!(peer.buffer_early_data(Packet::unreliable(addr, vec![1; 100])));
+        assert!(!peer.buffer_early_data(Packet::unreliable(addr, vec![2; 100])));
+        assert!(peer.buffer_early_data(Packet::unreliable(addr, vec![3; 50])));
+        assert_eq!(peer.early_data_bytes(), 150);
+
+        // Nothing is released before the connection is established
+        assert!(peer.take_early_data().is_empty());
+        peer.state = PeerState::Connected;
+        assert!(!peer.should_buffer_early_data());
+        let released: Vec<_> = peer.take_early_data().iter().map(|p| p.payload()[0]).collect();
+        assert_eq!(released, vec![1, 3]);
+        assert_eq!(peer.early_data_bytes(), 0);
+    }
+
+    #[test]
+    fn test_pacing_rate_follows_window_per_rtt() {
+        use bitfold_core::config::CongestionControllerKind;
+
+        let start = Instant::now();
+        let mut config = Config::default();
+        config.congestion_controller = CongestionControllerKind::NewReno;
+        config.fragment_size = 100;
+        config.outgoing_bandwidth_limit = 0;
+        config.use_pacing = true;
+        config.pacing_gain = 1.0;
+        let mut peer = Peer::new(get_fake_addr(), &config, start);
+
+        // 1000-byte window per 50ms initial RTT
+        assert_eq!(peer.pacing_rate(), Some(20_000));
+        peer.record_paced_send(100, start);
+        assert_eq!(peer.time_to_send_next(start), Some(Duration::from_millis(5)));
+
+        // A slower path halves the rate from the next send on
+        peer.acknowledge_handler
+            .congestion_mut()
+            .rtt_estimator_mut()
+            .restore(Duration::from_millis(100), Duration::from_millis(10));
+        let next = start + Duration::from_millis(5);
+        assert!(peer.can_send_paced(next));
+        peer.record_paced_send(100, next);
+        assert_eq!(peer.pacing_rate(), Some(10_000));
+        assert_eq!(peer.time_to_send_next(next), Some(Duration::from_millis(10)));
+    }
 }
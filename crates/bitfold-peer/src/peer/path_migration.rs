@@ -0,0 +1,315 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use bitfold_core::config::Config;
+use bitfold_protocol::command::ProtocolCommand;
+use rand::Rng;
+
+use super::{command_processor::DecodedDatagram, Peer};
+use crate::path_validation::PathValidation;
+
+impl Peer {
+    /// Notes that the next datagram for this connection arrived from `source`. Call it before
+    /// processing the datagram.
+    ///
+    /// A datagram from a source other than the remote address that identifies this connection
+    /// with a [`ProtocolCommand::ConnectionId`] starts validating that address, unless another
+    /// validation is in progress: [`Peer::poll_path_challenge`] then yields challenges to send
+    /// there, and a path response from it echoing the challenge migrates the connection.
+    /// Disabled when `path_validation_timeout_ms` is 0.
+    pub fn on_datagram_from(&mut self, source: SocketAddr) {
+        self.datagram_source = Some(source);
+    }
+
+    /// Returns the connect ID a datagram sent by a remote configured like `config` identifies
+    /// its connection with, if it decodes and carries a [`ProtocolCommand::ConnectionId`].
+    pub fn datagram_connection_id(config: &Config, data: &[u8]) -> Option<u32> {
+        let DecodedDatagram { packet, .. } = Self::decode_datagram(config, data).ok()?;
+        packet.commands.iter().find_map(|command| match command {
+            ProtocolCommand::ConnectionId { connect_id, .. } => Some(*connect_id),
+            _ => None,
+        })
+    }
+
+    /// Queues a [`ProtocolCommand::ConnectionId`] identifying this connection to the remote,
+    /// if the connection was established with a handshake and may migrate.
+    pub fn enqueue_connection_id(&mut self) {
+        if !self.config.use_connection_handshake
+            || self.config.path_validation_timeout_ms == 0
+            || !self.is_established()
+        {
+            return;
+        }
+        self.enqueue_command(ProtocolCommand::ConnectionId {
+            session_id: self.outgoing_session_id,
+            connect_id: self.connect_id,
+        });
+    }
+
+    /// Identifies the connection in the queued commands once the remote has been silent for
+    /// an RTO, in case our address changed (e.g. a NAT rebinding) and the remote no longer
+    /// recognizes it. Repeated at most once per RTO while the remote stays silent.
+    pub fn identify_if_silent(&mut self, time: Instant) {
+        let rto = self.rto();
+        if !self.has_queued_commands()
+            || self.last_heard(time) < rto
+            || self.last_identified.is_some_and(|last| time.saturating_duration_since(last) < rto)
+        {
+            return;
+        }
+        self.last_identified = Some(time);
+        self.enqueue_connection_id();
+    }
+
+    /// Starts or continues validating the source of the datagram being processed, if it is
+    /// not the remote address and `commands` identify this connection. Other datagrams
+    /// neither start a validation nor count towards one.
+    pub(super) fn check_datagram_source(
+        &mut self,
+        commands: &[ProtocolCommand],
+        bytes: usize,
+        time: Instant,
+    ) {
+        let Some(source) = self.datagram_source.filter(|&source| source != self.remote_address)
+        else {
+            return;
+        };
+        if self.config.path_validation_timeout_ms == 0
+            || !self.is_established()
+            || !commands.iter().any(|command| self.identifies_connection(command))
+        {
+            return;
+        }
+        match &mut self.path_validation {
+            Some(validation) if validation.candidate() == source => {
+                validation.record_received(bytes);
+            }
+            // One address at a time, so replayed datagrams cannot keep restarting validation
+            Some(_) => {}
+            None => {
+                tracing::debug!(
+                    labels = ?self.labels,
+                    "Validating new address {} of {}",
+                    source,
+                    self.remote_address
+                );
+                let mut validation = PathValidation::new(source, self.rng.random(), time);
+                validation.record_received(bytes);
+                self.path_validation = Some(validation);
+            }
+        }
+    }
+
+    /// Returns true if `command` is a [`ProtocolCommand::ConnectionId`] matching this
+    /// connection.
+    fn identifies_connection(&self, command: &ProtocolCommand) -> bool {
+        matches!(
+            command,
+            ProtocolCommand::ConnectionId { session_id, connect_id }
+                if *session_id == self.incoming_session_id && *connect_id == self.connect_id
+        )
+    }
+
+    /// Returns the address under validation and a path challenge datagram to send to it, if
+    /// one is due and fits within `path_amplification_factor` times the bytes received from
+    /// the address. Abandons the validation once `path_validation_timeout_ms` has passed
+    /// without an answer.
+    pub fn poll_path_challenge(&mut self, time: Instant) -> Option<(SocketAddr, Vec<u8>)> {
+        let timeout = Duration::from_millis(self.config.path_validation_timeout_ms.into());
+        let validation = self.path_validation.as_ref()?;
+        if validation.is_expired(time, timeout) {
+            tracing::debug!(
+                labels = ?self.labels,
+                "Abandoning unvalidated address {}",
+                validation.candidate()
+            );
+            self.path_validation = None;
+            return None;
+        }
+        if !validation.is_challenge_due(time, timeout) {
+            return None;
+        }
+
+        let challenge = ProtocolCommand::PathChallenge { data: validation.challenge() };
+        let datagram = self.encode_standalone_command(challenge).ok()?;
+        let factor = self.config.path_amplification_factor;
+        let validation = self.path_validation.as_mut()?;
+        if !validation.try_send_challenge(datagram.len(), factor, time) {
+            return None;
+        }
+        Some((validation.candidate(), datagram))
+    }
+
+    /// Returns the address being validated as the connection's new remote address, if any.
+    pub fn validating_address(&self) -> Option<SocketAddr> {
+        self.path_validation.as_ref().map(PathValidation::candidate)
+    }
+
+    /// Migrates the connection to the address under validation if the path response echoes
+    /// its challenge and arrived from it. Returns whether the connection migrated.
+    pub(super) fn process_path_response(&mut self, data: u64) -> bool {
+        let Some(validation) = &self.path_validation else {
+            return false;
+        };
+        let Some(source) = self.datagram_source.filter(|&s| validation.is_answered_by(s, data))
+        else {
+            tracing::debug!(labels = ?self.labels, "Ignoring path response not matching a challenge");
+            return false;
+        };
+        let previous = std::mem::replace(&mut self.remote_address, source);
+        self.path_validation = None;
+        tracing::info!(labels = ?self.labels, "Migrated connection from {} to {}", previous, source);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitfold_core::config::Config;
+    use bitfold_protocol::command_codec::CommandDecoder;
+
+    use super::*;
+    use crate::peer_state::PeerState;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    /// A server peer connected to a client at port 1000, and the client itself.
+    fn connected_pair(time: Instant) -> (Peer, Peer) {
+        let config = Config::default();
+        let mut server = Peer::new(addr(1000), &config, time);
+        let mut client = Peer::new(addr(9000), &config, time);
+        server.state = PeerState::Connected;
+        client.state = PeerState::Connected;
+        server.incoming_session_id = client.outgoing_session_id;
+        client.incoming_session_id = server.outgoing_session_id;
+        server.connect_id = client.connect_id;
+        (server, client)
+    }
+
+    fn challenge_data(datagram: &[u8]) -> u64 {
+        let payload = CommandDecoder::validate_and_strip_checksum(datagram).unwrap();
+        let decompressed = CommandDecoder::decompress(payload).unwrap();
+        match CommandDecoder::decode_packet(&decompressed).unwrap().commands[..] {
+            [ProtocolCommand::PathChallenge { data }] => data,
+            ref commands => panic!("expected a path challenge, got {commands:?}"),
+        }
+    }
+
+    /// Delivers `datagram` from `source` to `peer`.
+    fn deliver(peer: &mut Peer, datagram: &[u8], source: SocketAddr, time: Instant) {
+        peer.on_datagram_from(source);
+        peer.process_command_packet(datagram, time).unwrap();
+    }
+
+    #[test]
+    fn test_validated_address_migrates_connection() {
+        let time = Instant::now();
+        let (mut server, mut client) = connected_pair(time);
+
+        // The client's NAT rebinds to port 2000
+        client.enqueue_command(ProtocolCommand::Ping { timestamp: 1 });
+        client.enqueue_connection_id();
+        let ping = client.encode_queued_commands().unwrap();
+        deliver(&mut server, &ping, addr(2000), time);
+        assert_eq!(server.remote_address, addr(1000));
+        assert_eq!(server.validating_address(), Some(addr(2000)));
+        server.drain_commands().for_each(drop);
+
+        let (destination, challenge) = server.poll_path_challenge(time).unwrap();
+        assert_eq!(destination, addr(2000));
+        // One challenge per interval
+        assert!(server.poll_path_challenge(time).is_none());
+
+        deliver(&mut client, &challenge, addr(9000), time);
+        let response = client.encode_queued_commands().unwrap();
+        deliver(&mut server, &response, addr(2000), time);
+
+        assert_eq!(server.remote_address, addr(2000));
+        assert_eq!(server.validating_address(), None);
+    }
+
+    #[test]
+    fn test_failed_challenge_echo_leaves_address_unvalidated() {
+        let time = Instant::now();
+        let (mut server, mut client) = connected_pair(time);
+        let timeout = Duration::from_millis(server.config.path_validation_timeout_ms.into());
+
+        client.enqueue_command(ProtocolCommand::Ping { timestamp: 1 });
+        client.enqueue_connection_id();
+        let ping = client.encode_queued_commands().unwrap();
+        deliver(&mut server, &ping, addr(2000), time);
+        let (_, challenge) = server.poll_path_challenge(time).unwrap();
+        let data = challenge_data(&challenge);
+
+        // A wrong echo from the candidate, and the right one from elsewhere, are both ignored
+        client.enqueue_command(ProtocolCommand::PathResponse { data: data.wrapping_add(1) });
+        client.enqueue_connection_id();
+        let wrong = client.encode_queued_commands().unwrap();
+        deliver(&mut server, &wrong, addr(2000), time);
+        client.enqueue_command(ProtocolCommand::PathResponse { data });
+        client.enqueue_connection_id();
+        let misrouted = client.encode_queued_commands().unwrap();
+        deliver(&mut server, &misrouted, addr(3000), time);
+        assert_eq!(server.remote_address, addr(1000));
+
+        // The validation is abandoned once it times out
+        assert!(server.poll_path_challenge(time + timeout).is_none());
+        assert_eq!(server.validating_address(), None);
+        assert_eq!(server.remote_address, addr(1000));
+    }
+
+    #[test]
+    fn test_unidentified_datagrams_do_not_start_or_replace_validation() {
+        let time = Instant::now();
+        let (mut server, mut client) = connected_pair(time);
+
+        // Spoofed or stray datagrams from another address without the connection's identity
+        client.enqueue_command(ProtocolCommand::Ping { timestamp: 1 });
+        let ping = client.encode_queued_commands().unwrap();
+        deliver(&mut server, &ping, addr(2000), time);
+        assert_eq!(server.validating_address(), None);
+
+        // A wrong connect ID does not identify the connection either
+        client.enqueue_command(ProtocolCommand::ConnectionId {
+            session_id: client.outgoing_session_id,
+            connect_id: client.connect_id.wrapping_add(1),
+        });
+        let forged = client.encode_queued_commands().unwrap();
+        deliver(&mut server, &forged, addr(2000), time);
+        assert_eq!(server.validating_address(), None);
+
+        client.enqueue_connection_id();
+        let identified = client.encode_queued_commands().unwrap();
+        deliver(&mut server, &identified, addr(2000), time);
+        assert_eq!(server.validating_address(), Some(addr(2000)));
+
+        // Another address cannot take over the validation in progress
+        deliver(&mut server, &identified, addr(3000), time);
+        assert_eq!(server.validating_address(), Some(addr(2000)));
+    }
+
+    #[test]
+    fn test_silent_remote_gets_connection_identified() {
+        let time = Instant::now();
+        let (_, mut client) = connected_pair(time);
+        let later = time + client.rto();
+
+        // Nothing queued: nothing to piggyback on
+        client.identify_if_silent(later);
+        assert!(!client.has_queued_commands());
+
+        client.enqueue_command(ProtocolCommand::Ping { timestamp: 1 });
+        client.identify_if_silent(later);
+        let commands: Vec<_> = client.drain_commands().collect();
+        assert!(matches!(commands[..], [_, ProtocolCommand::ConnectionId { .. }]));
+
+        // Once per RTO
+        client.enqueue_command(ProtocolCommand::Ping { timestamp: 2 });
+        client.identify_if_silent(later);
+        assert_eq!(client.drain_commands().count(), 1);
+    }
+}
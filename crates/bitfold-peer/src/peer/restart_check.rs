@@ -0,0 +1,79 @@
+use std::time::Instant;
+
+use bitfold_protocol::command::ProtocolCommand;
+use rand::Rng;
+
+use super::Peer;
+use crate::{close_reason::CloseReason, peer_state::PeerState};
+
+/// Challenges sent before a connection that answers none of them is reset.
+const RESTART_CHALLENGES: u32 = 3;
+
+/// A Connect with a new connect ID arrived on an established connection. It either comes from
+/// the remote after a restart or is spoofed, so the connection is only reset once it stops
+/// answering path challenges.
+#[derive(Debug, Clone)]
+pub(super) struct RestartCheck {
+    /// Data the established connection has to echo
+    challenge: u64,
+    /// Challenges sent so far
+    challenges_sent: u32,
+    /// When the last challenge was sent
+    last_sent: Instant,
+}
+
+impl Peer {
+    /// Starts checking whether the established connection is still alive after a Connect
+    /// with a new connect ID. A check in progress is kept, so repeated Connects cannot keep
+    /// renewing it.
+    pub(super) fn challenge_restart(&mut self, time: Instant) {
+        if self.restart_check.is_some() {
+            return;
+        }
+        tracing::debug!(
+            labels = ?self.labels,
+            "Connect with a new connect ID from {}, checking the connection",
+            self.remote_address
+        );
+        let challenge = self.rng.random();
+        self.restart_check = Some(RestartCheck { challenge, challenges_sent: 1, last_sent: time });
+        self.enqueue_command(ProtocolCommand::PathChallenge { data: challenge });
+    }
+
+    /// Repeats the restart challenge every RTO, backing the RTO off, and resets the connection
+    /// (with [`CloseReason::Reset`]) once the last one went unanswered for an RTO, as the
+    /// remote then lost its connection state.
+    pub fn handle_restart_check(&mut self, time: Instant) {
+        let rto = self.rto();
+        let Some(check) = self.restart_check.as_mut() else {
+            return;
+        };
+        if time.saturating_duration_since(check.last_sent) < rto {
+            return;
+        }
+        if check.challenges_sent >= RESTART_CHALLENGES {
+            tracing::info!(labels = ?self.labels, "Connection to {} reset", self.remote_address);
+            self.restart_check = None;
+            self.state = PeerState::Zombie;
+            self.set_close_reason(CloseReason::Reset);
+            return;
+        }
+        check.challenges_sent += 1;
+        check.last_sent = time;
+        let challenge = check.challenge;
+        self.on_retransmission_timeout();
+        self.enqueue_command(ProtocolCommand::PathChallenge { data: challenge });
+    }
+
+    /// Ends the restart check if the path response echoes its challenge: the connection is
+    /// alive, so the Connect that started the check was stale or spoofed. Returns whether it
+    /// answered the check.
+    pub(super) fn process_restart_response(&mut self, data: u64) -> bool {
+        if self.restart_check.as_ref().is_none_or(|check| check.challenge != data) {
+            return false;
+        }
+        tracing::debug!(labels = ?self.labels, "Connection to {} is alive", self.remote_address);
+        self.restart_check = None;
+        true
+    }
+}
@@ -3,6 +3,7 @@
 /// Tracks the lifecycle of a peer connection from initial contact through
 /// active communication to graceful shutdown.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PeerState {
     /// Peer has been created but no packets exchanged yet
     #[default]
@@ -0,0 +1,149 @@
+//! On-demand round-trip time measurement with application pings.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use bitfold_core::error::{ErrorKind, Result};
+
+/// Tokens of application pings have the high bit set, keeping them apart from the
+/// millisecond timestamps carried by keepalive pings.
+const APP_PING_BIT: u32 = 1 << 31;
+
+#[derive(Debug, Default)]
+struct PingSlot {
+    result: Option<Result<Duration>>,
+    waker: Option<Waker>,
+}
+
+/// Resolves with the round-trip time of a ping sent by [`Peer::ping`](crate::Peer::ping).
+///
+/// Fails with [`ErrorKind::PingTimeout`] if no pong arrives within `Config::ping_timeout`,
+/// or if the peer is dropped first. The future needs no particular executor; it is woken
+/// from whichever thread drives the peer.
+#[derive(Debug)]
+#[must_use = "the round-trip time is only available through the future"]
+pub struct PingFuture {
+    slot: Arc<Mutex<PingSlot>>,
+}
+
+impl Future for PingFuture {
+    type Output = Result<Duration>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.slot.lock().unwrap();
+        match slot.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Application pings awaiting their pong, keyed by token.
+#[derive(Debug, Default)]
+pub(crate) struct PendingPings {
+    pending: HashMap<u32, (Instant, Arc<Mutex<PingSlot>>)>,
+    next_token: u32,
+}
+
+impl PendingPings {
+    /// Registers a new ping sent at `time`, returning its token and the future it resolves.
+    pub(crate) fn register(&mut self, time: Instant) -> (u32, PingFuture) {
+        let token = APP_PING_BIT | self.next_token;
+        self.next_token = (self.next_token + 1) & !APP_PING_BIT;
+        let slot = Arc::new(Mutex::new(PingSlot::default()));
+        self.pending.insert(token, (time, Arc::clone(&slot)));
+        (token, PingFuture { slot })
+    }
+
+    /// Resolves the ping answered by a pong carrying `token`. Returns false for pongs that
+    /// answer keepalive pings or pings that already timed out.
+    pub(crate) fn resolve(&mut self, token: u32, time: Instant) -> bool {
+        match self.pending.remove(&token) {
+            Some((sent, slot)) => {
+                complete(&slot, Ok(time.saturating_duration_since(sent)));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Fails every ping sent more than `timeout` before `time`.
+    pub(crate) fn expire(&mut self, time: Instant, timeout: Duration) {
+        self.pending.retain(|_, (sent, slot)| {
+            if time.saturating_duration_since(*sent) < timeout {
+                return true;
+            }
+            complete(slot, Err(ErrorKind::PingTimeout));
+            false
+        });
+    }
+
+    /// Number of pings awaiting their pong.
+    pub(crate) fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Time the oldest ping awaiting its pong was sent.
+    #[cfg(any(test, feature = "diagnostics"))]
+    pub(crate) fn oldest(&self) -> Option<Instant> {
+        self.pending.values().map(|(sent, _)| *sent).min()
+    }
+}
+
+impl Drop for PendingPings {
+    fn drop(&mut self) {
+        for (_, slot) in self.pending.values() {
+            complete(slot, Err(ErrorKind::PingTimeout));
+        }
+    }
+}
+
+fn complete(slot: &Mutex<PingSlot>, result: Result<Duration>) {
+    let mut slot = slot.lock().unwrap();
+    slot.result = Some(result);
+    if let Some(waker) = slot.waker.take() {
+        waker.wake();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::pin;
+
+    use super::*;
+
+    fn poll(future: Pin<&mut PingFuture>) -> Poll<Result<Duration>> {
+        future.poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn test_tokens_are_distinct_from_keepalive_timestamps() {
+        let mut pings = PendingPings::default();
+        let time = Instant::now();
+        let (first, _a) = pings.register(time);
+        let (second, _b) = pings.register(time);
+        assert_ne!(first, second);
+        assert!(first & APP_PING_BIT != 0 && second & APP_PING_BIT != 0);
+        assert!(!pings.resolve(5, time));
+        assert_eq!(pings.len(), 2);
+    }
+
+    #[test]
+    fn test_dropped_registry_fails_pending_pings() {
+        let mut pings = PendingPings::default();
+        let (_, future) = pings.register(Instant::now());
+        let mut future = pin!(future);
+        assert!(poll(future.as_mut()).is_pending());
+        drop(pings);
+        assert!(matches!(poll(future), Poll::Ready(Err(ErrorKind::PingTimeout))));
+    }
+}
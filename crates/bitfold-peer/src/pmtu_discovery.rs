@@ -12,34 +12,214 @@
 //! - Adjusts bounds based on probe success/failure
 //! - Converges when the search space becomes smaller than a threshold
 //!
+//! With `PmtuSearchMode::Schedule` there is no bisection: the operator-supplied
+//! `pmtu_candidate_sizes` within the bounds are probed largest first, a failure moving on to
+//! the next smaller one, and the search converges on the first that gets a reply.
+//!
 //! # Probe Flow
 //!
 //! 1. Sender generates a PMTUProbe with a test size and unique token
 //! 2. If the probe reaches the receiver, they respond with PMTUReply
 //! 3. On successful reply: increase low bound (larger packets work)
-//! 4. On timeout: decrease high bound (that size is too large)
+//! 4. On timeout: re-send the probe up to `pmtu_probe_retries` times, then decrease the high
+//!    bound (that size is too large)
 //! 5. Continue until convergence
 //!
+//! # Probe Tokens
+//!
+//! Tokens are structured so that several searches can share one token space: the high
+//! [`PROBE_TOKEN_STREAM_BITS`] bits carry the search's stream ID (see
+//! [`PmtuDiscovery::with_stream`]) and the remaining bits a random value or counter. A reply
+//! can be routed to its search with [`probe_token_stream`], and a search ignores replies
+//! carrying another stream's ID.
+//!
+//! Replying to probes does not depend on `use_pmtu_discovery`: a peer with discovery
+//! disabled still answers probes so that the remote side can converge.
+//!
 //! # Configuration
 //!
 //! Key parameters from `Config`:
 //! - `use_pmtu_discovery`: Enable/disable PMTU discovery
-//! - `pmtu_min`: Minimum MTU to probe (low bound starting point)
+//! - `pmtu_min`: Minimum MTU to probe (low bound starting point), raised to
+//!   [`IPV6_MIN_MTU`] on IPv6 paths (see [`PmtuDiscovery::set_address_family`])
 //! - `pmtu_max`: Maximum MTU to probe (high bound starting point)
 //! - `pmtu_interval_ms`: Time between probes
+//! - `pmtu_interval_jitter_pct`: Random spread applied to each wait between probes
+//! - `pmtu_backoff_max_ms`: Cap for the interval as it doubles after consecutive timeouts
 //! - `pmtu_converge_threshold`: Convergence threshold (stop when high - low <= this)
+//! - `pmtu_converge_pct`: Scale the threshold with the midpoint of the search range
+//! - `pmtu_discovery_timeout_ms`: Give up on a search that has not converged in time
+//! - `pmtu_safe_floor`: Fragment size pinned when giving up without a confirmed probe
+//! - `pmtu_candidate_sizes`: Known MTU boundaries probed in preference to midpoints
+//! - `pmtu_probe_quantum`: Round probe sizes to a multiple of this many bytes
+//! - `pmtu_search_mode`: Binary search, exponential growth until the first failure, or the
+//!   candidate sizes alone
+//! - `pmtu_probe_payload_mode`: Random, zeroed, patterned or verifiable probe payload content
+//! - `pmtu_sequential_tokens`: Use a counter instead of random probe tokens
+//! - `pmtu_probe_on_start`: Probe immediately instead of waiting for the first interval
+//! - `pmtu_paired_probes`: Pair each probe with a fragmentable one to diagnose losses
+//! - `pmtu_confirm_on_converge`: Confirm the converged size with a final probe before using it
+//! - `pmtu_blackhole_loss_threshold`: Loss burst size that triggers a black-hole reset
+//! - `pmtu_blackhole_cooldown_ms`: Minimum time between black-hole resets
+//! - `pmtu_reprobe_loss_threshold`: Consecutive losses at data sizes that restart a converged
+//!   search below the converged size
+//! - `pmtu_max_replies_per_sec`: Rate limit on replies to the remote's probes
+//! - `pmtu_probe_retries`: Re-sends of a timed-out probe before its size is judged too big
+//! - `pmtu_reflected_probes`: Ask the remote to send the probes instead
+//! - `pmtu_probes_count_as_activity`: Whether received PMTU traffic resets the idle timer
+//!
+//! # Acknowledged Data
+//!
+//! A data datagram the remote acknowledged is as good as a probe reply for its size:
+//! [`PmtuDiscovery::observe_acked_size`] raises the low bound to it without a probe.
+//!
+//! # Paired Probes
+//!
+//! A lost probe may have been too big for the path or simply lost. With `pmtu_paired_probes`,
+//! each probe (sent with DF set) has a twin of the same size that the transport takes with
+//! [`PmtuDiscovery::take_fragmentable_probe`] and sends with DF cleared. When the DF probe
+//! times out, a reply to the twin means the size was the problem ([`PmtuProbeLoss::TooBig`])
+//! and the high bound is lowered; no reply means the path dropped both
+//! ([`PmtuProbeLoss::Generic`]) and the bounds are left alone. If the twin was never taken,
+//! the loss is treated as too big, as without pairing.
+//!
+//! # Reflected Probes
+//!
+//! A peer that cannot send DF-marked datagrams can still search with `pmtu_reflected_probes`:
+//! instead of a probe it sends a PMTUProbeRequest, and the remote answers with a probe of the
+//! requested size carrying the request's token (see [`PmtuDiscovery::create_reflected_probe`]).
+//! That probe arriving counts as the reply ([`PmtuDiscovery::process_reflected_probe`]), so
+//! the search runs as usual, over the path from the remote. Probes are never paired in this
+//! mode.
+//!
+//! So requests cannot be used to amplify traffic towards a spoofed address, a probe is only
+//! reflected within [`REFLECTED_PROBE_AMPLIFICATION`] times the size of the datagram carrying
+//! the request, and only on an established connection. Requests are padded accordingly.
+
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use bitfold_core::{
+    config::{Config, PmtuProbePayload, PmtuSearchMode},
+    shared::SharedBytes,
+};
+use bitfold_protocol::{command::ProtocolCommand, command_codec::CommandEncoder};
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+
+/// Number of high token bits that identify the probe stream.
+pub const PROBE_TOKEN_STREAM_BITS: u32 = 8;
+
+/// Number of low token bits carrying the per-stream random value or counter.
+const PROBE_TOKEN_COUNTER_BITS: u32 = u32::BITS - PROBE_TOKEN_STREAM_BITS;
+
+/// Mask selecting the per-stream part of a probe token.
+const PROBE_TOKEN_COUNTER_MASK: u32 = (1 << PROBE_TOKEN_COUNTER_BITS) - 1;
+
+/// First step above the known-good size in `PmtuSearchMode::Exponential` (bytes).
+const EXPONENTIAL_INITIAL_STEP: u16 = 64;
+
+/// Probe timeouts after which an unanswered probe counts as stale: the caller stopped
+/// ticking, so the probe is timed out as of when it expired and probing resumes at once.
+const STALE_PROBE_TIMEOUTS: u32 = 16;
+
+/// Length of the target size and payload length at the start of a verifiable probe payload.
+const VERIFIABLE_HEADER_LEN: usize = 4;
+
+/// Smallest MTU every IPv6 link must support (RFC 8200), in bytes.
+pub const IPV6_MIN_MTU: u16 = 1280;
+
+/// Most times the request datagram's size a reflected probe may be.
+pub const REFLECTED_PROBE_AMPLIFICATION: usize = 3;
+
+/// Returns the stream ID encoded in a probe token.
+pub fn probe_token_stream(token: u32) -> u8 {
+    (token >> PROBE_TOKEN_COUNTER_BITS) as u8
+}
+
+/// Address family of the probed path, which sets the smallest size worth probing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmtuAddressFamily {
+    /// IPv4: `pmtu_min` applies as configured
+    V4,
+    /// IPv6: every link carries at least [`IPV6_MIN_MTU`] bytes
+    V6,
+}
+
+impl PmtuAddressFamily {
+    /// Returns the family of the path to `addr`; IPv4-mapped IPv6 addresses count as IPv4.
+    pub fn of(addr: SocketAddr) -> Self {
+        if addr.ip().to_canonical().is_ipv6() {
+            PmtuAddressFamily::V6
+        } else {
+            PmtuAddressFamily::V4
+        }
+    }
+}
+
+/// Diagnosis of a PMTU probe that timed out, from its paired fragmentable probe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmtuProbeLoss {
+    /// The fragmentable probe got through: the DF probe was too big for the path
+    TooBig,
+    /// Neither probe got through: the loss says nothing about the path MTU
+    Generic,
+}
+
+/// Phase of the PMTU search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PmtuState {
+    /// Probing for the path MTU
+    Searching,
+    /// The search finished; the fragment size is fixed until losses suggest the path changed
+    Converged,
+    /// Searching again below the previously converged size after losses at that size
+    ErrorProbing,
+}
 
-use std::time::{Duration, Instant};
+/// Counters and state of a PMTU search for observability, returned by
+/// [`PmtuDiscovery::stats`].
+///
+/// Counters are cumulative over the life of the instance and are not cleared by `reset()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PmtuStats {
+    /// Probes sent, including re-sends after a timeout
+    pub probes_sent: u64,
+    /// Replies that confirmed a probed size
+    pub replies: u64,
+    /// Probes that timed out, including those re-sent afterwards
+    pub timeouts: u64,
+    /// Probes re-sent after a timeout (`pmtu_probe_retries`)
+    pub retries: u64,
+    /// Replies matching no outstanding probe, e.g. late replies after a timeout or reset
+    pub stray_replies: u64,
+    /// Phase of the search
+    pub state: PmtuState,
+    /// Fragment size the search settled on, once converged
+    pub converged_size: Option<u16>,
+}
 
-use bitfold_core::{config::Config, shared::SharedBytes};
-use bitfold_protocol::command::ProtocolCommand;
-use rand::RngCore;
+/// Discovered path MTU state that can be persisted and restored on a later connection.
+///
+/// Callers may cache it keyed by peer address so a reconnect skips most of the search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PmtuSnapshot {
+    /// Effective fragment size (bytes)
+    pub fragment_size: u16,
+    /// Search low bound (bytes)
+    pub low: u16,
+    /// Search high bound (bytes)
+    pub high: u16,
+}
 
 /// Manages Path MTU discovery state for a peer connection.
 ///
 /// This struct tracks the binary search for optimal packet size and manages
 /// outstanding probes.
-#[derive(Debug)]
 pub struct PmtuDiscovery {
     /// Configuration reference
     config: Config,
@@ -51,13 +231,133 @@ pub struct PmtuDiscovery {
     high: u16,
     /// Last time we probed PMTU
     last_probe: Instant,
-    /// Outstanding PMTU probe info: (size, token, sent_time)
-    outstanding: Option<(u16, u32, Instant)>,
+    /// Outstanding PMTU probe info: (size, token, sent_time, retries sent)
+    outstanding: Option<(u16, u32, Instant, u8)>,
+    /// Time the current search started (reset by `reset()`)
+    started: Instant,
+    /// Largest probe size confirmed by a reply during the current search
+    confirmed: Option<u16>,
+    /// Whether the search timed out and probing is suspended until `reset()`
+    gave_up: bool,
+    /// Next probe token when `pmtu_sequential_tokens` is enabled
+    next_token: u32,
+    /// Whether the next probe may skip the interval wait (`pmtu_probe_on_start`)
+    probe_pending: bool,
+    /// Stream ID stamped into the high bits of this search's probe tokens
+    stream: u8,
+    /// Source of random probe tokens and payloads
+    rng: StdRng,
+    /// Bytes added to each datagram after encoding (see `set_datagram_overhead`)
+    datagram_overhead: u16,
+    /// Fragmentable twin of the outstanding probe, waiting for the transport to take it
+    fragmentable_probe: Option<ProtocolCommand>,
+    /// Token of the taken fragmentable probe and whether it was answered
+    fragmentable: Option<(u32, bool)>,
+    /// Diagnosis of the most recent timed-out paired probe
+    last_loss: Option<PmtuProbeLoss>,
+    /// Whether the outstanding probe confirms the converged size (`pmtu_confirm_on_converge`)
+    confirming: bool,
+    /// Whether the converged size was confirmed and adopted as the fragment size
+    convergence_confirmed: bool,
+    /// Whether restored bounds still await their confirmation probe (see `restore`)
+    verify_restored: bool,
+    /// Time of the last fallback to the safe floor (kept across `reset()` to enforce the
+    /// black-hole cooldown)
+    last_blackhole_reset: Option<Instant>,
+    /// Replies that claimed a larger size than their probe was sent with
+    anomalies: u32,
+    /// Probe timeouts since the last reply, stretching the interval (`pmtu_backoff_max_ms`)
+    consecutive_failures: u32,
+    /// Start of the current one-second reply rate-limit window
+    reply_window_start: Instant,
+    /// Replies sent in the current window (`pmtu_max_replies_per_sec`)
+    replies_in_window: u32,
+    /// Probes left unanswered by the reply rate limit
+    suppressed_replies: u64,
+    /// Consecutive losses at data sizes since convergence (`pmtu_reprobe_loss_threshold`)
+    losses_at_size: u32,
+    /// Whether the current search was restarted after losses at the converged size
+    error_probing: bool,
+    /// Step above `low` for the next probe while the exponential phase lasts
+    /// (`PmtuSearchMode::Exponential`); `None` once bisecting
+    exponential_step: Option<u16>,
+    /// Multiplier applied to the probe interval until the next probe (`pmtu_interval_jitter_pct`)
+    interval_jitter: f64,
+    /// Whether new probes are held back while congestion control recovers
+    probes_deferred: bool,
+    /// Probes sent, including re-sends
+    probes_sent: u64,
+    /// Replies that confirmed a probed size
+    replies: u64,
+    /// Probes that timed out
+    timeouts: u64,
+    /// Probes re-sent after a timeout
+    retries: u64,
+    /// Replies matching no outstanding probe
+    stray_replies: u64,
+    /// Called with the old and new size whenever the fragment size changes
+    on_fragment_size_change: Option<Box<dyn FnMut(u16, u16) + Send>>,
+}
+
+impl std::fmt::Debug for PmtuDiscovery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PmtuDiscovery")
+            .field("config", &self.config)
+            .field("fragment_size", &self.fragment_size)
+            .field("low", &self.low)
+            .field("high", &self.high)
+            .field("last_probe", &self.last_probe)
+            .field("outstanding", &self.outstanding)
+            .field("started", &self.started)
+            .field("confirmed", &self.confirmed)
+            .field("gave_up", &self.gave_up)
+            .field("next_token", &self.next_token)
+            .field("probe_pending", &self.probe_pending)
+            .field("stream", &self.stream)
+            .field("rng", &self.rng)
+            .field("datagram_overhead", &self.datagram_overhead)
+            .field("fragmentable_probe", &self.fragmentable_probe)
+            .field("fragmentable", &self.fragmentable)
+            .field("last_loss", &self.last_loss)
+            .field("confirming", &self.confirming)
+            .field("convergence_confirmed", &self.convergence_confirmed)
+            .field("verify_restored", &self.verify_restored)
+            .field("last_blackhole_reset", &self.last_blackhole_reset)
+            .field("anomalies", &self.anomalies)
+            .field("consecutive_failures", &self.consecutive_failures)
+            .field("reply_window_start", &self.reply_window_start)
+            .field("replies_in_window", &self.replies_in_window)
+            .field("suppressed_replies", &self.suppressed_replies)
+            .field("losses_at_size", &self.losses_at_size)
+            .field("error_probing", &self.error_probing)
+            .field("exponential_step", &self.exponential_step)
+            .field("interval_jitter", &self.interval_jitter)
+            .field("probes_deferred", &self.probes_deferred)
+            .field("probes_sent", &self.probes_sent)
+            .field("replies", &self.replies)
+            .field("timeouts", &self.timeouts)
+            .field("retries", &self.retries)
+            .field("stray_replies", &self.stray_replies)
+            .field(
+                "on_fragment_size_change",
+                &self.on_fragment_size_change.as_ref().map(|_| "<callback>"),
+            )
+            .finish()
+    }
 }
 
 impl PmtuDiscovery {
     /// Creates a new PMTU discovery instance.
+    ///
+    /// Randomness is seeded from `rng_seed` if set, otherwise from OS entropy.
     pub fn new(config: &Config, time: Instant) -> Self {
+        let rng = config.rng_seed.map_or_else(StdRng::from_os_rng, StdRng::seed_from_u64);
+        Self::with_rng(config, time, rng)
+    }
+
+    /// Creates a new PMTU discovery instance drawing probe tokens and payloads from `rng`.
+    pub fn with_rng(config: &Config, time: Instant, mut rng: StdRng) -> Self {
+        let interval_jitter = Self::draw_interval_jitter(config, &mut rng);
         Self {
             config: config.clone(),
             fragment_size: config.fragment_size,
@@ -65,7 +365,260 @@ impl PmtuDiscovery {
             high: config.pmtu_max,
             last_probe: time,
             outstanding: None,
+            started: time,
+            confirmed: None,
+            gave_up: false,
+            next_token: 0,
+            probe_pending: config.pmtu_probe_on_start,
+            stream: 0,
+            rng,
+            datagram_overhead: 0,
+            fragmentable_probe: None,
+            fragmentable: None,
+            last_loss: None,
+            confirming: false,
+            convergence_confirmed: false,
+            verify_restored: false,
+            last_blackhole_reset: None,
+            anomalies: 0,
+            consecutive_failures: 0,
+            reply_window_start: time,
+            replies_in_window: 0,
+            suppressed_replies: 0,
+            losses_at_size: 0,
+            error_probing: false,
+            exponential_step: Self::initial_step(config),
+            interval_jitter,
+            probes_deferred: false,
+            probes_sent: 0,
+            replies: 0,
+            timeouts: 0,
+            retries: 0,
+            stray_replies: 0,
+            on_fragment_size_change: None,
+        }
+    }
+
+    /// Creates a PMTU discovery instance whose probe tokens carry `stream` in their high bits.
+    pub fn with_stream(config: &Config, time: Instant, stream: u8) -> Self {
+        Self { stream, ..Self::new(config, time) }
+    }
+
+    /// Returns when the current search started (creation or the last `reset()`).
+    pub fn started_at(&self) -> Instant {
+        self.started
+    }
+
+    /// Returns the stream ID stamped into this search's probe tokens.
+    pub fn stream(&self) -> u8 {
+        self.stream
+    }
+
+    /// Restarts the search from the configured bounds.
+    ///
+    /// The current fragment size is kept until a new probe is confirmed.
+    pub fn reset(&mut self, time: Instant) {
+        self.low = self.config.pmtu_min;
+        self.high = self.config.pmtu_max;
+        self.last_probe = time;
+        self.outstanding = None;
+        self.started = time;
+        self.confirmed = None;
+        self.gave_up = false;
+        self.probe_pending = self.config.pmtu_probe_on_start;
+        self.fragmentable_probe = None;
+        self.fragmentable = None;
+        self.confirming = false;
+        self.convergence_confirmed = false;
+        self.verify_restored = false;
+        self.consecutive_failures = 0;
+        self.losses_at_size = 0;
+        self.error_probing = false;
+        self.exponential_step = Self::initial_step(&self.config);
+    }
+
+    /// Returns the step the exponential phase starts with, or `None` for binary search.
+    fn initial_step(config: &Config) -> Option<u16> {
+        match config.pmtu_search_mode {
+            PmtuSearchMode::Binary | PmtuSearchMode::Schedule => None,
+            PmtuSearchMode::Exponential => Some(EXPONENTIAL_INITIAL_STEP),
+        }
+    }
+
+    /// Returns the phase of the search.
+    pub fn state(&self) -> PmtuState {
+        if self.gave_up || self.is_settled() {
+            PmtuState::Converged
+        } else if self.error_probing {
+            PmtuState::ErrorProbing
+        } else {
+            PmtuState::Searching
+        }
+    }
+
+    /// Reports that a datagram of `size` bytes was lost.
+    ///
+    /// Once the search has converged, `pmtu_reprobe_loss_threshold` consecutive losses of
+    /// datagrams larger than `pmtu_safe_floor` suggest the path MTU shrank: the fragment size
+    /// falls back to `pmtu_safe_floor` and the search restarts with its high bound below the
+    /// converged size, so it cannot settle on the broken size again. Losses while searching
+    /// are ignored, as are those of small datagrams, which say nothing about the MTU. An
+    /// acknowledged datagram of such a size (see [`PmtuDiscovery::observe_acked_size`]) breaks
+    /// the run, and the fallback shares the black-hole cooldown (see
+    /// [`PmtuDiscovery::handle_blackhole`]).
+    ///
+    /// Returns `true` if the search was restarted.
+    pub fn report_loss_at_size(&mut self, size: u16, time: Instant) -> bool {
+        let threshold = self.config.pmtu_reprobe_loss_threshold;
+        if threshold == 0
+            || !self.config.use_pmtu_discovery
+            || self.state() != PmtuState::Converged
+            || size <= self.config.pmtu_safe_floor
+        {
+            return false;
         }
+        self.losses_at_size += 1;
+        if self.losses_at_size < threshold {
+            return false;
+        }
+
+        let last_good = self.low;
+        if !self.fall_back_to_safe_floor(time) {
+            return false;
+        }
+        tracing::debug!(
+            "PMTU: {} consecutive losses at {} bytes, searching again below {}",
+            threshold,
+            size,
+            last_good
+        );
+        self.high = last_good.saturating_sub(1).max(self.config.pmtu_min);
+        self.error_probing = true;
+        true
+    }
+
+    /// Returns the time to wait between probes: `pmtu_interval_ms`, doubled for each
+    /// consecutive probe timeout up to `pmtu_backoff_max_ms` when backoff is enabled.
+    ///
+    /// `pmtu_interval_jitter_pct` is applied on top of this, with a new draw for every probe.
+    pub fn probe_interval(&self) -> Duration {
+        let base = self.config.pmtu_interval_ms as u64;
+        let cap = self.config.pmtu_backoff_max_ms as u64;
+        if cap == 0 {
+            return Duration::from_millis(base);
+        }
+        let backed_off = base.saturating_mul(1 << self.consecutive_failures.min(32));
+        Duration::from_millis(backed_off.min(cap.max(base)))
+    }
+
+    /// Returns a multiplier spreading the next probe interval by up to
+    /// `pmtu_interval_jitter_pct` percent either way, or exactly 1 with jitter disabled.
+    fn draw_interval_jitter(config: &Config, rng: &mut StdRng) -> f64 {
+        let pct = config.pmtu_interval_jitter_pct.min(100);
+        if pct == 0 {
+            return 1.0;
+        }
+        let spread = pct as f64 / 100.0;
+        1.0 + rng.random_range(-spread..=spread)
+    }
+
+    /// Handles a suspected PMTU black hole: falls back to `pmtu_safe_floor` and restarts the
+    /// search, unless the previous black-hole reset was less than `pmtu_blackhole_cooldown_ms`
+    /// ago.
+    ///
+    /// Returns `true` if the fragment size was reset.
+    pub fn handle_blackhole(&mut self, time: Instant) -> bool {
+        if !self.fall_back_to_safe_floor(time) {
+            return false;
+        }
+        tracing::debug!(
+            "PMTU black hole suspected: falling back to {} bytes",
+            self.config.pmtu_safe_floor
+        );
+        true
+    }
+
+    /// Restarts the search from `pmtu_safe_floor`, unless the previous fallback was less than
+    /// `pmtu_blackhole_cooldown_ms` ago. Returns `true` if it fell back.
+    fn fall_back_to_safe_floor(&mut self, time: Instant) -> bool {
+        let cooldown = Duration::from_millis(self.config.pmtu_blackhole_cooldown_ms as u64);
+        if self.last_blackhole_reset.is_some_and(|last| time.duration_since(last) < cooldown) {
+            return false;
+        }
+        self.reset(time);
+        self.update_fragment_size(self.config.pmtu_safe_floor);
+        self.last_blackhole_reset = Some(time);
+        true
+    }
+
+    /// Returns the discovered fragment size and search bounds for persistence.
+    pub fn snapshot(&self) -> PmtuSnapshot {
+        PmtuSnapshot { fragment_size: self.fragment_size, low: self.low, high: self.high }
+    }
+
+    /// Resumes discovery from state captured by [`PmtuDiscovery::snapshot`].
+    ///
+    /// Bounds are clamped to the configured search range. The cached fragment size is used
+    /// right away, but the path may have changed since: once the search has converged, a
+    /// single probe at the low bound confirms it, and if that probe is lost the search
+    /// reopens below it and the fragment size falls back to `pmtu_safe_floor`.
+    pub fn restore(&mut self, snapshot: &PmtuSnapshot, time: Instant) {
+        self.reset(time);
+        self.low = snapshot.low.clamp(self.config.pmtu_min, self.config.pmtu_max);
+        self.high = snapshot.high.clamp(self.low, self.config.pmtu_max);
+        self.update_fragment_size(snapshot.fragment_size);
+        self.verify_restored = true;
+    }
+
+    /// Merges the search state of `other`, a discovery on a connection to the same peer that
+    /// is being coalesced into this one.
+    ///
+    /// The bounds combine conservatively: the larger low bound (a size one of the searches
+    /// confirmed) and the smaller high bound. If `other` has settled, its fragment size is
+    /// adopted unless this search settled on a larger one. This search's outstanding probe,
+    /// counters and configuration are kept.
+    pub fn merge(&mut self, other: &PmtuDiscovery) {
+        self.low = self.low.max(other.low);
+        self.high = self.high.min(other.high).max(self.low);
+        self.confirmed = self.confirmed.max(other.confirmed);
+        if other.is_settled() && (!self.is_settled() || other.fragment_size > self.fragment_size) {
+            self.update_fragment_size(other.fragment_size);
+            self.convergence_confirmed |= other.convergence_confirmed;
+        }
+        self.exponential_step = self.exponential_step.and(other.exponential_step);
+    }
+
+    /// Returns the base minimum MTU for `family`: `pmtu_min`, raised to [`IPV6_MIN_MTU`] for
+    /// IPv6.
+    pub fn min_for_family(config: &Config, family: PmtuAddressFamily) -> u16 {
+        match family {
+            PmtuAddressFamily::V4 => config.pmtu_min,
+            PmtuAddressFamily::V6 => config.pmtu_min.max(IPV6_MIN_MTU),
+        }
+    }
+
+    /// Sets the address family of the probed path. On IPv6 the search never goes below
+    /// [`IPV6_MIN_MTU`], even if `pmtu_min` and `pmtu_safe_floor` are configured lower.
+    pub fn set_address_family(&mut self, family: PmtuAddressFamily) {
+        let min = Self::min_for_family(&self.config, family);
+        self.config.pmtu_min = min;
+        self.config.pmtu_max = self.config.pmtu_max.max(min);
+        self.config.pmtu_safe_floor = self.config.pmtu_safe_floor.max(min);
+        self.low = self.low.max(min);
+        self.high = self.high.max(self.low);
+    }
+
+    /// Holds back new probes while `deferred`, for use while congestion control recovers: a
+    /// large probe would take capacity the path is short of. A probe that times out while
+    /// deferred leaves the search bounds alone. Probes resume once no longer deferred.
+    pub fn set_probes_deferred(&mut self, deferred: bool) {
+        self.probes_deferred = deferred;
+    }
+
+    /// Accounts for `bytes` added to each datagram after encoding, so a probe's size on the
+    /// wire still matches the size it advertises.
+    pub fn set_datagram_overhead(&mut self, bytes: u16) {
+        self.datagram_overhead = bytes;
     }
 
     /// Returns the current effective fragment size in bytes.
@@ -75,7 +628,28 @@ impl PmtuDiscovery {
 
     /// Sets the fragment size to a specific value.
     pub fn set_fragment_size(&mut self, size: u16) {
+        self.update_fragment_size(size);
+    }
+
+    /// Registers `callback` to be called with the old and new size each time the fragment
+    /// size changes, replacing any previous callback.
+    ///
+    /// Called from whichever method moved the size: a reply, a probe timeout, convergence,
+    /// or an explicit reset or restore. Updates that leave the size unchanged are not reported.
+    pub fn on_fragment_size_change(&mut self, callback: impl FnMut(u16, u16) + Send + 'static) {
+        self.on_fragment_size_change = Some(Box::new(callback));
+    }
+
+    /// Sets the fragment size, reporting the change to the registered callback.
+    fn update_fragment_size(&mut self, size: u16) {
+        let old = self.fragment_size;
+        if size == old {
+            return;
+        }
         self.fragment_size = size;
+        if let Some(callback) = &mut self.on_fragment_size_change {
+            callback(old, size);
+        }
     }
 
     /// Returns the current low bound of the PMTU search.
@@ -88,14 +662,125 @@ impl PmtuDiscovery {
         self.high
     }
 
+    /// Returns when [`PmtuDiscovery::handle_pmtu`] will send the next probe, or None while a
+    /// probe is outstanding or no further probe is planned.
+    pub fn next_probe_time(&self) -> Option<Instant> {
+        if !self.config.use_pmtu_discovery
+            || self.gave_up
+            || self.probes_deferred
+            || self.outstanding.is_some()
+            || (self.is_settled() && !self.verify_restored)
+        {
+            return None;
+        }
+        if self.probe_pending {
+            return Some(self.last_probe);
+        }
+        Some(self.last_probe + self.probe_interval().mul_f64(self.interval_jitter))
+    }
+
+    /// Returns when the outstanding probe times out under `rto`, if one is outstanding.
+    pub fn probe_timeout_time(&self, rto: Duration) -> Option<Instant> {
+        self.outstanding.map(|(_, _, sent, _)| sent + rto.max(Duration::from_millis(200)))
+    }
+
+    /// Returns when the search gives up (`pmtu_discovery_timeout_ms`), if it still can.
+    pub fn discovery_timeout_time(&self) -> Option<Instant> {
+        let timeout = self.config.pmtu_discovery_timeout_ms;
+        if !self.config.use_pmtu_discovery || self.gave_up || timeout == 0 || self.is_converged() {
+            return None;
+        }
+        Some(self.started + Duration::from_millis(timeout as u64))
+    }
+
     /// Returns whether there is an outstanding probe.
     pub fn has_outstanding_probe(&self) -> bool {
         self.outstanding.is_some()
     }
 
+    /// Returns whether the search bounds are within the convergence threshold, or with
+    /// `PmtuSearchMode::Schedule` whether no scheduled size is left between them.
+    pub fn is_converged(&self) -> bool {
+        if self.config.pmtu_search_mode == PmtuSearchMode::Schedule {
+            return self.next_scheduled_size().is_none();
+        }
+        self.high.saturating_sub(self.low) <= self.converge_threshold()
+    }
+
+    /// Returns whether the search has settled: converged, and with `pmtu_confirm_on_converge`
+    /// also confirmed by a final probe at the low bound.
+    pub fn is_settled(&self) -> bool {
+        self.is_converged() && (!self.config.pmtu_confirm_on_converge || self.convergence_confirmed)
+    }
+
+    /// Returns how far the search has progressed towards convergence, from 0.0 at the start
+    /// to 1.0 once converged, based on how much `high - low` has narrowed relative to the
+    /// configured range. Meant for status displays.
+    pub fn progress(&self) -> f32 {
+        if self.is_converged() {
+            return 1.0;
+        }
+        let initial = self.config.pmtu_max.saturating_sub(self.config.pmtu_min);
+        let target = initial.saturating_sub(self.converge_threshold());
+        let narrowed = initial.saturating_sub(self.high.saturating_sub(self.low));
+        if target == 0 {
+            return 0.0;
+        }
+        (narrowed as f32 / target as f32).clamp(0.0, 1.0)
+    }
+
+    /// Returns the convergence threshold for the current search range: the absolute
+    /// `pmtu_converge_threshold`, or `pmtu_converge_pct` percent of the midpoint if larger,
+    /// and never less than `pmtu_probe_quantum`.
+    pub fn converge_threshold(&self) -> u16 {
+        let mid = (self.low as u32 + self.high as u32) / 2;
+        let scaled = (mid * self.config.pmtu_converge_pct as u32 / 100) as u16;
+        self.config.pmtu_converge_threshold.max(scaled).max(self.config.pmtu_probe_quantum)
+    }
+
+    /// Returns whether discovery timed out and probing is suspended until `reset()`.
+    pub fn has_given_up(&self) -> bool {
+        self.gave_up
+    }
+
+    /// Returns the diagnosis of the most recent paired probe that timed out.
+    pub fn last_probe_loss(&self) -> Option<PmtuProbeLoss> {
+        self.last_loss
+    }
+
+    /// Returns the probe counters and search state.
+    pub fn stats(&self) -> PmtuStats {
+        let state = self.state();
+        PmtuStats {
+            probes_sent: self.probes_sent,
+            replies: self.replies,
+            timeouts: self.timeouts,
+            retries: self.retries,
+            stray_replies: self.stray_replies,
+            state,
+            converged_size: (state == PmtuState::Converged).then_some(self.fragment_size),
+        }
+    }
+
+    /// Returns how many replies claimed a larger size than their probe was sent with.
+    pub fn protocol_anomalies(&self) -> u32 {
+        self.anomalies
+    }
+
+    /// Takes the fragmentable twin of the outstanding probe (`pmtu_paired_probes`).
+    ///
+    /// The transport must send it in a datagram of its own with the DF bit cleared.
+    pub fn take_fragmentable_probe(&mut self) -> Option<ProtocolCommand> {
+        let probe = self.fragmentable_probe.take()?;
+        if let ProtocolCommand::PMTUProbe { token, .. } = probe {
+            self.fragmentable = Some((token, false));
+        }
+        Some(probe)
+    }
+
     /// Returns the outstanding probe information for testing purposes.
     #[cfg(test)]
-    pub fn outstanding_probe(&self) -> Option<(u16, u32, Instant)> {
+    pub fn outstanding_probe(&self) -> Option<(u16, u32, Instant, u8)> {
         self.outstanding
     }
 
@@ -106,99 +791,551 @@ impl PmtuDiscovery {
     /// - Check for convergence
     /// - Generate new probes when appropriate
     ///
+    /// A probe outstanding for more than [`STALE_PROBE_TIMEOUTS`] timeouts, because the caller
+    /// stopped ticking for a while, is timed out as of when it expired, and the same call goes
+    /// on to decide whether the next probe is due rather than waiting another interval.
+    ///
     /// Returns `Some(ProtocolCommand)` if a new probe should be sent.
     pub fn handle_pmtu(&mut self, time: Instant, rto: Duration) -> Option<ProtocolCommand> {
-        if !self.config.use_pmtu_discovery {
+        if !self.config.use_pmtu_discovery || self.gave_up {
+            return None;
+        }
+
+        // Give up on a search that has not converged within the overall timeout
+        let discovery_timeout = Duration::from_millis(self.config.pmtu_discovery_timeout_ms as u64);
+        if self.config.pmtu_discovery_timeout_ms > 0
+            && !self.is_converged()
+            && time.duration_since(self.started) >= discovery_timeout
+        {
+            self.update_fragment_size(self.confirmed.unwrap_or(self.config.pmtu_safe_floor));
+            self.outstanding = None;
+            self.fragmentable_probe = None;
+            self.fragmentable = None;
+            self.confirming = false;
+            self.gave_up = true;
+            tracing::debug!(
+                "PMTU discovery timed out: pinning fragment size to {}",
+                self.fragment_size
+            );
             return None;
         }
 
         // Timeout outstanding probe
-        if let Some((size, _token, sent)) = self.outstanding {
+        if let Some((size, token, sent, retries)) = self.outstanding {
             let timeout = rto.max(Duration::from_millis(200));
-            if time.duration_since(sent) > timeout {
-                // Consider it failed: reduce high bound
-                if size > 0 {
-                    self.high = self.high.min(size - 1);
-                }
+            let age = time.duration_since(sent);
+            if age <= timeout {
+                return None;
+            }
+            let stale = age > timeout * STALE_PROBE_TIMEOUTS;
+            self.timeouts += 1;
+            if self.probes_deferred {
+                // As likely lost to congestion as to size: leave the bounds for after recovery
+                tracing::debug!("PMTU probe of {} bytes timed out during recovery", size);
                 self.outstanding = None;
+                self.fragmentable_probe = None;
+                self.fragmentable = None;
+                self.confirming = false;
                 self.last_probe = time;
+                return None;
             }
-            return None;
+            // Without a paired diagnosis the loss may be transient: re-send the same probe
+            if self.fragmentable.is_none() && retries < self.config.pmtu_probe_retries {
+                tracing::debug!("PMTU probe of {} bytes timed out, re-sending", size);
+                let target = size.min(self.datagram_cap());
+                self.outstanding = Some((size, token, time, retries + 1));
+                self.last_probe = time;
+                self.probes_sent += 1;
+                self.retries += 1;
+                return Some(self.probe_command(target, token));
+            }
+            let loss = self.fragmentable.take().map(|(_token, replied)| {
+                if replied {
+                    PmtuProbeLoss::TooBig
+                } else {
+                    PmtuProbeLoss::Generic
+                }
+            });
+            // Consider it too big unless the fragmentable twin was lost as well
+            if loss == Some(PmtuProbeLoss::Generic) {
+                // Retry the same probe after the interval
+            } else if self.confirming {
+                // The size that raised `low` was a fluke: reopen the search below it
+                tracing::debug!("PMTU confirmation of {} failed: reopening search", size);
+                self.high = size.saturating_sub(1).max(self.config.pmtu_min);
+                self.low = self.config.pmtu_min;
+                self.confirmed = None;
+                // A restored fragment size was never confirmed on this connection
+                if self.fragment_size > self.high {
+                    self.update_fragment_size(self.config.pmtu_safe_floor);
+                }
+            } else if size > 0 {
+                self.high = self.high.min(size - 1);
+                // The first failure bounds the range: bisect between it and `low`
+                self.exponential_step = None;
+            }
+            self.confirming = false;
+            if loss.is_some() {
+                tracing::debug!("PMTU probe of {} bytes lost: {:?}", size, loss);
+                self.last_loss = loss;
+            }
+            self.fragmentable_probe = None;
+            self.outstanding = None;
+            self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+            if !stale {
+                self.last_probe = time;
+                return None;
+            }
+            // Measure the next interval from the expiry, not from whenever ticking resumed
+            tracing::debug!("PMTU probe of {} bytes went stale after {:?}", size, age);
+            self.last_probe = sent + timeout;
         }
 
         // Clamp high bound to what we can actually send as a single datagram
-        let datagram_cap = self.config.receive_buffer_max_size.min(u16::MAX as usize) as u16;
+        let datagram_cap = self.datagram_cap();
         if self.high > datagram_cap {
             self.high = datagram_cap;
         }
 
-        // Check convergence
-        if self.high.saturating_sub(self.low) <= self.config.pmtu_converge_threshold {
-            self.fragment_size = self.low;
+        // A probe cannot be smaller than its own overhead: search only sizes it can test
+        let min_size = self.min_probe_size();
+        if self.low < min_size {
+            tracing::debug!(
+                "PMTU low bound {} below smallest probe, raising to {}",
+                self.low,
+                min_size
+            );
+            self.low = min_size;
+            self.high = self.high.max(min_size);
+        }
+
+        // Check convergence, confirming the low bound first if configured or restored
+        if self.is_settled() && !self.verify_restored {
+            self.update_fragment_size(self.low);
             return None;
         }
+        let confirming = self.is_converged();
 
         // Time to probe?
-        let interval = Duration::from_millis(self.config.pmtu_interval_ms as u64);
-        if time.duration_since(self.last_probe) < interval {
+        let interval = self.probe_interval().mul_f64(self.interval_jitter);
+        if !self.probe_pending && time.duration_since(self.last_probe) < interval {
+            return None;
+        }
+        if self.probes_deferred {
             return None;
         }
 
         // Next candidate: mid (clamped to what we can actually send in one datagram)
-        let mid = ((self.low as u32 + self.high as u32) / 2) as u16;
+        let mid = if confirming { self.low } else { self.next_probe_size() };
         let target = mid.min(datagram_cap);
 
-        // Compute payload length so total encoded datagram size ~= target
+        let token = self.next_probe_token();
+        let command = self.probe_command(target, token);
+
+        if self.config.pmtu_paired_probes && !self.config.pmtu_reflected_probes {
+            let token = self.next_probe_token();
+            self.fragmentable_probe = Some(self.probe_command(target, token));
+        }
+
+        self.outstanding = Some((mid, token, time, 0));
+        self.confirming = confirming;
+        if confirming {
+            self.verify_restored = false;
+        }
+        self.last_probe = time;
+        self.probe_pending = false;
+        self.interval_jitter = Self::draw_interval_jitter(&self.config, &mut self.rng);
+        self.probes_sent += 1;
+
+        Some(command)
+    }
+
+    /// Handles the probing state machine for everything that happened up to `time`, however
+    /// long ago the previous call was.
+    ///
+    /// [`PmtuDiscovery::handle_pmtu`] notices a lost probe on one call and sends the next
+    /// probe on a later one, so its cadence depends on how often it is called. This processes
+    /// a timeout as of when it expired, then sends the next probe if its interval has also
+    /// passed. At most one probe is returned, never a backlog.
+    pub fn handle_pmtu_batch(&mut self, time: Instant, rto: Duration) -> Option<ProtocolCommand> {
+        if let Some((_size, _token, sent, _retries)) = self.outstanding {
+            let expired = sent + rto.max(Duration::from_millis(200)) + Duration::from_millis(1);
+            if expired < time {
+                // A re-sent probe goes out now, so its own timeout runs from `time`
+                if let Some(probe) = self.handle_pmtu(expired, rto) {
+                    if let Some((_size, _token, sent, _retries)) = &mut self.outstanding {
+                        *sent = time;
+                    }
+                    self.last_probe = time;
+                    return Some(probe);
+                }
+            }
+        }
+        self.handle_pmtu(time, rto)
+    }
+
+    /// Returns the largest datagram that can be sent, bounded by `receive_buffer_max_size`.
+    fn datagram_cap(&self) -> u16 {
+        self.config.receive_buffer_max_size.min(u16::MAX as usize) as u16
+    }
+
+    /// Picks the next probe size within the search bounds.
+    ///
+    /// During the exponential phase this is `low` plus the current step, capped at `high`.
+    /// Otherwise it prefers the configured candidate size closest to the binary-search
+    /// midpoint, falling back to the midpoint when no candidate lies in `(low, high]`. The
+    /// result is rounded to `pmtu_probe_quantum`. A scheduled size is probed as is.
+    fn next_probe_size(&self) -> u16 {
+        if self.config.pmtu_search_mode == PmtuSearchMode::Schedule {
+            return self.next_scheduled_size().unwrap_or(self.low);
+        }
+        if let Some(step) = self.exponential_step {
+            return self.quantize(self.low.saturating_add(step).min(self.high));
+        }
+        let mid = ((self.low as u32 + self.high as u32) / 2) as u16;
+        let size = self
+            .config
+            .pmtu_candidate_sizes
+            .iter()
+            .copied()
+            .filter(|&size| size > self.low && size <= self.high)
+            .min_by_key(|&size| size.abs_diff(mid))
+            .unwrap_or(mid);
+        self.quantize(size)
+    }
+
+    /// Returns the largest of `pmtu_candidate_sizes` within `(low, high]`, the next size
+    /// `PmtuSearchMode::Schedule` probes.
+    fn next_scheduled_size(&self) -> Option<u16> {
+        self.config
+            .pmtu_candidate_sizes
+            .iter()
+            .copied()
+            .filter(|&size| size > self.low && size <= self.high)
+            .max()
+    }
+
+    /// Rounds `size` to the nearest multiple of `pmtu_probe_quantum` within `(low, high]`.
+    ///
+    /// Such a multiple exists whenever `high - low` exceeds the quantum, which holds until the
+    /// search converges (see [`PmtuDiscovery::converge_threshold`]).
+    fn quantize(&self, size: u16) -> u16 {
+        let quantum = self.config.pmtu_probe_quantum as u32;
+        if quantum <= 1 {
+            return size;
+        }
+        let first = (self.low as u32 / quantum + 1) * quantum;
+        let last = self.high as u32 / quantum * quantum;
+        if first > last {
+            return size;
+        }
+        ((size as u32 + quantum / 2) / quantum * quantum).clamp(first, last) as u16
+    }
+
+    /// Returns a token for the next probe: the stream ID in the high bits over a random value,
+    /// or a counter with `pmtu_sequential_tokens`.
+    fn next_probe_token(&mut self) -> u32 {
+        let value = if self.config.pmtu_sequential_tokens {
+            let token = self.next_token;
+            self.next_token = self.next_token.wrapping_add(1) & PROBE_TOKEN_COUNTER_MASK;
+            token
+        } else {
+            self.rng.random::<u32>() & PROBE_TOKEN_COUNTER_MASK
+        };
+        ((self.stream as u32) << PROBE_TOKEN_COUNTER_BITS) | value
+    }
+
+    /// Returns the command that tests `target` bytes: a probe of that size, or with
+    /// `pmtu_reflected_probes` a request for the remote to send one back.
+    fn probe_command(&mut self, target: u16, token: u32) -> ProtocolCommand {
+        if self.config.pmtu_reflected_probes {
+            let padding = (target as usize).div_ceil(REFLECTED_PROBE_AMPLIFICATION) as u16;
+            return ProtocolCommand::PMTUProbeRequest { size: target, token, padding };
+        }
+        let payload_len = Self::probe_payload_len(&self.config, target, self.datagram_overhead);
+        Self::assemble_probe(&self.config, target, token, payload_len, &mut self.rng)
+    }
+
+    /// Builds a well-formed PMTUProbe whose encoded datagram is `target` bytes under `config`,
+    /// with a payload drawn from `rng` according to `pmtu_probe_payload_mode`.
+    ///
+    /// Useful for interop tools and fuzzers that need probes without running discovery.
+    pub fn build_probe(
+        config: &Config,
+        target: u16,
+        token: u32,
+        rng: &mut impl RngCore,
+    ) -> ProtocolCommand {
+        let payload_len = Self::probe_payload_len(config, target, 0);
+        Self::assemble_probe(config, target, token, payload_len, rng)
+    }
+
+    /// Returns the payload length that makes a PMTUProbe datagram `target` bytes in total,
+    /// given `datagram_overhead` bytes added after encoding. Never less than 1.
+    pub fn probe_payload_len(config: &Config, target: u16, datagram_overhead: u16) -> usize {
+        let total_overhead = Self::probe_overhead(config, target, datagram_overhead);
+
+        // Ensure at least 1 byte payload to avoid degenerate probes
+        (if target > total_overhead { (target - total_overhead).max(1) } else { 1 }) as usize
+    }
+
+    /// Returns the bytes a PMTUProbe datagram of `target` bytes carries besides its payload.
+    ///
+    /// Probes below `compression_threshold` are sent uncompressed and skip the LZ4 size field
+    /// or Zstd frame.
+    fn probe_overhead(config: &Config, target: u16, datagram_overhead: u16) -> u16 {
         // Total datagram size = static_overhead (packet-level) + per-command length prefix
         //                      + PMTUProbe header (type + size + token + payload_len) + payload_len
-        let compression_overhead = match self.config.compression {
-            bitfold_core::config::CompressionAlgorithm::Lz4 => 5, // 1 marker + 4 original size
-            _ => 1,                                               // 1 marker for None/Zlib
+        let static_overhead = if config.trusted_path {
+            CommandEncoder::packet_overhead_for_config(config)
+        } else {
+            CommandEncoder::packet_overhead_for_size(
+                config.compression,
+                config.compression_threshold,
+                config.checksum_algorithm(),
+                target.saturating_sub(datagram_overhead) as usize,
+            )
         } as u16;
-        let checksum_overhead = if self.config.use_checksums { 4 } else { 0 } as u16;
-        let static_overhead = 1 /* command count */ + compression_overhead + checksum_overhead;
         let per_command_overhead = 2 /* len prefix */ + (1 /* type */ + 2 /* size */ + 4 /* token */ + 2 /* payload len */);
-        let total_overhead = static_overhead + per_command_overhead;
+        static_overhead + per_command_overhead + datagram_overhead
+    }
 
-        // Ensure at least 1 byte payload to avoid degenerate probes
-        let payload_len =
-            if target > total_overhead { (target - total_overhead).max(1) } else { 1 } as usize;
-        let token: u32 = rand::random();
-        // Fill payload with random bytes to avoid being shrunk by compression
-        let mut payload_vec = vec![0u8; payload_len];
-        rand::rng().fill_bytes(&mut payload_vec);
-        let payload = SharedBytes::from_vec(payload_vec);
+    /// Returns the smallest datagram that can carry a probe of exactly its advertised size:
+    /// the probe overhead plus the smallest payload the payload mode allows.
+    fn min_probe_size(&self) -> u16 {
+        let min_payload = match self.config.pmtu_probe_payload_mode {
+            PmtuProbePayload::Verifiable => VERIFIABLE_HEADER_LEN as u16,
+            _ => 1,
+        };
+        // The smallest probe may be too small to be compressed; size it uncompressed first
+        let uncompressed = Self::probe_overhead(&self.config, 0, self.datagram_overhead);
+        Self::probe_overhead(&self.config, uncompressed + min_payload, self.datagram_overhead)
+            + min_payload
+    }
 
+    /// Assembles a probe advertising `target` with a `payload_len`-byte payload.
+    fn assemble_probe(
+        config: &Config,
+        target: u16,
+        token: u32,
+        payload_len: usize,
+        rng: &mut impl RngCore,
+    ) -> ProtocolCommand {
+        let payload_len = match config.pmtu_probe_payload_mode {
+            PmtuProbePayload::Verifiable => payload_len.max(VERIFIABLE_HEADER_LEN),
+            _ => payload_len,
+        };
+        let mut payload = vec![0u8; payload_len];
+        match &config.pmtu_probe_payload_mode {
+            // Random bytes avoid being shrunk by compression
+            PmtuProbePayload::Random => rng.fill_bytes(&mut payload),
+            PmtuProbePayload::Zeroed => {}
+            PmtuProbePayload::Pattern(pattern) => {
+                for (byte, value) in payload.iter_mut().zip(pattern.iter().cycle()) {
+                    *byte = *value;
+                }
+            }
+            PmtuProbePayload::Verifiable => {
+                payload[..2].copy_from_slice(&target.to_be_bytes());
+                payload[2..4].copy_from_slice(&(payload_len as u16).to_be_bytes());
+                for (i, byte) in payload.iter_mut().enumerate().skip(VERIFIABLE_HEADER_LEN) {
+                    *byte = Self::verifiable_pattern(token, i);
+                }
+            }
+        }
         // Use `target` as the advertised size (intended datagram size)
-        let command = ProtocolCommand::PMTUProbe { size: target, token, payload };
+        ProtocolCommand::PMTUProbe { size: target, token, payload: SharedBytes::from_vec(payload) }
+    }
 
-        self.outstanding = Some((mid, token, time));
-        self.last_probe = time;
+    /// Returns the byte at `index` of a verifiable payload for the probe carrying `token`.
+    fn verifiable_pattern(token: u32, index: usize) -> u8 {
+        (token as u8).wrapping_add(index as u8)
+    }
 
-        Some(command)
+    /// Returns whether a received probe arrived intact and may be answered.
+    ///
+    /// Only checked when `pmtu_probe_payload_mode` is [`PmtuProbePayload::Verifiable`]: the
+    /// payload must carry the probe's advertised size, be as long as it declares and hold the
+    /// token's pattern. Probes are accepted as-is in every other mode.
+    pub fn verify_probe(config: &Config, size: u16, token: u32, payload: &[u8]) -> bool {
+        if config.pmtu_probe_payload_mode != PmtuProbePayload::Verifiable {
+            return true;
+        }
+        if payload.len() < VERIFIABLE_HEADER_LEN {
+            return false;
+        }
+        let target = u16::from_be_bytes([payload[0], payload[1]]);
+        let declared_len = u16::from_be_bytes([payload[2], payload[3]]) as usize;
+        target == size
+            && declared_len == payload.len()
+            && payload
+                .iter()
+                .enumerate()
+                .skip(VERIFIABLE_HEADER_LEN)
+                .all(|(i, &byte)| byte == Self::verifiable_pattern(token, i))
     }
 
     /// Processes a PMTUReply command.
     ///
-    /// Returns `true` if the reply was valid and processed successfully.
+    /// Returns `true` if the reply was valid and processed successfully. Replies whose token
+    /// belongs to another stream are ignored. A reply claiming a larger size than its probe
+    /// was sent with (counted in [`PmtuDiscovery::protocol_anomalies`]) or a smaller size than
+    /// the current low bound is rejected, leaving the probe outstanding. A reply matching no
+    /// outstanding probe is counted in [`PmtuStats::stray_replies`].
     pub fn process_reply(&mut self, size: u16, token: u32, time: Instant) -> bool {
-        if let Some((_pending_size, pending_token, _sent)) = self.outstanding {
+        if probe_token_stream(token) != self.stream {
+            return false;
+        }
+        if let Some((fragmentable_token, replied)) = &mut self.fragmentable {
+            if *fragmentable_token == token {
+                // Delivery with fragmentation allowed says nothing about the path MTU
+                *replied = true;
+                return true;
+            }
+        }
+        if let Some((pending_size, pending_token, _sent, _retries)) = self.outstanding {
             if pending_token == token {
+                // Only the size actually sent was tested; a larger claim is not trusted
+                if size > pending_size {
+                    self.anomalies += 1;
+                    tracing::warn!(
+                        "PMTU reply claims size {} for a {}-byte probe, rejecting",
+                        size,
+                        pending_size
+                    );
+                    return false;
+                }
+                if size < self.low {
+                    tracing::debug!("Ignoring stale PMTU reply: size {} below {}", size, self.low);
+                    return false;
+                }
                 // Success: raise low bound and update effective fragment size
-                self.low = self.low.max(size);
-                self.fragment_size = self.low;
+                self.low = size;
+                if self.confirming {
+                    self.convergence_confirmed = true;
+                }
+                // With confirmation, the fragment size is only adopted once confirmed
+                if !self.config.pmtu_confirm_on_converge || self.convergence_confirmed {
+                    self.update_fragment_size(self.low);
+                }
+                self.confirming = false;
+                self.confirmed = Some(self.low);
+                self.replies += 1;
+                self.consecutive_failures = 0;
+                self.losses_at_size = 0;
+                self.exponential_step = self.exponential_step.map(|step| step.saturating_mul(2));
                 self.outstanding = None;
+                self.fragmentable_probe = None;
+                self.fragmentable = None;
                 self.last_probe = time;
                 tracing::debug!("PMTU success: token={}, size={}", token, size);
                 return true;
             }
         }
+        // No timed-out token history is kept, so a late reply cannot be credited
+        self.stray_replies += 1;
+        tracing::debug!("Stray PMTU reply: token={}, size={}", token, size);
         false
     }
 
+    /// Records that a data datagram of `size` bytes was acknowledged at `time`, which proves
+    /// the path carries it just as a probe reply would. Raises the low bound (and the
+    /// fragment size) to `size` if it is larger, returning true if so.
+    ///
+    /// An outstanding probe no larger than `size` is dropped since it can no longer raise the
+    /// bound, and a high bound below `size` (lowered after a probe loss that was not size
+    /// related) is raised to it. Does not count as a probe reply in [`PmtuDiscovery::stats`].
+    ///
+    /// A datagram larger than `pmtu_safe_floor` getting through also ends a run of losses
+    /// counted by [`PmtuDiscovery::report_loss_at_size`].
+    pub fn observe_acked_size(&mut self, size: u16, time: Instant) -> bool {
+        if size > self.config.pmtu_safe_floor {
+            self.losses_at_size = 0;
+        }
+        let size = size.min(self.config.pmtu_max);
+        if size <= self.low {
+            return false;
+        }
+        self.low = size;
+        self.high = self.high.max(size);
+        self.update_fragment_size(self.low);
+        self.confirmed = Some(self.low);
+        self.consecutive_failures = 0;
+        self.losses_at_size = 0;
+        if self.outstanding.is_some_and(|(pending_size, ..)| pending_size <= size) {
+            self.outstanding = None;
+            self.fragmentable_probe = None;
+            self.fragmentable = None;
+            self.confirming = false;
+            self.last_probe = time;
+        }
+        tracing::debug!("PMTU low bound raised to {} by acknowledged data", size);
+        true
+    }
+
+    /// Returns whether a reply to a probe received at `time` is within
+    /// `pmtu_max_replies_per_sec`, counting it if so.
+    pub fn allow_reply(&mut self, time: Instant) -> bool {
+        let limit = self.config.pmtu_max_replies_per_sec;
+        if limit == 0 {
+            return true;
+        }
+        if time.saturating_duration_since(self.reply_window_start) >= Duration::from_secs(1) {
+            self.reply_window_start = time;
+            self.replies_in_window = 0;
+        }
+        if self.replies_in_window < limit {
+            self.replies_in_window += 1;
+            true
+        } else {
+            self.suppressed_replies += 1;
+            false
+        }
+    }
+
+    /// Returns how many received probes went unanswered because of the reply rate limit.
+    pub fn suppressed_replies(&self) -> u64 {
+        self.suppressed_replies
+    }
+
+    /// Processes a received PMTUProbe as the answer to an outstanding PMTUProbeRequest
+    /// (`pmtu_reflected_probes`).
+    ///
+    /// Returns `true` if the probe answered this search's request, in which case it must not
+    /// be replied to; otherwise it is an ordinary probe from the remote's own search.
+    pub fn process_reflected_probe(&mut self, size: u16, token: u32, time: Instant) -> bool {
+        let requested = self
+            .outstanding
+            .is_some_and(|(_size, pending_token, _sent, _retries)| pending_token == token);
+        if !self.config.pmtu_reflected_probes || !requested {
+            return false;
+        }
+        self.process_reply(size, token, time);
+        true
+    }
+
+    /// Creates the probe answering a PMTUProbeRequest that arrived in a datagram of
+    /// `request_bytes`: `size` bytes on the wire (capped at what a single datagram can carry),
+    /// with the request's token. Returns None if the probe would exceed
+    /// [`REFLECTED_PROBE_AMPLIFICATION`] times the request datagram.
+    pub fn create_reflected_probe(
+        &mut self,
+        size: u16,
+        token: u32,
+        request_bytes: usize,
+    ) -> Option<ProtocolCommand> {
+        let target = size.min(self.datagram_cap());
+        if target as usize > request_bytes.saturating_mul(REFLECTED_PROBE_AMPLIFICATION) {
+            return None;
+        }
+        let payload_len = Self::probe_payload_len(&self.config, target, self.datagram_overhead);
+        Some(Self::assemble_probe(&self.config, target, token, payload_len, &mut self.rng))
+    }
+
     /// Creates a PMTUReply command for a received probe.
     ///
-    /// This should be called when receiving a PMTUProbe command.
+    /// This should be called when receiving a PMTUProbe command, regardless of whether
+    /// discovery is enabled locally.
     pub fn create_reply(size: u16, token: u32) -> ProtocolCommand {
         ProtocolCommand::PMTUReply { size, token }
     }
@@ -233,7 +1370,7 @@ mod tests {
 
         // Simulate successful reply
         if let Some(outstanding) = pmtu.outstanding {
-            let (size, token, _) = outstanding;
+            let (size, token, _, _) = outstanding;
             let success = pmtu.process_reply(size, token, time);
             assert!(success);
             // After successful reply, low should be updated
@@ -249,6 +1386,7 @@ mod tests {
         config.pmtu_min = 576;
         config.pmtu_max = 1400;
         config.pmtu_interval_ms = 100;
+        config.pmtu_probe_retries = 0;
 
         let start_time = Instant::now();
         let mut pmtu = PmtuDiscovery::new(&config, start_time);
@@ -274,11 +1412,109 @@ mod tests {
     }
 
     #[test]
-    fn test_pmtu_discovery_enabled_by_default() {
-        let config = Config::default();
-        assert!(config.use_pmtu_discovery);
+    fn test_timed_out_probe_resent_before_lowering_high() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        config.pmtu_probe_retries = 2;
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        let rto = Duration::from_millis(200);
 
-        let creation_time = Instant::now();
+        let mut time = start + Duration::from_millis(150);
+        pmtu.handle_pmtu(time, rto).unwrap();
+        let (size, token, _, _) = pmtu.outstanding_probe().unwrap();
+        let high_before = pmtu.high_bound();
+
+        // Each timeout re-sends the same probe while retries remain
+        for retry in 1..=2 {
+            time += Duration::from_millis(250);
+            let resent = pmtu.handle_pmtu(time, rto);
+            assert!(matches!(
+                resent,
+                Some(ProtocolCommand::PMTUProbe { size: s, token: t, .. }) if s == size && t == token
+            ));
+            assert_eq!(pmtu.outstanding_probe().unwrap().3, retry);
+            assert_eq!(pmtu.high_bound(), high_before);
+        }
+
+        // Retries exhausted: the size is judged too big
+        time += Duration::from_millis(250);
+        assert!(pmtu.handle_pmtu(time, rto).is_none());
+        assert!(!pmtu.has_outstanding_probe());
+        assert_eq!(pmtu.high_bound(), size - 1);
+    }
+
+    #[test]
+    fn test_reply_to_resent_probe_raises_low() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        let rto = Duration::from_millis(200);
+
+        let time = start + Duration::from_millis(150);
+        pmtu.handle_pmtu(time, rto).unwrap();
+        let (size, token, _, _) = pmtu.outstanding_probe().unwrap();
+        let high_before = pmtu.high_bound();
+
+        // A late batch call re-sends the probe as of now
+        let later = time + Duration::from_secs(5);
+        assert!(pmtu.handle_pmtu_batch(later, rto).is_some());
+        assert_eq!(pmtu.outstanding_probe().unwrap().2, later);
+
+        assert!(pmtu.process_reply(size, token, later));
+        assert_eq!(pmtu.low_bound(), size);
+        assert_eq!(pmtu.high_bound(), high_before);
+    }
+
+    #[test]
+    fn test_acked_data_raises_low_without_probe() {
+        let config = Config::default();
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        let low = pmtu.low_bound();
+        let high = pmtu.high_bound();
+
+        assert!(!pmtu.observe_acked_size(low, start));
+        assert!(pmtu.observe_acked_size(low + 300, start));
+        assert_eq!(pmtu.low_bound(), low + 300);
+        assert_eq!(pmtu.high_bound(), high);
+        assert_eq!(pmtu.current_fragment_size(), low + 300);
+        assert!(!pmtu.has_outstanding_probe());
+        assert_eq!(pmtu.stats().replies, 0);
+
+        // Smaller deliveries say nothing new
+        assert!(!pmtu.observe_acked_size(low + 100, start));
+        assert_eq!(pmtu.low_bound(), low + 300);
+    }
+
+    #[test]
+    fn test_acked_data_supersedes_smaller_outstanding_probe() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        let rto = Duration::from_millis(200);
+
+        let time = start + Duration::from_millis(150);
+        pmtu.handle_pmtu(time, rto).unwrap();
+        let (size, token, _, _) = pmtu.outstanding_probe().unwrap();
+
+        assert!(pmtu.observe_acked_size(size + 10, time));
+        assert!(!pmtu.has_outstanding_probe());
+        assert_eq!(pmtu.low_bound(), size + 10);
+
+        // The superseded probe's reply is now stray
+        assert!(!pmtu.process_reply(size, token, time));
+        assert_eq!(pmtu.low_bound(), size + 10);
+    }
+
+    #[test]
+    fn test_pmtu_discovery_enabled_by_default() {
+        let config = Config::default();
+        assert!(config.use_pmtu_discovery);
+
+        let creation_time = Instant::now();
         let mut pmtu = PmtuDiscovery::new(&config, creation_time);
 
         // Wait for the PMTU interval to elapse before checking for probes
@@ -364,4 +1600,1376 @@ mod tests {
             _ => panic!("Expected PMTUReply command"),
         }
     }
+
+    fn timeout_config() -> Config {
+        let mut config = Config::default();
+        config.use_pmtu_discovery = true;
+        config.pmtu_min = 576;
+        config.pmtu_max = 1400;
+        config.pmtu_interval_ms = 100;
+        config.pmtu_converge_threshold = 8;
+        config.pmtu_discovery_timeout_ms = 1000;
+        config.pmtu_safe_floor = 600;
+        config
+    }
+
+    #[test]
+    fn test_pmtu_discovery_timeout_pins_safe_floor() {
+        let config = timeout_config();
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        let rto = Duration::from_millis(200);
+
+        // Every probe is lost; the search keeps going until the overall timeout
+        let mut time = start;
+        let mut probes = 0;
+        while time < start + Duration::from_millis(900) {
+            time += Duration::from_millis(50);
+            if pmtu.handle_pmtu(time, rto).is_some() {
+                probes += 1;
+            }
+        }
+        assert!(probes > 0);
+        assert!(!pmtu.is_converged());
+        assert!(!pmtu.has_given_up());
+
+        // Past the timeout, discovery gives up and pins the safe floor
+        time = start + Duration::from_millis(1000);
+        assert!(pmtu.handle_pmtu(time, rto).is_none());
+        assert!(pmtu.has_given_up());
+        assert!(!pmtu.has_outstanding_probe());
+        assert_eq!(pmtu.current_fragment_size(), config.pmtu_safe_floor);
+
+        // No further probes are generated
+        for _ in 0..100 {
+            time += Duration::from_millis(100);
+            assert!(pmtu.handle_pmtu(time, rto).is_none());
+        }
+    }
+
+    #[test]
+    fn test_pmtu_discovery_timeout_pins_confirmed_size() {
+        let config = timeout_config();
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        let rto = Duration::from_millis(200);
+
+        // First probe is confirmed
+        let time = start + Duration::from_millis(150);
+        assert!(pmtu.handle_pmtu(time, rto).is_some());
+        let (size, token, _, _) = pmtu.outstanding_probe().unwrap();
+        assert!(pmtu.process_reply(size, token, time));
+
+        // Subsequent probes are lost until the timeout expires
+        let time = start + Duration::from_millis(1000);
+        assert!(pmtu.handle_pmtu(time, rto).is_none());
+        assert!(pmtu.has_given_up());
+        assert_eq!(pmtu.current_fragment_size(), size);
+    }
+
+    #[test]
+    fn test_pmtu_discovery_reset_resumes_probing() {
+        let config = timeout_config();
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        let rto = Duration::from_millis(200);
+
+        let time = start + Duration::from_millis(1000);
+        assert!(pmtu.handle_pmtu(time, rto).is_none());
+        assert!(pmtu.has_given_up());
+
+        pmtu.reset(time);
+        assert!(!pmtu.has_given_up());
+        assert_eq!(pmtu.low_bound(), config.pmtu_min);
+        assert_eq!(pmtu.high_bound(), config.pmtu_max);
+        assert!(pmtu.handle_pmtu(time + Duration::from_millis(150), rto).is_some());
+    }
+
+    #[test]
+    fn test_pmtu_discovery_timeout_disabled_by_default() {
+        let config = Config::default();
+        assert_eq!(config.pmtu_discovery_timeout_ms, 0);
+
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        let rto = Duration::from_millis(200);
+
+        // Long after any plausible timeout, an unconverged search keeps probing
+        let time = start + Duration::from_secs(3600);
+        assert!(pmtu.handle_pmtu(time, rto).is_some());
+        assert!(!pmtu.has_given_up());
+    }
+
+    /// Drives a search against a simulated path that drops probes larger than `path_mtu`.
+    /// Returns the number of probes sent and the resulting fragment size.
+    fn simulate_search(config: &Config, path_mtu: u16) -> (usize, u16) {
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(config, start);
+        let rto = Duration::from_millis(200);
+        let step = Duration::from_millis(config.pmtu_interval_ms as u64 + 250);
+        let mut time = start;
+        let mut probes = 0;
+
+        while !pmtu.is_converged() && probes < 64 {
+            time += step;
+            if let Some(ProtocolCommand::PMTUProbe { size, token, .. }) =
+                pmtu.handle_pmtu(time, rto)
+            {
+                probes += 1;
+                if size <= path_mtu {
+                    pmtu.process_reply(size, token, time);
+                }
+            }
+        }
+        pmtu.handle_pmtu(time + step, rto);
+        (probes, pmtu.current_fragment_size())
+    }
+
+    #[test]
+    fn test_candidate_sizes_converge_in_fewer_probes() {
+        let mut config = Config::default();
+        config.pmtu_probe_retries = 0;
+        config.pmtu_interval_ms = 100;
+        let (binary_probes, binary_size) = simulate_search(&config, 1280);
+
+        config.pmtu_candidate_sizes = vec![1500, 1492, 1400, 1280];
+        let (candidate_probes, candidate_size) = simulate_search(&config, 1280);
+
+        assert!(candidate_probes < binary_probes);
+        assert_eq!(candidate_size, 1280);
+        assert!(binary_size <= 1280);
+    }
+
+    #[test]
+    fn test_candidate_sizes_fall_back_to_midpoint() {
+        let mut config = Config::default();
+        // No candidate lies within (pmtu_min, pmtu_max]
+        config.pmtu_candidate_sizes = vec![1500, 1492];
+        let pmtu = PmtuDiscovery::new(&config, Instant::now());
+
+        let mid = ((config.pmtu_min as u32 + config.pmtu_max as u32) / 2) as u16;
+        assert_eq!(pmtu.next_probe_size(), mid);
+    }
+
+    #[test]
+    fn test_build_probe_payload_matches_overhead() {
+        use bitfold_core::config::CompressionAlgorithm;
+
+        // Packet overhead (count + compression marker [+ LZ4 size or Zstd frame] [+ checksum])
+        // plus 11 bytes
+        // of length prefix and probe header
+        for (compression, use_checksums, overhead) in [
+            (CompressionAlgorithm::None, false, 13),
+            (CompressionAlgorithm::None, true, 17),
+            (CompressionAlgorithm::Lz4, false, 17),
+            (CompressionAlgorithm::Lz4, true, 21),
+            (CompressionAlgorithm::Zstd { level: 3 }, false, 23),
+        ] {
+            let mut config = Config::default();
+            config.compression = compression;
+            config.use_checksums = use_checksums;
+            let mut rng = StdRng::seed_from_u64(1);
+            match PmtuDiscovery::build_probe(&config, 1200, 42, &mut rng) {
+                ProtocolCommand::PMTUProbe { size, token, payload } => {
+                    assert_eq!((size, token), (1200, 42));
+                    assert_eq!(payload.len(), 1200 - overhead);
+                }
+                other => panic!("Expected PMTUProbe, got {:?}", other),
+            }
+            assert_eq!(PmtuDiscovery::probe_payload_len(&config, 1200, 28), 1200 - overhead - 28);
+            assert_eq!(PmtuDiscovery::probe_payload_len(&config, 10, 0), 1);
+        }
+
+        // Probes below the compression threshold are sent uncompressed, so LZ4 adds nothing
+        let mut config = Config::default();
+        config.compression = CompressionAlgorithm::Lz4;
+        config.compression_threshold = 2000;
+        assert_eq!(PmtuDiscovery::probe_payload_len(&config, 1200, 0), 1200 - 17);
+
+        // xxHash64 adds 8 bytes instead of 4
+        config.checksum = bitfold_core::config::ChecksumAlgorithm::XxHash64;
+        assert_eq!(PmtuDiscovery::probe_payload_len(&config, 1200, 0), 1200 - 21);
+    }
+
+    #[test]
+    fn test_min_probe_size_below_compression_threshold() {
+        use bitfold_core::config::CompressionAlgorithm;
+
+        let mut config = Config::default();
+        config.compression = CompressionAlgorithm::Lz4;
+        let pmtu = PmtuDiscovery::new(&config, Instant::now());
+        // 17 bytes of overhead without the LZ4 size field, plus a 1-byte payload
+        assert_eq!(pmtu.min_probe_size(), 18);
+
+        config.compression_threshold = 0;
+        let pmtu = PmtuDiscovery::new(&config, Instant::now());
+        assert_eq!(pmtu.min_probe_size(), 22);
+    }
+
+    #[test]
+    fn test_snapshot_restore_resumes_search() {
+        let config = Config::default();
+        let time = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, time);
+        pmtu.low = 1200;
+        pmtu.high = 1300;
+        pmtu.fragment_size = 1200;
+        let snapshot = pmtu.snapshot();
+
+        let mut restored = PmtuDiscovery::new(&config, time);
+        restored.restore(&snapshot, time);
+        assert_eq!(restored.snapshot(), snapshot);
+        assert_eq!(restored.current_fragment_size(), 1200);
+
+        // Out-of-range bounds are clamped to the configured range
+        restored.restore(&PmtuSnapshot { fragment_size: 9000, low: 9000, high: 9000 }, time);
+        assert_eq!(restored.low_bound(), config.pmtu_max);
+        assert_eq!(restored.high_bound(), config.pmtu_max);
+    }
+
+    fn probe_payload_for(mode: PmtuProbePayload) -> Vec<u8> {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        config.pmtu_probe_payload_mode = mode;
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+
+        match pmtu.handle_pmtu(start + Duration::from_millis(150), Duration::from_millis(200)) {
+            Some(ProtocolCommand::PMTUProbe { payload, .. }) => payload.as_slice().to_vec(),
+            other => panic!("Expected PMTUProbe, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_probe_payload_random_by_default() {
+        assert_eq!(Config::default().pmtu_probe_payload_mode, PmtuProbePayload::Random);
+        let payload = probe_payload_for(PmtuProbePayload::Random);
+        assert!(payload.len() > 64);
+        assert!(payload.iter().any(|&byte| byte != 0));
+    }
+
+    #[test]
+    fn test_probe_payload_zeroed() {
+        let payload = probe_payload_for(PmtuProbePayload::Zeroed);
+        assert!(!payload.is_empty());
+        assert!(payload.iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn test_probe_payload_pattern() {
+        let payload = probe_payload_for(PmtuProbePayload::Pattern(vec![0xDE, 0xAD, 0xBE]));
+        assert!(payload.len() > 3);
+        for (i, &byte) in payload.iter().enumerate() {
+            assert_eq!(byte, [0xDE, 0xAD, 0xBE][i % 3]);
+        }
+
+        // An empty pattern degrades to a zeroed payload
+        let payload = probe_payload_for(PmtuProbePayload::Pattern(Vec::new()));
+        assert!(payload.iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn test_verifiable_probe_checked_by_receiver() {
+        let mut config = Config::default();
+        config.pmtu_probe_payload_mode = PmtuProbePayload::Verifiable;
+        let mut rng = StdRng::seed_from_u64(1);
+        let probe = PmtuDiscovery::build_probe(&config, 1200, 0x1234, &mut rng);
+        let ProtocolCommand::PMTUProbe { size, token, payload } = probe else {
+            panic!("Expected PMTUProbe");
+        };
+        let payload = payload.as_slice();
+        assert_eq!(&payload[..2], &1200u16.to_be_bytes());
+        assert!(PmtuDiscovery::verify_probe(&config, size, token, payload));
+
+        // Truncated, mislabelled or corrupted probes are rejected
+        assert!(!PmtuDiscovery::verify_probe(&config, size, token, &payload[..payload.len() - 1]));
+        assert!(!PmtuDiscovery::verify_probe(&config, size, token, &payload[..2]));
+        assert!(!PmtuDiscovery::verify_probe(&config, 1400, token, payload));
+        let mut corrupted = payload.to_vec();
+        corrupted[100] ^= 0xFF;
+        assert!(!PmtuDiscovery::verify_probe(&config, size, token, &corrupted));
+
+        // Other modes accept any payload
+        assert!(PmtuDiscovery::verify_probe(&Config::default(), size, token, &payload[..2]));
+    }
+
+    #[test]
+    fn test_sequential_tokens_increment_and_match_replies() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        config.pmtu_sequential_tokens = true;
+        let mut time = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, time);
+        let rto = Duration::from_millis(200);
+
+        for expected in 0..3u32 {
+            time += Duration::from_millis(150);
+            let Some(ProtocolCommand::PMTUProbe { size, token, .. }) = pmtu.handle_pmtu(time, rto)
+            else {
+                panic!("Expected PMTUProbe");
+            };
+            assert_eq!(token, expected);
+
+            // A stale token is ignored, the current one matches
+            if expected > 0 {
+                assert!(!pmtu.process_reply(size, expected - 1, time));
+            }
+            assert!(pmtu.process_reply(size, token, time));
+        }
+    }
+
+    #[test]
+    fn test_probe_on_start_sends_first_probe_immediately() {
+        let mut config = Config::default();
+        let start = Instant::now();
+        let rto = Duration::from_millis(200);
+
+        // Default: the first probe waits a full interval
+        assert!(PmtuDiscovery::new(&config, start).handle_pmtu(start, rto).is_none());
+
+        config.pmtu_probe_on_start = true;
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        let Some(ProtocolCommand::PMTUProbe { size, token, .. }) = pmtu.handle_pmtu(start, rto)
+        else {
+            panic!("Expected PMTUProbe");
+        };
+
+        // Subsequent probes are paced by the interval again
+        assert!(pmtu.process_reply(size, token, start));
+        assert!(pmtu.handle_pmtu(start, rto).is_none());
+        let next = start + Duration::from_millis(config.pmtu_interval_ms as u64);
+        assert!(pmtu.handle_pmtu(next, rto).is_some());
+
+        // Restarting the search probes immediately
+        pmtu.reset(next);
+        assert!(pmtu.handle_pmtu(next, rto).is_some());
+    }
+
+    #[test]
+    fn test_replies_routed_by_token_stream() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        config.pmtu_sequential_tokens = true;
+        let start = Instant::now();
+        let time = start + Duration::from_millis(150);
+        let rto = Duration::from_millis(200);
+
+        // Two searches (e.g. one per direction) with identical counters
+        let mut searches = [
+            PmtuDiscovery::with_stream(&config, start, 0),
+            PmtuDiscovery::with_stream(&config, start, 1),
+        ];
+        let mut probes = Vec::new();
+        for search in searches.iter_mut() {
+            let Some(ProtocolCommand::PMTUProbe { size, token, .. }) =
+                search.handle_pmtu(time, rto)
+            else {
+                panic!("Expected PMTUProbe");
+            };
+            assert_eq!(probe_token_stream(token), search.stream());
+            probes.push((size, token));
+        }
+        assert_ne!(probes[0].1, probes[1].1);
+
+        // A reply for stream 1 is not mistaken for stream 0's outstanding probe
+        let (size, token) = probes[1];
+        assert!(!searches[0].process_reply(size, token, time));
+        assert!(searches[0].has_outstanding_probe());
+
+        // Routing by the token's stream bits reaches the right search
+        for (size, token) in probes.into_iter().rev() {
+            let search = &mut searches[probe_token_stream(token) as usize];
+            assert!(search.process_reply(size, token, time));
+            assert!(!search.has_outstanding_probe());
+        }
+    }
+
+    #[test]
+    fn test_sequential_token_counter_stays_within_stream() {
+        let mut config = Config::default();
+        config.pmtu_sequential_tokens = true;
+        let mut pmtu = PmtuDiscovery::with_stream(&config, Instant::now(), 3);
+        pmtu.next_token = PROBE_TOKEN_COUNTER_MASK;
+
+        assert_eq!(
+            pmtu.next_probe_token(),
+            (3 << PROBE_TOKEN_COUNTER_BITS) | PROBE_TOKEN_COUNTER_MASK
+        );
+        assert_eq!(pmtu.next_probe_token(), 3 << PROBE_TOKEN_COUNTER_BITS);
+    }
+
+    #[test]
+    fn test_progress_tracks_narrowing_bounds() {
+        let mut config = Config::default();
+        config.pmtu_min = 1000;
+        config.pmtu_max = 1400;
+        config.pmtu_converge_threshold = 16;
+        let mut pmtu = PmtuDiscovery::new(&config, Instant::now());
+        assert_eq!(pmtu.progress(), 0.0);
+
+        let mut last = 0.0;
+        for (low, high) in [(1200, 1400), (1200, 1300), (1250, 1300), (1250, 1275)] {
+            pmtu.low = low;
+            pmtu.high = high;
+            let progress = pmtu.progress();
+            assert!(progress > last && progress < 1.0, "{progress} after {last}");
+            last = progress;
+        }
+
+        pmtu.low = 1262;
+        assert!(pmtu.is_converged());
+        assert_eq!(pmtu.progress(), 1.0);
+    }
+
+    #[test]
+    fn test_converge_threshold_scales_with_search_range() {
+        let mut config = Config::default();
+        config.pmtu_converge_threshold = 16;
+        let time = Instant::now();
+
+        // Percentage disabled: the absolute threshold applies everywhere
+        let mut pmtu = PmtuDiscovery::new(&config, time);
+        pmtu.restore(&PmtuSnapshot { fragment_size: 1200, low: 8000, high: 9000 }, time);
+        assert_eq!(pmtu.converge_threshold(), 16);
+
+        config.pmtu_converge_pct = 5;
+        config.pmtu_min = 200;
+        config.pmtu_max = 9000;
+        let mut pmtu = PmtuDiscovery::new(&config, time);
+
+        // Jumbo range: 5% of the 8500-byte midpoint dominates
+        pmtu.restore(&PmtuSnapshot { fragment_size: 1200, low: 8000, high: 9000 }, time);
+        assert_eq!(pmtu.converge_threshold(), 425);
+        pmtu.restore(&PmtuSnapshot { fragment_size: 1200, low: 8200, high: 8600 }, time);
+        assert!(pmtu.is_converged());
+
+        // Small range: 5% of the 250-byte midpoint falls back to the absolute threshold
+        pmtu.restore(&PmtuSnapshot { fragment_size: 200, low: 200, high: 300 }, time);
+        assert_eq!(pmtu.converge_threshold(), 16);
+        assert!(!pmtu.is_converged());
+    }
+
+    #[test]
+    fn test_probe_interval_backs_off_after_timeouts() {
+        let mut config = Config::default();
+        config.pmtu_probe_retries = 0;
+        config.pmtu_interval_ms = 100;
+        config.pmtu_backoff_max_ms = 500;
+        let rto = Duration::from_millis(200);
+        let mut time = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, time);
+
+        // Each timeout doubles the wait before the next probe, up to the cap
+        for expected_ms in [200, 400, 500] {
+            time += pmtu.probe_interval();
+            assert!(pmtu.handle_pmtu(time, rto).is_some());
+            time += Duration::from_millis(201);
+            assert!(pmtu.handle_pmtu(time, rto).is_none());
+            assert_eq!(pmtu.probe_interval(), Duration::from_millis(expected_ms));
+
+            let early = time + Duration::from_millis(expected_ms - 1);
+            assert!(pmtu.handle_pmtu(early, rto).is_none());
+        }
+
+        // The first reply restores the base interval
+        time += pmtu.probe_interval();
+        let Some(ProtocolCommand::PMTUProbe { size, token, .. }) = pmtu.handle_pmtu(time, rto)
+        else {
+            panic!("expected a probe");
+        };
+        assert!(pmtu.process_reply(size, token, time));
+        assert_eq!(pmtu.probe_interval(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_probe_interval_fixed_without_backoff() {
+        let mut config = Config::default();
+        config.pmtu_probe_retries = 0;
+        config.pmtu_interval_ms = 100;
+        let rto = Duration::from_millis(200);
+        let time = Instant::now() + Duration::from_millis(100);
+        let mut pmtu = PmtuDiscovery::new(&config, time - Duration::from_millis(100));
+
+        assert!(pmtu.handle_pmtu(time, rto).is_some());
+        assert!(pmtu.handle_pmtu(time + Duration::from_millis(201), rto).is_none());
+        assert_eq!(pmtu.probe_interval(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_reply_rate_limit_resets_each_second() {
+        let mut config = Config::default();
+        config.pmtu_max_replies_per_sec = 3;
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+
+        let allowed = (0..10).filter(|_| pmtu.allow_reply(start)).count();
+        assert_eq!(allowed, 3);
+        assert_eq!(pmtu.suppressed_replies(), 7);
+        assert!(!pmtu.allow_reply(start + Duration::from_millis(999)));
+
+        // A new window opens after a second
+        assert!(pmtu.allow_reply(start + Duration::from_secs(1)));
+        assert_eq!(pmtu.suppressed_replies(), 8);
+    }
+
+    #[test]
+    fn test_losses_after_convergence_restart_search_below_converged_size() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        let rto = Duration::from_millis(200);
+        let mut time = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, time);
+        assert_eq!(pmtu.state(), PmtuState::Searching);
+        assert!(!pmtu.report_loss_at_size(1300, time));
+
+        pmtu.restore(&PmtuSnapshot { fragment_size: 1300, low: 1300, high: 1320 }, time);
+        assert_eq!(pmtu.state(), PmtuState::Converged);
+
+        // Small datagrams say nothing about the MTU
+        assert!(!pmtu.report_loss_at_size(1300, time));
+        assert!(!pmtu.report_loss_at_size(500, time));
+        assert!(!pmtu.report_loss_at_size(1300, time));
+        assert!(pmtu.report_loss_at_size(1300, time));
+        assert_eq!(pmtu.state(), PmtuState::ErrorProbing);
+        assert_eq!(pmtu.current_fragment_size(), config.pmtu_safe_floor);
+        assert_eq!((pmtu.low_bound(), pmtu.high_bound()), (config.pmtu_min, 1299));
+
+        // The path now carries 1000 bytes: the search settles there, never probing 1300 again
+        while pmtu.state() != PmtuState::Converged {
+            time += Duration::from_millis(config.pmtu_interval_ms as u64 + 1);
+            if let Some(ProtocolCommand::PMTUProbe { size, token, .. }) =
+                pmtu.handle_pmtu(time, rto)
+            {
+                assert!(size < 1300);
+                if size <= 1000 {
+                    assert!(pmtu.process_reply(size, token, time));
+                } else {
+                    time += rto + Duration::from_millis(1);
+                    pmtu.handle_pmtu(time, rto);
+                }
+            }
+        }
+        assert!(pmtu.current_fragment_size() <= 1000);
+        assert!(pmtu.current_fragment_size() > 1000 - config.pmtu_converge_threshold);
+    }
+
+    #[test]
+    fn test_only_consecutive_losses_at_size_restart_search() {
+        let mut config = Config::default();
+        config.pmtu_reprobe_loss_threshold = 3;
+        let time = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, time);
+        pmtu.restore(&PmtuSnapshot { fragment_size: 1300, low: 1300, high: 1320 }, time);
+
+        // Losses spread between acknowledged datagrams at the size are not a shrunk path
+        for _ in 0..5 {
+            assert!(!pmtu.report_loss_at_size(1300, time));
+            assert!(!pmtu.report_loss_at_size(1300, time));
+            pmtu.observe_acked_size(1300, time);
+        }
+        assert_eq!(pmtu.state(), PmtuState::Converged);
+        assert_eq!(pmtu.current_fragment_size(), 1300);
+
+        // Nor does an acknowledged small datagram break a run
+        assert!(!pmtu.report_loss_at_size(1300, time));
+        pmtu.observe_acked_size(config.pmtu_safe_floor, time);
+        assert!(!pmtu.report_loss_at_size(1300, time));
+        assert!(pmtu.report_loss_at_size(1300, time));
+    }
+
+    #[test]
+    fn test_losses_at_size_share_blackhole_cooldown() {
+        let mut config = Config::default();
+        config.pmtu_reprobe_loss_threshold = 1;
+        config.pmtu_blackhole_cooldown_ms = 5000;
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        assert!(pmtu.handle_blackhole(start));
+
+        // Converged again shortly after: losses fall back only once the cooldown elapsed
+        let snapshot = PmtuSnapshot { fragment_size: 1300, low: 1300, high: 1320 };
+        pmtu.restore(&snapshot, start);
+        assert!(!pmtu.report_loss_at_size(1300, start + Duration::from_millis(4999)));
+        assert_eq!(pmtu.current_fragment_size(), 1300);
+        assert!(pmtu.report_loss_at_size(1300, start + Duration::from_millis(5000)));
+        assert_eq!(pmtu.current_fragment_size(), config.pmtu_safe_floor);
+    }
+
+    /// Runs a search against a path carrying `mtu` bytes and returns the probed sizes.
+    fn probe_sizes_until_converged(pmtu: &mut PmtuDiscovery, mtu: u16) -> Vec<u16> {
+        let rto = Duration::from_millis(200);
+        let mut time = Instant::now();
+        let mut sizes = Vec::new();
+        while !pmtu.is_converged() {
+            time += Duration::from_millis(pmtu.config.pmtu_interval_ms as u64 + 1);
+            let Some(ProtocolCommand::PMTUProbe { size, token, .. }) = pmtu.handle_pmtu(time, rto)
+            else {
+                continue;
+            };
+            sizes.push(size);
+            if size <= mtu {
+                assert!(pmtu.process_reply(size, token, time));
+            } else {
+                time += rto + Duration::from_millis(1);
+                pmtu.handle_pmtu(time, rto);
+            }
+        }
+        sizes
+    }
+
+    #[test]
+    fn test_schedule_probes_candidates_largest_first() {
+        let mut config = Config::default();
+        config.pmtu_probe_retries = 0;
+        config.pmtu_interval_ms = 100;
+        config.pmtu_max = 1500;
+        config.receive_buffer_max_size = 1500;
+        config.pmtu_search_mode = PmtuSearchMode::Schedule;
+        // Unordered, with one size above `pmtu_max` that is never probed
+        config.pmtu_candidate_sizes = vec![1280, 1500, 9000, 1400, 1492, 1360];
+
+        let mut pmtu = PmtuDiscovery::new(&config, Instant::now());
+        let sizes = probe_sizes_until_converged(&mut pmtu, 1450);
+        assert_eq!(sizes, vec![1500, 1492, 1400]);
+        assert_eq!(pmtu.low_bound(), 1400);
+        assert_eq!(pmtu.current_fragment_size(), 1400);
+        assert_eq!(pmtu.state(), PmtuState::Converged);
+
+        // Exact candidates, with no bisection in between
+        let mut pmtu = PmtuDiscovery::new(&config, Instant::now());
+        assert_eq!(probe_sizes_until_converged(&mut pmtu, 1300), vec![
+            1500, 1492, 1400, 1360, 1280
+        ]);
+        assert_eq!(pmtu.low_bound(), 1280);
+    }
+
+    #[test]
+    fn test_schedule_without_success_stays_at_min() {
+        let mut config = Config::default();
+        config.pmtu_probe_retries = 0;
+        config.pmtu_interval_ms = 100;
+        config.pmtu_search_mode = PmtuSearchMode::Schedule;
+        config.pmtu_candidate_sizes = vec![1400, 1280];
+
+        let mut pmtu = PmtuDiscovery::new(&config, Instant::now());
+        assert_eq!(probe_sizes_until_converged(&mut pmtu, 1000), vec![1400, 1280]);
+        assert_eq!(pmtu.low_bound(), config.pmtu_min);
+        assert!(pmtu.is_converged());
+    }
+
+    #[test]
+    fn test_exponential_search_grows_then_bisects() {
+        let mut config = Config::default();
+        config.pmtu_probe_retries = 0;
+        config.pmtu_interval_ms = 100;
+        config.pmtu_converge_threshold = 16;
+        config.pmtu_search_mode = PmtuSearchMode::Exponential;
+        let mut pmtu = PmtuDiscovery::new(&config, Instant::now());
+
+        let sizes = probe_sizes_until_converged(&mut pmtu, 1300);
+        // Steps of 64, 128, 256 and 512 from each new low, until 1400 fails; then bisection
+        // between the last success (1024) and the failure
+        assert_eq!(sizes[..4], [640, 768, 1024, 1400]);
+        assert_eq!(sizes[4], (1024 + 1399) / 2);
+        assert!(pmtu.low_bound() <= 1300 && pmtu.low_bound() >= 1300 - 16);
+        assert!(pmtu.high_bound() >= 1300 && pmtu.high_bound() < 1400);
+
+        // Binary search needs more probes to reach an MTU at `pmtu_max`
+        let exponential =
+            probe_sizes_until_converged(&mut PmtuDiscovery::new(&config, Instant::now()), 1400);
+        assert_eq!(exponential, vec![640, 768, 1024, 1400]);
+        config.pmtu_search_mode = PmtuSearchMode::Binary;
+        let binary =
+            probe_sizes_until_converged(&mut PmtuDiscovery::new(&config, Instant::now()), 1400);
+        assert!(binary.len() > exponential.len());
+    }
+
+    #[test]
+    fn test_exponential_step_clamped_to_datagram_cap() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        config.pmtu_max = 9000;
+        config.receive_buffer_max_size = 1100;
+        config.pmtu_search_mode = PmtuSearchMode::Exponential;
+        let mut pmtu = PmtuDiscovery::new(&config, Instant::now());
+
+        let sizes = probe_sizes_until_converged(&mut pmtu, 9000);
+        assert_eq!(sizes, vec![640, 768, 1024, 1100]);
+        assert_eq!(pmtu.current_fragment_size(), 1100);
+    }
+
+    /// Path that drops DF datagrams above its MTU, and everything when `lossy`.
+    struct MockPath {
+        mtu: u16,
+        lossy: bool,
+    }
+
+    impl MockPath {
+        fn delivers(&self, command: &ProtocolCommand, dont_fragment: bool) -> bool {
+            let ProtocolCommand::PMTUProbe { size, .. } = command else { unreachable!() };
+            !self.lossy && !(dont_fragment && *size > self.mtu)
+        }
+    }
+
+    /// Sends one paired probe over `path` and times it out if unanswered.
+    fn paired_round(pmtu: &mut PmtuDiscovery, path: &MockPath, time: &mut Instant) {
+        let rto = Duration::from_millis(200);
+        *time += Duration::from_millis(pmtu.config.pmtu_interval_ms as u64 + 1);
+        let probe = pmtu.handle_pmtu(*time, rto).expect("probe");
+        let twin = pmtu.take_fragmentable_probe().expect("fragmentable probe");
+        for (command, dont_fragment) in [(&twin, false), (&probe, true)] {
+            if let ProtocolCommand::PMTUProbe { size, token, .. } = command {
+                if path.delivers(command, dont_fragment) {
+                    pmtu.process_reply(*size, *token, *time);
+                }
+            }
+        }
+        if pmtu.has_outstanding_probe() {
+            *time += Duration::from_secs(1);
+            pmtu.handle_pmtu(*time, rto);
+        }
+    }
+
+    fn paired_config() -> Config {
+        let mut config = Config::default();
+        config.pmtu_paired_probes = true;
+        config.pmtu_min = 576;
+        config.pmtu_max = 1400;
+        config.pmtu_interval_ms = 100;
+        config.pmtu_converge_threshold = 16;
+        config
+    }
+
+    #[test]
+    fn test_paired_probe_diagnoses_too_big() {
+        let mut time = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&paired_config(), time);
+
+        // First probe (988) fits a 1000-byte path, the second (1194) does not
+        let path = MockPath { mtu: 1000, lossy: false };
+        paired_round(&mut pmtu, &path, &mut time);
+        assert_eq!(pmtu.low_bound(), 988);
+        assert_eq!(pmtu.last_probe_loss(), None);
+
+        paired_round(&mut pmtu, &path, &mut time);
+        assert_eq!(pmtu.last_probe_loss(), Some(PmtuProbeLoss::TooBig));
+        assert_eq!(pmtu.high_bound(), 1193);
+        // A fragmentable delivery does not count as a confirmed size
+        assert_eq!(pmtu.low_bound(), 988);
+    }
+
+    #[test]
+    fn test_paired_probe_diagnoses_generic_loss() {
+        let mut time = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&paired_config(), time);
+
+        paired_round(&mut pmtu, &MockPath { mtu: 1000, lossy: true }, &mut time);
+        assert_eq!(pmtu.last_probe_loss(), Some(PmtuProbeLoss::Generic));
+        // Loss unrelated to size leaves the search bounds untouched
+        assert_eq!(pmtu.high_bound(), 1400);
+        assert_eq!(pmtu.low_bound(), 576);
+        assert!(!pmtu.has_outstanding_probe());
+
+        // The same size is retried and the search still converges below the path MTU
+        let path = MockPath { mtu: 1000, lossy: false };
+        for _ in 0..16 {
+            if pmtu.is_converged() {
+                break;
+            }
+            paired_round(&mut pmtu, &path, &mut time);
+        }
+        assert!(pmtu.is_converged());
+        assert!(pmtu.low_bound() <= 1000 && pmtu.high_bound() >= 1000);
+    }
+
+    #[test]
+    fn test_untaken_fragmentable_probe_treated_as_too_big() {
+        let mut config = paired_config();
+        config.pmtu_probe_retries = 0;
+        let start = Instant::now();
+        let rto = Duration::from_millis(200);
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+
+        let time = start + Duration::from_millis(150);
+        let (size, _, _, _) = {
+            pmtu.handle_pmtu(time, rto).unwrap();
+            pmtu.outstanding_probe().unwrap()
+        };
+        pmtu.handle_pmtu(time + Duration::from_secs(1), rto);
+
+        assert_eq!(pmtu.high_bound(), size - 1);
+        assert_eq!(pmtu.last_probe_loss(), None);
+        // The stale twin is discarded with its probe
+        assert!(pmtu.take_fragmentable_probe().is_none());
+    }
+
+    #[test]
+    fn test_unpaired_probes_have_no_fragmentable_twin() {
+        let config = Config::default();
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        let time = start + Duration::from_millis(config.pmtu_interval_ms as u64 + 1);
+        assert!(pmtu.handle_pmtu(time, Duration::from_millis(200)).is_some());
+        assert!(pmtu.take_fragmentable_probe().is_none());
+    }
+
+    fn confirm_config() -> Config {
+        let mut config = Config::default();
+        config.pmtu_confirm_on_converge = true;
+        config.pmtu_min = 1200;
+        config.pmtu_max = 1232; // Converged from the start
+        config.pmtu_converge_threshold = 64;
+        config.pmtu_interval_ms = 100;
+        config
+    }
+
+    #[test]
+    fn test_convergence_finalized_after_confirming_probe() {
+        let config = confirm_config();
+        let start = Instant::now();
+        let rto = Duration::from_millis(200);
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+
+        // Converged bounds alone do not adopt `low`
+        assert!(pmtu.handle_pmtu(start, rto).is_none());
+        assert!(pmtu.is_converged());
+        assert!(!pmtu.is_settled());
+        assert_eq!(pmtu.current_fragment_size(), config.fragment_size);
+
+        // The confirming probe targets the low bound
+        let time = start + Duration::from_millis(150);
+        let probe = pmtu.handle_pmtu(time, rto).unwrap();
+        assert!(matches!(probe, ProtocolCommand::PMTUProbe { size: 1200, .. }));
+        let (size, token, _, _) = pmtu.outstanding_probe().unwrap();
+        assert!(pmtu.process_reply(size, token, time));
+
+        assert!(pmtu.is_settled());
+        assert_eq!(pmtu.current_fragment_size(), 1200);
+        assert!(pmtu.handle_pmtu(time + Duration::from_secs(1), rto).is_none());
+    }
+
+    #[test]
+    fn test_failed_confirmation_reopens_search() {
+        let mut config = confirm_config();
+        config.pmtu_probe_retries = 0;
+        config.pmtu_min = 576;
+        let start = Instant::now();
+        let rto = Duration::from_millis(200);
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        pmtu.restore(&PmtuSnapshot { fragment_size: 576, low: 1200, high: 1232 }, start);
+
+        let time = start + Duration::from_millis(150);
+        pmtu.handle_pmtu(time, rto).unwrap();
+        assert_eq!(pmtu.outstanding_probe().unwrap().0, 1200);
+
+        // Confirmation times out: the search reopens below the unconfirmed size
+        pmtu.handle_pmtu(time + Duration::from_secs(1), rto);
+        assert_eq!(pmtu.low_bound(), 576);
+        assert_eq!(pmtu.high_bound(), 1199);
+        assert!(!pmtu.is_converged());
+        assert_eq!(pmtu.current_fragment_size(), 576);
+
+        // Search replies during the reopened search are not adopted until confirmed
+        let time = time + Duration::from_secs(2);
+        pmtu.handle_pmtu(time, rto).unwrap();
+        let (size, token, _, _) = pmtu.outstanding_probe().unwrap();
+        assert!(pmtu.process_reply(size, token, time));
+        assert_eq!(pmtu.low_bound(), size);
+        assert_eq!(pmtu.current_fragment_size(), 576);
+    }
+
+    #[test]
+    fn test_convergence_adopted_immediately_without_confirmation() {
+        let mut config = confirm_config();
+        config.pmtu_confirm_on_converge = false;
+        let mut pmtu = PmtuDiscovery::new(&config, Instant::now());
+        assert!(pmtu.handle_pmtu(Instant::now(), Duration::from_millis(200)).is_none());
+        assert!(pmtu.is_settled());
+        assert_eq!(pmtu.current_fragment_size(), 1200);
+    }
+
+    #[test]
+    fn test_restored_snapshot_verified_by_single_probe() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        let start = Instant::now();
+        let rto = Duration::from_millis(200);
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        pmtu.restore(&PmtuSnapshot { fragment_size: 1300, low: 1300, high: 1320 }, start);
+        assert_eq!(pmtu.current_fragment_size(), 1300);
+
+        // One probe at the cached size, sent after the usual interval
+        assert!(pmtu.handle_pmtu(start + Duration::from_millis(50), rto).is_none());
+        let time = start + Duration::from_millis(150);
+        pmtu.handle_pmtu(time, rto).unwrap();
+        let (size, token, _, _) = pmtu.outstanding_probe().unwrap();
+        assert_eq!(size, 1300);
+
+        assert!(pmtu.process_reply(size, token, time));
+        assert_eq!(pmtu.current_fragment_size(), 1300);
+        assert!(pmtu.handle_pmtu(time + Duration::from_secs(10), rto).is_none());
+    }
+
+    #[test]
+    fn test_restored_snapshot_dropped_when_verification_lost() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        config.pmtu_probe_retries = 0;
+        let start = Instant::now();
+        let rto = Duration::from_millis(200);
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        pmtu.restore(&PmtuSnapshot { fragment_size: 1300, low: 1300, high: 1320 }, start);
+
+        let time = start + Duration::from_millis(150);
+        pmtu.handle_pmtu(time, rto).unwrap();
+        pmtu.handle_pmtu(time + Duration::from_secs(1), rto);
+        assert_eq!(pmtu.current_fragment_size(), config.pmtu_safe_floor);
+        assert_eq!((pmtu.low_bound(), pmtu.high_bound()), (config.pmtu_min, 1299));
+        assert!(!pmtu.is_converged());
+    }
+
+    #[test]
+    fn test_no_degenerate_probe_below_overhead() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        config.pmtu_min = 8;
+        config.pmtu_max = 64;
+        config.pmtu_converge_threshold = 1;
+        let start = Instant::now();
+        let rto = Duration::from_millis(200);
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        pmtu.set_datagram_overhead(28);
+        let min_size = pmtu.min_probe_size();
+        assert!(min_size > config.pmtu_min);
+
+        // Every probe fits its advertised size, all the way down to the smallest failure
+        let mut time = start;
+        for _ in 0..20 {
+            time += Duration::from_millis(150);
+            let Some(ProtocolCommand::PMTUProbe { size, payload, .. }) =
+                pmtu.handle_pmtu(time, rto)
+            else {
+                continue;
+            };
+            assert!(size >= min_size);
+            let packet_len =
+                PmtuDiscovery::probe_overhead(&config, size, 28) as usize + payload.len();
+            assert_eq!(packet_len, size as usize);
+            // The path drops every probe
+            time += Duration::from_secs(1);
+            pmtu.handle_pmtu(time, rto);
+        }
+        assert_eq!(pmtu.low_bound(), min_size);
+        assert!(pmtu.is_converged());
+    }
+
+    #[test]
+    fn test_address_family_of_socket_address() {
+        let v4: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let v6: SocketAddr = "[::1]:0".parse().unwrap();
+        let mapped: SocketAddr = "[::ffff:127.0.0.1]:0".parse().unwrap();
+        assert_eq!(PmtuAddressFamily::of(v4), PmtuAddressFamily::V4);
+        assert_eq!(PmtuAddressFamily::of(v6), PmtuAddressFamily::V6);
+        assert_eq!(PmtuAddressFamily::of(mapped), PmtuAddressFamily::V4);
+
+        let config = Config::default();
+        assert_eq!(PmtuDiscovery::min_for_family(&config, PmtuAddressFamily::V4), 576);
+        assert_eq!(PmtuDiscovery::min_for_family(&config, PmtuAddressFamily::V6), IPV6_MIN_MTU);
+    }
+
+    #[test]
+    fn test_ipv6_never_probes_below_minimum_mtu() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        config.pmtu_probe_retries = 0;
+        let start = Instant::now();
+        let rto = Duration::from_millis(200);
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        pmtu.set_address_family(PmtuAddressFamily::V6);
+        assert_eq!(pmtu.low_bound(), IPV6_MIN_MTU);
+
+        // Every probe is lost, driving the search as low as it goes
+        let mut time = start;
+        for _ in 0..20 {
+            time += Duration::from_millis(150);
+            if let Some(ProtocolCommand::PMTUProbe { size, .. }) = pmtu.handle_pmtu(time, rto) {
+                assert!(size >= IPV6_MIN_MTU);
+                time += Duration::from_secs(1);
+                pmtu.handle_pmtu(time, rto);
+            }
+        }
+        assert!(pmtu.is_converged());
+        assert_eq!(pmtu.low_bound(), IPV6_MIN_MTU);
+
+        // Falling back after a black hole keeps to the IPv6 minimum as well
+        assert!(pmtu.handle_blackhole(time));
+        assert_eq!(pmtu.current_fragment_size(), IPV6_MIN_MTU);
+        assert_eq!(pmtu.low_bound(), IPV6_MIN_MTU);
+    }
+
+    #[test]
+    fn test_probe_lost_while_deferred_keeps_bounds() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        config.pmtu_probe_retries = 0;
+        let start = Instant::now();
+        let rto = Duration::from_millis(200);
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+
+        let time = start + Duration::from_millis(150);
+        pmtu.handle_pmtu(time, rto).unwrap();
+        let (size, _, _, _) = pmtu.outstanding_probe().unwrap();
+
+        // Congestion sets in while the probe is out: its loss says nothing about the size
+        pmtu.set_probes_deferred(true);
+        let time = time + Duration::from_secs(1);
+        assert!(pmtu.handle_pmtu(time, rto).is_none());
+        assert!(!pmtu.has_outstanding_probe());
+        assert_eq!(pmtu.high_bound(), config.pmtu_max);
+        assert!(pmtu.handle_pmtu(time + Duration::from_secs(1), rto).is_none());
+
+        // After recovery the same size is probed again
+        pmtu.set_probes_deferred(false);
+        pmtu.handle_pmtu(time + Duration::from_secs(2), rto).unwrap();
+        assert_eq!(pmtu.outstanding_probe().unwrap().0, size);
+    }
+
+    #[test]
+    fn test_stats_count_probes_replies_and_timeouts() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        config.pmtu_probe_retries = 1;
+        config.pmtu_min = 1200;
+        config.pmtu_max = 1400;
+        config.pmtu_converge_threshold = 150;
+        let rto = Duration::from_millis(200);
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        assert_eq!(pmtu.stats(), PmtuStats {
+            probes_sent: 0,
+            replies: 0,
+            timeouts: 0,
+            retries: 0,
+            stray_replies: 0,
+            state: PmtuState::Searching,
+            converged_size: None,
+        });
+
+        // A probe is lost, re-sent, then answered
+        let time = start + Duration::from_millis(150);
+        pmtu.handle_pmtu(time, rto).unwrap();
+        let time = time + Duration::from_secs(1);
+        pmtu.handle_pmtu(time, rto).unwrap();
+        let (size, token, _, _) = pmtu.outstanding_probe().unwrap();
+        assert!(pmtu.process_reply(size, token, time));
+        assert!(pmtu.handle_pmtu(time + Duration::from_secs(1), rto).is_none());
+
+        assert_eq!(pmtu.stats(), PmtuStats {
+            probes_sent: 2,
+            replies: 1,
+            timeouts: 1,
+            retries: 1,
+            stray_replies: 0,
+            state: PmtuState::Converged,
+            converged_size: Some(size),
+        });
+    }
+
+    #[test]
+    fn test_reply_without_outstanding_probe_counts_as_stray() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        let rto = Duration::from_millis(200);
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+
+        // Nothing outstanding yet
+        assert!(!pmtu.process_reply(1200, 7, start));
+        assert_eq!(pmtu.stats().stray_replies, 1);
+
+        // A late reply to a probe abandoned by a reset
+        let time = start + Duration::from_millis(150);
+        pmtu.handle_pmtu(time, rto).unwrap();
+        let (size, token, _, _) = pmtu.outstanding_probe().unwrap();
+        pmtu.reset(time);
+        assert!(!pmtu.process_reply(size, token, time));
+        assert_eq!(pmtu.stats().stray_replies, 2);
+        assert_eq!(pmtu.stats().replies, 0);
+        assert_eq!(pmtu.low_bound(), config.pmtu_min);
+    }
+
+    #[test]
+    fn test_merge_adopts_converged_state() {
+        let config = Config::default();
+        let time = Instant::now();
+        let mut converged = PmtuDiscovery::new(&config, time);
+        converged.restore(&PmtuSnapshot { fragment_size: 1300, low: 1300, high: 1320 }, time);
+
+        let mut fresh = PmtuDiscovery::new(&config, time);
+        fresh.merge(&converged);
+        assert_eq!(fresh.current_fragment_size(), 1300);
+        assert_eq!((fresh.low_bound(), fresh.high_bound()), (1300, 1320));
+        assert!(fresh.is_converged());
+        assert_eq!(fresh.state(), PmtuState::Converged);
+
+        // Merging a fresh search back changes nothing
+        let before = converged.snapshot();
+        converged.merge(&PmtuDiscovery::new(&config, time));
+        assert_eq!(converged.snapshot(), before);
+    }
+
+    #[test]
+    fn test_merge_combines_partial_searches() {
+        let config = Config::default();
+        let time = Instant::now();
+        let mut first = PmtuDiscovery::new(&config, time);
+        first.restore(&PmtuSnapshot { fragment_size: 1000, low: 1000, high: 1400 }, time);
+        let mut second = PmtuDiscovery::new(&config, time);
+        second.restore(&PmtuSnapshot { fragment_size: 800, low: 800, high: 1200 }, time);
+
+        first.merge(&second);
+        assert_eq!((first.low_bound(), first.high_bound()), (1000, 1200));
+        assert_eq!(first.current_fragment_size(), 1000);
+        assert!(!first.is_converged());
+    }
+
+    #[test]
+    fn test_blackhole_resets_limited_by_cooldown() {
+        let mut config = Config::default();
+        config.pmtu_blackhole_cooldown_ms = 5000;
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        pmtu.restore(&PmtuSnapshot { fragment_size: 1400, low: 1400, high: 1400 }, start);
+
+        // First burst falls back to the safe floor and restarts the search
+        assert!(pmtu.handle_blackhole(start));
+        assert_eq!(pmtu.current_fragment_size(), config.pmtu_safe_floor);
+        assert_eq!(pmtu.high_bound(), config.pmtu_max);
+
+        // The search climbs back up, then a second burst arrives within the cooldown
+        pmtu.set_fragment_size(1200);
+        assert!(!pmtu.handle_blackhole(start + Duration::from_millis(4999)));
+        assert_eq!(pmtu.current_fragment_size(), 1200);
+
+        // Once the cooldown has elapsed, loss resets again
+        assert!(pmtu.handle_blackhole(start + Duration::from_millis(5000)));
+        assert_eq!(pmtu.current_fragment_size(), config.pmtu_safe_floor);
+    }
+
+    #[test]
+    fn test_inflated_reply_size_rejected() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+
+        let time = start + Duration::from_millis(150);
+        pmtu.handle_pmtu(time, Duration::from_millis(200)).unwrap();
+        let (target, token, _, _) = pmtu.outstanding.unwrap();
+
+        assert!(target < config.pmtu_max);
+        assert!(!pmtu.process_reply(config.pmtu_max, token, time));
+        assert!(pmtu.has_outstanding_probe());
+        assert_eq!(pmtu.low_bound(), config.pmtu_min);
+        assert_eq!(pmtu.current_fragment_size(), config.fragment_size);
+        assert_eq!(pmtu.protocol_anomalies(), 1);
+
+        // The genuine reply is still accepted
+        assert!(pmtu.process_reply(target, token, time));
+        assert_eq!(pmtu.low_bound(), target);
+    }
+
+    #[test]
+    fn test_stale_reply_below_low_rejected() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        let rto = Duration::from_millis(200);
+
+        let time = start + Duration::from_millis(150);
+        pmtu.handle_pmtu(time, rto).unwrap();
+        let (first, token, _, _) = pmtu.outstanding.unwrap();
+        assert!(pmtu.process_reply(first, token, time));
+
+        let time = time + Duration::from_millis(150);
+        pmtu.handle_pmtu(time, rto).unwrap();
+        let (_, token, _, _) = pmtu.outstanding.unwrap();
+        assert!(!pmtu.process_reply(first - 1, token, time));
+        assert!(pmtu.has_outstanding_probe());
+        assert_eq!(pmtu.low_bound(), first);
+        assert_eq!(pmtu.protocol_anomalies(), 0);
+    }
+
+    #[test]
+    fn test_probe_quantum_rounds_targets_and_converges() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        config.pmtu_converge_threshold = 8;
+        config.pmtu_probe_quantum = 64;
+        config.pmtu_candidate_sizes = vec![1300];
+        let rto = Duration::from_millis(200);
+        let step = Duration::from_millis(350);
+
+        for path_mtu in [600, 1000, 1280, 1399, 1400] {
+            let start = Instant::now();
+            let mut pmtu = PmtuDiscovery::new(&config, start);
+            let mut time = start;
+            let mut probes = 0;
+            while !pmtu.is_converged() {
+                assert!(probes < 16, "search did not terminate for path MTU {}", path_mtu);
+                time += step;
+                if let Some(ProtocolCommand::PMTUProbe { size, token, .. }) =
+                    pmtu.handle_pmtu(time, rto)
+                {
+                    probes += 1;
+                    assert_eq!(size % 64, 0);
+                    if size <= path_mtu {
+                        pmtu.process_reply(size, token, time);
+                    }
+                }
+            }
+            assert!(pmtu.high_bound() - pmtu.low_bound() <= 64);
+            assert!(pmtu.low_bound() <= path_mtu);
+        }
+    }
+
+    #[test]
+    fn test_batch_after_large_jump_sends_one_probe() {
+        let mut config = Config::default();
+        config.pmtu_probe_retries = 0;
+        config.pmtu_interval_ms = 100;
+        let rto = Duration::from_millis(200);
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+
+        // Nothing ticked for a minute: one probe, not one per missed interval
+        let later = start + Duration::from_secs(60);
+        assert!(pmtu.handle_pmtu_batch(later, rto).is_some());
+        assert!(pmtu.handle_pmtu_batch(later, rto).is_none());
+        let (size, _, _, _) = pmtu.outstanding_probe().unwrap();
+
+        // The probe is lost and another minute passes: the timeout and the next probe are
+        // both handled by the same call
+        let much_later = later + Duration::from_secs(60);
+        let next = pmtu.handle_pmtu_batch(much_later, rto);
+        assert!(
+            matches!(next, Some(ProtocolCommand::PMTUProbe { size: next_size, .. }) if next_size < size)
+        );
+        assert_eq!(pmtu.high_bound(), size - 1);
+        assert!(pmtu.handle_pmtu_batch(much_later, rto).is_none());
+
+        // Plain ticking recovers the stale probe the same way
+        let mut ticked = PmtuDiscovery::new(&config, start);
+        ticked.handle_pmtu(later, rto).unwrap();
+        assert!(ticked.handle_pmtu(much_later, rto).is_some());
+    }
+
+    #[test]
+    fn test_stale_probe_times_out_and_probes_promptly() {
+        let mut config = Config::default();
+        config.pmtu_probe_retries = 0;
+        config.pmtu_interval_ms = 100;
+        let rto = Duration::from_millis(200);
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+
+        let sent = start + Duration::from_millis(150);
+        pmtu.handle_pmtu(sent, rto).unwrap();
+        let (size, _, _, _) = pmtu.outstanding_probe().unwrap();
+
+        // Nothing ticked for an hour: the wedged probe times out and the next goes out at once
+        let resumed = sent + Duration::from_secs(3600);
+        let next = pmtu.handle_pmtu(resumed, rto);
+        assert!(
+            matches!(next, Some(ProtocolCommand::PMTUProbe { size: next_size, .. }) if next_size < size)
+        );
+        assert_eq!(pmtu.high_bound(), size - 1);
+        let (_, _, next_sent, _) = pmtu.outstanding_probe().unwrap();
+        assert_eq!(next_sent, resumed);
+        assert!(pmtu.handle_pmtu(resumed + Duration::from_millis(10), rto).is_none());
+
+        // With the interval not yet passed since the expiry, the next probe is due one interval
+        // after the expiry rather than one interval after ticking resumed
+        config.pmtu_interval_ms = 10_000;
+        config.pmtu_probe_on_start = true;
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        pmtu.handle_pmtu(sent, rto).unwrap();
+        let resumed = sent + Duration::from_secs(5);
+        assert!(pmtu.handle_pmtu(resumed, rto).is_none());
+        assert!(!pmtu.has_outstanding_probe());
+        let due = sent + rto + Duration::from_millis(10_000);
+        assert!(pmtu.handle_pmtu(due - Duration::from_millis(1), rto).is_none());
+        assert!(pmtu.handle_pmtu(due, rto).is_some());
+    }
+
+    #[test]
+    fn test_fragment_size_change_callback_fires_once_per_change() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 100;
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(&config, start);
+        let changes = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = changes.clone();
+        pmtu.on_fragment_size_change(move |old, new| recorded.lock().unwrap().push((old, new)));
+
+        // No-op updates are not reported
+        pmtu.set_fragment_size(config.fragment_size);
+        assert!(changes.lock().unwrap().is_empty());
+
+        // A reply raises the fragment size
+        pmtu.handle_pmtu(start + Duration::from_millis(150), Duration::from_millis(200)).unwrap();
+        let (size, token, _, _) = pmtu.outstanding_probe().unwrap();
+        pmtu.process_reply(size, token, start);
+        assert_eq!(*changes.lock().unwrap(), vec![(config.fragment_size, size)]);
+
+        // A black hole drops it to the safe floor; a second reset within the cooldown does not
+        pmtu.handle_blackhole(start);
+        pmtu.handle_blackhole(start + Duration::from_millis(1));
+        pmtu.set_fragment_size(config.pmtu_safe_floor);
+        assert_eq!(*changes.lock().unwrap(), vec![
+            (config.fragment_size, size),
+            (size, config.pmtu_safe_floor)
+        ]);
+    }
+
+    /// Returns how long after creation the first probe goes out, checking every 10ms.
+    fn first_probe_delay(config: &Config) -> Duration {
+        let start = Instant::now();
+        let mut pmtu = PmtuDiscovery::new(config, start);
+        let rto = Duration::from_millis(200);
+        let mut elapsed = Duration::ZERO;
+        while pmtu.handle_pmtu(start + elapsed, rto).is_none() {
+            elapsed += Duration::from_millis(10);
+        }
+        elapsed
+    }
+
+    #[test]
+    fn test_probe_interval_jitter_spreads_probes() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 1000;
+        config.pmtu_interval_jitter_pct = 25;
+
+        let delays: Vec<_> = (0..20)
+            .map(|seed| {
+                config.rng_seed = Some(seed);
+                first_probe_delay(&config)
+            })
+            .collect();
+        for delay in &delays {
+            assert!(*delay >= Duration::from_millis(750) && *delay <= Duration::from_millis(1250));
+        }
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+    }
+
+    #[test]
+    fn test_probe_interval_fixed_without_jitter() {
+        let mut config = Config::default();
+        config.pmtu_interval_ms = 1000;
+        for seed in 0..5 {
+            config.rng_seed = Some(seed);
+            assert_eq!(first_probe_delay(&config), Duration::from_millis(1000));
+        }
+    }
 }
@@ -0,0 +1,12 @@
+use bitfold_core::config::CompressionAlgorithm;
+
+/// Options for a single message sent with [`Peer::send_with_options`](crate::Peer::send_with_options).
+///
+/// The defaults send the message like any other on its channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SendOptions {
+    /// Compression used for the message instead of the connection's `compression`, e.g.
+    /// `None` for data that is already compressed. The datagram's compression marker names
+    /// the algorithm used, so the receiver needs no matching setting.
+    pub compression: Option<CompressionAlgorithm>,
+}
@@ -4,6 +4,15 @@
 //! including metrics for packets sent/received, bytes transferred, and network
 //! quality indicators like packet loss rate.
 
+use std::{collections::BTreeMap, fmt::Write, time::Duration};
+
+use bitfold_core::config::CompressionAlgorithm;
+
+use crate::histogram::{escape_label_value, Histogram};
+
+/// Label whose value is attached to histogram observations as the exemplar trace ID.
+pub const TRACE_ID_LABEL: &str = "trace_id";
+
 /// Comprehensive statistics for a peer connection.
 /// Tracks packets, bytes, and network quality metrics.
 #[derive(Debug, Clone, Default)]
@@ -18,6 +27,12 @@ pub struct PeerStatistics {
     pub bytes_sent: u64,
     /// Total data bytes received from this peer (excluding protocol overhead)
     pub bytes_received: u64,
+    /// Bytes passed through Zlib compression
+    pub zlib_compression: CompressionStats,
+    /// Bytes passed through LZ4 compression
+    pub lz4_compression: CompressionStats,
+    /// Bytes passed through Zstd compression, at any level
+    pub zstd_compression: CompressionStats,
 }
 
 impl PeerStatistics {
@@ -29,12 +44,104 @@ impl PeerStatistics {
         self.packets_lost as f32 / self.packets_sent as f32
     }
 
+    /// Returns the cumulative compression totals for `algorithm`.
+    pub fn compression_stats(&self, algorithm: CompressionAlgorithm) -> CompressionStats {
+        match algorithm {
+            CompressionAlgorithm::None => CompressionStats::default(),
+            CompressionAlgorithm::Zlib => self.zlib_compression,
+            CompressionAlgorithm::Lz4 => self.lz4_compression,
+            CompressionAlgorithm::Zstd { .. } => self.zstd_compression,
+        }
+    }
+
+    /// Adds one datagram's sizes before and after compression with `algorithm`.
+    pub fn record_compression(
+        &mut self,
+        algorithm: CompressionAlgorithm,
+        original: usize,
+        compressed: usize,
+    ) {
+        let stats = match algorithm {
+            CompressionAlgorithm::None => return,
+            CompressionAlgorithm::Zlib => &mut self.zlib_compression,
+            CompressionAlgorithm::Lz4 => &mut self.lz4_compression,
+            CompressionAlgorithm::Zstd { .. } => &mut self.zstd_compression,
+        };
+        stats.original += original as u64;
+        stats.compressed += compressed as u64;
+    }
+
     /// Resets all statistics counters.
     pub fn reset(&mut self) {
         *self = Self::default();
     }
 }
 
+/// Cumulative bytes before and after compression for one algorithm.
+///
+/// Datagrams sent uncompressed because they were below the threshold or did not shrink count
+/// too, so the ratio reflects what compression achieves on the actual workload.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressionStats {
+    /// Encoded bytes before compression
+    pub original: u64,
+    /// Bytes after compression, including the compression header
+    pub compressed: u64,
+}
+
+impl CompressionStats {
+    /// Returns compressed bytes per original byte (1.0 if nothing was compressed); values
+    /// below 1.0 mean compression is saving bandwidth.
+    pub fn ratio(&self) -> f64 {
+        if self.original == 0 {
+            return 1.0;
+        }
+        self.compressed as f64 / self.original as f64
+    }
+}
+
+/// Point-in-time metrics for a peer connection, tagged with its application labels.
+#[derive(Debug, Clone)]
+pub struct PeerMetricsSnapshot {
+    /// Counters at the time of the snapshot
+    pub statistics: PeerStatistics,
+    /// Smoothed round-trip time
+    pub rtt: Duration,
+    /// Packet loss rate (0.0 to 1.0)
+    pub packet_loss_rate: f32,
+    /// Application-provided labels (e.g. user ID, tenant) for grouping
+    pub labels: BTreeMap<String, String>,
+    /// Time (seconds) each PMTU search took to settle
+    pub pmtu_convergence: Histogram,
+    /// Flow-control window size (packets) after each adjustment
+    pub window_size: Histogram,
+}
+
+impl PeerMetricsSnapshot {
+    /// Renders the histograms in OpenMetrics text format, labelled with the application labels.
+    ///
+    /// Observations made while a `trace_id` label was set carry it as an exemplar; the label
+    /// itself is left off the samples to keep series cardinality bounded.
+    pub fn to_openmetrics(&self) -> String {
+        let labels = self
+            .labels
+            .iter()
+            .filter(|(key, _)| key.as_str() != TRACE_ID_LABEL)
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape_label_value(value)))
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut out = String::new();
+        self.pmtu_convergence.write_openmetrics(
+            &mut out,
+            "bitfold_pmtu_convergence_seconds",
+            &labels,
+        );
+        self.window_size.write_openmetrics(&mut out, "bitfold_window_size_packets", &labels);
+        let _ = writeln!(out, "# EOF");
+        out
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -84,6 +191,27 @@ mod tests {
         assert_eq!(stats.bytes_received, 0);
     }
 
+    #[test]
+    fn test_compression_stats_accumulate_per_algorithm() {
+        let mut stats = PeerStatistics::default();
+        assert_eq!(stats.compression_stats(CompressionAlgorithm::Zlib).ratio(), 1.0);
+
+        stats.record_compression(CompressionAlgorithm::Zlib, 1000, 200);
+        stats.record_compression(CompressionAlgorithm::Zlib, 500, 300);
+        stats.record_compression(CompressionAlgorithm::Lz4, 100, 101);
+        stats.record_compression(CompressionAlgorithm::None, 100, 101);
+
+        let zlib = stats.compression_stats(CompressionAlgorithm::Zlib);
+        assert_eq!(zlib, CompressionStats { original: 1500, compressed: 500 });
+        assert!((zlib.ratio() - 1.0 / 3.0).abs() < 1e-9);
+        let lz4 = stats.compression_stats(CompressionAlgorithm::Lz4);
+        assert_eq!(lz4, CompressionStats { original: 100, compressed: 101 });
+        assert_eq!(
+            stats.compression_stats(CompressionAlgorithm::None),
+            CompressionStats::default()
+        );
+    }
+
     #[test]
     fn test_statistics_track_packet_loss() {
         let mut stats = PeerStatistics::default();
@@ -0,0 +1,27 @@
+/// Delivery behaviour of a stream, chosen when it is opened with
+/// [`Peer::open_stream`](crate::Peer::open_stream).
+///
+/// A stream is carried on a channel; its mode overrides the delivery and ordering guarantees of
+/// packets sent on that channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StreamMode {
+    /// Retransmitted until acknowledged and delivered in send order
+    #[default]
+    ReliableOrdered,
+    /// Retransmitted until acknowledged and delivered as soon as it arrives
+    ReliableUnordered,
+    /// Sent once and delivered as soon as it arrives, like a datagram
+    Unreliable,
+}
+
+impl StreamMode {
+    /// Returns true if data on the stream is retransmitted until acknowledged.
+    pub fn is_reliable(&self) -> bool {
+        !matches!(self, StreamMode::Unreliable)
+    }
+
+    /// Returns true if data on the stream is delivered in send order.
+    pub fn is_ordered(&self) -> bool {
+        matches!(self, StreamMode::ReliableOrdered)
+    }
+}
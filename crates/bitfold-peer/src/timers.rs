@@ -0,0 +1,28 @@
+//! Diagnostic view of a peer's armed timers.
+
+/// A timer that drives a peer, as reported by [`Peer::timers`](crate::Peer::timers).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimerKind {
+    /// The oldest packet passed by later acknowledgments is declared lost
+    LossDetection,
+    /// The next PMTU probe is due
+    PmtuProbe,
+    /// The outstanding PMTU probe times out
+    PmtuProbeTimeout,
+    /// PMTU discovery gives up (`pmtu_discovery_timeout_ms`)
+    PmtuDiscoveryTimeout,
+    /// The connection is dropped unless something arrives (`idle_connection_timeout`)
+    Idle,
+    /// A heartbeat is sent (`heartbeat_interval`)
+    Keepalive,
+    /// A delayed ACK is sent (`ack_delay_ms`)
+    AckDelay,
+    /// A window probe is sent while the flow-control window is full
+    FlowControlProbe,
+    /// The oldest application ping fails (`ping_timeout`)
+    PingTimeout,
+    /// The connection closes itself (`Peer::set_ttl`)
+    Ttl,
+    /// The close handshake started by `Peer::shutdown` is abandoned (`disconnect_timeout`)
+    ShutdownTimeout,
+}
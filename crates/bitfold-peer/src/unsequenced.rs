@@ -49,6 +49,20 @@ impl UnsequencedState {
         }
     }
 
+    /// Recreates a state from its window base, window bits and outgoing counter.
+    pub fn from_parts(incoming_group: u16, window: [u32; 32], outgoing_group: u16) -> Self {
+        Self {
+            incoming_unsequenced_group: incoming_group,
+            unsequenced_window: window,
+            outgoing_unsequenced_group: outgoing_group,
+        }
+    }
+
+    /// Returns the duplicate-detection window bits, relative to `incoming_group()`.
+    pub fn window(&self) -> [u32; 32] {
+        self.unsequenced_window
+    }
+
     /// Gets the next outgoing unsequenced group ID and increments the counter.
     pub fn next_outgoing_group(&mut self) -> u16 {
         let group = self.outgoing_unsequenced_group;
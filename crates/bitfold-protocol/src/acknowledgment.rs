@@ -1,4 +1,8 @@
-use std::{collections::HashMap, time::Instant};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 
 use super::{
     congestion::CongestionControl,
@@ -7,7 +11,11 @@ use super::{
 };
 
 const REDUNDANT_PACKET_ACKS_SIZE: u16 = 32;
+/// Received sequence numbers remembered for selective acknowledgments
+const SACK_WINDOW: u16 = 256;
 const DEFAULT_SEND_PACKETS_SIZE: usize = 256;
+const DEFAULT_REORDER_THRESHOLD: u16 = 3;
+const DEFAULT_TIME_THRESHOLD: f32 = 1.125;
 
 /// Responsible for handling the acknowledgment of packets.
 pub struct AcknowledgmentHandler {
@@ -17,6 +25,14 @@ pub struct AcknowledgmentHandler {
     received_packets: SequenceBuffer<ReceivedPacket>,
     /// Congestion control for RTT tracking and throttling
     congestion: CongestionControl,
+    /// Highest local sequence number the remote has acknowledged
+    largest_acked: Option<SequenceNumber>,
+    /// Largest distance an acknowledgment arrived behind `largest_acked`
+    reorder_extent: u16,
+    /// Minimum packets acknowledged after a packet before it can be declared lost
+    reorder_threshold: u16,
+    /// Fraction of the smoothed RTT that must elapse after sending before a loss declaration
+    time_threshold: f32,
 }
 
 impl Default for AcknowledgmentHandler {
@@ -37,8 +53,12 @@ impl AcknowledgmentHandler {
             sequence_number: 0,
             remote_ack_sequence_num: u16::MAX,
             sent_packets: HashMap::with_capacity(DEFAULT_SEND_PACKETS_SIZE),
-            received_packets: SequenceBuffer::with_capacity(REDUNDANT_PACKET_ACKS_SIZE + 1),
+            received_packets: SequenceBuffer::with_capacity(SACK_WINDOW),
             congestion,
+            largest_acked: None,
+            reorder_extent: 0,
+            reorder_threshold: DEFAULT_REORDER_THRESHOLD,
+            time_threshold: DEFAULT_TIME_THRESHOLD,
         }
     }
 
@@ -47,16 +67,102 @@ impl AcknowledgmentHandler {
         self.sent_packets.len() as u16
     }
 
+    /// Returns the payload bytes of the packets awaiting acknowledgment.
+    pub fn bytes_in_flight(&self) -> usize {
+        self.sent_packets.values().map(|packet| packet.payload.len()).sum()
+    }
+
     /// Returns the local sequence number for the next outgoing packet.
     pub fn local_sequence_num(&self) -> SequenceNumber {
         self.sequence_number
     }
 
+    /// Sets the local sequence number for the next outgoing packet, e.g. when resuming
+    /// from persisted connection state.
+    pub fn set_local_sequence_num(&mut self, sequence: SequenceNumber) {
+        self.sequence_number = sequence;
+    }
+
     /// Returns the most recent remote sequence number received.
     pub fn remote_sequence_num(&self) -> SequenceNumber {
         self.received_packets.sequence_num().wrapping_sub(1)
     }
 
+    /// Returns the most recent local sequence number acknowledged by the remote.
+    pub fn remote_ack_sequence_num(&self) -> SequenceNumber {
+        self.remote_ack_sequence_num
+    }
+
+    /// Returns the received remote sequence numbers still covered by ACKs and SACKs (the
+    /// last 256 sequence numbers), oldest first.
+    pub fn received_sequences(&self) -> Vec<SequenceNumber> {
+        let newest = self.remote_sequence_num();
+        (0..SACK_WINDOW)
+            .rev()
+            .map(|age| newest.wrapping_sub(age))
+            .filter(|&sequence| self.received_packets.exists(sequence))
+            .collect()
+    }
+
+    /// Restores receive-side state captured with [`AcknowledgmentHandler::received_sequences`]
+    /// and [`AcknowledgmentHandler::remote_ack_sequence_num`], e.g. when migrating a connection.
+    pub fn restore_received(
+        &mut self,
+        remote_ack_sequence: SequenceNumber,
+        received: &[SequenceNumber],
+    ) {
+        self.remote_ack_sequence_num = remote_ack_sequence;
+        self.received_packets = SequenceBuffer::with_capacity(SACK_WINDOW);
+        for &sequence in received {
+            self.received_packets.insert(sequence, ReceivedPacket);
+        }
+    }
+
+    /// Tracks a packet sent before a migration as still awaiting acknowledgment.
+    pub fn restore_sent_packet(&mut self, sequence: SequenceNumber, packet: SentPacket) {
+        self.sent_packets.insert(sequence, packet);
+    }
+
+    /// Sets the packet and time thresholds used by [`AcknowledgmentHandler::lost_packets`].
+    pub fn set_loss_thresholds(&mut self, reorder_threshold: u16, time_threshold: f32) {
+        self.reorder_threshold = reorder_threshold.max(1);
+        self.time_threshold = time_threshold.max(0.0);
+    }
+
+    /// Returns the largest number of sequence numbers by which an acknowledgment trailed the
+    /// highest one already acknowledged.
+    pub fn reorder_extent(&self) -> u16 {
+        self.reorder_extent
+    }
+
+    /// Returns how many later packets must be acknowledged before a packet can be declared
+    /// lost: the configured threshold, raised past the observed reorder extent.
+    pub fn packet_threshold(&self) -> u16 {
+        self.reorder_threshold.max(self.reorder_extent.saturating_add(1))
+    }
+
+    /// Returns how long after sending a packet must stay unacknowledged before it can be
+    /// declared lost: the time threshold applied to the smoothed RTT, at least 1ms.
+    pub fn loss_delay(&self) -> Duration {
+        self.congestion.rtt().mul_f32(self.time_threshold).max(Duration::from_millis(1))
+    }
+
+    /// Returns when the oldest packet already passed by [`AcknowledgmentHandler::packet_threshold`]
+    /// acknowledged packets will be declared lost by [`AcknowledgmentHandler::lost_packets`],
+    /// or None if there is no such packet.
+    pub fn loss_time(&self) -> Option<Instant> {
+        let largest = self.largest_acked?;
+        let packet_threshold = self.packet_threshold();
+        self.sent_packets
+            .iter()
+            .filter(|(&sequence, _)| {
+                sequence_less_than(sequence, largest)
+                    && largest.wrapping_sub(sequence) >= packet_threshold
+            })
+            .map(|(_, packet)| packet.sent_time + self.loss_delay())
+            .min()
+    }
+
     /// Returns the current round-trip time.
     pub fn rtt(&self) -> std::time::Duration {
         self.congestion.rtt()
@@ -112,25 +218,116 @@ impl AcknowledgmentHandler {
         ack_bitfield
     }
 
+    /// Returns up to `max_ranges` inclusive `(first, last)` ranges of received remote sequence
+    /// numbers, newest first, covering the last 256 sequence numbers.
+    ///
+    /// The gaps between the ranges are the packets the remote should retransmit.
+    pub fn sack_ranges(&self, max_ranges: usize) -> Vec<(SequenceNumber, SequenceNumber)> {
+        let newest = self.remote_sequence_num();
+        let mut ranges: Vec<(SequenceNumber, SequenceNumber)> = Vec::new();
+        let mut run_end = None;
+        for age in 0..SACK_WINDOW {
+            let sequence = newest.wrapping_sub(age);
+            match (self.received_packets.exists(sequence), run_end) {
+                (true, None) => run_end = Some(sequence),
+                (false, Some(last)) => {
+                    ranges.push((sequence.wrapping_add(1), last));
+                    run_end = None;
+                    if ranges.len() == max_ranges {
+                        return ranges;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(last) = run_end.filter(|_| ranges.len() < max_ranges) {
+            ranges.push((newest.wrapping_sub(SACK_WINDOW - 1), last));
+        }
+        ranges
+    }
+
+    /// Processes a selective acknowledgment of inclusive `(first, last)` sequence ranges and
+    /// returns the packets newly acknowledged.
+    ///
+    /// Packets in the gaps stay in flight; once enough later packets are acknowledged,
+    /// [`AcknowledgmentHandler::lost_packets`] declares just those lost.
+    ///
+    /// Only the last 256 sequence numbers sent can be acknowledged this way. A Sack with a
+    /// range that wraps, spans more than that or reaches past the newest packet sent is bogus
+    /// and ignored as a whole.
+    pub fn process_sack(
+        &mut self,
+        ranges: &[(SequenceNumber, SequenceNumber)],
+        now: Instant,
+    ) -> Vec<AckedPacket> {
+        // Acknowledged sequence numbers by age, the newest packet sent being age 0
+        let newest = self.sequence_number.wrapping_sub(1);
+        let mut acked_ages = [false; SACK_WINDOW as usize];
+        for &(first, last) in ranges {
+            if last.wrapping_sub(first) >= SACK_WINDOW || sequence_greater_than(last, newest) {
+                return Vec::new();
+            }
+            let last_age = newest.wrapping_sub(last);
+            if last_age >= SACK_WINDOW {
+                continue;
+            }
+            let first_age = newest.wrapping_sub(first).min(SACK_WINDOW - 1);
+            acked_ages[last_age as usize..=first_age as usize].fill(true);
+        }
+        let sequences: Vec<SequenceNumber> = self
+            .sent_packets
+            .keys()
+            .copied()
+            .filter(|&sequence| {
+                let age = newest.wrapping_sub(sequence);
+                age < SACK_WINDOW && acked_ages[age as usize]
+            })
+            .collect();
+
+        let previous_largest = self.largest_acked;
+        let mut acked = Vec::with_capacity(sequences.len());
+        for sequence in sequences {
+            if let Some(sent_packet) = self.sent_packets.remove(&sequence) {
+                let rtt = now.duration_since(sent_packet.sent_time);
+                self.congestion.update_rtt(rtt);
+                self.record_acked(sequence, previous_largest);
+                acked.push(AckedPacket { bytes: sent_packet.payload.len(), rtt });
+            }
+        }
+        acked
+    }
+
+    /// Records that the remote's packet `remote_seq_num` arrived, so outgoing ACKs cover it.
+    /// Unlike [`AcknowledgmentHandler::process_incoming`], it acknowledges nothing we sent.
+    pub fn record_received(&mut self, remote_seq_num: SequenceNumber) {
+        self.received_packets.insert(remote_seq_num, ReceivedPacket {});
+    }
+
     /// Processes an incoming packet and updates congestion metrics.
-    /// Calculates RTT when ACKs are received.
+    /// Calculates RTT when ACKs are received and returns the packets newly acknowledged.
     pub fn process_incoming(
         &mut self,
         remote_seq_num: u16,
         remote_ack_seq: u16,
         mut remote_ack_field: u32,
         now: Instant,
-    ) {
+    ) -> Vec<AckedPacket> {
         if sequence_greater_than(remote_ack_seq, self.remote_ack_sequence_num) {
             self.remote_ack_sequence_num = remote_ack_seq;
         }
 
         self.received_packets.insert(remote_seq_num, ReceivedPacket {});
 
+        // Acknowledgments in this ACK only count as reordered relative to earlier ones
+        let previous_largest = self.largest_acked;
+        let mut acked = Vec::new();
+
         // Process ACK for most recent packet and calculate RTT
         if let Some(sent_packet) = self.sent_packets.remove(&remote_ack_seq) {
             let rtt = now.duration_since(sent_packet.sent_time);
             self.congestion.update_rtt(rtt);
+            self.record_acked(remote_ack_seq, previous_largest);
+            acked.push(AckedPacket { bytes: sent_packet.payload.len(), rtt });
         }
 
         // Process ACKs from bitfield
@@ -140,10 +337,32 @@ impl AcknowledgmentHandler {
                 if let Some(sent_packet) = self.sent_packets.remove(&ack_sequence) {
                     let rtt = now.duration_since(sent_packet.sent_time);
                     self.congestion.update_rtt(rtt);
+                    self.record_acked(ack_sequence, previous_largest);
+                    acked.push(AckedPacket { bytes: sent_packet.payload.len(), rtt });
                 }
             }
             remote_ack_field >>= 1;
         }
+        acked
+    }
+
+    /// Updates the largest acknowledged sequence and the reorder extent with a newly
+    /// acknowledged packet.
+    fn record_acked(&mut self, sequence: SequenceNumber, previous_largest: Option<SequenceNumber>) {
+        if let Some(previous) = previous_largest {
+            if sequence_less_than(sequence, previous) {
+                self.reorder_extent = self.reorder_extent.max(previous.wrapping_sub(sequence));
+            }
+        }
+        match self.largest_acked {
+            Some(largest) if !sequence_greater_than(sequence, largest) => {}
+            _ => self.largest_acked = Some(sequence),
+        }
+    }
+
+    /// Returns the packets sent but not yet acknowledged, in no particular order.
+    pub fn sent_packets(&self) -> impl Iterator<Item = (SequenceNumber, &SentPacket)> + '_ {
+        self.sent_packets.iter().map(|(&sequence, packet)| (sequence, packet))
     }
 
     /// Processes an outgoing packet and tracks it for acknowledgment.
@@ -200,6 +419,43 @@ impl AcknowledgmentHandler {
 
         dropped
     }
+
+    /// Returns packets declared lost at `now` and records the loss for congestion control.
+    ///
+    /// Besides the packets [`AcknowledgmentHandler::dropped_packets`] gives up on, a packet
+    /// sent before the largest acknowledged one is declared lost once both thresholds are met:
+    /// at least [`AcknowledgmentHandler::packet_threshold`] later packets were acknowledged and
+    /// [`AcknowledgmentHandler::loss_delay`] has passed since it was sent. Requiring both keeps
+    /// packets that are merely reordered from being retransmitted spuriously.
+    pub fn lost_packets(&mut self, now: Instant) -> Vec<SentPacket> {
+        let mut lost = self.dropped_packets();
+        let Some(largest) = self.largest_acked else {
+            return lost;
+        };
+
+        let packet_threshold = self.packet_threshold();
+        let loss_delay = self.loss_delay();
+        let mut sequences: Vec<SequenceNumber> = self
+            .sent_packets
+            .iter()
+            .filter(|(&sequence, packet)| {
+                sequence_less_than(sequence, largest)
+                    && largest.wrapping_sub(sequence) >= packet_threshold
+                    && now.saturating_duration_since(packet.sent_time) >= loss_delay
+            })
+            .map(|(&sequence, _)| sequence)
+            .collect();
+        // Oldest first
+        sequences.sort_unstable_by_key(|&sequence| Reverse(largest.wrapping_sub(sequence)));
+
+        for sequence in sequences {
+            if let Some(packet) = self.sent_packets.remove(&sequence) {
+                self.congestion.record_loss();
+                lost.push(packet);
+            }
+        }
+        lost
+    }
 }
 
 /// Represents a packet that has been sent but not yet acknowledged.
@@ -217,6 +473,16 @@ pub struct SentPacket {
     pub sent_time: Instant,
 }
 
+/// A packet acknowledged by the remote, as reported by
+/// [`AcknowledgmentHandler::process_incoming`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AckedPacket {
+    /// Payload bytes of the packet
+    pub bytes: usize,
+    /// Time from sending the packet to receiving its acknowledgment
+    pub rtt: Duration,
+}
+
 /// Marker for a received packet in the sequence buffer.
 #[derive(Clone, Default)]
 pub struct ReceivedPacket;
@@ -280,6 +546,25 @@ mod tests {
         assert!(initial_loss_rate < 0.01); // Should be very low or zero
     }
 
+    #[test]
+    fn test_restore_received_preserves_ack_bitfield() {
+        let mut handler = AcknowledgmentHandler::new();
+        let now = Instant::now();
+        for sequence in [3, 4, 6, 9, 150, 200] {
+            handler.process_incoming(sequence, sequence, 0, now);
+        }
+
+        let mut restored = AcknowledgmentHandler::new();
+        restored.restore_received(handler.remote_ack_sequence_num(), &handler.received_sequences());
+
+        // Sequences beyond the ACK bitfield are kept for SACKs
+        assert_eq!(handler.received_sequences(), vec![3, 4, 6, 9, 150, 200]);
+        assert_eq!(restored.remote_sequence_num(), 200);
+        assert_eq!(restored.ack_bitfield(), handler.ack_bitfield());
+        assert_eq!(restored.sack_ranges(8), handler.sack_ranges(8));
+        assert_eq!(restored.remote_ack_sequence_num(), 200);
+    }
+
     #[test]
     fn test_congestion_metrics_api() {
         let handler = AcknowledgmentHandler::new();
@@ -316,6 +601,32 @@ mod tests {
         assert!(updated);
     }
 
+    #[test]
+    fn test_process_incoming_reports_acked_packets() {
+        let mut handler = AcknowledgmentHandler::new();
+        let start = Instant::now();
+        for payload in [&b"a"[..], b"bb", b"ccc"] {
+            handler.process_outgoing(
+                PacketType::Packet,
+                payload,
+                OrderingGuarantee::None,
+                None,
+                start,
+            );
+        }
+        assert_eq!(handler.bytes_in_flight(), 6);
+
+        // Acknowledge sequences 2 and 0, skipping 1
+        let later = start + Duration::from_millis(40);
+        let acked = handler.process_incoming(0, 2, 0b10, later);
+        let rtt = Duration::from_millis(40);
+        assert_eq!(acked, vec![AckedPacket { bytes: 3, rtt }, AckedPacket { bytes: 1, rtt }]);
+        assert_eq!(handler.bytes_in_flight(), 2);
+
+        // Acknowledgments for packets no longer in flight report nothing
+        assert!(handler.process_incoming(0, 2, 0b10, later).is_empty());
+    }
+
     #[test]
     fn test_dropped_packets_only_behind_ack() {
         let mut handler = AcknowledgmentHandler::new();
@@ -407,4 +718,122 @@ mod tests {
         let dropped = handler.dropped_packets();
         assert_eq!(dropped.len(), 1, "Packet >32 behind should be dropped");
     }
+
+    fn send_packets(handler: &mut AcknowledgmentHandler, count: usize, now: Instant) {
+        for _ in 0..count {
+            handler.process_outgoing(PacketType::Packet, b"x", OrderingGuarantee::None, None, now);
+        }
+    }
+
+    fn in_flight(handler: &AcknowledgmentHandler) -> Vec<SequenceNumber> {
+        let mut sequences: Vec<_> = handler.sent_packets().map(|(sequence, _)| sequence).collect();
+        sequences.sort_unstable();
+        sequences
+    }
+
+    #[test]
+    fn test_reordered_packet_is_not_declared_lost() {
+        let mut handler = AcknowledgmentHandler::new();
+        let start = Instant::now();
+        send_packets(&mut handler, 6, start);
+
+        // 3, 4 and 5 are acknowledged; 2 is three behind but was sent less than an RTT ago
+        let acked = start + Duration::from_millis(40);
+        handler.process_incoming(0, 5, 0b11, acked);
+        assert!(handler.lost_packets(acked).is_empty());
+        assert_eq!(in_flight(&handler), vec![0, 1, 2]);
+
+        // 2 was only reordered: its late acknowledgment raises the packet threshold
+        handler.process_incoming(0, 2, 0, acked);
+        assert_eq!(handler.reorder_extent(), 3);
+        assert_eq!(handler.packet_threshold(), 4);
+
+        // 0 and 1 are past both thresholds and truly lost
+        let lost = handler.lost_packets(start + Duration::from_millis(500));
+        assert_eq!(lost.len(), 2);
+        assert!(in_flight(&handler).is_empty());
+    }
+
+    #[test]
+    fn test_packet_threshold_adapts_to_reordering() {
+        let mut handler = AcknowledgmentHandler::new();
+        let start = Instant::now();
+        send_packets(&mut handler, 10, start);
+
+        let acked = start + Duration::from_millis(40);
+        handler.process_incoming(0, 9, 0, acked);
+        handler.process_incoming(0, 3, 0, acked);
+        assert_eq!(handler.reorder_extent(), 6);
+
+        // Long after sending, only packets at least seven behind 9 are declared lost
+        let lost = handler.lost_packets(start + Duration::from_secs(1));
+        assert_eq!(lost.len(), 3);
+        assert_eq!(in_flight(&handler), vec![4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_bitfield_acknowledgments_are_not_reordering() {
+        let mut handler = AcknowledgmentHandler::new();
+        let now = Instant::now();
+        send_packets(&mut handler, 4, now);
+
+        handler.process_incoming(0, 3, 0b111, now);
+        assert_eq!(handler.reorder_extent(), 0);
+        assert_eq!(handler.packet_threshold(), DEFAULT_REORDER_THRESHOLD);
+    }
+
+    #[test]
+    fn test_sack_ranges_list_received_runs_newest_first() {
+        let mut receiver = AcknowledgmentHandler::new();
+        let now = Instant::now();
+        assert!(receiver.sack_ranges(8).is_empty());
+
+        for sequence in (0..=40).filter(|sequence| ![4, 20, 21].contains(sequence)) {
+            receiver.process_incoming(sequence, 0, 0, now);
+        }
+        assert_eq!(receiver.sack_ranges(8), vec![(22, 40), (5, 19), (0, 3)]);
+        // Capped, keeping the newest ranges
+        assert_eq!(receiver.sack_ranges(2), vec![(22, 40), (5, 19)]);
+    }
+
+    #[test]
+    fn test_sack_leaves_only_the_gap_to_retransmit() {
+        let mut sender = AcknowledgmentHandler::new();
+        let mut receiver = AcknowledgmentHandler::new();
+        let start = Instant::now();
+        send_packets(&mut sender, 10, start);
+
+        // The middle packet is lost on the way
+        for sequence in (0..10).filter(|&sequence| sequence != 4) {
+            receiver.process_incoming(sequence, 0, 0, start);
+        }
+        let ranges = receiver.sack_ranges(4);
+        assert_eq!(ranges, vec![(5, 9), (0, 3)]);
+
+        let acked = sender.process_sack(&ranges, start + Duration::from_millis(40));
+        assert_eq!(acked.len(), 9);
+        assert_eq!(in_flight(&sender), vec![4]);
+
+        let lost = sender.lost_packets(start + Duration::from_millis(500));
+        assert_eq!(lost.len(), 1);
+        assert!(in_flight(&sender).is_empty());
+    }
+
+    #[test]
+    fn test_bogus_sack_ranges_are_ignored() {
+        let mut sender = AcknowledgmentHandler::new();
+        let start = Instant::now();
+        send_packets(&mut sender, 10, start);
+        let later = start + Duration::from_millis(40);
+
+        // Wrapping, spanning every sequence number, or reaching past the newest packet sent
+        for ranges in [vec![(9, 0)], vec![(0, 65535)], vec![(0, 10)], vec![(0, 3), (5, 300)]] {
+            assert!(sender.process_sack(&ranges, later).is_empty(), "{ranges:?}");
+            assert_eq!(in_flight(&sender).len(), 10);
+        }
+
+        // A range starting before the first packet sent only acknowledges what was sent
+        assert_eq!(sender.process_sack(&[(65530, 4)], later).len(), 5);
+        assert_eq!(in_flight(&sender), vec![5, 6, 7, 8, 9]);
+    }
 }
@@ -8,10 +8,13 @@ use std::time::Instant;
 
 use bitfold_core::shared::SharedBytes;
 
+use crate::command_codec::{CommandDecoder, CommandEncoder, DecodeError};
+
 /// Protocol commands that can be sent between peers.
 ///
 /// All protocol operations are represented as discrete commands that can be aggregated.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ProtocolCommand {
     /// Send reliable data on a channel
     SendReliable {
@@ -84,6 +87,10 @@ pub enum ProtocolCommand {
     },
 
     /// Acknowledge received reliable packets
+    ///
+    /// Packets up to 32 before `sequence` are acknowledged in the fixed-size `received_mask`.
+    /// Runs received further back are sent as ranges in a separate `Sack` command, which is
+    /// only processed when selective acknowledgment is enabled.
     Acknowledge {
         /// Sequence number being acknowledged
         sequence: u16,
@@ -117,6 +124,8 @@ pub enum ProtocolCommand {
         outgoing_session_id: u16,
         /// Connect ID for replay protection
         connect_id: u32,
+        /// Idle timeout the client wants to enforce (milliseconds)
+        idle_timeout_ms: u32,
     },
 
     /// Verify connection (3-way handshake step 2) - replaces old ConnectAck
@@ -133,6 +142,8 @@ pub enum ProtocolCommand {
         outgoing_session_id: u16,
         /// Window size for flow control
         window_size: u32,
+        /// Idle timeout agreed for the connection: the smaller of both sides' (milliseconds)
+        idle_timeout_ms: u32,
     },
 
     /// Request to disconnect
@@ -176,9 +187,87 @@ pub enum ProtocolCommand {
         /// Echoed token
         token: u32,
     },
+
+    /// Path MTU probe request: asks the receiver to send a PMTU probe of the given size back,
+    /// testing the path towards the requester
+    PMTUProbeRequest {
+        /// Requested probe size in bytes
+        size: u16,
+        /// Correlation token to carry in the probe
+        token: u32,
+        /// Zero bytes appended to the request, since the receiver answers only requests at
+        /// least a third of the probe size
+        padding: u16,
+    },
+
+    /// Flow-control window probe: asks the receiver for a fresh window update
+    /// while the sender is blocked on a full window
+    WindowProbe,
+
+    /// Flow-control window update: sent in response to a window probe and whenever the
+    /// receiver's free buffer space changes markedly
+    WindowUpdate {
+        /// Total reliable payload bytes received so far (wrapping)
+        received: u32,
+        /// Further bytes the receiver can buffer beyond `received`
+        available: u32,
+    },
+
+    /// Refusal of a Connect whose protocol version is not supported, listing the versions
+    /// that are so the client can retry with one of them
+    VersionNegotiation {
+        /// Connect ID of the refused Connect, so the client ignores refusals it did not cause
+        connect_id: u32,
+        /// Protocol versions the sender supports, ascending
+        supported: Vec<u16>,
+    },
+
+    /// Selective acknowledgment: ranges of received sequence numbers, reaching further back
+    /// than the bitfield of an Acknowledge so the sender only retransmits the gaps
+    Sack {
+        /// Inclusive `(first, last)` ranges of received sequence numbers, newest first
+        ranges: Vec<(u16, u16)>,
+    },
+
+    /// Path validation challenge, sent to a new source address before a connection migrates
+    /// to it
+    PathChallenge {
+        /// Unpredictable data the response must echo
+        data: u64,
+    },
+
+    /// Path validation response echoing the data of a received challenge
+    PathResponse {
+        /// Data of the challenge being answered
+        data: u64,
+    },
+
+    /// Identifies the connection a datagram belongs to, so it is still recognized when it
+    /// arrives from a new source address (e.g. after a NAT rebinding)
+    ConnectionId {
+        /// Session ID of the sender (its outgoing session ID)
+        session_id: u16,
+        /// Connect ID of the connection
+        connect_id: u32,
+    },
 }
 
 impl ProtocolCommand {
+    /// Appends this command to `buf` in its wire format, framed with a length prefix as
+    /// within a packet.
+    ///
+    /// `decode(encode(command)) == command` holds for every command whose payloads fit their
+    /// 16-bit length prefixes.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        CommandEncoder::encode_framed(buf, self).expect("writing to a Vec cannot fail");
+    }
+
+    /// Decodes one command written by [`ProtocolCommand::encode`] from the front of `buf`,
+    /// advancing `buf` past it. `buf` is left untouched on error.
+    pub fn decode(buf: &mut &[u8]) -> Result<ProtocolCommand, DecodeError> {
+        CommandDecoder::decode_framed(buf)
+    }
+
     /// Returns the command type identifier for serialization
     pub fn command_type(&self) -> u8 {
         match self {
@@ -198,6 +287,14 @@ impl ProtocolCommand {
             ProtocolCommand::ThrottleConfigure { .. } => 14,
             ProtocolCommand::PMTUProbe { .. } => 15,
             ProtocolCommand::PMTUReply { .. } => 16,
+            ProtocolCommand::WindowProbe => 17,
+            ProtocolCommand::WindowUpdate { .. } => 18,
+            ProtocolCommand::PMTUProbeRequest { .. } => 19,
+            ProtocolCommand::VersionNegotiation { .. } => 20,
+            ProtocolCommand::Sack { .. } => 21,
+            ProtocolCommand::PathChallenge { .. } => 22,
+            ProtocolCommand::PathResponse { .. } => 23,
+            ProtocolCommand::ConnectionId { .. } => 24,
         }
     }
 
@@ -213,6 +310,36 @@ impl ProtocolCommand {
         )
     }
 
+    /// Returns true if receiving this command may warrant an acknowledgment.
+    ///
+    /// Pure ACKs and keepalives (ping/pong) are not ack-eliciting, which keeps ACKs from
+    /// being acknowledged in turn.
+    pub fn is_ack_eliciting(&self) -> bool {
+        !matches!(
+            self,
+            ProtocolCommand::Acknowledge { .. }
+                | ProtocolCommand::Sack { .. }
+                | ProtocolCommand::Ping { .. }
+                | ProtocolCommand::Pong { .. }
+                | ProtocolCommand::ConnectionId { .. }
+        )
+    }
+
+    /// Returns true if this command is PMTU discovery traffic (probe, reply or probe request)
+    pub fn is_pmtu(&self) -> bool {
+        matches!(
+            self,
+            ProtocolCommand::PMTUProbe { .. }
+                | ProtocolCommand::PMTUReply { .. }
+                | ProtocolCommand::PMTUProbeRequest { .. }
+        )
+    }
+
+    /// Returns true if this command carries application payload on a channel
+    pub fn is_data(&self) -> bool {
+        self.channel_id().is_some()
+    }
+
     /// Returns the channel ID if this is a data command
     pub fn channel_id(&self) -> Option<u8> {
         match self {
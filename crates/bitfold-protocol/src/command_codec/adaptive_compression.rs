@@ -0,0 +1,161 @@
+//! Adaptive compression that suspends itself on incompressible traffic.
+
+use std::collections::VecDeque;
+
+/// Tracks the compression ratio over a sliding window of recent datagrams and suspends
+/// compression while it stays poor.
+///
+/// Compression is suspended once a full window of datagrams averages a ratio (compressed /
+/// original size) above `max_ratio`. While suspended, one datagram per window is still
+/// compressed as a sample; compression resumes as soon as a sample shrinks below `max_ratio`.
+#[derive(Debug, Clone)]
+pub struct AdaptiveCompression {
+    window: usize,
+    max_ratio: f32,
+    /// Original and compressed sizes of the datagrams in the current window
+    samples: VecDeque<(usize, usize)>,
+    suspended: bool,
+    /// Datagrams sent uncompressed since the last sample while suspended
+    skipped: usize,
+}
+
+impl AdaptiveCompression {
+    /// Creates a tracker averaging over `window` datagrams (at least 1) that suspends
+    /// compression while the ratio exceeds `max_ratio`.
+    pub fn new(window: usize, max_ratio: f32) -> Self {
+        let window = window.max(1);
+        Self {
+            window,
+            max_ratio,
+            samples: VecDeque::with_capacity(window),
+            suspended: false,
+            skipped: 0,
+        }
+    }
+
+    /// Returns true if `data` should be run through the compressor.
+    ///
+    /// While suspended this returns true once per window, so changes in the traffic are
+    /// noticed. Every datagram it returns true for must be reported with [`Self::record`].
+    pub fn should_compress(&mut self, data: &[u8]) -> bool {
+        if data.is_empty() {
+            return false;
+        }
+        if !self.suspended {
+            return true;
+        }
+        self.skipped += 1;
+        if self.skipped >= self.window {
+            self.skipped = 0;
+            return true;
+        }
+        false
+    }
+
+    /// Records the outcome of compressing a datagram of `original` bytes into `compressed`
+    /// bytes (including any fallback to storing it uncompressed).
+    pub fn record(&mut self, original: usize, compressed: usize) {
+        if original == 0 {
+            return;
+        }
+        if self.suspended {
+            // A single sample that compresses well resumes compression
+            if (compressed as f32 / original as f32) <= self.max_ratio {
+                self.suspended = false;
+                self.samples.clear();
+            }
+            return;
+        }
+
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((original, compressed));
+        if self.samples.len() == self.window && self.ratio() > self.max_ratio {
+            self.suspended = true;
+            self.skipped = 0;
+            self.samples.clear();
+        }
+    }
+
+    /// Returns true while compression is suspended.
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    /// Returns the ratio (compressed / original size) over the current window, or 1.0 when
+    /// it is empty.
+    pub fn ratio(&self) -> f32 {
+        let (original, compressed) =
+            self.samples.iter().fold((0, 0), |(original, compressed), sample| {
+                (original + sample.0, compressed + sample.1)
+            });
+        if original == 0 {
+            1.0
+        } else {
+            compressed as f32 / original as f32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitfold_core::config::CompressionAlgorithm;
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+    use super::*;
+    use crate::command_codec::compress;
+
+    /// Compresses `data` with LZ4 if the tracker allows it, recording the outcome.
+    fn send(adaptive: &mut AdaptiveCompression, data: &[u8]) -> bool {
+        if !adaptive.should_compress(data) {
+            return false;
+        }
+        let compressed = compress(data, CompressionAlgorithm::Lz4, 0).unwrap();
+        adaptive.record(data.len(), compressed.len());
+        true
+    }
+
+    #[test]
+    fn test_incompressible_traffic_suspends_compression() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut adaptive = AdaptiveCompression::new(8, 0.95);
+        let mut data = vec![0u8; 512];
+
+        for sent in 1..=8 {
+            rng.fill_bytes(&mut data);
+            assert!(send(&mut adaptive, &data));
+            assert_eq!(adaptive.is_suspended(), sent == 8);
+        }
+
+        // Only one datagram per window is sampled while suspended
+        let compressed: usize = (0..16).map(|_| send(&mut adaptive, &data) as usize).sum();
+        assert_eq!(compressed, 2);
+        assert!(adaptive.is_suspended());
+    }
+
+    #[test]
+    fn test_compressible_sample_resumes_compression() {
+        let mut adaptive = AdaptiveCompression::new(4, 0.95);
+        for _ in 0..4 {
+            adaptive.record(100, 101);
+        }
+        assert!(adaptive.is_suspended());
+
+        let text = vec![b'a'; 512];
+        while !send(&mut adaptive, &text) {}
+        assert!(!adaptive.is_suspended());
+        assert!(adaptive.should_compress(&text));
+    }
+
+    #[test]
+    fn test_compressible_traffic_stays_enabled() {
+        let mut adaptive = AdaptiveCompression::new(4, 0.95);
+        for _ in 0..32 {
+            adaptive.record(500, 100);
+        }
+        assert!(!adaptive.is_suspended());
+        assert!((adaptive.ratio() - 0.2).abs() < 1e-6);
+        assert!(!adaptive.should_compress(&[]));
+    }
+}
@@ -1,55 +1,78 @@
-//! CRC32 checksum utilities for data integrity verification.
+//! Checksum utilities (CRC32, xxHash) for data integrity verification.
 
 use std::io;
 
+use bitfold_core::config::ChecksumAlgorithm;
 use crc32fast::Hasher;
+use xxhash_rust::{xxh32::xxh32, xxh64::xxh64};
+
+/// Header-byte flag marking a datagram sent without a trailing checksum.
+///
+/// Shares the leading byte with the compression marker, which only uses the low bits.
+pub const CHECKSUM_OMITTED_FLAG: u8 = 0x80;
+
+/// Flags a (compressed) datagram as carrying no checksum.
+pub fn mark_checksum_omitted(data: &mut [u8]) {
+    if let Some(header) = data.first_mut() {
+        *header |= CHECKSUM_OMITTED_FLAG;
+    }
+}
+
+/// Returns true if the datagram was flagged as carrying no checksum.
+pub fn is_checksum_omitted(data: &[u8]) -> bool {
+    data.first().is_some_and(|header| header & CHECKSUM_OMITTED_FLAG != 0)
+}
 
 /// Appends a CRC32 checksum to the encoded packet data.
 /// Returns a new vector with the checksum appended.
 pub fn append_checksum(data: &[u8]) -> Vec<u8> {
-    let mut hasher = Hasher::new();
-    hasher.update(data);
-    let checksum = hasher.finalize();
-
     let mut result = Vec::with_capacity(data.len() + 4);
     result.extend_from_slice(data);
-    result.extend_from_slice(&checksum.to_be_bytes());
+    append_checksum_in_place(&mut result);
     result
 }
 
 /// Appends a CRC32 checksum to the provided buffer in-place.
 pub fn append_checksum_in_place(data: &mut Vec<u8>) {
-    let mut hasher = Hasher::new();
-    hasher.update(data);
-    let checksum = hasher.finalize();
-    data.extend_from_slice(&checksum.to_be_bytes());
+    append_checksum_in_place_with(data, ChecksumAlgorithm::Crc32);
+}
+
+/// Appends a checksum computed with `algorithm` to the provided buffer in-place.
+/// Appends nothing for `ChecksumAlgorithm::None`.
+pub fn append_checksum_in_place_with(data: &mut Vec<u8>, algorithm: ChecksumAlgorithm) {
+    let checksum = compute_checksum(data, algorithm);
+    let bytes = checksum.to_be_bytes();
+    data.extend_from_slice(&bytes[bytes.len() - algorithm.len_bytes()..]);
 }
 
 /// Validates and strips the CRC32 checksum from packet data.
 /// Returns the data without checksum if valid, or an error if checksum fails.
 pub fn validate_and_strip_checksum(data: &[u8]) -> io::Result<&[u8]> {
-    if data.len() < 4 {
+    validate_and_strip_checksum_with(data, ChecksumAlgorithm::Crc32)
+}
+
+/// Validates and strips a checksum computed with `algorithm` from packet data.
+/// Returns the data unchanged for `ChecksumAlgorithm::None`.
+pub fn validate_and_strip_checksum_with(
+    data: &[u8],
+    algorithm: ChecksumAlgorithm,
+) -> io::Result<&[u8]> {
+    let len = algorithm.len_bytes();
+    if data.len() < len {
         return Err(io::Error::new(io::ErrorKind::InvalidData, "Data too short for checksum"));
     }
 
-    let (payload, checksum_bytes) = data.split_at(data.len() - 4);
-    let received_checksum = u32::from_be_bytes([
-        checksum_bytes[0],
-        checksum_bytes[1],
-        checksum_bytes[2],
-        checksum_bytes[3],
-    ]);
-
-    let mut hasher = Hasher::new();
-    hasher.update(payload);
-    let computed_checksum = hasher.finalize();
+    let (payload, checksum_bytes) = data.split_at(data.len() - len);
+    let received_checksum =
+        checksum_bytes.iter().fold(0u64, |checksum, &byte| (checksum << 8) | byte as u64);
+    let computed_checksum = compute_checksum(payload, algorithm);
 
     if received_checksum != computed_checksum {
         return Err(io::Error::new(
             io::ErrorKind::InvalidData,
             format!(
-                "CRC32 checksum mismatch: expected {}, got {}",
-                computed_checksum, received_checksum
+                "{:?} checksum mismatch: expected {}, got {}",
+                algorithm, computed_checksum, received_checksum
             ),
         ));
     }
@@ -57,6 +80,20 @@ pub fn validate_and_strip_checksum(data: &[u8]) -> io::Result<&[u8]> {
     Ok(payload)
 }
 
+/// Computes the checksum of `data`, widened to 64 bits.
+fn compute_checksum(data: &[u8], algorithm: ChecksumAlgorithm) -> u64 {
+    match algorithm {
+        ChecksumAlgorithm::None => 0,
+        ChecksumAlgorithm::Crc32 => {
+            let mut hasher = Hasher::new();
+            hasher.update(data);
+            hasher.finalize() as u64
+        }
+        ChecksumAlgorithm::XxHash32 => xxh32(data, 0) as u64,
+        ChecksumAlgorithm::XxHash64 => xxh64(data, 0),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -99,6 +136,56 @@ mod tests {
         assert_eq!(validated, data);
     }
 
+    const ALGORITHMS: [ChecksumAlgorithm; 4] = [
+        ChecksumAlgorithm::None,
+        ChecksumAlgorithm::Crc32,
+        ChecksumAlgorithm::XxHash32,
+        ChecksumAlgorithm::XxHash64,
+    ];
+
+    #[test]
+    fn test_checksum_algorithms_append_and_validate() {
+        let data = b"Hello, world!";
+        for algorithm in ALGORITHMS {
+            let mut buffer = data.to_vec();
+            append_checksum_in_place_with(&mut buffer, algorithm);
+            assert_eq!(buffer.len(), data.len() + algorithm.len_bytes());
+
+            let validated = validate_and_strip_checksum_with(&buffer, algorithm).unwrap();
+            assert_eq!(validated, data);
+        }
+        assert_eq!(ChecksumAlgorithm::XxHash64.len_bytes(), 8);
+    }
+
+    #[test]
+    fn test_checksum_algorithms_detect_corrupted_byte() {
+        let data = b"Hello, world!";
+        for algorithm in ALGORITHMS.into_iter().skip(1) {
+            let mut buffer = data.to_vec();
+            append_checksum_in_place_with(&mut buffer, algorithm);
+            for index in 0..buffer.len() {
+                let mut corrupted = buffer.clone();
+                corrupted[index] ^= 0x01;
+                assert!(
+                    validate_and_strip_checksum_with(&corrupted, algorithm).is_err(),
+                    "{:?} missed corruption at byte {}",
+                    algorithm,
+                    index
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_checksum_algorithms_are_not_interchangeable() {
+        let mut buffer = b"Hello, world!".to_vec();
+        append_checksum_in_place_with(&mut buffer, ChecksumAlgorithm::XxHash32);
+        assert!(validate_and_strip_checksum(&buffer).is_err());
+        assert!(
+            validate_and_strip_checksum_with(&buffer[..4], ChecksumAlgorithm::XxHash64).is_err()
+        );
+    }
+
     #[test]
     fn test_append_checksum_in_place() {
         let data = b"Test data";
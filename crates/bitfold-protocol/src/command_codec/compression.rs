@@ -5,6 +5,29 @@ use std::io::{self, Read, Write};
 use bitfold_core::config::CompressionAlgorithm;
 use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
 
+use super::checksum::CHECKSUM_OMITTED_FLAG;
+
+/// Bytes a Zstd frame adds around a datagram-sized block: magic number (4), frame header
+/// descriptor (1), content size (2) and block header (3).
+pub const ZSTD_FRAME_OVERHEAD: usize = 10;
+
+/// Header-byte flag asking the receiver to acknowledge the datagram without delay.
+///
+/// Shares the leading byte with the compression marker, which only uses the low bits.
+pub const IMMEDIATE_ACK_FLAG: u8 = 0x40;
+
+/// Flags a (compressed) datagram as requesting an immediate acknowledgment.
+pub fn mark_immediate_ack(data: &mut [u8]) {
+    if let Some(header) = data.first_mut() {
+        *header |= IMMEDIATE_ACK_FLAG;
+    }
+}
+
+/// Returns true if the datagram requests an immediate acknowledgment.
+pub fn is_immediate_ack_requested(data: &[u8]) -> bool {
+    data.first().is_some_and(|header| header & IMMEDIATE_ACK_FLAG != 0)
+}
+
 /// Compresses data using the specified algorithm.
 /// Returns compressed data with 1-byte header: `[algorithm_id][compressed_data]`
 /// Returns original data with header `[0][original_data]` if compression is disabled or ineffective.
@@ -77,20 +100,58 @@ pub fn compress_with_buffer(
                 Ok(output)
             }
         }
+        #[cfg(feature = "zstd")]
+        CompressionAlgorithm::Zstd { level } => {
+            let compressed = zstd::bulk::compress(data, level)?;
+
+            // Only use compression if it actually reduces size
+            if compressed.len() < data.len() {
+                output.reserve(compressed.len() + 1);
+                output.push(3); // Zstd marker
+                output.extend_from_slice(&compressed);
+                Ok(output)
+            } else {
+                output.reserve(data.len() + 1);
+                output.push(0); // Uncompressed marker
+                output.extend_from_slice(data);
+                Ok(output)
+            }
+        }
+        #[cfg(not(feature = "zstd"))]
+        CompressionAlgorithm::Zstd { .. } => Err(zstd_unsupported()),
     }
 }
 
+/// Error for Zstd datagrams when the `zstd` feature is disabled.
+#[cfg(not(feature = "zstd"))]
+fn zstd_unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "Zstd compression requires the zstd feature")
+}
+
 /// Decompresses data based on the 1-byte header.
 /// Header format: `[algorithm_id][data]`
 /// - 0: Uncompressed
 /// - 1: Zlib
 /// - 2: LZ4
+/// - 3: Zstd (requires the `zstd` feature)
+///
+/// Output is capped at `u16::MAX` bytes, see [`decompress_limited`].
 pub fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+    decompress_limited(data, u16::MAX as usize)
+}
+
+/// Decompresses data like [`decompress`], failing once compressed data inflates to more than
+/// `max_len` bytes. Uncompressed data is already bounded by its own length.
+///
+/// Sizes declared by the compressed data are not trusted: output is never grown past
+/// `max_len`, so a small datagram cannot force a large allocation.
+pub fn decompress_limited(data: &[u8], max_len: usize) -> io::Result<Vec<u8>> {
     if data.is_empty() {
         return Err(io::Error::new(io::ErrorKind::InvalidData, "Empty data for decompression"));
     }
 
-    let algorithm_id = data[0];
+    // The high bits of the marker byte are reserved for header flags
+    let algorithm_id = data[0] & !(CHECKSUM_OMITTED_FLAG | IMMEDIATE_ACK_FLAG);
     let payload = &data[1..];
 
     match algorithm_id {
@@ -100,10 +161,7 @@ pub fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
         }
         1 => {
             // Zlib
-            let mut decoder = ZlibDecoder::new(payload);
-            let mut decompressed = Vec::new();
-            decoder.read_to_end(&mut decompressed)?;
-            Ok(decompressed)
+            read_limited(ZlibDecoder::new(payload), max_len)
         }
         2 => {
             // LZ4 - first 4 bytes are original size
@@ -112,10 +170,20 @@ pub fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
             }
             let original_size =
                 u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]) as usize;
+            if original_size > max_len {
+                return Err(too_large(max_len));
+            }
             let compressed_data = &payload[4..];
             let decompressed = lz4::block::decompress(compressed_data, Some(original_size as i32))?;
             Ok(decompressed)
         }
+        #[cfg(feature = "zstd")]
+        3 => {
+            // Zstd - a complete frame
+            read_limited(zstd::stream::read::Decoder::new(payload)?, max_len)
+        }
+        #[cfg(not(feature = "zstd"))]
+        3 => Err(zstd_unsupported()),
         _ => Err(io::Error::new(
             io::ErrorKind::InvalidData,
             format!("Unknown compression algorithm: {}", algorithm_id),
@@ -123,6 +191,24 @@ pub fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
     }
 }
 
+/// Reads `reader` to the end, failing once more than `max_len` bytes come out.
+fn read_limited(reader: impl Read, max_len: usize) -> io::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    reader.take(max_len as u64 + 1).read_to_end(&mut decompressed)?;
+    if decompressed.len() > max_len {
+        return Err(too_large(max_len));
+    }
+    Ok(decompressed)
+}
+
+/// Error for datagrams that decompress to more than `max_len` bytes.
+fn too_large(max_len: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Decompressed data exceeds {} bytes", max_len),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,6 +246,58 @@ mod tests {
         assert_eq!(decompressed, data);
     }
 
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_compression_zstd_round_trip() {
+        let data: Vec<u8> = (0..4096u32).map(|i| (i % 251) as u8 ^ (i / 512) as u8).collect();
+        for level in [1, 3, 19] {
+            let compressed = compress(&data, CompressionAlgorithm::Zstd { level }, 10).unwrap();
+            assert_eq!(compressed[0], 3); // Zstd marker
+            assert!(compressed.len() < data.len());
+
+            let decompressed = decompress(&compressed).unwrap();
+            assert_eq!(decompressed, data);
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_decompress_limited_rejects_zstd_bomb() {
+        // A frame of a few dozen bytes whose header declares a megabyte of content
+        let data = vec![0u8; 1 << 20];
+        let compressed = compress(&data, CompressionAlgorithm::Zstd { level: 3 }, 10).unwrap();
+        assert!(compressed.len() < 1500);
+
+        let error = decompress_limited(&compressed, 1500).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(decompress(&compressed).is_err());
+        assert_eq!(decompress_limited(&compressed, 1 << 20).unwrap(), data);
+    }
+
+    #[test]
+    fn test_decompress_limited_rejects_oversized_output() {
+        let data = vec![7u8; 4096];
+        let compressed = compress(&data, CompressionAlgorithm::Zlib, 10).unwrap();
+        let error = decompress_limited(&compressed, 4095).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(decompress_limited(&compressed, 4096).unwrap(), data);
+
+        // LZ4 is rejected on its declared size alone
+        let mut inflated = compress(&data, CompressionAlgorithm::Lz4, 10).unwrap();
+        inflated[1..5].copy_from_slice(&u32::MAX.to_be_bytes());
+        let error = decompress_limited(&inflated, 4096).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn test_compression_zstd_unsupported_without_feature() {
+        let data = [0u8; 4096];
+        let error = compress(&data, CompressionAlgorithm::Zstd { level: 3 }, 10).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+        assert!(decompress(&[3, 0x28, 0xb5, 0x2f, 0xfd]).is_err());
+    }
+
     #[test]
     fn test_compression_below_threshold() {
         let data = b"tiny";
@@ -0,0 +1,94 @@
+//! Replays captured datagrams from `corpus/datagrams` through the decoder.
+//!
+//! Each subdirectory classifies the datagrams in it (captured with CRC32 checksums):
+//!
+//! - `valid/` must decode
+//! - `invalid_data/`, `unexpected_eof/` must fail with that [`io::ErrorKind`]
+//! - `unclassified/` only must not panic, for new fuzz findings not yet triaged
+//!
+//! Adding a regression case is dropping its raw bytes into the right directory.
+
+use std::{
+    fs, io,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use bitfold_core::config::ChecksumAlgorithm;
+
+use super::CommandDecoder;
+
+/// Outcome a corpus directory expects from decoding each of its datagrams.
+#[derive(Debug, Clone, Copy)]
+enum Expected {
+    Valid,
+    Error(io::ErrorKind),
+    Unclassified,
+}
+
+impl Expected {
+    fn from_dir_name(name: &str) -> Option<Self> {
+        match name {
+            "valid" => Some(Expected::Valid),
+            "invalid_data" => Some(Expected::Error(io::ErrorKind::InvalidData)),
+            "unexpected_eof" => Some(Expected::Error(io::ErrorKind::UnexpectedEof)),
+            "unclassified" => Some(Expected::Unclassified),
+            _ => None,
+        }
+    }
+}
+
+fn corpus_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus").join("datagrams")
+}
+
+/// Lists the files in `dir`, sorted so failures are reported in a stable order.
+fn sorted_entries(dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .unwrap_or_else(|e| panic!("cannot read {}: {e}", dir.display()))
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    entries.sort();
+    entries
+}
+
+/// Decodes `path`, describing how the outcome differs from `expected`, if it does.
+fn replay(path: &Path, expected: Expected) -> Option<String> {
+    let data = fs::read(path).unwrap();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        CommandDecoder::decode_datagram(&data, ChecksumAlgorithm::Crc32)
+    }));
+    let outcome = match result {
+        Ok(outcome) => outcome,
+        Err(_) => return Some(format!("{}: decoder panicked", path.display())),
+    };
+    match (expected, outcome) {
+        (Expected::Valid, Err(e)) => {
+            Some(format!("{}: expected a valid decode, got {e}", path.display()))
+        }
+        (Expected::Error(kind), Ok(_)) => {
+            Some(format!("{}: expected a {kind:?} error, decoded fine", path.display()))
+        }
+        (Expected::Error(kind), Err(e)) if e.kind() != kind => {
+            Some(format!("{}: expected a {kind:?} error, got {:?}: {e}", path.display(), e.kind()))
+        }
+        _ => None,
+    }
+}
+
+#[test]
+fn test_decoder_corpus() {
+    let mut replayed = 0;
+    let mut failures = Vec::new();
+    for dir in sorted_entries(&corpus_dir()).into_iter().filter(|path| path.is_dir()) {
+        let name = dir.file_name().unwrap().to_string_lossy().into_owned();
+        let expected = Expected::from_dir_name(&name)
+            .unwrap_or_else(|| panic!("unknown corpus classification {name:?}"));
+        for path in sorted_entries(&dir) {
+            replayed += 1;
+            failures.extend(replay(&path, expected));
+        }
+    }
+    assert!(replayed > 0, "no datagrams found in {}", corpus_dir().display());
+    assert!(failures.is_empty(), "corpus regressions:\n{}", failures.join("\n"));
+}
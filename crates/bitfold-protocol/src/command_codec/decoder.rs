@@ -4,19 +4,116 @@
 //! This module handles the deserialization of command packets, including support for
 //! compression and checksum validation through companion modules.
 
-use std::io::{self, Cursor, Read};
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io::{self, Cursor, Read},
+};
 
-use bitfold_core::shared::SharedBytes;
+use bitfold_core::{config::ChecksumAlgorithm, shared::SharedBytes};
 use byteorder::{BigEndian, ReadBytesExt};
 
 use super::super::command::{CommandPacket, ProtocolCommand};
 
+/// Most ranges a selective acknowledgment can carry on the wire (its count is one byte).
+pub const MAX_SACK_RANGES: usize = u8::MAX as usize;
+
+/// Why a command could not be decoded.
+///
+/// The `io::Error`s returned by [`CommandDecoder`] wrap this, so it can be recovered with
+/// [`DecodeError::from_io`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input ended before the command did
+    Truncated,
+    /// The command type byte does not name a known command
+    UnknownCommandType(u8),
+    /// A length prefix declares more bytes than are available
+    LengthOverflow {
+        /// What the length prefix measures
+        field: &'static str,
+        /// Length declared by the prefix, in bytes
+        declared: usize,
+        /// Bytes available (remaining in the input, or allowed by a limit)
+        available: usize,
+    },
+    /// A PMTU probe declares a payload larger than the limit or the bytes remaining
+    PayloadTooLarge {
+        /// Payload length declared by the probe, in bytes
+        declared: usize,
+        /// Most payload bytes accepted
+        available: usize,
+    },
+    /// A selective acknowledgment lists more ranges than allowed
+    TooManySackRanges {
+        /// Ranges declared by the command
+        declared: usize,
+        /// Most ranges accepted
+        max: usize,
+    },
+}
+
+impl DecodeError {
+    /// Recovers the decode error behind an `io::Error` returned by [`CommandDecoder`].
+    ///
+    /// Errors not raised by the decoder itself can only be the input running out, so they
+    /// map to [`DecodeError::Truncated`].
+    pub fn from_io(err: io::Error) -> Self {
+        err.into_inner()
+            .and_then(|inner| inner.downcast::<DecodeError>().ok())
+            .map_or(DecodeError::Truncated, |err| *err)
+    }
+
+    fn into_io(self, kind: io::ErrorKind) -> io::Error {
+        io::Error::new(kind, self)
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(fmt, "Command truncated"),
+            DecodeError::UnknownCommandType(cmd_type) => {
+                write!(fmt, "Unknown command type: {}", cmd_type)
+            }
+            DecodeError::LengthOverflow { field, declared, available } => {
+                write!(fmt, "{} too large: {} bytes ({} available)", field, declared, available)
+            }
+            DecodeError::PayloadTooLarge { declared, available } => {
+                write!(
+                    fmt,
+                    "PMTU probe payload too large: {} bytes ({} available)",
+                    declared, available
+                )
+            }
+            DecodeError::TooManySackRanges { declared, max } => {
+                write!(fmt, "Too many SACK ranges: {} (at most {})", declared, max)
+            }
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
 /// Deserializes commands from network bytes.
 pub struct CommandDecoder;
 
 impl CommandDecoder {
     /// Decodes a single command from a cursor
     pub fn decode_command(cursor: &mut Cursor<&[u8]>) -> io::Result<ProtocolCommand> {
+        Self::decode_command_limited(cursor, u16::MAX as usize, MAX_SACK_RANGES)
+    }
+
+    /// Decodes a single command from a cursor, rejecting PMTU probes whose declared payload
+    /// exceeds `max_probe_payload` or the bytes remaining in the cursor, and SACKs listing
+    /// more than `max_sack_ranges` ranges.
+    ///
+    /// Both are validated before anything is allocated.
+    pub fn decode_command_limited(
+        cursor: &mut Cursor<&[u8]>,
+        max_probe_payload: usize,
+        max_sack_ranges: usize,
+    ) -> io::Result<ProtocolCommand> {
         let cmd_type = cursor.read_u8()?;
 
         let command = match cmd_type {
@@ -127,12 +224,14 @@ impl CommandDecoder {
                 let protocol_version = cursor.read_u16::<BigEndian>()?;
                 let outgoing_session_id = cursor.read_u16::<BigEndian>()?;
                 let connect_id = cursor.read_u32::<BigEndian>()?;
+                let idle_timeout_ms = cursor.read_u32::<BigEndian>()?;
                 ProtocolCommand::Connect {
                     channels,
                     mtu,
                     protocol_version,
                     outgoing_session_id,
                     connect_id,
+                    idle_timeout_ms,
                 }
             }
             11 => {
@@ -143,6 +242,7 @@ impl CommandDecoder {
                 let incoming_session_id = cursor.read_u16::<BigEndian>()?;
                 let outgoing_session_id = cursor.read_u16::<BigEndian>()?;
                 let window_size = cursor.read_u32::<BigEndian>()?;
+                let idle_timeout_ms = cursor.read_u32::<BigEndian>()?;
                 ProtocolCommand::VerifyConnect {
                     peer_id,
                     channels,
@@ -150,6 +250,7 @@ impl CommandDecoder {
                     incoming_session_id,
                     outgoing_session_id,
                     window_size,
+                    idle_timeout_ms,
                 }
             }
             12 => {
@@ -175,6 +276,12 @@ impl CommandDecoder {
                 let size = cursor.read_u16::<BigEndian>()?;
                 let token = cursor.read_u32::<BigEndian>()?;
                 let payload_len = cursor.read_u16::<BigEndian>()? as usize;
+                let remaining = cursor.get_ref().len().saturating_sub(cursor.position() as usize);
+                let available = max_probe_payload.min(remaining);
+                if payload_len > available {
+                    return Err(DecodeError::PayloadTooLarge { declared: payload_len, available }
+                        .into_io(io::ErrorKind::InvalidData));
+                }
                 let mut payload = vec![0u8; payload_len];
                 cursor.read_exact(&mut payload)?;
                 ProtocolCommand::PMTUProbe { size, token, payload: SharedBytes::from_vec(payload) }
@@ -185,19 +292,118 @@ impl CommandDecoder {
                 let token = cursor.read_u32::<BigEndian>()?;
                 ProtocolCommand::PMTUReply { size, token }
             }
+            17 => {
+                // WindowProbe
+                ProtocolCommand::WindowProbe
+            }
+            18 => {
+                // WindowUpdate
+                let received = cursor.read_u32::<BigEndian>()?;
+                let available = cursor.read_u32::<BigEndian>()?;
+                ProtocolCommand::WindowUpdate { received, available }
+            }
+            19 => {
+                // PMTUProbeRequest
+                let size = cursor.read_u16::<BigEndian>()?;
+                let token = cursor.read_u32::<BigEndian>()?;
+                let padding = cursor.read_u16::<BigEndian>()?;
+                let remaining = cursor.get_ref().len().saturating_sub(cursor.position() as usize);
+                if padding as usize > remaining {
+                    return Err(DecodeError::LengthOverflow {
+                        field: "PMTU probe request padding",
+                        declared: padding as usize,
+                        available: remaining,
+                    }
+                    .into_io(io::ErrorKind::UnexpectedEof));
+                }
+                cursor.set_position(cursor.position() + u64::from(padding));
+                ProtocolCommand::PMTUProbeRequest { size, token, padding }
+            }
+            20 => {
+                // VersionNegotiation
+                let connect_id = cursor.read_u32::<BigEndian>()?;
+                let count = cursor.read_u8()? as usize;
+                let supported = (0..count)
+                    .map(|_| cursor.read_u16::<BigEndian>())
+                    .collect::<io::Result<Vec<_>>>()?;
+                ProtocolCommand::VersionNegotiation { connect_id, supported }
+            }
+            21 => {
+                // Sack
+                let count = cursor.read_u8()? as usize;
+                if count > max_sack_ranges.min(MAX_SACK_RANGES) {
+                    return Err(DecodeError::TooManySackRanges {
+                        declared: count,
+                        max: max_sack_ranges.min(MAX_SACK_RANGES),
+                    }
+                    .into_io(io::ErrorKind::InvalidData));
+                }
+                let ranges = (0..count)
+                    .map(|_| Ok((cursor.read_u16::<BigEndian>()?, cursor.read_u16::<BigEndian>()?)))
+                    .collect::<io::Result<Vec<_>>>()?;
+                ProtocolCommand::Sack { ranges }
+            }
+            22 => {
+                // PathChallenge
+                let data = cursor.read_u64::<BigEndian>()?;
+                ProtocolCommand::PathChallenge { data }
+            }
+            23 => {
+                // PathResponse
+                let data = cursor.read_u64::<BigEndian>()?;
+                ProtocolCommand::PathResponse { data }
+            }
+            24 => {
+                // ConnectionId
+                let session_id = cursor.read_u16::<BigEndian>()?;
+                let connect_id = cursor.read_u32::<BigEndian>()?;
+                ProtocolCommand::ConnectionId { session_id, connect_id }
+            }
             _ => {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("Unknown command type: {}", cmd_type),
-                ));
+                return Err(
+                    DecodeError::UnknownCommandType(cmd_type).into_io(io::ErrorKind::InvalidData)
+                );
             }
         };
 
         Ok(command)
     }
 
+    /// Decodes one length-prefixed command, as written by [`CommandEncoder::encode_framed`],
+    /// from the front of `buf` and advances `buf` past it. `buf` is left untouched on error.
+    ///
+    /// [`CommandEncoder::encode_framed`]: super::CommandEncoder::encode_framed
+    pub fn decode_framed(buf: &mut &[u8]) -> Result<ProtocolCommand, DecodeError> {
+        let &[high, low, ref rest @ ..] = *buf else {
+            return Err(DecodeError::Truncated);
+        };
+        let cmd_len = u16::from_be_bytes([high, low]) as usize;
+        if cmd_len > rest.len() {
+            return Err(DecodeError::LengthOverflow {
+                field: "Command length",
+                declared: cmd_len,
+                available: rest.len(),
+            });
+        }
+        let (cmd_data, rest) = rest.split_at(cmd_len);
+        let command =
+            Self::decode_command(&mut Cursor::new(cmd_data)).map_err(DecodeError::from_io)?;
+        *buf = rest;
+        Ok(command)
+    }
+
     /// Decodes a command packet containing multiple commands
     pub fn decode_packet(data: &[u8]) -> io::Result<CommandPacket> {
+        Self::decode_packet_limited(data, u16::MAX as usize, MAX_SACK_RANGES)
+    }
+
+    /// Decodes a command packet, capping PMTU probe payloads at `max_probe_payload` bytes and
+    /// SACKs at `max_sack_ranges` ranges (see [`CommandDecoder::decode_command_limited`]).
+    pub fn decode_packet_limited(
+        data: &[u8],
+        max_probe_payload: usize,
+        max_sack_ranges: usize,
+    ) -> io::Result<CommandPacket> {
         let mut cursor = Cursor::new(data);
         let mut packet = CommandPacket::new();
 
@@ -210,15 +416,18 @@ impl CommandDecoder {
             let pos = cursor.position() as usize;
 
             if pos + cmd_len > data.len() {
-                return Err(io::Error::new(
-                    io::ErrorKind::UnexpectedEof,
-                    "Command length exceeds buffer",
-                ));
+                return Err(DecodeError::LengthOverflow {
+                    field: "Command length",
+                    declared: cmd_len,
+                    available: data.len() - pos,
+                }
+                .into_io(io::ErrorKind::UnexpectedEof));
             }
 
             let cmd_data = &data[pos..pos + cmd_len];
             let mut cmd_cursor = Cursor::new(cmd_data);
-            let command = Self::decode_command(&mut cmd_cursor)?;
+            let command =
+                Self::decode_command_limited(&mut cmd_cursor, max_probe_payload, max_sack_ranges)?;
 
             packet.add_command(command);
             cursor.set_position((pos + cmd_len) as u64);
@@ -227,6 +436,17 @@ impl CommandDecoder {
         Ok(packet)
     }
 
+    /// Decodes a raw datagram as received from the socket: validates and strips a checksum
+    /// computed with `algorithm`, decompresses and decodes the command packet.
+    ///
+    /// Peers additionally honor `Config::checksum_data_only`, cap PMTU probe payloads at the
+    /// receive buffer size and SACKs at `Config::sack_max_ranges`; this is the plain wire format, e.g. for replaying captures.
+    pub fn decode_datagram(data: &[u8], algorithm: ChecksumAlgorithm) -> io::Result<CommandPacket> {
+        let payload = Self::validate_and_strip_checksum_with(data, algorithm)?;
+        let decompressed = Self::decompress(payload)?;
+        Self::decode_packet(&decompressed)
+    }
+
     /// Validates and strips the CRC32 checksum from packet data.
     /// Returns the data without checksum if valid, or an error if checksum fails.
     ///
@@ -235,14 +455,29 @@ impl CommandDecoder {
         super::checksum::validate_and_strip_checksum(data)
     }
 
+    /// Validates and strips a checksum computed with `algorithm` from packet data.
+    pub fn validate_and_strip_checksum_with(
+        data: &[u8],
+        algorithm: ChecksumAlgorithm,
+    ) -> io::Result<&[u8]> {
+        super::checksum::validate_and_strip_checksum_with(data, algorithm)
+    }
+
     /// Decompresses data based on the 1-byte header.
     /// Header format: `[algorithm_id][data]`
     /// - 0: Uncompressed
     /// - 1: Zlib
     /// - 2: LZ4
+    /// - 3: Zstd
     ///
     /// This is a convenience wrapper around the compression module's decompression function.
     pub fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
         super::compression::decompress(data)
     }
+
+    /// Decompresses data like [`CommandDecoder::decompress`], failing once compressed data
+    /// inflates to more than `max_len` bytes.
+    pub fn decompress_limited(data: &[u8], max_len: usize) -> io::Result<Vec<u8>> {
+        super::compression::decompress_limited(data, max_len)
+    }
 }
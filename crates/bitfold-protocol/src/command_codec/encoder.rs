@@ -5,14 +5,65 @@
 
 use std::io::{self, Write};
 
+use bitfold_core::config::{ChecksumAlgorithm, CompressionAlgorithm, Config};
 use byteorder::{BigEndian, WriteBytesExt};
 
-use super::super::command::{CommandPacket, ProtocolCommand};
+use super::{
+    super::command::{CommandPacket, ProtocolCommand},
+    compression::ZSTD_FRAME_OVERHEAD,
+};
+
+/// Size of the length prefix framing each command within a packet.
+pub const COMMAND_LENGTH_PREFIX_SIZE: usize = 2;
 
 /// Serializes a command packet into bytes for transmission.
 pub struct CommandEncoder;
 
 impl CommandEncoder {
+    /// Returns the bytes a datagram carries beyond its length-prefixed commands:
+    /// the command count, the compression marker (plus original size for LZ4, or frame
+    /// header for Zstd) and the optional checksum.
+    pub fn packet_overhead(
+        compression: CompressionAlgorithm,
+        checksum: ChecksumAlgorithm,
+    ) -> usize {
+        let compression_overhead = match compression {
+            CompressionAlgorithm::Lz4 => 5, // 1 marker + 4 original size
+            CompressionAlgorithm::Zstd { .. } => 1 + ZSTD_FRAME_OVERHEAD, // 1 marker + frame
+            _ => 1,                         // 1 marker for None/Zlib
+        };
+        1 /* command count */ + compression_overhead + checksum.len_bytes()
+    }
+
+    /// Returns the [`CommandEncoder::packet_overhead`] of datagrams sent under `config`. On a
+    /// `trusted_path` that is only the command count.
+    pub fn packet_overhead_for_config(config: &Config) -> usize {
+        if config.trusted_path {
+            return 1;
+        }
+        Self::packet_overhead(config.compression, config.checksum_algorithm())
+    }
+
+    /// Like [`CommandEncoder::packet_overhead`], for a datagram of `datagram_size` bytes.
+    ///
+    /// Datagrams whose encoded commands fall below `compression_threshold` are always stored
+    /// uncompressed, so they only carry the 1-byte marker whatever the algorithm.
+    pub fn packet_overhead_for_size(
+        compression: CompressionAlgorithm,
+        compression_threshold: usize,
+        checksum: ChecksumAlgorithm,
+        datagram_size: usize,
+    ) -> usize {
+        let compressed_overhead = Self::packet_overhead(compression, checksum);
+        // Encoded commands (including the count) if the datagram were sized for compression
+        let encoded = datagram_size.saturating_sub(compressed_overhead - 1);
+        if encoded < compression_threshold {
+            Self::packet_overhead(CompressionAlgorithm::None, checksum)
+        } else {
+            compressed_overhead
+        }
+    }
+
     /// Encodes a single command into the provided buffer (appends bytes).
     pub fn encode_command_into(buffer: &mut Vec<u8>, command: &ProtocolCommand) -> io::Result<()> {
         // Write command type
@@ -92,12 +143,14 @@ impl CommandEncoder {
                 protocol_version,
                 outgoing_session_id,
                 connect_id,
+                idle_timeout_ms,
             } => {
                 buffer.write_u8(*channels)?;
                 buffer.write_u16::<BigEndian>(*mtu)?;
                 buffer.write_u16::<BigEndian>(*protocol_version)?;
                 buffer.write_u16::<BigEndian>(*outgoing_session_id)?;
                 buffer.write_u32::<BigEndian>(*connect_id)?;
+                buffer.write_u32::<BigEndian>(*idle_timeout_ms)?;
             }
             ProtocolCommand::VerifyConnect {
                 peer_id,
@@ -106,6 +159,7 @@ impl CommandEncoder {
                 incoming_session_id,
                 outgoing_session_id,
                 window_size,
+                idle_timeout_ms,
             } => {
                 buffer.write_u16::<BigEndian>(*peer_id)?;
                 buffer.write_u8(*channels)?;
@@ -113,6 +167,7 @@ impl CommandEncoder {
                 buffer.write_u16::<BigEndian>(*incoming_session_id)?;
                 buffer.write_u16::<BigEndian>(*outgoing_session_id)?;
                 buffer.write_u32::<BigEndian>(*window_size)?;
+                buffer.write_u32::<BigEndian>(*idle_timeout_ms)?;
             }
             ProtocolCommand::Disconnect { reason } => {
                 buffer.write_u32::<BigEndian>(*reason)?;
@@ -136,27 +191,66 @@ impl CommandEncoder {
                 buffer.write_u16::<BigEndian>(*size)?;
                 buffer.write_u32::<BigEndian>(*token)?;
             }
+            ProtocolCommand::PMTUProbeRequest { size, token, padding } => {
+                buffer.write_u16::<BigEndian>(*size)?;
+                buffer.write_u32::<BigEndian>(*token)?;
+                buffer.write_u16::<BigEndian>(*padding)?;
+                buffer.resize(buffer.len() + *padding as usize, 0);
+            }
+            ProtocolCommand::WindowProbe => {}
+            ProtocolCommand::WindowUpdate { received, available } => {
+                buffer.write_u32::<BigEndian>(*received)?;
+                buffer.write_u32::<BigEndian>(*available)?;
+            }
+            ProtocolCommand::VersionNegotiation { connect_id, supported } => {
+                buffer.write_u32::<BigEndian>(*connect_id)?;
+                buffer.write_u8(supported.len().min(u8::MAX as usize) as u8)?;
+                for version in supported.iter().take(u8::MAX as usize) {
+                    buffer.write_u16::<BigEndian>(*version)?;
+                }
+            }
+            ProtocolCommand::Sack { ranges } => {
+                buffer.write_u8(ranges.len().min(u8::MAX as usize) as u8)?;
+                for (first, last) in ranges.iter().take(u8::MAX as usize) {
+                    buffer.write_u16::<BigEndian>(*first)?;
+                    buffer.write_u16::<BigEndian>(*last)?;
+                }
+            }
+            ProtocolCommand::PathChallenge { data } | ProtocolCommand::PathResponse { data } => {
+                buffer.write_u64::<BigEndian>(*data)?;
+            }
+            ProtocolCommand::ConnectionId { session_id, connect_id } => {
+                buffer.write_u16::<BigEndian>(*session_id)?;
+                buffer.write_u32::<BigEndian>(*connect_id)?;
+            }
         }
 
         Ok(())
     }
 
+    /// Appends `command` to `buffer` framed with its length prefix, as within a packet, so
+    /// that it can be read back with [`CommandDecoder::decode_framed`].
+    ///
+    /// [`CommandDecoder::decode_framed`]: super::CommandDecoder::decode_framed
+    pub fn encode_framed(buffer: &mut Vec<u8>, command: &ProtocolCommand) -> io::Result<()> {
+        // Reserve space for the length, encode the command, then patch the length in place
+        let len_pos = buffer.len();
+        buffer.write_u16::<BigEndian>(0)?;
+        let start = buffer.len();
+        Self::encode_command_into(buffer, command)?;
+        let cmd_len = buffer.len() - start;
+        buffer[len_pos..start].copy_from_slice(&(cmd_len as u16).to_be_bytes());
+        Ok(())
+    }
+
     /// Encodes a command packet into the provided buffer (appends bytes) without intermediate allocations per command.
     pub fn encode_packet_into(buffer: &mut Vec<u8>, packet: &CommandPacket) -> io::Result<()> {
         // Write command count
         buffer.write_u8(packet.commands.len() as u8)?;
 
-        // Write each command with a length prefix. We first reserve space for the length,
-        // encode the command, then patch the length in place.
+        // Write each command with a length prefix
         for command in &packet.commands {
-            let len_pos = buffer.len();
-            buffer.write_u16::<BigEndian>(0)?; // placeholder for length
-            let start = buffer.len();
-            Self::encode_command_into(buffer, command)?;
-            let cmd_len = buffer.len() - start;
-            // Patch the length
-            buffer[len_pos] = ((cmd_len >> 8) & 0xFF) as u8;
-            buffer[len_pos + 1] = (cmd_len & 0xFF) as u8;
+            Self::encode_framed(buffer, command)?;
         }
 
         Ok(())
@@ -243,12 +337,14 @@ impl CommandEncoder {
                 protocol_version,
                 outgoing_session_id,
                 connect_id,
+                idle_timeout_ms,
             } => {
                 buffer.write_u8(*channels)?;
                 buffer.write_u16::<BigEndian>(*mtu)?;
                 buffer.write_u16::<BigEndian>(*protocol_version)?;
                 buffer.write_u16::<BigEndian>(*outgoing_session_id)?;
                 buffer.write_u32::<BigEndian>(*connect_id)?;
+                buffer.write_u32::<BigEndian>(*idle_timeout_ms)?;
             }
             ProtocolCommand::VerifyConnect {
                 peer_id,
@@ -257,6 +353,7 @@ impl CommandEncoder {
                 incoming_session_id,
                 outgoing_session_id,
                 window_size,
+                idle_timeout_ms,
             } => {
                 buffer.write_u16::<BigEndian>(*peer_id)?;
                 buffer.write_u8(*channels)?;
@@ -264,6 +361,7 @@ impl CommandEncoder {
                 buffer.write_u16::<BigEndian>(*incoming_session_id)?;
                 buffer.write_u16::<BigEndian>(*outgoing_session_id)?;
                 buffer.write_u32::<BigEndian>(*window_size)?;
+                buffer.write_u32::<BigEndian>(*idle_timeout_ms)?;
             }
             ProtocolCommand::Disconnect { reason } => {
                 buffer.write_u32::<BigEndian>(*reason)?;
@@ -287,6 +385,38 @@ impl CommandEncoder {
                 buffer.write_u16::<BigEndian>(*size)?;
                 buffer.write_u32::<BigEndian>(*token)?;
             }
+            ProtocolCommand::PMTUProbeRequest { size, token, padding } => {
+                buffer.write_u16::<BigEndian>(*size)?;
+                buffer.write_u32::<BigEndian>(*token)?;
+                buffer.write_u16::<BigEndian>(*padding)?;
+                buffer.resize(buffer.len() + *padding as usize, 0);
+            }
+            ProtocolCommand::WindowProbe => {}
+            ProtocolCommand::WindowUpdate { received, available } => {
+                buffer.write_u32::<BigEndian>(*received)?;
+                buffer.write_u32::<BigEndian>(*available)?;
+            }
+            ProtocolCommand::VersionNegotiation { connect_id, supported } => {
+                buffer.write_u32::<BigEndian>(*connect_id)?;
+                buffer.write_u8(supported.len().min(u8::MAX as usize) as u8)?;
+                for version in supported.iter().take(u8::MAX as usize) {
+                    buffer.write_u16::<BigEndian>(*version)?;
+                }
+            }
+            ProtocolCommand::Sack { ranges } => {
+                buffer.write_u8(ranges.len().min(u8::MAX as usize) as u8)?;
+                for (first, last) in ranges.iter().take(u8::MAX as usize) {
+                    buffer.write_u16::<BigEndian>(*first)?;
+                    buffer.write_u16::<BigEndian>(*last)?;
+                }
+            }
+            ProtocolCommand::PathChallenge { data } | ProtocolCommand::PathResponse { data } => {
+                buffer.write_u64::<BigEndian>(*data)?;
+            }
+            ProtocolCommand::ConnectionId { session_id, connect_id } => {
+                buffer.write_u16::<BigEndian>(*session_id)?;
+                buffer.write_u32::<BigEndian>(*connect_id)?;
+            }
         }
 
         Ok(buffer)
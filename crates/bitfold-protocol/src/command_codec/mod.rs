@@ -7,20 +7,32 @@
 //!
 //! - [`encoder`] - Command and packet encoding to binary format
 //! - [`decoder`] - Command and packet decoding from binary format
-//! - [`checksum`] - CRC32 checksum utilities for data integrity
-//! - [`compression`] - Data compression/decompression (Zlib, LZ4)
+//! - [`checksum`] - Checksum utilities (CRC32, xxHash) for data integrity
+//! - [`compression`] - Data compression/decompression (Zlib, LZ4, Zstd)
+//! - [`adaptive_compression`] - Suspends compression on incompressible traffic
 
+pub mod adaptive_compression;
 pub mod checksum;
 pub mod compression;
 pub mod decoder;
 pub mod encoder;
 
+#[cfg(test)]
+mod corpus;
 #[cfg(test)]
 mod tests;
 
 // Re-export main types for backward compatibility
 // Re-export utility functions for convenience
-pub use checksum::{append_checksum, append_checksum_in_place, validate_and_strip_checksum};
-pub use compression::{compress, compress_with_buffer, decompress};
-pub use decoder::CommandDecoder;
-pub use encoder::CommandEncoder;
+pub use adaptive_compression::AdaptiveCompression;
+pub use checksum::{
+    append_checksum, append_checksum_in_place, append_checksum_in_place_with, is_checksum_omitted,
+    mark_checksum_omitted, validate_and_strip_checksum, validate_and_strip_checksum_with,
+    CHECKSUM_OMITTED_FLAG,
+};
+pub use compression::{
+    compress, compress_with_buffer, decompress, decompress_limited, is_immediate_ack_requested,
+    mark_immediate_ack, IMMEDIATE_ACK_FLAG,
+};
+pub use decoder::{CommandDecoder, DecodeError, MAX_SACK_RANGES};
+pub use encoder::{CommandEncoder, COMMAND_LENGTH_PREFIX_SIZE};
@@ -5,10 +5,35 @@ mod tests {
     use std::io::Cursor;
 
     use bitfold_core::shared::SharedBytes;
+    use quickcheck::{Arbitrary, Gen};
+    use quickcheck_macros::quickcheck;
 
-    use super::super::{CommandDecoder, CommandEncoder};
+    use super::super::{CommandDecoder, CommandEncoder, DecodeError, MAX_SACK_RANGES};
     use crate::command::{CommandPacket, ProtocolCommand};
 
+    #[test]
+    fn test_packet_overhead_for_size_below_compression_threshold() {
+        use bitfold_core::config::{ChecksumAlgorithm, CompressionAlgorithm};
+
+        let (none, crc32) = (ChecksumAlgorithm::None, ChecksumAlgorithm::Crc32);
+        for (compression, compressed) in [
+            (CompressionAlgorithm::Lz4, 6),
+            (CompressionAlgorithm::Zstd { level: 3 }, 12),
+            (CompressionAlgorithm::Zlib, 2),
+        ] {
+            assert_eq!(CommandEncoder::packet_overhead_for_size(compression, 128, none, 20), 2);
+            assert_eq!(
+                CommandEncoder::packet_overhead_for_size(compression, 128, crc32, 1200),
+                compressed + 4
+            );
+            // Threshold 0 compresses everything
+            assert_eq!(
+                CommandEncoder::packet_overhead_for_size(compression, 0, none, 0),
+                compressed
+            );
+        }
+    }
+
     #[test]
     fn test_encode_decode_send_reliable() {
         let cmd = ProtocolCommand::SendReliable {
@@ -25,6 +50,40 @@ mod tests {
         assert_eq!(cmd, decoded);
     }
 
+    #[test]
+    fn test_encode_decode_version_negotiation() {
+        let cmd = ProtocolCommand::VersionNegotiation {
+            connect_id: 0xDEAD_BEEF,
+            supported: vec![1, 2, 5],
+        };
+
+        let encoded = CommandEncoder::encode_command(&cmd).unwrap();
+        let mut into = Vec::new();
+        CommandEncoder::encode_command_into(&mut into, &cmd).unwrap();
+        assert_eq!(encoded, into);
+        let mut cursor = Cursor::new(encoded.as_slice());
+        let decoded = CommandDecoder::decode_command(&mut cursor).unwrap();
+
+        assert_eq!(cmd, decoded);
+    }
+
+    #[test]
+    fn test_encode_decode_sack() {
+        let cmd = ProtocolCommand::Sack { ranges: vec![(40, 52), (65530, 3)] };
+
+        let encoded = CommandEncoder::encode_command(&cmd).unwrap();
+        let mut into = Vec::new();
+        CommandEncoder::encode_command_into(&mut into, &cmd).unwrap();
+        assert_eq!(encoded, into);
+        assert_eq!(encoded.len(), 1 + 1 + 2 * 4);
+        let decoded = CommandDecoder::decode_command(&mut Cursor::new(encoded.as_slice())).unwrap();
+        assert_eq!(cmd, decoded);
+
+        // A range count promising more ranges than present is truncated input
+        let err = CommandDecoder::decode_command(&mut Cursor::new(&encoded[..7])).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
     #[test]
     fn test_encode_decode_acknowledge() {
         let cmd = ProtocolCommand::Acknowledge {
@@ -40,6 +99,25 @@ mod tests {
         assert_eq!(cmd, decoded);
     }
 
+    #[test]
+    fn test_decode_acknowledge_ignores_trailing_bytes() {
+        let cmd = ProtocolCommand::Acknowledge {
+            sequence: 100,
+            received_mask: u32::MAX,
+            sent_time: Some(12345),
+        };
+
+        // A peer padding the ack with extra "ranges" cannot make decoding read past the fixed fields
+        let mut encoded = CommandEncoder::encode_command(&cmd).unwrap();
+        let fixed_len = encoded.len() as u64;
+        encoded.extend(std::iter::repeat_n(0xAB, 4096));
+        let mut cursor = Cursor::new(encoded.as_slice());
+        let decoded = CommandDecoder::decode_command(&mut cursor).unwrap();
+
+        assert_eq!(cmd, decoded);
+        assert_eq!(cursor.position(), fixed_len);
+    }
+
     #[test]
     fn test_encode_decode_packet() {
         let mut packet = CommandPacket::new();
@@ -77,4 +155,305 @@ mod tests {
 
         assert_eq!(encoded_vec, into_buf);
     }
+
+    #[test]
+    fn test_encode_decode_window_probe_and_update() {
+        let mut packet = CommandPacket::new();
+        packet.add_command(ProtocolCommand::WindowProbe);
+        packet.add_command(ProtocolCommand::WindowUpdate {
+            received: 0xDEAD_BEEF,
+            available: 0x0102_0304,
+        });
+
+        let encoded = CommandEncoder::encode_packet(&packet).unwrap();
+        let decoded = CommandDecoder::decode_packet(&encoded).unwrap();
+
+        assert_eq!(packet.commands, decoded.commands);
+    }
+
+    #[test]
+    fn test_encode_decode_path_commands() {
+        let mut packet = CommandPacket::new();
+        packet.add_command(ProtocolCommand::PathChallenge { data: 0x0102_0304_0506_0708 });
+        packet.add_command(ProtocolCommand::PathResponse { data: u64::MAX });
+        packet
+            .add_command(ProtocolCommand::ConnectionId { session_id: 7, connect_id: 0xDEAD_BEEF });
+
+        let encoded = CommandEncoder::encode_packet(&packet).unwrap();
+        let decoded = CommandDecoder::decode_packet(&encoded).unwrap();
+
+        assert_eq!(packet.commands, decoded.commands);
+    }
+
+    #[test]
+    fn test_encode_decode_pmtu_probe_request() {
+        let mut packet = CommandPacket::new();
+        packet.add_command(ProtocolCommand::PMTUProbeRequest {
+            size: 1200,
+            token: 0x0100_0007,
+            padding: 400,
+        });
+
+        let encoded = CommandEncoder::encode_packet(&packet).unwrap();
+        assert!(encoded.len() > 400);
+        let decoded = CommandDecoder::decode_packet(&encoded).unwrap();
+
+        assert_eq!(packet.commands, decoded.commands);
+    }
+
+    fn inflated_probe(payload_len: u16, actual: usize) -> Vec<u8> {
+        let mut bytes = vec![15u8];
+        bytes.extend_from_slice(&1200u16.to_be_bytes());
+        bytes.extend_from_slice(&7u32.to_be_bytes());
+        bytes.extend_from_slice(&payload_len.to_be_bytes());
+        bytes.extend(std::iter::repeat_n(0u8, actual));
+        bytes
+    }
+
+    #[test]
+    fn test_decode_probe_rejects_payload_beyond_datagram() {
+        let bytes = inflated_probe(u16::MAX, 8);
+        let mut cursor = Cursor::new(bytes.as_slice());
+        let err = CommandDecoder::decode_command(&mut cursor).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("PMTU probe payload too large"));
+        assert_eq!(DecodeError::from_io(err), DecodeError::PayloadTooLarge {
+            declared: u16::MAX as usize,
+            available: 8
+        });
+        // Rejected right after the header, before touching the payload
+        assert_eq!(cursor.position(), 9);
+    }
+
+    #[test]
+    fn test_decode_probe_rejects_payload_over_limit() {
+        let bytes = inflated_probe(600, 600);
+        let err = CommandDecoder::decode_command_limited(
+            &mut Cursor::new(bytes.as_slice()),
+            512,
+            MAX_SACK_RANGES,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(DecodeError::from_io(err), DecodeError::PayloadTooLarge {
+            declared: 600,
+            available: 512
+        });
+
+        let decoded = CommandDecoder::decode_command_limited(
+            &mut Cursor::new(bytes.as_slice()),
+            600,
+            MAX_SACK_RANGES,
+        )
+        .unwrap();
+        assert!(matches!(decoded, ProtocolCommand::PMTUProbe { size: 1200, token: 7, .. }));
+    }
+
+    #[test]
+    fn test_decode_packet_limited_rejects_inflated_probe() {
+        let command = inflated_probe(600, 600);
+        let mut bytes = vec![1u8];
+        bytes.extend_from_slice(&(command.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&command);
+
+        let err = CommandDecoder::decode_packet_limited(&bytes, 512, MAX_SACK_RANGES).unwrap_err();
+        assert_eq!(DecodeError::from_io(err), DecodeError::PayloadTooLarge {
+            declared: 600,
+            available: 512
+        });
+        assert_eq!(CommandDecoder::decode_packet(&bytes).unwrap().commands.len(), 1);
+    }
+
+    #[test]
+    fn test_decode_sack_rejects_too_many_ranges() {
+        let ranges: Vec<(u16, u16)> = (0..8u16).map(|i| (i * 4, i * 4 + 1)).collect();
+        let command = ProtocolCommand::Sack { ranges: ranges.clone() };
+        let bytes = CommandEncoder::encode_command(&command).unwrap();
+
+        let mut cursor = Cursor::new(bytes.as_slice());
+        let err = CommandDecoder::decode_command_limited(&mut cursor, 512, 4).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(DecodeError::from_io(err), DecodeError::TooManySackRanges {
+            declared: 8,
+            max: 4
+        });
+        // Rejected right after the count, before reading any range
+        assert_eq!(cursor.position(), 2);
+
+        let decoded =
+            CommandDecoder::decode_command_limited(&mut Cursor::new(bytes.as_slice()), 512, 8)
+                .unwrap();
+        assert_eq!(decoded, ProtocolCommand::Sack { ranges });
+    }
+
+    /// Any command, with each variant equally likely.
+    #[derive(Debug, Clone)]
+    struct AnyCommand(ProtocolCommand);
+
+    impl Arbitrary for AnyCommand {
+        fn arbitrary(g: &mut Gen) -> Self {
+            let bytes = |g: &mut Gen| SharedBytes::from_vec(Vec::arbitrary(g));
+            let command = match u8::arbitrary(g) % 24 + 1 {
+                1 => ProtocolCommand::SendReliable {
+                    channel_id: u8::arbitrary(g),
+                    sequence: u16::arbitrary(g),
+                    ordered: bool::arbitrary(g),
+                    data: bytes(g),
+                },
+                2 => {
+                    ProtocolCommand::SendUnreliable { channel_id: u8::arbitrary(g), data: bytes(g) }
+                }
+                3 => ProtocolCommand::SendUnreliableSequenced {
+                    channel_id: u8::arbitrary(g),
+                    sequence: u16::arbitrary(g),
+                    data: bytes(g),
+                },
+                4 => ProtocolCommand::SendUnsequenced {
+                    channel_id: u8::arbitrary(g),
+                    unsequenced_group: u16::arbitrary(g),
+                    data: bytes(g),
+                },
+                5 => ProtocolCommand::SendFragment {
+                    channel_id: u8::arbitrary(g),
+                    sequence: u16::arbitrary(g),
+                    ordered: bool::arbitrary(g),
+                    fragment_id: u8::arbitrary(g),
+                    fragment_count: u8::arbitrary(g),
+                    data: bytes(g),
+                },
+                6 => ProtocolCommand::SendUnreliableFragment {
+                    channel_id: u8::arbitrary(g),
+                    sequence: u16::arbitrary(g),
+                    fragment_id: u8::arbitrary(g),
+                    fragment_count: u8::arbitrary(g),
+                    data: bytes(g),
+                },
+                7 => ProtocolCommand::Acknowledge {
+                    sequence: u16::arbitrary(g),
+                    received_mask: u32::arbitrary(g),
+                    sent_time: Option::arbitrary(g),
+                },
+                8 => ProtocolCommand::Ping { timestamp: u32::arbitrary(g) },
+                9 => ProtocolCommand::Pong { timestamp: u32::arbitrary(g) },
+                10 => ProtocolCommand::Connect {
+                    channels: u8::arbitrary(g),
+                    mtu: u16::arbitrary(g),
+                    protocol_version: u16::arbitrary(g),
+                    outgoing_session_id: u16::arbitrary(g),
+                    connect_id: u32::arbitrary(g),
+                    idle_timeout_ms: u32::arbitrary(g),
+                },
+                11 => ProtocolCommand::VerifyConnect {
+                    peer_id: u16::arbitrary(g),
+                    channels: u8::arbitrary(g),
+                    mtu: u16::arbitrary(g),
+                    incoming_session_id: u16::arbitrary(g),
+                    outgoing_session_id: u16::arbitrary(g),
+                    window_size: u32::arbitrary(g),
+                    idle_timeout_ms: u32::arbitrary(g),
+                },
+                12 => ProtocolCommand::Disconnect { reason: u32::arbitrary(g) },
+                13 => ProtocolCommand::BandwidthLimit {
+                    incoming: u32::arbitrary(g),
+                    outgoing: u32::arbitrary(g),
+                },
+                14 => ProtocolCommand::ThrottleConfigure {
+                    interval: u32::arbitrary(g),
+                    acceleration: u32::arbitrary(g),
+                    deceleration: u32::arbitrary(g),
+                },
+                15 => ProtocolCommand::PMTUProbe {
+                    size: u16::arbitrary(g),
+                    token: u32::arbitrary(g),
+                    payload: bytes(g),
+                },
+                16 => {
+                    ProtocolCommand::PMTUReply { size: u16::arbitrary(g), token: u32::arbitrary(g) }
+                }
+                17 => ProtocolCommand::WindowProbe,
+                18 => ProtocolCommand::WindowUpdate {
+                    received: u32::arbitrary(g),
+                    available: u32::arbitrary(g),
+                },
+                19 => ProtocolCommand::PMTUProbeRequest {
+                    size: u16::arbitrary(g),
+                    token: u32::arbitrary(g),
+                    padding: u16::arbitrary(g) % 1500,
+                },
+                20 => ProtocolCommand::VersionNegotiation {
+                    connect_id: u32::arbitrary(g),
+                    supported: Vec::arbitrary(g),
+                },
+                21 => ProtocolCommand::Sack { ranges: Vec::arbitrary(g) },
+                22 => ProtocolCommand::PathChallenge { data: u64::arbitrary(g) },
+                23 => ProtocolCommand::PathResponse { data: u64::arbitrary(g) },
+                _ => ProtocolCommand::ConnectionId {
+                    session_id: u16::arbitrary(g),
+                    connect_id: u32::arbitrary(g),
+                },
+            };
+            AnyCommand(command)
+        }
+    }
+
+    #[quickcheck]
+    fn prop_decode_inverts_encode(commands: Vec<AnyCommand>) -> bool {
+        let mut buf = Vec::new();
+        for AnyCommand(command) in &commands {
+            command.encode(&mut buf);
+        }
+        let mut input = buf.as_slice();
+        let decoded: Vec<_> =
+            commands.iter().map_while(|_| ProtocolCommand::decode(&mut input).ok()).collect();
+        input.is_empty() && decoded.iter().eq(commands.iter().map(|AnyCommand(command)| command))
+    }
+
+    #[test]
+    fn test_decode_error_kinds() {
+        let probe = ProtocolCommand::PMTUProbe {
+            size: 1200,
+            token: 7,
+            payload: SharedBytes::from_vec(vec![0xAB; 32]),
+        };
+        let mut encoded = Vec::new();
+        probe.encode(&mut encoded);
+
+        // Cut anywhere inside the frame: truncated or an overflowing length prefix
+        let mut input = &encoded[..1];
+        assert_eq!(ProtocolCommand::decode(&mut input), Err(DecodeError::Truncated));
+        assert_eq!(input.len(), 1, "input consumed on error");
+        let mut input = &encoded[..encoded.len() - 1];
+        assert_eq!(
+            ProtocolCommand::decode(&mut input),
+            Err(DecodeError::LengthOverflow {
+                field: "Command length",
+                declared: encoded.len() - 2,
+                available: encoded.len() - 3,
+            })
+        );
+
+        // A frame too short for the command inside it
+        let mut short = vec![0, 4];
+        short.extend_from_slice(&encoded[2..6]);
+        assert_eq!(ProtocolCommand::decode(&mut short.as_slice()), Err(DecodeError::Truncated));
+
+        // A probe payload longer than its frame
+        let mut inflated = encoded.clone();
+        inflated[9..11].copy_from_slice(&64u16.to_be_bytes());
+        assert_eq!(
+            ProtocolCommand::decode(&mut inflated.as_slice()),
+            Err(DecodeError::PayloadTooLarge { declared: 64, available: 32 })
+        );
+
+        let mut unknown = &[0, 1, 0xEE][..];
+        assert_eq!(
+            ProtocolCommand::decode(&mut unknown),
+            Err(DecodeError::UnknownCommandType(0xEE))
+        );
+
+        // The io::Errors from CommandDecoder carry the same classification
+        let err = CommandDecoder::decode_packet(&[1, 0, 1, 0xEE]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(DecodeError::from_io(err), DecodeError::UnknownCommandType(0xEE));
+    }
 }
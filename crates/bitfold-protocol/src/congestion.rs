@@ -1,16 +1,33 @@
 use std::time::{Duration, Instant};
 
+use crate::rtt_estimator::RttEstimator;
+
+/// Smoothed RTT relative to the lowest RTT seen above which a path counts as queueing.
+const DELAY_INFLATION_THRESHOLD: f32 = 1.5;
+
+/// Consecutive throttle intervals the active signal must underperform before an adaptive
+/// controller switches, so it does not flap between signals.
+const SIGNAL_SWITCH_INTERVALS: u32 = 3;
+
+/// How long an RTT sample can remain the RTT baseline, so the baseline follows a path change
+/// instead of holding on to the lowest RTT of an earlier path.
+const MIN_RTT_WINDOW: Duration = Duration::from_secs(10);
+
+/// Congestion signal the throttle reacts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CongestionSignal {
+    /// Back off on packet loss (Reno-like)
+    Loss,
+    /// Back off as soon as the RTT inflates above the lowest seen, before queues overflow
+    /// (Vegas-like); heavy loss still backs off
+    Delay,
+}
+
 /// Congestion control and RTT tracking with dynamic throttle.
 #[derive(Debug, Clone)]
 pub struct CongestionControl {
-    /// Smoothed round-trip time
-    rtt: Duration,
-    /// RTT variance
-    rtt_variance: Duration,
-    /// Smoothing factor for RTT calculations (typically 0.1)
-    rtt_alpha: f32,
-    /// Variance smoothing factor (typically 0.25)
-    rtt_beta: f32,
+    /// Smoothed RTT, its variance and the retransmission timeout
+    rtt: RttEstimator,
     /// Number of packets lost
     packets_lost: u32,
     /// Number of packets sent
@@ -37,16 +54,42 @@ pub struct CongestionControl {
     throttle_acceleration: u32,
     /// Throttle deceleration (rate of degradation)
     throttle_deceleration: u32,
+
+    // Signal selection
+    /// Congestion signal driving the throttle
+    signal: CongestionSignal,
+    /// Whether the signal switches automatically when the active one underperforms
+    adaptive_signal: bool,
+    /// Lowest RTT sample over the last one to two `MIN_RTT_WINDOW`s, the baseline for RTT
+    /// inflation
+    min_rtt: Option<Duration>,
+    /// Lowest RTT sample in the current window, which becomes the baseline once it ends
+    window_min_rtt: Option<Duration>,
+    /// When the current min RTT window started
+    min_rtt_window_start: Instant,
+    /// Consecutive intervals in which the active signal underperformed
+    underperforming_intervals: u32,
+}
+
+/// Learned congestion state that can be persisted and restored on a later connection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CongestionSnapshot {
+    /// Smoothed round-trip time
+    pub rtt: Duration,
+    /// RTT variance
+    pub rtt_variance: Duration,
+    /// Throttle value (0.0-1.0)
+    pub throttle: f32,
+    /// Packet throttle in scale units (0 to throttle_scale)
+    pub packet_throttle: u32,
 }
 
 impl CongestionControl {
     /// Creates a new congestion control instance.
     pub fn new(rtt_alpha: f32, rtt_beta: f32) -> Self {
         Self {
-            rtt: Duration::from_millis(50), // Initial estimate
-            rtt_variance: Duration::from_millis(25),
-            rtt_alpha,
-            rtt_beta,
+            rtt: RttEstimator::new(rtt_alpha, rtt_beta),
             packets_lost: 0,
             packets_sent: 0,
             throttle: 0.0,
@@ -59,6 +102,12 @@ impl CongestionControl {
             packet_throttle: 32,      // Start at maximum (no throttling)
             throttle_acceleration: 2, // Default acceleration
             throttle_deceleration: 2, // Default deceleration
+            signal: CongestionSignal::Loss,
+            adaptive_signal: false,
+            min_rtt: None,
+            window_min_rtt: None,
+            min_rtt_window_start: Instant::now(),
+            underperforming_intervals: 0,
         }
     }
 
@@ -78,37 +127,36 @@ impl CongestionControl {
         self.throttle_interval = Duration::from_millis(interval_ms as u64);
     }
 
-    /// Updates RTT measurement with a new sample.
-    /// Uses exponential weighted moving average (EWMA).
+    /// Updates RTT measurement with a new sample (see [`RttEstimator::on_rtt_sample`]).
     pub fn update_rtt(&mut self, sample: Duration) {
-        let sample_ms = sample.as_millis() as f32;
-        let rtt_ms = self.rtt.as_millis() as f32;
-
-        // EWMA for RTT: RTT = (1 - α) * RTT + α * sample
-        let new_rtt_ms = (1.0 - self.rtt_alpha) * rtt_ms + self.rtt_alpha * sample_ms;
-        self.rtt = Duration::from_millis(new_rtt_ms as u64);
-
-        // Update variance: Var = (1 - β) * Var + β * |RTT - sample|
-        let diff = (rtt_ms - sample_ms).abs();
-        let var_ms = self.rtt_variance.as_millis() as f32;
-        let new_var_ms = (1.0 - self.rtt_beta) * var_ms + self.rtt_beta * diff;
-        self.rtt_variance = Duration::from_millis(new_var_ms as u64);
+        self.min_rtt = Some(self.min_rtt.map_or(sample, |min| min.min(sample)));
+        self.window_min_rtt = Some(self.window_min_rtt.map_or(sample, |min| min.min(sample)));
+        self.rtt.on_rtt_sample(sample);
     }
 
     /// Returns the current smoothed RTT.
     pub fn rtt(&self) -> Duration {
-        self.rtt
+        self.rtt.srtt()
     }
 
     /// Returns the RTT variance.
     pub fn rtt_variance(&self) -> Duration {
-        self.rtt_variance
+        self.rtt.rttvar()
     }
 
-    /// Returns the retransmission timeout (RTO) based on RTT.
-    /// Uses the standard RTO = RTT + 4 * variance formula.
+    /// Returns the retransmission timeout (RTO), backoff included.
     pub fn rto(&self) -> Duration {
-        self.rtt + Duration::from_millis(4 * self.rtt_variance.as_millis() as u64)
+        self.rtt.rto()
+    }
+
+    /// Returns the RTT estimator.
+    pub fn rtt_estimator(&self) -> &RttEstimator {
+        &self.rtt
+    }
+
+    /// Returns the RTT estimator mutably, e.g. to configure the RTO bounds or back it off.
+    pub fn rtt_estimator_mut(&mut self) -> &mut RttEstimator {
+        &mut self.rtt
     }
 
     /// Records a packet loss event.
@@ -132,18 +180,30 @@ impl CongestionControl {
     /// Updates the dynamic throttle based on current network conditions.
     /// Returns true if throttle was updated.
     pub fn update_throttle(&mut self, now: Instant) -> bool {
+        self.expire_min_rtt(now);
         if now.duration_since(self.last_throttle_update) < self.throttle_interval {
             return false;
         }
 
         let loss_rate = self.loss_rate();
+        let inflated = self.is_rtt_inflated();
+        if self.adaptive_signal {
+            self.select_signal(loss_rate, inflated);
+        }
+
+        // The delay signal reports an inflated RTT as full congestion
+        let congestion = match self.signal {
+            CongestionSignal::Loss => loss_rate,
+            CongestionSignal::Delay if inflated => 1.0,
+            CongestionSignal::Delay => loss_rate,
+        };
 
         if self.use_advanced_throttling {
             // Advanced throttling with acceleration/deceleration
-            self.update_advanced_throttle(loss_rate);
+            self.update_advanced_throttle(congestion);
         } else {
             // Simple throttling (backward compatible)
-            self.update_simple_throttle(loss_rate);
+            self.update_simple_throttle(congestion);
         }
 
         // Reset counters
@@ -154,6 +214,50 @@ impl CongestionControl {
         true
     }
 
+    /// Ends the min RTT window once `MIN_RTT_WINDOW` has passed: the baseline becomes the
+    /// lowest sample of the window that ended, dropping older samples. A window without
+    /// samples keeps the baseline.
+    fn expire_min_rtt(&mut self, now: Instant) {
+        if now.saturating_duration_since(self.min_rtt_window_start) < MIN_RTT_WINDOW {
+            return;
+        }
+        if let Some(min) = self.window_min_rtt.take() {
+            self.min_rtt = Some(min);
+        }
+        self.min_rtt_window_start = now;
+    }
+
+    /// Returns whether the smoothed RTT is inflated above the lowest recent RTT, a sign of
+    /// queues building along the path.
+    pub fn is_rtt_inflated(&self) -> bool {
+        self.min_rtt.is_some_and(|min| {
+            self.rtt().as_secs_f32() > min.as_secs_f32() * DELAY_INFLATION_THRESHOLD
+        })
+    }
+
+    /// Switches the signal once the active one has underperformed for
+    /// `SIGNAL_SWITCH_INTERVALS` consecutive intervals: the loss signal when the RTT stays
+    /// inflated without loss (queues grow unnoticed), the delay signal when loss persists
+    /// without RTT inflation (the path drops without queueing).
+    fn select_signal(&mut self, loss_rate: f32, inflated: bool) {
+        let underperforming = match self.signal {
+            CongestionSignal::Loss => inflated && loss_rate < 0.01,
+            CongestionSignal::Delay => !inflated && loss_rate > 0.05,
+        };
+        if !underperforming {
+            self.underperforming_intervals = 0;
+            return;
+        }
+        self.underperforming_intervals += 1;
+        if self.underperforming_intervals >= SIGNAL_SWITCH_INTERVALS {
+            self.signal = match self.signal {
+                CongestionSignal::Loss => CongestionSignal::Delay,
+                CongestionSignal::Delay => CongestionSignal::Loss,
+            };
+            self.underperforming_intervals = 0;
+        }
+    }
+
     /// Simple throttle update (original implementation).
     fn update_simple_throttle(&mut self, loss_rate: f32) {
         // Increase throttle if packet loss is high
@@ -234,11 +338,57 @@ impl CongestionControl {
         self.throttle_deceleration = deceleration;
     }
 
+    /// Returns the congestion signal driving the throttle.
+    pub fn signal(&self) -> CongestionSignal {
+        self.signal
+    }
+
+    /// Sets the congestion signal driving the throttle.
+    pub fn set_signal(&mut self, signal: CongestionSignal) {
+        self.signal = signal;
+        self.underperforming_intervals = 0;
+    }
+
+    /// Lets the controller switch between the loss and delay signals on its own, whenever the
+    /// active one consistently underperforms.
+    pub fn set_adaptive_signal(&mut self, adaptive: bool) {
+        self.adaptive_signal = adaptive;
+        self.underperforming_intervals = 0;
+    }
+
+    /// Returns the lowest recent RTT sample, the baseline for RTT inflation.
+    pub fn min_rtt(&self) -> Option<Duration> {
+        self.min_rtt
+    }
+
+    /// Returns whether the throttle is backed off from its minimum after losses, i.e. the
+    /// connection is still recovering rather than in steady state.
+    pub fn is_recovering(&self) -> bool {
+        self.throttle > self.min_throttle
+    }
+
     /// Resets all statistics.
     pub fn reset_stats(&mut self) {
         self.packets_lost = 0;
         self.packets_sent = 0;
     }
+
+    /// Returns the learned RTT and throttle state for persistence.
+    pub fn snapshot(&self) -> CongestionSnapshot {
+        CongestionSnapshot {
+            rtt: self.rtt(),
+            rtt_variance: self.rtt_variance(),
+            throttle: self.throttle,
+            packet_throttle: self.packet_throttle,
+        }
+    }
+
+    /// Restores state captured by [`CongestionControl::snapshot`].
+    pub fn restore(&mut self, snapshot: &CongestionSnapshot) {
+        self.rtt.restore(snapshot.rtt, snapshot.rtt_variance);
+        self.throttle = snapshot.throttle.clamp(self.min_throttle, self.max_throttle);
+        self.packet_throttle = snapshot.packet_throttle.min(self.throttle_scale);
+    }
 }
 
 impl Default for CongestionControl {
@@ -255,11 +405,13 @@ mod tests {
     fn test_rtt_update() {
         let mut cc = CongestionControl::default();
 
+        // The first sample replaces the initial estimate
         cc.update_rtt(Duration::from_millis(100));
-        assert!(cc.rtt() > Duration::from_millis(50)); // Should increase from initial
+        assert_eq!(cc.rtt(), Duration::from_millis(100));
 
-        cc.update_rtt(Duration::from_millis(100));
-        assert!(cc.rtt() < Duration::from_millis(100)); // Smoothed, so less than sample
+        cc.update_rtt(Duration::from_millis(200));
+        assert!(cc.rtt() > Duration::from_millis(100));
+        assert!(cc.rtt() < Duration::from_millis(200)); // Smoothed, so less than sample
     }
 
     #[test]
@@ -307,6 +459,114 @@ mod tests {
         assert!(cc.throttle() > 0.0);
     }
 
+    #[test]
+    fn test_recovering_until_throttle_back_at_minimum() {
+        let mut cc = CongestionControl::default();
+        let mut time = Instant::now();
+        assert!(!cc.is_recovering());
+
+        for _ in 0..10 {
+            cc.record_sent();
+        }
+        cc.record_loss();
+        time += Duration::from_secs(2);
+        cc.update_throttle(time);
+        assert!(cc.is_recovering());
+
+        // Loss-free intervals bring the throttle back down
+        while cc.throttle() > 0.0 {
+            cc.record_sent();
+            time += Duration::from_secs(2);
+            cc.update_throttle(time);
+        }
+        assert!(!cc.is_recovering());
+    }
+
+    /// Runs one throttle interval of `sent` packets with `lost` losses at `rtt`.
+    fn run_interval(
+        cc: &mut CongestionControl,
+        time: &mut Instant,
+        rtt: u64,
+        sent: u32,
+        lost: u32,
+    ) {
+        // Enough samples for the smoothed RTT to settle at `rtt`
+        for _ in 0..30 {
+            cc.update_rtt(Duration::from_millis(rtt));
+        }
+        for _ in 0..sent {
+            cc.record_sent();
+        }
+        for _ in 0..lost {
+            cc.record_loss();
+        }
+        *time += Duration::from_secs(2);
+        assert!(cc.update_throttle(*time));
+    }
+
+    #[test]
+    fn test_adaptive_switches_to_delay_on_rtt_inflation() {
+        let mut cc = CongestionControl::default();
+        cc.set_adaptive_signal(true);
+        let mut time = Instant::now();
+        for _ in 0..5 {
+            run_interval(&mut cc, &mut time, 50, 100, 0);
+        }
+        assert_eq!(cc.signal(), CongestionSignal::Loss);
+        assert_eq!(cc.min_rtt(), Some(Duration::from_millis(50)));
+
+        // Queues build without loss: the loss signal never reacts
+        for _ in 0..SIGNAL_SWITCH_INTERVALS - 1 {
+            run_interval(&mut cc, &mut time, 150, 100, 0);
+            assert_eq!(cc.signal(), CongestionSignal::Loss);
+            assert_eq!(cc.throttle(), 0.0);
+        }
+        run_interval(&mut cc, &mut time, 150, 100, 0);
+        assert_eq!(cc.signal(), CongestionSignal::Delay);
+
+        // The delay signal backs off while the RTT stays inflated
+        run_interval(&mut cc, &mut time, 150, 100, 0);
+        assert!(cc.throttle() > 0.0);
+    }
+
+    #[test]
+    fn test_adaptive_signal_hysteresis() {
+        let mut cc = CongestionControl::default();
+        cc.set_adaptive_signal(true);
+        let mut time = Instant::now();
+        run_interval(&mut cc, &mut time, 50, 100, 0);
+
+        // Inflation interrupted by a clean interval never accumulates enough to switch
+        for _ in 0..5 {
+            for _ in 0..SIGNAL_SWITCH_INTERVALS - 1 {
+                run_interval(&mut cc, &mut time, 200, 100, 0);
+            }
+            run_interval(&mut cc, &mut time, 50, 100, 0);
+            run_interval(&mut cc, &mut time, 50, 100, 0);
+        }
+        assert_eq!(cc.signal(), CongestionSignal::Loss);
+
+        // Without adaptation the signal never changes
+        let mut fixed = CongestionControl::default();
+        run_interval(&mut fixed, &mut time, 50, 100, 0);
+        for _ in 0..10 {
+            run_interval(&mut fixed, &mut time, 200, 100, 0);
+        }
+        assert_eq!(fixed.signal(), CongestionSignal::Loss);
+    }
+
+    #[test]
+    fn test_adaptive_switches_back_to_loss_on_loss_without_inflation() {
+        let mut cc = CongestionControl::default();
+        cc.set_adaptive_signal(true);
+        cc.set_signal(CongestionSignal::Delay);
+        let mut time = Instant::now();
+        for _ in 0..SIGNAL_SWITCH_INTERVALS {
+            run_interval(&mut cc, &mut time, 50, 100, 10);
+        }
+        assert_eq!(cc.signal(), CongestionSignal::Loss);
+    }
+
     #[test]
     fn test_advanced_throttling_enabled() {
         let mut cc = CongestionControl::default();
@@ -407,6 +667,32 @@ mod tests {
         assert!(cc.throttle() > 0.5);
     }
 
+    #[test]
+    fn test_min_rtt_follows_path_change() {
+        let mut cc = CongestionControl::default();
+        cc.set_signal(CongestionSignal::Delay);
+        let mut time = Instant::now();
+        run_interval(&mut cc, &mut time, 50, 100, 0);
+        assert_eq!(cc.min_rtt(), Some(Duration::from_millis(50)));
+
+        // The path changes to one with a higher base RTT: at first it looks like queueing
+        run_interval(&mut cc, &mut time, 150, 100, 0);
+        assert!(cc.is_rtt_inflated());
+
+        // Within two windows the samples of the old path expire and the new RTT is the baseline
+        let changed = time;
+        while cc.min_rtt() != Some(Duration::from_millis(150)) {
+            run_interval(&mut cc, &mut time, 150, 100, 0);
+            assert!(time.duration_since(changed) <= MIN_RTT_WINDOW * 2);
+        }
+        assert!(!cc.is_rtt_inflated());
+
+        // The delay signal stops backing off and the throttle recovers
+        let throttle = cc.throttle();
+        run_interval(&mut cc, &mut time, 150, 100, 0);
+        assert!(cc.throttle() < throttle);
+    }
+
     #[test]
     fn test_configure_throttle_dynamically() {
         let mut cc = CongestionControl::default();
@@ -425,4 +711,20 @@ mod tests {
         std::thread::sleep(Duration::from_millis(1100));
         assert!(cc.update_throttle(Instant::now()));
     }
+
+    #[test]
+    fn test_snapshot_restores_learned_state() {
+        let mut cc = CongestionControl::default();
+        cc.enable_advanced_throttling(32, 2, 2, 5000);
+        cc.update_rtt(Duration::from_millis(180));
+        cc.update_rtt(Duration::from_millis(220));
+        let snapshot = cc.snapshot();
+
+        let mut restored = CongestionControl::default();
+        restored.enable_advanced_throttling(32, 2, 2, 5000);
+        restored.restore(&snapshot);
+
+        assert_eq!(restored.snapshot(), snapshot);
+        assert_eq!(restored.rto(), cc.rto());
+    }
 }
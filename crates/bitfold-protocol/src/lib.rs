@@ -16,10 +16,13 @@ pub mod command_codec;
 pub mod congestion;
 /// Packet types and structures.
 pub mod packet;
+/// RFC 6298 round-trip time and retransmission timeout estimation.
+pub mod rtt_estimator;
 /// Sequence buffers for tracking sent/received packets.
 pub mod sequence_buffer;
 
-pub use acknowledgment::{AcknowledgmentHandler, SentPacket};
+pub use acknowledgment::{AckedPacket, AcknowledgmentHandler, SentPacket};
 pub use packet::{
     DeliveryGuarantee, IncomingPackets, OrderingGuarantee, Packet, PacketInfo, PacketType,
 };
+pub use rtt_estimator::RttEstimator;
@@ -61,6 +61,7 @@ impl TryFrom<u8> for DeliveryGuarantee {
 
 /// Enum to specify how a packet should be arranged.
 #[derive(Copy, Clone, Debug, PartialOrd, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OrderingGuarantee {
     /// No arranging will be done.
     #[default]
@@ -103,6 +104,7 @@ impl TryFrom<u8> for OrderingGuarantee {
 
 /// Id to identify a certain packet type.
 #[derive(Copy, Clone, Debug, PartialOrd, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PacketType {
     /// Full packet that is not fragmented
     Packet = 0,
@@ -144,6 +146,7 @@ pub struct Packet {
     delivery: DeliveryGuarantee,
     ordering: OrderingGuarantee,
     channel_id: u8,
+    immediate_ack: bool,
 }
 
 impl Packet {
@@ -155,7 +158,7 @@ impl Packet {
         ordering: OrderingGuarantee,
         channel_id: u8,
     ) -> Packet {
-        Packet { addr, payload, delivery, ordering, channel_id }
+        Packet { addr, payload, delivery, ordering, channel_id, immediate_ack: false }
     }
 
     /// Creates an unreliable, unordered packet on channel 0 (fire-and-forget).
@@ -166,6 +169,7 @@ impl Packet {
             delivery: DeliveryGuarantee::Unreliable,
             ordering: OrderingGuarantee::None,
             channel_id: 0,
+            immediate_ack: false,
         }
     }
 
@@ -181,6 +185,7 @@ impl Packet {
             delivery: DeliveryGuarantee::Unreliable,
             ordering: OrderingGuarantee::Sequenced(stream_id),
             channel_id: 0,
+            immediate_ack: false,
         }
     }
 
@@ -192,6 +197,7 @@ impl Packet {
             delivery: DeliveryGuarantee::Unreliable,
             ordering: OrderingGuarantee::Unsequenced,
             channel_id: 0,
+            immediate_ack: false,
         }
     }
 
@@ -203,6 +209,7 @@ impl Packet {
             delivery: DeliveryGuarantee::Reliable,
             ordering: OrderingGuarantee::None,
             channel_id: 0,
+            immediate_ack: false,
         }
     }
 
@@ -214,6 +221,7 @@ impl Packet {
             delivery: DeliveryGuarantee::Reliable,
             ordering: OrderingGuarantee::Ordered(stream_id),
             channel_id: 0,
+            immediate_ack: false,
         }
     }
 
@@ -225,6 +233,7 @@ impl Packet {
             delivery: DeliveryGuarantee::Reliable,
             ordering: OrderingGuarantee::Sequenced(stream_id),
             channel_id: 0,
+            immediate_ack: false,
         }
     }
 
@@ -236,6 +245,7 @@ impl Packet {
             delivery: DeliveryGuarantee::Unreliable,
             ordering: OrderingGuarantee::None,
             channel_id,
+            immediate_ack: false,
         }
     }
 
@@ -247,6 +257,7 @@ impl Packet {
             delivery: DeliveryGuarantee::Reliable,
             ordering: OrderingGuarantee::None,
             channel_id,
+            immediate_ack: false,
         }
     }
 
@@ -284,6 +295,18 @@ impl Packet {
     pub fn channel_id(&self) -> u8 {
         self.channel_id
     }
+
+    /// Asks the receiver to acknowledge the datagram carrying this packet immediately,
+    /// bypassing its delayed-ACK timer. Useful for latency-sensitive messages.
+    pub fn with_immediate_ack(mut self) -> Packet {
+        self.immediate_ack = true;
+        self
+    }
+
+    /// Returns whether an immediate acknowledgment was requested for this packet.
+    pub fn immediate_ack_requested(&self) -> bool {
+        self.immediate_ack
+    }
 }
 
 /// Non-owning packet metadata used during processing.
@@ -357,22 +380,36 @@ impl<T> Iterator for ZeroOrMore<T> {
 #[derive(Debug)]
 pub struct IncomingPackets {
     data: ZeroOrMore<(Packet, PacketType)>,
+    datagram_size: Option<usize>,
 }
 
 impl IncomingPackets {
     /// Creates an empty collection (no packets received).
     pub fn zero() -> Self {
-        Self { data: ZeroOrMore::zero() }
+        Self { data: ZeroOrMore::zero(), datagram_size: None }
     }
 
     /// Creates a collection with a single packet and type.
     pub fn one(packet: Packet, packet_type: PacketType) -> Self {
-        Self { data: ZeroOrMore::one((packet, packet_type)) }
+        Self { data: ZeroOrMore::one((packet, packet_type)), datagram_size: None }
     }
 
     /// Creates a collection with multiple packets.
     pub fn many(vec: VecDeque<(Packet, PacketType)>) -> Self {
-        Self { data: ZeroOrMore::many(vec) }
+        Self { data: ZeroOrMore::many(vec), datagram_size: None }
+    }
+
+    /// Records the size of the raw datagram the packets were decoded from.
+    pub fn with_datagram_size(mut self, size: usize) -> Self {
+        self.datagram_size = Some(size);
+        self
+    }
+
+    /// Returns the size in bytes of the raw datagram the packets were decoded from, as
+    /// received from the transport (before checksum removal and decompression), or `None`
+    /// if they did not come from a whole datagram.
+    pub fn datagram_size(&self) -> Option<usize> {
+        self.datagram_size
     }
 }
 
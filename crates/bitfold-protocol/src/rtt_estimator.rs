@@ -0,0 +1,266 @@
+//! Round-trip time and retransmission timeout estimation following RFC 6298.
+//!
+//! An [`RttEstimator`] keeps the smoothed RTT (SRTT) and its variation (RTTVAR). The first
+//! sample sets SRTT to the sample and RTTVAR to half of it; later samples update both as
+//! exponentially weighted moving averages, RTTVAR first so it sees the old SRTT:
+//!
+//! ```text
+//! RTTVAR = (1 - beta) * RTTVAR + beta * |SRTT - R|
+//! SRTT   = (1 - alpha) * SRTT + alpha * R
+//! RTO    = max(SRTT + max(G, 4 * RTTVAR), min_rto)
+//! ```
+//!
+//! Each retransmission timeout multiplies the RTO by the backoff factor, up to `max_rto`,
+//! until the next sample (Karn's algorithm).
+//!
+//! # Example
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use bitfold_protocol::rtt_estimator::RttEstimator;
+//!
+//! let mut estimator = RttEstimator::default();
+//! estimator.on_rtt_sample(Duration::from_millis(100));
+//! assert_eq!(estimator.rto(), Duration::from_millis(300));
+//! estimator.on_timeout();
+//! assert_eq!(estimator.rto(), Duration::from_millis(600));
+//! ```
+
+use std::time::Duration;
+
+use bitfold_core::config::Config;
+
+/// Clock granularity G: the RTO is at least this much above SRTT.
+const CLOCK_GRANULARITY: Duration = Duration::from_millis(1);
+
+/// RFC 6298 SRTT/RTTVAR estimator with a clamped, backed-off retransmission timeout.
+#[derive(Debug, Clone)]
+pub struct RttEstimator {
+    /// Smoothed round-trip time
+    srtt: Duration,
+    /// Round-trip time variation
+    rttvar: Duration,
+    /// Whether a sample (or restored state) replaced the initial estimate
+    has_sample: bool,
+    /// Gain of the SRTT average
+    alpha: f32,
+    /// Gain of the RTTVAR average
+    beta: f32,
+    /// Lowest RTO before backoff
+    min_rto: Duration,
+    /// Highest RTO, backoff included
+    max_rto: Duration,
+    /// Factor the RTO grows by per timeout
+    backoff_factor: f32,
+    /// Timeouts since the last sample that backed the RTO off, i.e. before it hit `max_rto`
+    backoffs: u32,
+}
+
+impl RttEstimator {
+    /// SRTT assumed before the first sample
+    pub const INITIAL_RTT: Duration = Duration::from_millis(50);
+    /// RTTVAR assumed before the first sample
+    pub const INITIAL_RTT_VARIANCE: Duration = Duration::from_millis(25);
+
+    /// Creates an estimator with gains `alpha` (SRTT) and `beta` (RTTVAR), no RTO floor, a
+    /// 60 second ceiling and a backoff factor of 2.
+    pub fn new(alpha: f32, beta: f32) -> Self {
+        Self {
+            srtt: Self::INITIAL_RTT,
+            rttvar: Self::INITIAL_RTT_VARIANCE,
+            has_sample: false,
+            alpha,
+            beta,
+            min_rto: Duration::ZERO,
+            max_rto: Duration::from_secs(60),
+            backoff_factor: 2.0,
+            backoffs: 0,
+        }
+    }
+
+    /// Creates an estimator using `rtt_smoothing_factor` as the SRTT gain and the RTO bounds
+    /// and backoff factor from `config`.
+    pub fn from_config(config: &Config) -> Self {
+        let mut estimator = Self::new(config.rtt_smoothing_factor, 0.25);
+        estimator.set_rto_bounds(
+            Duration::from_millis(config.rto_min_ms.into()),
+            Duration::from_millis(config.rto_max_ms.into()),
+        );
+        estimator.set_backoff_factor(config.rto_backoff_factor);
+        estimator
+    }
+
+    /// Sets the lowest RTO before backoff and the highest RTO overall.
+    pub fn set_rto_bounds(&mut self, min_rto: Duration, max_rto: Duration) {
+        self.min_rto = min_rto;
+        self.max_rto = max_rto.max(min_rto);
+    }
+
+    /// Sets the factor the RTO is multiplied by per timeout (at least 1).
+    pub fn set_backoff_factor(&mut self, factor: f32) {
+        self.backoff_factor = factor.max(1.0);
+    }
+
+    /// Updates SRTT and RTTVAR with a round-trip time measurement and clears the backoff.
+    ///
+    /// Samples from retransmitted packets are ambiguous and should not be fed in.
+    pub fn on_rtt_sample(&mut self, sample: Duration) {
+        if self.has_sample {
+            let srtt = self.srtt.as_secs_f64();
+            let sample_secs = sample.as_secs_f64();
+            let beta = f64::from(self.beta);
+            let alpha = f64::from(self.alpha);
+            let rttvar =
+                (1.0 - beta) * self.rttvar.as_secs_f64() + beta * (srtt - sample_secs).abs();
+            self.rttvar = Duration::from_secs_f64(rttvar);
+            self.srtt = Duration::from_secs_f64((1.0 - alpha) * srtt + alpha * sample_secs);
+        } else {
+            self.srtt = sample;
+            self.rttvar = sample / 2;
+            self.has_sample = true;
+        }
+        self.backoffs = 0;
+    }
+
+    /// Backs the RTO off after a retransmission timer expired. Once the RTO reached the
+    /// ceiling, or if it cannot grow (a backoff factor of 1), timeouts no longer count.
+    pub fn on_timeout(&mut self) {
+        if self.backoff_factor > 1.0 && self.rto() < self.max_rto {
+            self.backoffs += 1;
+        }
+    }
+
+    /// Returns the smoothed round-trip time.
+    pub fn srtt(&self) -> Duration {
+        self.srtt
+    }
+
+    /// Returns the round-trip time variation.
+    pub fn rttvar(&self) -> Duration {
+        self.rttvar
+    }
+
+    /// Returns the number of timeouts since the last sample that backed the RTO off.
+    pub fn backoffs(&self) -> u32 {
+        self.backoffs
+    }
+
+    /// Returns the retransmission timeout, backoff included.
+    pub fn rto(&self) -> Duration {
+        let base = (self.srtt + CLOCK_GRANULARITY.max(self.rttvar * 4)).max(self.min_rto);
+        let mut rto = base.min(self.max_rto);
+        for _ in 0..self.backoffs {
+            let backed_off = rto.mul_f64(f64::from(self.backoff_factor)).min(self.max_rto);
+            if backed_off <= rto {
+                break;
+            }
+            rto = backed_off;
+        }
+        rto
+    }
+
+    /// Replaces SRTT and RTTVAR with previously learned values, as if sampled.
+    pub fn restore(&mut self, srtt: Duration, rttvar: Duration) {
+        self.srtt = srtt;
+        self.rttvar = rttvar;
+        self.has_sample = true;
+        self.backoffs = 0;
+    }
+}
+
+impl Default for RttEstimator {
+    fn default() -> Self {
+        Self::new(0.125, 0.25)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_first_sample_initializes_estimate() {
+        let mut estimator = RttEstimator::default();
+        assert_eq!(estimator.srtt(), RttEstimator::INITIAL_RTT);
+
+        estimator.on_rtt_sample(ms(80));
+        assert_eq!(estimator.srtt(), ms(80));
+        assert_eq!(estimator.rttvar(), ms(40));
+        assert_eq!(estimator.rto(), ms(240));
+    }
+
+    #[test]
+    fn test_rtt_spike_widens_variance() {
+        let mut estimator = RttEstimator::default();
+        for _ in 0..50 {
+            estimator.on_rtt_sample(ms(100));
+        }
+        let steady_var = estimator.rttvar();
+        let steady_rto = estimator.rto();
+        assert!(steady_var < ms(1), "variance {steady_var:?}");
+
+        estimator.on_rtt_sample(ms(500));
+        // RTTVAR moves by beta * |SRTT - R| = 100ms, SRTT only by alpha * 400ms = 50ms
+        assert!(estimator.rttvar() >= ms(99), "variance {:?}", estimator.rttvar());
+        assert!(estimator.srtt() < ms(151));
+        assert!(estimator.rto() > steady_rto + ms(400));
+    }
+
+    #[test]
+    fn test_min_rto_clamp() {
+        let mut estimator = RttEstimator::default();
+        estimator.set_rto_bounds(ms(1000), ms(60_000));
+        estimator.on_rtt_sample(ms(10));
+        assert_eq!(estimator.rto(), ms(1000));
+    }
+
+    #[test]
+    fn test_timeouts_double_rto_up_to_ceiling() {
+        let mut estimator = RttEstimator::default();
+        estimator.set_rto_bounds(ms(200), ms(3000));
+        estimator.on_rtt_sample(ms(50));
+        assert_eq!(estimator.rto(), ms(200));
+
+        let mut rtos = Vec::new();
+        for _ in 0..6 {
+            estimator.on_timeout();
+            rtos.push(estimator.rto());
+        }
+        assert_eq!(rtos, [ms(400), ms(800), ms(1600), ms(3000), ms(3000), ms(3000)]);
+        assert_eq!(estimator.backoffs(), 4);
+
+        // A fresh sample recomputes the RTO without backoff
+        estimator.on_rtt_sample(ms(50));
+        assert_eq!(estimator.backoffs(), 0);
+        assert!(estimator.rto() < ms(400));
+    }
+
+    #[test]
+    fn test_timeouts_without_growth_do_not_back_off() {
+        let mut estimator = RttEstimator::default();
+        estimator.set_backoff_factor(1.0);
+        estimator.on_rtt_sample(ms(100));
+        for _ in 0..1000 {
+            estimator.on_timeout();
+        }
+        assert_eq!(estimator.backoffs(), 0);
+        assert_eq!(estimator.rto(), ms(300));
+    }
+
+    #[test]
+    fn test_from_config() {
+        let mut config = Config::default();
+        config.rto_min_ms = 500;
+        config.rto_backoff_factor = 1.5;
+        let mut estimator = RttEstimator::from_config(&config);
+        estimator.on_rtt_sample(ms(20));
+        assert_eq!(estimator.rto(), ms(500));
+        estimator.on_timeout();
+        assert_eq!(estimator.rto(), ms(750));
+    }
+}
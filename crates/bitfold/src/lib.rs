@@ -32,12 +32,17 @@
 // Re-export all workspace crates
 pub use bitfold_core as core;
 // Core config
-pub use bitfold_core::config::{CompressionAlgorithm, Config};
+pub use bitfold_core::config::{
+    AckPolicy, ChecksumAlgorithm, CompressionAlgorithm, Config, ConfigBuilder, ConfigError,
+    CongestionControllerKind, PmtuProbePayload, PmtuSearchMode,
+};
 pub use bitfold_core::utilities;
 pub use bitfold_host as host;
 // Host: manages multiple peer sessions and events
 pub use bitfold_host::{Host, SocketEvent};
 pub use bitfold_peer as peer;
+// Peer: per-stream reliability and close causes
+pub use bitfold_peer::{CloseReason, SendOptions, StreamMode, DRAIN_REASON};
 pub use bitfold_protocol as protocol;
 // Protocol: packets and guarantees
 pub use bitfold_protocol::{DeliveryGuarantee, OrderingGuarantee, Packet, PacketInfo, PacketType};
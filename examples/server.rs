@@ -69,6 +69,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 SocketEvent::Timeout(addr) => {
                     println!("[timeout] {}", addr);
                 }
+                SocketEvent::Closed(addr, reason) => {
+                    println!("[closed] {} reason={:?}", addr, reason);
+                }
             }
         }
 